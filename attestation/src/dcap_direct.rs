@@ -0,0 +1,99 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License.
+
+//! This module provides an attestation service backend for ECDSA quotes
+//! without a separate attestation service. The quote itself is put in the
+//! certificate, and the peer verifies it with the quote verification enclave
+//! (QvE) of its own platform, so no signing key is needed in the enclave.
+
+use crate::platform;
+use crate::service::AttestationServiceBackend;
+use crate::EndorsedAttestationReport;
+
+use std::prelude::v1::*;
+
+use anyhow::Result;
+use sgx_types::*;
+
+/// Length of the quote header and the report body, i.e., the quote without
+/// the signature data.
+#[cfg(dcap)]
+const QUOTE_BODY_LEN: usize = 432;
+
+/// Convert the quote verification result to the quote status named by the IAS
+/// report API, like the reference DCAP attestation service.
+#[cfg(dcap)]
+fn quote_status(result: u32) -> &'static str {
+    use sgx_ql_qv_result_t::*;
+    match result {
+        r if r == SGX_QL_QV_RESULT_OK as u32 => "OK",
+        r if r == SGX_QL_QV_RESULT_CONFIG_NEEDED as u32 => "CONFIGURATION_NEEDED",
+        r if r == SGX_QL_QV_RESULT_OUT_OF_DATE as u32 => "OUT_OF_DATE",
+        r if r == SGX_QL_QV_RESULT_OUT_OF_DATE_CONFIG_NEEDED as u32 => {
+            "OUT_OF_DATE_CONFIGURATION_NEEDED"
+        }
+        r if r == SGX_QL_QV_RESULT_INVALID_SIGNATURE as u32 => "SIGNATURE_INVALID",
+        r if r == SGX_QL_QV_RESULT_REVOKED as u32 => "KEY_REVOKED",
+        r if r == SGX_QL_QV_RESULT_SW_HARDENING_NEEDED as u32 => "SW_HARDENING_NEEDED",
+        r if r == SGX_QL_QV_RESULT_CONFIG_AND_SW_HARDENING_NEEDED as u32 => {
+            "CONFIGURATION_AND_SW_HARDENING_NEEDED"
+        }
+        _ => "UNSPECIFIED",
+    }
+}
+
+pub(crate) struct DcapDirectBackend;
+
+impl DcapDirectBackend {
+    pub(crate) fn new() -> Result<Self> {
+        if cfg!(dcap) {
+            Ok(Self)
+        } else {
+            anyhow::bail!("The dcap_direct attestation backend requires a DCAP build")
+        }
+    }
+}
+
+impl AttestationServiceBackend for DcapDirectBackend {
+    fn attest(&self, pub_k: sgx_ec256_public_t) -> Result<EndorsedAttestationReport> {
+        // SPID in the attestation key ID stays 0 for ECDSA quotes
+        let (ak_id, qe_target_info) = platform::init_sgx_quote()?;
+        let sgx_report = platform::create_sgx_isv_enclave_report(pub_k, qe_target_info)?;
+        let quote = platform::get_sgx_quote(&ak_id, sgx_report)?;
+
+        Ok(EndorsedAttestationReport {
+            quote,
+            ..Default::default()
+        })
+    }
+}
+
+/// Verifies a quote of a peer with the QvE of this platform, and returns the
+/// quote status and the quote body, i.e., the quote without the signature
+/// data.
+#[cfg(dcap)]
+pub(crate) fn verify_quote(quote: &[u8]) -> Result<(&'static str, &[u8])> {
+    anyhow::ensure!(quote.len() >= QUOTE_BODY_LEN, "Quote is too short");
+    let result = platform::verify_sgx_quote(quote)?;
+
+    Ok((quote_status(result), &quote[..QUOTE_BODY_LEN]))
+}
+
+#[cfg(not(dcap))]
+pub(crate) fn verify_quote(_quote: &[u8]) -> Result<(&'static str, &[u8])> {
+    anyhow::bail!("Cannot verify the quote: not a DCAP build")
+}
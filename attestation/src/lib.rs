@@ -75,11 +75,38 @@ impl AttestationAlgorithm {
     }
 }
 
+/// Backend endorsing the quotes of enclaves
+#[derive(Clone, PartialEq)]
+pub(crate) enum AttestationBackend {
+    /// Intel Attestation Service, or a service with the same report API like
+    /// the reference DCAP attestation service
+    Ias,
+    /// Quote verified by the quote verification enclave on the platform of
+    /// the peer, without a separate attestation service
+    DcapDirect,
+    /// Reports signed by a test CA without verifying the quote, for testing
+    /// without SGX hardware or network access. Only in test mode builds.
+    Mock,
+}
+
+impl AttestationBackend {
+    pub(crate) fn from_str(s: &str) -> Option<Self> {
+        match s {
+            "ias" => Some(AttestationBackend::Ias),
+            "dcap_direct" => Some(AttestationBackend::DcapDirect),
+            "mock" => Some(AttestationBackend::Mock),
+            _ => None,
+        }
+    }
+}
+
 /// Attestation Service Configuration
 #[derive(Clone)]
 pub struct AttestationServiceConfig {
     /// Algorithm to use
     algo: AttestationAlgorithm,
+    /// Backend endorsing the quote
+    backend: AttestationBackend,
     /// URL of attestation service
     as_url: url::Url,
     /// IAS API Key
@@ -97,8 +124,18 @@ impl AttestationConfig {
     }
 
     /// Creates `AttestationConfig` for attestation using given values
-    pub fn new(algorithm: &str, url: &str, api_key: &str, spid_str: &str) -> Result<Arc<Self>> {
-        if cfg!(sgx_sim) {
+    pub fn new(
+        algorithm: &str,
+        backend: &str,
+        url: &str,
+        api_key: &str,
+        spid_str: &str,
+    ) -> Result<Arc<Self>> {
+        let backend =
+            AttestationBackend::from_str(backend).context("Unsupported attestation backend")?;
+
+        // Only the mock backend works without SGX hardware
+        if cfg!(sgx_sim) && backend != AttestationBackend::Mock {
             return Ok(Self::no_attestation());
         }
 
@@ -113,6 +150,7 @@ impl AttestationConfig {
 
         let att_service_cfg = AttestationServiceConfig {
             algo,
+            backend,
             as_url: url::Url::parse(url).context("Invalid URL")?,
             api_key: api_key.to_string(),
             spid,
@@ -126,6 +164,7 @@ impl AttestationConfig {
         let as_config = &config.attestation;
         Self::new(
             &as_config.algorithm,
+            &as_config.backend,
             &as_config.url,
             &as_config.key,
             &as_config.spid,
@@ -144,6 +183,10 @@ pub(crate) struct EndorsedAttestationReport {
     pub signature: Vec<u8>,
     /// Certificate matching the signing key of the signature
     pub signing_cert: Vec<u8>,
+    /// Quote to be verified by the peer itself, instead of a report endorsed
+    /// by an attestation service
+    #[serde(default)]
+    pub quote: Vec<u8>,
 }
 
 /// Configuration for TLS communication in Remote Attestation
@@ -157,12 +200,16 @@ pub struct AttestedTlsConfig {
 
 #[macro_use]
 mod cert;
+pub mod policy;
 pub mod report;
+pub mod revocation;
 pub mod verifier;
 
 cfg_if::cfg_if! {
     if #[cfg(feature = "mesalock_sgx")]  {
         mod service;
+        mod dcap_direct;
+        mod mock;
         mod key;
         mod platform;
         mod attestation;
@@ -176,6 +223,12 @@ pub mod tests {
     use teaclave_test_utils::*;
 
     pub fn run_tests() -> bool {
-        run_tests!(platform::tests::run_tests, report::tests::run_tests)
+        run_tests!(
+            mock::tests::run_tests,
+            platform::tests::run_tests,
+            policy::tests::run_tests,
+            report::tests::run_tests,
+            revocation::tests::run_tests
+        )
     }
 }
@@ -0,0 +1,182 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License.
+
+//! This module provides a local mock of the attestation service. It endorses
+//! a report of the enclave without any quoting enclave or network access, so
+//! remote attestation can be exercised in simulation mode and in CI. Reports
+//! are signed with the test keys under `keys/` and are only accepted by peers
+//! built with `mock_as_root_ca_cert.pem` as the attestation service root CA.
+//! The signing key is only built into test mode builds.
+
+use crate::platform;
+use crate::service::AttestationServiceBackend;
+use crate::EndorsedAttestationReport;
+
+use std::prelude::v1::*;
+#[cfg(test_mode)]
+use std::time::SystemTime;
+#[cfg(test_mode)]
+use std::untrusted::time::SystemTimeEx;
+
+#[cfg(test_mode)]
+use anyhow::anyhow;
+use anyhow::Result;
+#[cfg(test_mode)]
+use serde_json::json;
+use sgx_types::*;
+
+#[cfg(test_mode)]
+const MOCK_AS_SIGNING_KEY: &str = include_str!("../../keys/mock_as_signing_key.pem");
+#[cfg(test_mode)]
+const MOCK_AS_SIGNING_CERT: &str = include_str!("../../keys/mock_as_signing_cert.pem");
+
+pub(crate) struct MockBackend;
+
+impl MockBackend {
+    pub(crate) fn new() -> Result<Self> {
+        if cfg!(test_mode) {
+            Ok(Self)
+        } else {
+            anyhow::bail!("The mock attestation backend requires a test mode build")
+        }
+    }
+}
+
+impl AttestationServiceBackend for MockBackend {
+    fn attest(&self, pub_k: sgx_ec256_public_t) -> Result<EndorsedAttestationReport> {
+        let sgx_report =
+            platform::create_sgx_isv_enclave_report(pub_k, sgx_target_info_t::default())?;
+        let quote_body = quote_body(&sgx_report.body);
+
+        #[cfg(test_mode)]
+        return sign_report("OK", &quote_body, MOCK_AS_SIGNING_KEY, MOCK_AS_SIGNING_CERT);
+
+        #[cfg(not(test_mode))]
+        anyhow::bail!(
+            "Cannot sign the report of {} bytes: not a test mode build",
+            quote_body.len()
+        )
+    }
+}
+
+/// Build the quote without signature data, i.e., a version 2 quote header
+/// followed by the report body of the enclave.
+fn quote_body(report_body: &sgx_report_body_t) -> Vec<u8> {
+    let mut body = Vec::with_capacity(432);
+    body.extend_from_slice(&2u16.to_le_bytes()); // version
+    body.extend_from_slice(&1u16.to_le_bytes()); // signature type: linkable
+    body.extend_from_slice(&[0u8; 4]); // gid
+    body.extend_from_slice(&[0u8; 2]); // isv_svn_qe
+    body.extend_from_slice(&[0u8; 2]); // isv_svn_pce
+    body.extend_from_slice(&[0u8; 16]); // qe_vendor_id
+    body.extend_from_slice(&[0u8; 20]); // user_data
+
+    let report_body = unsafe {
+        std::slice::from_raw_parts(
+            report_body as *const sgx_report_body_t as *const u8,
+            std::mem::size_of::<sgx_report_body_t>(),
+        )
+    };
+    body.extend_from_slice(report_body);
+
+    body
+}
+
+/// Builds a report in the format of the IAS v4 report API and signs it with
+/// `signing_key` (PKCS#8 in PEM).
+#[cfg(test_mode)]
+fn sign_report(
+    sgx_quote_status: &str,
+    quote_body: &[u8],
+    signing_key: &str,
+    signing_cert: &str,
+) -> Result<EndorsedAttestationReport> {
+    let id = ring::digest::digest(&ring::digest::SHA256, quote_body);
+    let now = chrono::DateTime::<chrono::offset::Utc>::from(SystemTime::now());
+    let report = json!({
+        "id": hex::encode(&id.as_ref()[..16]),
+        "version": 4,
+        "timestamp": now.format("%Y-%m-%dT%H:%M:%S%.f").to_string(),
+        "isvEnclaveQuoteStatus": sgx_quote_status,
+        "isvEnclaveQuoteBody": base64::encode(quote_body),
+    })
+    .to_string()
+    .into_bytes();
+
+    let key = rustls::internal::pemfile::pkcs8_private_keys(&mut signing_key.as_bytes())
+        .map_err(|_| anyhow!("pemfile error"))?;
+    let key = key.first().ok_or_else(|| anyhow!("No signing key"))?;
+    let signer = ring::signature::RsaKeyPair::from_pkcs8(&key.0)
+        .map_err(|_| anyhow!("Invalid signing key"))?;
+    let mut signature = vec![0; signer.public_modulus_len()];
+    signer
+        .sign(
+            &ring::signature::RSA_PKCS1_SHA256,
+            &ring::rand::SystemRandom::new(),
+            &report,
+            &mut signature,
+        )
+        .map_err(|_| anyhow!("Failed to sign the report"))?;
+
+    let certs = rustls::internal::pemfile::certs(&mut signing_cert.as_bytes())
+        .map_err(|_| anyhow!("pemfile error"))?;
+    let signing_cert = certs
+        .first()
+        .ok_or_else(|| anyhow!("No signing certificate"))?
+        .0
+        .clone();
+
+    Ok(EndorsedAttestationReport {
+        report,
+        signature,
+        signing_cert,
+        quote: Vec::new(),
+    })
+}
+
+#[cfg(all(feature = "enclave_unit_test", feature = "mesalock_sgx"))]
+pub mod tests {
+    use super::*;
+    use crate::report::{SgxEpidQuoteSigType, SgxQuote, SgxQuoteVersion};
+    use teaclave_test_utils::*;
+
+    pub fn run_tests() -> bool {
+        run_tests!(test_mock_quote_body,)
+    }
+
+    fn test_mock_quote_body() {
+        let pub_k = sgx_ec256_public_t {
+            gx: [1; 32],
+            gy: [2; 32],
+        };
+        let sgx_report =
+            platform::create_sgx_isv_enclave_report(pub_k, sgx_target_info_t::default()).unwrap();
+        let body = quote_body(&sgx_report.body);
+        assert_eq!(body.len(), 432);
+
+        let quote = SgxQuote::parse_from(&body).unwrap();
+        assert_eq!(
+            quote.version,
+            SgxQuoteVersion::V2(SgxEpidQuoteSigType::Linkable)
+        );
+        assert_eq!(
+            quote.isv_enclave_report.mr_enclave,
+            sgx_report.body.mr_enclave.m
+        );
+        assert_eq!(&quote.isv_enclave_report.report_data[..32], &[1; 32]);
+    }
+}
@@ -20,6 +20,11 @@
 
 use std::prelude::v1::*;
 
+#[cfg(dcap)]
+use std::time::{SystemTime, UNIX_EPOCH};
+#[cfg(dcap)]
+use std::untrusted::time::SystemTimeEx;
+
 use log::debug;
 use sgx_rand::{os::SgxRng, Rng};
 use sgx_tcrypto::rsgx_sha256_slice;
@@ -49,6 +54,12 @@ pub enum PlatformError {
         qe_report.body.report_data.d[..32] {1:?}"
     )]
     ReportReplay(Vec<u8>, Vec<u8>),
+    #[error("Failed to verify quote: {0}")]
+    VerifyQuoteError(SgxStatus),
+    #[error("Failed to verify the report and identity of the QvE: {0:?}")]
+    QveIdentityError(quote3_error_t),
+    #[error("Collateral of the quote is expired: {0}")]
+    CollateralExpired(u32),
     #[error("Failed to use SGX rng to generate random number: {0}")]
     SgxRngError(std::io::Error),
     #[error("Other SGX platform error: {0}")]
@@ -80,10 +91,45 @@ extern "C" {
         quote_size: u32,
     ) -> sgx_status_t;
 
+    /// Ocall to verify a quote with the quote verification library of the
+    /// platform.
+    fn ocall_sgx_verify_quote(
+        p_retval: *mut sgx_status_t,
+        p_quote: *const u8,
+        quote_size: u32,
+        expiration_check_date: i64,
+        p_collateral_expiration_status: *mut u32,
+        p_quote_verification_result: *mut u32,
+        p_qve_report_info: *mut sgx_qe_report_info_t,
+    ) -> sgx_status_t;
+
     /// OCall to get target information of myself.
     fn sgx_self_target(p_target_info: *mut sgx_target_info_t) -> sgx_status_t;
 }
 
+#[cfg(dcap)]
+extern "C" {
+    /// Verifies the QvE report in the trusted verification library of DCAP,
+    /// i.e., the report data binding the verification result to our nonce,
+    /// and the identity of the QvE.
+    fn sgx_tvl_verify_qve_report_and_identity(
+        p_quote: *const u8,
+        quote_size: u32,
+        p_qve_report_info: *const sgx_ql_qe_report_info_t,
+        expiration_check_date: i64,
+        collateral_expiration_status: u32,
+        quote_verification_result: sgx_ql_qv_result_t,
+        p_supplemental_data: *const u8,
+        supplemental_data_size: u32,
+        qve_isvsvn_threshold: sgx_isv_svn_t,
+    ) -> quote3_error_t;
+}
+
+/// Minimum ISV SVN of the QvE. Its MRSIGNER and ISVPRODID are checked against
+/// the ones of Intel by the trusted verification library.
+#[cfg(dcap)]
+const QVE_ISVSVN_THRESHOLD: sgx_isv_svn_t = 3;
+
 /// Initialize SGX quote, return attestation key ID selected by the platform and
 /// target information for creating report that only QE can verify.
 pub(crate) fn init_sgx_quote() -> Result<(sgx_att_key_id_t, sgx_target_info_t)> {
@@ -212,6 +258,110 @@ pub(crate) fn get_sgx_quote(ak_id: &sgx_att_key_id_t, report: sgx_report_t) -> R
     Ok(quote)
 }
 
+/// Convert the verification result given by the untrusted world, which can be
+/// any value.
+#[cfg(dcap)]
+fn qv_result(result: u32) -> sgx_ql_qv_result_t {
+    use sgx_ql_qv_result_t::*;
+    [
+        SGX_QL_QV_RESULT_OK,
+        SGX_QL_QV_RESULT_CONFIG_NEEDED,
+        SGX_QL_QV_RESULT_OUT_OF_DATE,
+        SGX_QL_QV_RESULT_OUT_OF_DATE_CONFIG_NEEDED,
+        SGX_QL_QV_RESULT_INVALID_SIGNATURE,
+        SGX_QL_QV_RESULT_REVOKED,
+        SGX_QL_QV_RESULT_SW_HARDENING_NEEDED,
+        SGX_QL_QV_RESULT_CONFIG_AND_SW_HARDENING_NEEDED,
+    ]
+    .iter()
+    .copied()
+    .find(|r| *r as u32 == result)
+    .unwrap_or(SGX_QL_QV_RESULT_UNSPECIFIED)
+}
+
+/// Verify the quote with the quote verification library of the platform, and
+/// return the verification result, i.e., `sgx_ql_qv_result_t`. The result is
+/// only trusted after the QvE report is verified to come from the QvE of
+/// Intel and to bind the result to this request.
+#[cfg(dcap)]
+pub(crate) fn verify_sgx_quote(quote: &[u8]) -> Result<u32> {
+    let mut rt = sgx_status_t::SGX_ERROR_UNEXPECTED;
+    let mut qve_report_info = sgx_qe_report_info_t::default();
+    let mut quote_nonce = sgx_quote_nonce_t::default();
+
+    let mut rng = SgxRng::new().map_err(PlatformError::SgxRngError)?;
+    rng.fill_bytes(&mut quote_nonce.rand);
+    qve_report_info.nonce = quote_nonce;
+
+    debug!("sgx_self_target");
+    // The QvE report targets ourselves, so that the result cannot be forged
+    // by the untrusted SW stack
+    let res = unsafe { sgx_self_target(&mut qve_report_info.app_enclave_target_info as _) };
+    if res != SGX_SUCCESS {
+        return Err(PlatformError::GetSelfTargetInfoError(res));
+    }
+
+    let expiration_check_date = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_err(|_| PlatformError::Others(sgx_status_t::SGX_ERROR_UNEXPECTED))?
+        .as_secs() as i64;
+    let mut collateral_expiration_status = 1u32;
+    let mut quote_verification_result = 0u32;
+
+    debug!("ocall_sgx_verify_quote");
+    let res = unsafe {
+        ocall_sgx_verify_quote(
+            &mut rt as _,
+            quote.as_ptr(),
+            quote.len() as u32,
+            expiration_check_date,
+            &mut collateral_expiration_status as _,
+            &mut quote_verification_result as _,
+            &mut qve_report_info as _,
+        )
+    };
+
+    if res != SGX_SUCCESS {
+        return Err(PlatformError::OCallError(
+            "ocall_sgx_verify_quote".to_string(),
+            res,
+        ));
+    }
+    if rt != SGX_SUCCESS {
+        return Err(PlatformError::VerifyQuoteError(rt));
+    }
+
+    // The QvE binds the verification to our request with report.data =
+    // SHA256(nonce||quote||expiration_check_date||collateral_expiration_status||result),
+    // and the report must be the one of the QvE, not of any enclave targeting
+    // us.
+    debug!("sgx_tvl_verify_qve_report_and_identity");
+    let ret = unsafe {
+        sgx_tvl_verify_qve_report_and_identity(
+            quote.as_ptr(),
+            quote.len() as u32,
+            &qve_report_info as *const sgx_qe_report_info_t as *const sgx_ql_qe_report_info_t,
+            expiration_check_date,
+            collateral_expiration_status,
+            qv_result(quote_verification_result),
+            std::ptr::null(),
+            0,
+            QVE_ISVSVN_THRESHOLD,
+        )
+    };
+    if ret != quote3_error_t::SGX_QL_SUCCESS {
+        return Err(PlatformError::QveIdentityError(ret));
+    }
+
+    if collateral_expiration_status != 0 {
+        return Err(PlatformError::CollateralExpired(
+            collateral_expiration_status,
+        ));
+    }
+
+    Ok(quote_verification_result)
+}
+
 #[cfg(all(feature = "enclave_unit_test", feature = "mesalock_sgx"))]
 pub mod tests {
     use super::*;
@@ -0,0 +1,347 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License.
+
+//! This module provides a declarative policy deciding which attestation
+//! reports are accepted, e.g., quote statuses and security versions.
+
+#[cfg(feature = "mesalock_sgx")]
+use std::prelude::v1::*;
+
+use crate::report::{AttestationReport, SgxQuoteStatus};
+use crate::verifier::AttestationReportPolicy;
+
+use std::convert::TryFrom;
+use std::time::Duration;
+
+use anyhow::{bail, ensure, Context, Result};
+use teaclave_config::{AttestationPolicyConfig, RuntimeConfig};
+
+/// Bit of the enclave attributes set when the enclave is launched in debug
+/// mode.
+const SGX_FLAGS_DEBUG: u8 = 0x02;
+
+/// Whether the quote content can be trusted at all. Reports in other statuses
+/// are refused by any policy.
+fn is_trustworthy(status: SgxQuoteStatus) -> bool {
+    match status {
+        SgxQuoteStatus::OK
+        | SgxQuoteStatus::GroupOutOfDate
+        | SgxQuoteStatus::ConfigurationNeeded
+        | SgxQuoteStatus::SwHardeningNeeded
+        | SgxQuoteStatus::ConfigurationAndSwHardeningNeeded
+        | SgxQuoteStatus::OutOfDate
+        | SgxQuoteStatus::OutOfDateConfigurationNeeded => true,
+        SgxQuoteStatus::SignatureInvalid
+        | SgxQuoteStatus::GroupRevoked
+        | SgxQuoteStatus::SignatureRevoked
+        | SgxQuoteStatus::KeyRevoked
+        | SgxQuoteStatus::SigrlVersionMismatch
+        | SgxQuoteStatus::InvalidSignature
+        | SgxQuoteStatus::UnknownBadStatus => false,
+    }
+}
+
+/// Attestation report policy built from accepted quote statuses and the
+/// minimum security versions of the platform and the enclave.
+#[derive(Clone, Debug)]
+pub struct AttestationPolicy {
+    accepted_quote_statuses: Vec<SgxQuoteStatus>,
+    min_isv_svn: u16,
+    min_cpu_svn: [u8; 16],
+    reject_debug_enclave: bool,
+    max_report_age: Option<Duration>,
+}
+
+impl Default for AttestationPolicy {
+    fn default() -> Self {
+        Self::from_config(&AttestationPolicyConfig::default())
+            .expect("The default attestation policy is valid")
+    }
+}
+
+impl AttestationPolicy {
+    /// Creates a policy accepting reports in the given quote statuses. Fails
+    /// if any of them means the quote is not trustworthy, e.g.,
+    /// `SignatureInvalid`.
+    pub fn new(accepted_quote_statuses: Vec<SgxQuoteStatus>) -> Result<Self> {
+        for status in &accepted_quote_statuses {
+            ensure!(
+                is_trustworthy(*status),
+                "Quote status {:?} cannot be accepted",
+                status
+            );
+        }
+
+        Ok(Self {
+            accepted_quote_statuses,
+            min_isv_svn: 0,
+            min_cpu_svn: [0; 16],
+            reject_debug_enclave: false,
+            max_report_age: None,
+        })
+    }
+
+    pub fn min_isv_svn(self, min_isv_svn: u16) -> Self {
+        Self {
+            min_isv_svn,
+            ..self
+        }
+    }
+
+    /// Every component of the CPU SVN must be at least the one given.
+    pub fn min_cpu_svn(self, min_cpu_svn: [u8; 16]) -> Self {
+        Self {
+            min_cpu_svn,
+            ..self
+        }
+    }
+
+    pub fn reject_debug_enclave(self, reject_debug_enclave: bool) -> Self {
+        Self {
+            reject_debug_enclave,
+            ..self
+        }
+    }
+
+    pub fn max_report_age(self, max_report_age: Duration) -> Self {
+        Self {
+            max_report_age: Some(max_report_age),
+            ..self
+        }
+    }
+
+    pub fn from_config(config: &AttestationPolicyConfig) -> Result<Self> {
+        let mut statuses = Vec::with_capacity(config.accepted_quote_statuses.len());
+        for name in &config.accepted_quote_statuses {
+            let status = SgxQuoteStatus::from(name.as_str());
+            ensure!(
+                is_trustworthy(status),
+                "Unknown or untrustworthy quote status {}",
+                name
+            );
+            statuses.push(status);
+        }
+
+        let mut policy = Self::new(statuses)?
+            .min_isv_svn(config.min_isv_svn)
+            .reject_debug_enclave(config.reject_debug_enclave);
+        if let Some(cpu_svn) = &config.min_cpu_svn {
+            let cpu_svn = hex::decode(cpu_svn).context("Illegal CPU SVN provided")?;
+            let cpu_svn =
+                <[u8; 16]>::try_from(cpu_svn.as_slice()).context("Illegal CPU SVN provided")?;
+            policy = policy.min_cpu_svn(cpu_svn);
+        }
+        if let Some(age) = config.max_report_age {
+            policy = policy.max_report_age(Duration::from_secs(age));
+        }
+
+        Ok(policy)
+    }
+
+    /// Creates the policy from Teaclave runtime configuration.
+    pub fn from_teaclave_config(config: &RuntimeConfig) -> Result<Self> {
+        Self::from_config(&config.attestation.policy)
+    }
+}
+
+impl AttestationReportPolicy for AttestationPolicy {
+    fn verify(&self, report: &AttestationReport) -> Result<()> {
+        if !self
+            .accepted_quote_statuses
+            .contains(&report.sgx_quote_status)
+        {
+            bail!("Quote status {:?} is not accepted", report.sgx_quote_status);
+        }
+
+        let enclave_report = &report.sgx_quote_body.isv_enclave_report;
+        ensure!(
+            enclave_report.isv_svn >= self.min_isv_svn,
+            "ISV SVN {} is lower than {}",
+            enclave_report.isv_svn,
+            self.min_isv_svn
+        );
+        ensure!(
+            enclave_report
+                .cpu_svn
+                .iter()
+                .zip(self.min_cpu_svn.iter())
+                .all(|(svn, min)| svn >= min),
+            "CPU SVN {} is lower than {}",
+            hex::encode(enclave_report.cpu_svn),
+            hex::encode(self.min_cpu_svn)
+        );
+        if self.reject_debug_enclave {
+            ensure!(
+                enclave_report.attributes[0] & SGX_FLAGS_DEBUG == 0,
+                "Debug enclave is not accepted"
+            );
+        }
+        if let Some(max_report_age) = self.max_report_age {
+            ensure!(
+                report.freshness <= max_report_age,
+                "Report is {} seconds old",
+                report.freshness.as_secs()
+            );
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(all(feature = "enclave_unit_test", feature = "mesalock_sgx"))]
+pub mod tests {
+    use super::*;
+    use crate::report::*;
+    use teaclave_test_utils::*;
+    use uuid::Uuid;
+
+    pub fn run_tests() -> bool {
+        run_tests!(
+            test_quote_status,
+            test_security_versions,
+            test_debug_enclave,
+            test_report_age,
+            test_from_config,
+        )
+    }
+
+    fn report(status: SgxQuoteStatus) -> AttestationReport {
+        let isv_enclave_report = SgxEnclaveReport {
+            cpu_svn: [5; 16],
+            misc_select: 0,
+            attributes: [7, 0, 0, 0, 0, 0, 0, 0, 7, 0, 0, 0, 0, 0, 0, 0],
+            mr_enclave: [0; 32],
+            mr_signer: [0; 32],
+            isv_prod_id: 0,
+            isv_svn: 2,
+            report_data: [0; 64],
+        };
+        AttestationReport {
+            freshness: Duration::from_secs(60),
+            timestamp: None,
+            sgx_quote_status: status,
+            advisory_ids: Vec::new(),
+            tcb_evaluation_data_number: None,
+            tcb_date: None,
+            sgx_quote_body: SgxQuote {
+                version: SgxQuoteVersion::V2(SgxEpidQuoteSigType::Linkable),
+                gid: 0,
+                isv_svn_qe: 0,
+                isv_svn_pce: 0,
+                qe_vendor_id: Uuid::nil(),
+                user_data: [0; 20],
+                isv_enclave_report,
+            },
+        }
+    }
+
+    fn test_quote_status() {
+        let policy = AttestationPolicy::new(vec![SgxQuoteStatus::OK]).unwrap();
+        assert!(policy.verify(&report(SgxQuoteStatus::OK)).is_ok());
+        assert!(policy
+            .verify(&report(SgxQuoteStatus::GroupOutOfDate))
+            .is_err());
+
+        let policy = AttestationPolicy::default();
+        assert!(policy.verify(&report(SgxQuoteStatus::OK)).is_ok());
+        assert!(policy
+            .verify(&report(SgxQuoteStatus::SwHardeningNeeded))
+            .is_ok());
+        assert!(policy
+            .verify(&report(SgxQuoteStatus::GroupOutOfDate))
+            .is_err());
+        assert!(policy.verify(&report(SgxQuoteStatus::OutOfDate)).is_err());
+        assert!(policy
+            .verify(&report(SgxQuoteStatus::SignatureInvalid))
+            .is_err());
+        assert!(policy
+            .verify(&report(SgxQuoteStatus::UnknownBadStatus))
+            .is_err());
+
+        assert!(AttestationPolicy::new(vec![SgxQuoteStatus::KeyRevoked]).is_err());
+    }
+
+    fn test_security_versions() {
+        let policy = AttestationPolicy::new(vec![SgxQuoteStatus::OK]).unwrap();
+        assert!(policy
+            .clone()
+            .min_isv_svn(2)
+            .verify(&report(SgxQuoteStatus::OK))
+            .is_ok());
+        assert!(policy
+            .clone()
+            .min_isv_svn(3)
+            .verify(&report(SgxQuoteStatus::OK))
+            .is_err());
+
+        let mut cpu_svn = [5; 16];
+        assert!(policy
+            .clone()
+            .min_cpu_svn(cpu_svn)
+            .verify(&report(SgxQuoteStatus::OK))
+            .is_ok());
+        cpu_svn[15] = 6;
+        assert!(policy
+            .min_cpu_svn(cpu_svn)
+            .verify(&report(SgxQuoteStatus::OK))
+            .is_err());
+    }
+
+    fn test_debug_enclave() {
+        let policy = AttestationPolicy::new(vec![SgxQuoteStatus::OK]).unwrap();
+        let mut debug_report = report(SgxQuoteStatus::OK);
+        debug_report.sgx_quote_body.isv_enclave_report.attributes[0] |= SGX_FLAGS_DEBUG;
+        let mut release_report = report(SgxQuoteStatus::OK);
+        release_report.sgx_quote_body.isv_enclave_report.attributes[0] &= !SGX_FLAGS_DEBUG;
+
+        assert!(policy.verify(&debug_report).is_ok());
+        let policy = policy.reject_debug_enclave(true);
+        assert!(policy.verify(&debug_report).is_err());
+        assert!(policy.verify(&release_report).is_ok());
+    }
+
+    fn test_report_age() {
+        let policy = AttestationPolicy::new(vec![SgxQuoteStatus::OK]).unwrap();
+        assert!(policy
+            .clone()
+            .max_report_age(Duration::from_secs(60))
+            .verify(&report(SgxQuoteStatus::OK))
+            .is_ok());
+        assert!(policy
+            .max_report_age(Duration::from_secs(59))
+            .verify(&report(SgxQuoteStatus::OK))
+            .is_err());
+    }
+
+    fn test_from_config() {
+        let mut config = AttestationPolicyConfig::default();
+        config.accepted_quote_statuses = vec!["OK".to_string()];
+        config.min_cpu_svn = Some("06".repeat(16));
+        let policy = AttestationPolicy::from_config(&config).unwrap();
+        assert!(policy.verify(&report(SgxQuoteStatus::OK)).is_err());
+
+        config.min_cpu_svn = Some("0506".to_string());
+        assert!(AttestationPolicy::from_config(&config).is_err());
+
+        config.min_cpu_svn = None;
+        config.accepted_quote_statuses = vec!["SIGNATURE_INVALID".to_string()];
+        assert!(AttestationPolicy::from_config(&config).is_err());
+
+        config.accepted_quote_statuses = vec!["NOT_A_STATUS".to_string()];
+        assert!(AttestationPolicy::from_config(&config).is_err());
+    }
+}
@@ -197,7 +197,7 @@ pub enum SgxEcdsaQuoteAkType {
 }
 
 /// SGX Quote status
-#[derive(PartialEq, Debug)]
+#[derive(Clone, Copy, PartialEq, Debug)]
 pub enum SgxQuoteStatus {
     /// EPID signature of the ISV enclave QUOTE was verified correctly and the
     /// TCB level of the SGX platform is up-to-date.
@@ -342,7 +342,7 @@ impl std::fmt::Debug for SgxQuote {
 
 impl SgxQuote {
     /// Parse from bytes to `SgxQuote`.
-    fn parse_from<'a>(bytes: &'a [u8]) -> Result<Self> {
+    pub(crate) fn parse_from<'a>(bytes: &'a [u8]) -> Result<Self> {
         let mut pos: usize = 0;
         let mut take = |n: usize| -> Result<&'a [u8]> {
             if n > 0 && bytes.len() >= pos + n {
@@ -426,30 +426,63 @@ pub struct AttestationReport {
     /// The freshness of the report, i.e., elapsed time after acquiring the
     /// report in seconds.
     pub freshness: Duration,
+    /// Time the report is issued by the attestation service in seconds since
+    /// the Unix epoch. Unlike the local time, it is signed with the report.
+    /// Not given for quotes verified by the peer itself.
+    pub timestamp: Option<u64>,
     /// Quote status
     pub sgx_quote_status: SgxQuoteStatus,
+    /// Intel security advisories affecting the platform, e.g.,
+    /// "INTEL-SA-00161", given when the quote status is not `OK`.
+    pub advisory_ids: Vec<String>,
+    /// TCB evaluation data number of the TCB info the platform is evaluated
+    /// with, given by DCAP attestation services only.
+    pub tcb_evaluation_data_number: Option<u64>,
+    /// Date of the TCB level of the platform, given by DCAP attestation
+    /// services only.
+    pub tcb_date: Option<String>,
     /// Content of the quote
     pub sgx_quote_body: SgxQuote,
 }
 
+/// Extract the endorsed attestation report and the public key from a X509
+/// certificate.
+fn endorsed_report_from_cert(cert: &[u8]) -> Result<(EndorsedAttestationReport, bit_vec::BitVec)> {
+    use crate::cert::*;
+
+    // Extract information for attestation from TLS certification.
+    let x509 = yasna::parse_der(cert, X509::load)?;
+    let tbs_cert: <TbsCert as Asn1Ty>::ValueTy = x509.0;
+    let pub_key: <PubKey as Asn1Ty>::ValueTy = ((((((tbs_cert.1).1).1).1).1).1).0;
+    let pub_k = (pub_key.1).0;
+    let cert_ext: <SgxRaCertExt as Asn1Ty>::ValueTy = (((((((tbs_cert.1).1).1).1).1).1).1).0;
+    let cert_ext_payload: Vec<u8> = ((cert_ext.0).1).0;
+
+    // Convert to endorsed report
+    let report: EndorsedAttestationReport = serde_json::from_slice(&cert_ext_payload)?;
+
+    Ok((report, pub_k))
+}
+
 impl AttestationReport {
+    /// Check whether a X509 certificate carries no endorsed attestation report,
+    /// i.e., it is generated by an enclave without attestation.
+    pub fn is_unattested_cert(cert: &[u8]) -> bool {
+        match endorsed_report_from_cert(cert) {
+            Ok((report, _)) => report.signing_cert.is_empty() && report.quote.is_empty(),
+            Err(_) => false,
+        }
+    }
+
     /// Construct a AttestationReport from a X509 certificate and verify
     /// attestation report with the report_ca_cert which is from the attestation
     /// service provider.
     pub fn from_cert(cert: &[u8], report_ca_cert: &[u8]) -> Result<Self> {
         // Before we reach here, Webpki already verifed the cert is properly signed.
-        use crate::cert::*;
-
-        // Extract information for attestation from TLS certification.
-        let x509 = yasna::parse_der(cert, X509::load)?;
-        let tbs_cert: <TbsCert as Asn1Ty>::ValueTy = x509.0;
-        let pub_key: <PubKey as Asn1Ty>::ValueTy = ((((((tbs_cert.1).1).1).1).1).1).0;
-        let pub_k = (pub_key.1).0;
-        let cert_ext: <SgxRaCertExt as Asn1Ty>::ValueTy = (((((((tbs_cert.1).1).1).1).1).1).1).0;
-        let cert_ext_payload: Vec<u8> = ((cert_ext.0).1).0;
-
-        // Convert to endorsed report
-        let report: EndorsedAttestationReport = serde_json::from_slice(&cert_ext_payload)?;
+        let (report, pub_k) = endorsed_report_from_cert(cert)?;
+        if !report.quote.is_empty() {
+            return Self::from_quote(&report.quote, &pub_k);
+        }
 
         // Verify report's signature
         let signing_cert = webpki::EndEntityCert::from(&report.signing_cert)?;
@@ -490,8 +523,8 @@ impl AttestationReport {
             .ok_or_else(|| Error::new(AttestationError::ReportError))?;
         ensure!(version == 4, AttestationError::ApiVersionNotCompatible);
 
-        // Get quote freshness
-        let freshness = {
+        // Get quote timestamp and freshness
+        let (timestamp, freshness) = {
             let time = attn_report["timestamp"]
                 .as_str()
                 .ok_or_else(|| Error::new(AttestationError::ReportError))?;
@@ -500,7 +533,10 @@ impl AttestationReport {
             let ts = date_time.naive_utc();
             let now = DateTime::<chrono::offset::Utc>::from(SystemTime::now()).naive_utc();
             let quote_freshness = u64::try_from((now - ts).num_seconds())?;
-            std::time::Duration::from_secs(quote_freshness)
+            (
+                u64::try_from(ts.timestamp())?,
+                std::time::Duration::from_secs(quote_freshness),
+            )
         };
 
         // Get quote status
@@ -511,6 +547,28 @@ impl AttestationReport {
             SgxQuoteStatus::from(status_string)
         };
 
+        // Get advisory IDs, which are only present for some quote statuses
+        let advisory_ids = match attn_report.get("advisoryIDs") {
+            Some(ids) => ids
+                .as_array()
+                .ok_or_else(|| Error::new(AttestationError::ReportError))?
+                .iter()
+                .map(|id| id.as_str().map(String::from))
+                .collect::<Option<Vec<String>>>()
+                .ok_or_else(|| Error::new(AttestationError::ReportError))?,
+            None => Vec::new(),
+        };
+
+        // Get TCB info, which is only present in reports of DCAP attestation
+        // services
+        let tcb_evaluation_data_number = attn_report
+            .get("tcbEvaluationDataNumber")
+            .and_then(Value::as_u64);
+        let tcb_date = attn_report
+            .get("tcbDate")
+            .and_then(Value::as_str)
+            .map(String::from);
+
         // Get quote body
         let sgx_quote_body = {
             let quote_encoded = attn_report["isvEnclaveQuoteBody"]
@@ -520,28 +578,66 @@ impl AttestationReport {
             SgxQuote::parse_from(quote_raw.as_slice())?
         };
 
-        // According to RFC 5480 `Elliptic Curve Cryptography Subject Public Key
-        // Information', SEC 2.2: ``The first octet of the OCTET STRING
-        // indicates whether the key is compressed or uncompressed. The
-        // uncompressed form is indicated by 0x04 and the compressed form is
-        // indicated by either 0x02 or 0x03 (see 2.3.3 in [SEC1]). The public
-        // key MUST be rejected if any other value is included in the first
-        // octet.''
-        //
-        // We only accept the uncompressed form here.
-        let raw_pub_k = pub_k.to_bytes();
-        let is_uncompressed = raw_pub_k[0] == 4;
-        let pub_k = &raw_pub_k.as_slice()[1..];
-        if !is_uncompressed || pub_k != &sgx_quote_body.isv_enclave_report.report_data[..] {
-            bail!(AttestationError::ReportError);
-        }
+        verify_report_data(&pub_k, &sgx_quote_body)?;
 
         Ok(Self {
             freshness,
+            timestamp: Some(timestamp),
             sgx_quote_status,
+            advisory_ids,
+            tcb_evaluation_data_number,
+            tcb_date,
+            sgx_quote_body,
+        })
+    }
+
+    /// Construct a AttestationReport from a quote put in the certificate by
+    /// the `dcap_direct` backend, which is verified with the quote
+    /// verification enclave of this platform. The report is as fresh as the
+    /// verification.
+    #[cfg(feature = "mesalock_sgx")]
+    fn from_quote(quote: &[u8], pub_k: &bit_vec::BitVec) -> Result<Self> {
+        let (status, quote_body) = crate::dcap_direct::verify_quote(quote)?;
+        let sgx_quote_body = SgxQuote::parse_from(quote_body)?;
+        verify_report_data(pub_k, &sgx_quote_body)?;
+
+        Ok(Self {
+            freshness: Duration::from_secs(0),
+            timestamp: None,
+            sgx_quote_status: SgxQuoteStatus::from(status),
+            advisory_ids: Vec::new(),
+            tcb_evaluation_data_number: None,
+            tcb_date: None,
             sgx_quote_body,
         })
     }
+
+    #[cfg(not(feature = "mesalock_sgx"))]
+    fn from_quote(_quote: &[u8], _pub_k: &bit_vec::BitVec) -> Result<Self> {
+        bail!("Quotes without an attestation report can only be verified in an enclave")
+    }
+}
+
+/// Check that the report data of the quote is the public key of the
+/// certificate.
+fn verify_report_data(pub_k: &bit_vec::BitVec, sgx_quote_body: &SgxQuote) -> Result<()> {
+    // According to RFC 5480 `Elliptic Curve Cryptography Subject Public Key
+    // Information', SEC 2.2: ``The first octet of the OCTET STRING
+    // indicates whether the key is compressed or uncompressed. The
+    // uncompressed form is indicated by 0x04 and the compressed form is
+    // indicated by either 0x02 or 0x03 (see 2.3.3 in [SEC1]). The public
+    // key MUST be rejected if any other value is included in the first
+    // octet.''
+    //
+    // We only accept the uncompressed form here.
+    let raw_pub_k = pub_k.to_bytes();
+    let is_uncompressed = raw_pub_k[0] == 4;
+    let pub_k = &raw_pub_k.as_slice()[1..];
+    if !is_uncompressed || pub_k != &sgx_quote_body.isv_enclave_report.report_data[..] {
+        bail!(AttestationError::ReportError);
+    }
+
+    Ok(())
 }
 
 #[cfg(all(feature = "enclave_unit_test", feature = "mesalock_sgx"))]
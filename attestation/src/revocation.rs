@@ -0,0 +1,200 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License.
+
+//! This module provides the revocation list of enclave measurements. A list
+//! signed by the auditors is installed for the whole process and consulted by
+//! every [`AttestationReportVerifier`](../verifier/struct.AttestationReportVerifier.html),
+//! so that a vulnerable build can be refused without redeploying services.
+
+#[cfg(feature = "mesalock_sgx")]
+use std::prelude::v1::*;
+
+#[cfg(not(feature = "mesalock_sgx"))]
+use std::sync::RwLock;
+#[cfg(feature = "mesalock_sgx")]
+use std::sync::SgxRwLock as RwLock;
+
+use std::sync::Arc;
+
+use anyhow::{ensure, Context, Result};
+use lazy_static::lazy_static;
+use log::info;
+use serde::Deserialize;
+use teaclave_types::{EnclaveInfo, SgxMeasurement};
+
+lazy_static! {
+    static ref REVOCATION_LIST: RwLock<Arc<RevocationList>> =
+        RwLock::new(Arc::new(RevocationList::default()));
+}
+
+/// An enclave measurement in the revocation list
+#[derive(Debug, Deserialize)]
+pub struct RevokedEnclave {
+    #[serde(deserialize_with = "teaclave_types::from_hex")]
+    pub mr_enclave: SgxMeasurement,
+    /// Reports issued by the attestation service before this time in seconds
+    /// since the Unix epoch are still accepted, e.g., while the fixed build is
+    /// rolled out. The enclave is revoked right away if it is not given.
+    #[serde(default)]
+    pub valid_until: Option<u64>,
+}
+
+/// Revocation list of enclave measurements, e.g.,
+///
+/// ```toml
+/// version = 2
+///
+/// [[revoked]]
+/// mr_enclave = "8c39a6...b3d1"
+/// valid_until = 1593561600
+/// ```
+#[derive(Debug, Default, Deserialize)]
+pub struct RevocationList {
+    /// Version of the list. A list only replaces lists of lower versions, so
+    /// a stale list cannot be replayed to lift revocations.
+    pub version: u64,
+    #[serde(default)]
+    pub revoked: Vec<RevokedEnclave>,
+}
+
+impl RevocationList {
+    /// Parse the list after verifying it is signed by every auditor.
+    pub fn verify_and_new<T, U>(list: &[u8], public_keys: &[T], signatures: &[U]) -> Result<Self>
+    where
+        T: AsRef<[u8]>,
+        U: AsRef<[u8]>,
+    {
+        ensure!(!signatures.is_empty(), "Revocation list is not signed");
+        ensure!(
+            signatures.len() == public_keys.len(),
+            "Invalid number of signatures"
+        );
+        ensure!(
+            public_keys
+                .iter()
+                .all(|k| signatures
+                    .iter()
+                    .any(|s| EnclaveInfo::verify(list, &[k], &[s]))),
+            "Invalid revocation list signature"
+        );
+
+        Self::from_bytes(list)
+    }
+
+    pub fn from_bytes(list: &[u8]) -> Result<Self> {
+        toml::from_slice(list).context("Invalid revocation list")
+    }
+
+    /// Whether reports of the enclave issued at the given time are refused.
+    /// The time comes from the signed report rather than the untrusted local
+    /// clock, and a report without it is refused once the enclave is listed.
+    pub fn is_revoked(&self, mr_enclave: &SgxMeasurement, issued_at: Option<u64>) -> bool {
+        self.revoked.iter().any(|r| {
+            &r.mr_enclave == mr_enclave
+                && match (r.valid_until, issued_at) {
+                    (Some(until), Some(issued_at)) => issued_at >= until,
+                    _ => true,
+                }
+        })
+    }
+}
+
+/// Install the list for all verifiers in this process if it is newer than the
+/// installed one. Returns whether it is installed.
+pub fn install(list: RevocationList) -> bool {
+    let mut current = REVOCATION_LIST.write().unwrap();
+    if list.version <= current.version {
+        return false;
+    }
+
+    info!(
+        "Revocation list updated to version {} with {} enclaves",
+        list.version,
+        list.revoked.len()
+    );
+    *current = Arc::new(list);
+    true
+}
+
+/// The revocation list installed in this process.
+pub fn current() -> Arc<RevocationList> {
+    REVOCATION_LIST.read().unwrap().clone()
+}
+
+#[cfg(all(feature = "enclave_unit_test", feature = "mesalock_sgx"))]
+pub mod tests {
+    use super::*;
+    use teaclave_test_utils::*;
+
+    const LIST: &str = r#"
+version = 2
+
+[[revoked]]
+mr_enclave = "0101010101010101010101010101010101010101010101010101010101010101"
+
+[[revoked]]
+mr_enclave = "0202020202020202020202020202020202020202020202020202020202020202"
+valid_until = 1000
+"#;
+
+    pub fn run_tests() -> bool {
+        run_tests!(
+            test_is_revoked,
+            test_unsigned_list,
+            test_install_newer_version,
+        )
+    }
+
+    fn test_is_revoked() {
+        let list = RevocationList::from_bytes(LIST.as_bytes()).unwrap();
+        let before = Some(999);
+        let after = Some(1000);
+
+        assert!(list.is_revoked(&[1; 32], before));
+        assert!(!list.is_revoked(&[2; 32], before));
+        assert!(list.is_revoked(&[2; 32], after));
+        assert!(list.is_revoked(&[2; 32], None));
+        assert!(!list.is_revoked(&[3; 32], after));
+        assert!(!list.is_revoked(&[3; 32], None));
+    }
+
+    fn test_unsigned_list() {
+        let public_keys: &[&[u8]] = &[b"key"];
+        let signatures: &[&[u8]] = &[];
+        assert!(RevocationList::verify_and_new(LIST.as_bytes(), public_keys, signatures).is_err());
+
+        let signatures: &[&[u8]] = &[b"signature"];
+        assert!(RevocationList::verify_and_new(LIST.as_bytes(), public_keys, signatures).is_err());
+
+        // Every auditor has to sign the list.
+        let public_keys: &[&[u8]] = &[b"key", b"another key"];
+        assert!(RevocationList::verify_and_new(LIST.as_bytes(), public_keys, signatures).is_err());
+    }
+
+    fn test_install_newer_version() {
+        let version = current().version;
+        let list = |version| RevocationList {
+            version,
+            revoked: Vec::new(),
+        };
+
+        assert!(install(list(version + 1)));
+        assert!(!install(list(version + 1)));
+        assert!(!install(list(version)));
+        assert_eq!(current().version, version + 1);
+    }
+}
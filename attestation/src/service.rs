@@ -18,8 +18,11 @@
 //! This module provide API to communicate with attestation service (AS) to get
 //! attestation report endorsed by AS.
 
+use crate::dcap_direct::DcapDirectBackend;
+use crate::mock::MockBackend;
 use crate::platform;
 use crate::AttestationAlgorithm;
+use crate::AttestationBackend;
 use crate::AttestationServiceConfig;
 use crate::EndorsedAttestationReport;
 
@@ -70,29 +73,58 @@ pub(crate) enum AttestationServiceError {
     Unknown,
 }
 
+/// Endorses the quote of this enclave, i.e., produces the attestation report
+/// embedded in the attested TLS certificate.
+pub(crate) trait AttestationServiceBackend {
+    /// Attests this enclave with `pub_k` bound in the report data of its quote.
+    fn attest(&self, pub_k: sgx_ec256_public_t) -> Result<EndorsedAttestationReport>;
+}
+
 impl EndorsedAttestationReport {
     pub(crate) fn new(
         att_service_cfg: &AttestationServiceConfig,
         pub_k: sgx_types::sgx_ec256_public_t,
     ) -> anyhow::Result<Self> {
+        let backend: Box<dyn AttestationServiceBackend> = match att_service_cfg.backend {
+            AttestationBackend::Ias => Box::new(IasBackend::new(att_service_cfg)),
+            AttestationBackend::DcapDirect => Box::new(DcapDirectBackend::new()?),
+            AttestationBackend::Mock => Box::new(MockBackend::new()?),
+        };
+
+        backend.attest(pub_k)
+    }
+}
+
+/// Gets the report from the Intel Attestation Service, or a service with the
+/// same report API like the reference DCAP attestation service.
+struct IasBackend<'a> {
+    config: &'a AttestationServiceConfig,
+}
+
+impl<'a> IasBackend<'a> {
+    fn new(config: &'a AttestationServiceConfig) -> Self {
+        Self { config }
+    }
+}
+
+impl<'a> AttestationServiceBackend for IasBackend<'a> {
+    fn attest(&self, pub_k: sgx_ec256_public_t) -> Result<EndorsedAttestationReport> {
         let (mut ak_id, qe_target_info) = platform::init_sgx_quote()?;
 
         // For IAS-based attestation, we need to fill our SPID (obtained from Intel)
         // into the attestation key id. For DCAP-based attestation, SPID should be 0
         const SPID_OFFSET: usize = std::mem::size_of::<sgx_ql_att_key_id_t>();
-        ak_id.att_key_id[SPID_OFFSET..(SPID_OFFSET + att_service_cfg.spid.id.len())]
-            .clone_from_slice(&att_service_cfg.spid.id);
+        ak_id.att_key_id[SPID_OFFSET..(SPID_OFFSET + self.config.spid.id.len())]
+            .clone_from_slice(&self.config.spid.id);
 
         let sgx_report = platform::create_sgx_isv_enclave_report(pub_k, qe_target_info)?;
         let quote = platform::get_sgx_quote(&ak_id, sgx_report)?;
-        let as_report = get_report(
-            &att_service_cfg.algo,
-            &att_service_cfg.as_url,
-            &att_service_cfg.api_key,
+        get_report(
+            &self.config.algo,
+            &self.config.as_url,
+            &self.config.api_key,
             &quote,
-        )?;
-
-        Ok(as_report)
+        )
     }
 }
 
@@ -251,6 +283,7 @@ fn get_report(
         report,
         signature,
         signing_cert,
+        quote: Vec::new(),
     })
 }
 
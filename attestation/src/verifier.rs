@@ -18,14 +18,41 @@
 //! This module provides types used to verify attestation reports.
 
 use crate::report::AttestationReport;
+use crate::revocation;
 
+use std::sync::Arc;
 use std::vec::Vec;
 
+use anyhow::{ensure, Result};
 use log::{debug, error};
 use teaclave_types::EnclaveAttr;
 
-/// User defined verification function to further verify the attestation report.
-pub type AttestationReportVerificationFn = fn(&AttestationReport) -> bool;
+/// Decides whether an attestation report, whose signature has been verified,
+/// is accepted. Policies are trait objects so that they can carry state,
+/// e.g., [`AttestationPolicy`](../policy/struct.AttestationPolicy.html).
+pub trait AttestationReportPolicy: Send + Sync {
+    /// Returns an error explaining why the report is refused.
+    fn verify(&self, report: &AttestationReport) -> Result<()>;
+}
+
+impl<F> AttestationReportPolicy for F
+where
+    F: Fn(&AttestationReport) -> bool + Send + Sync,
+{
+    fn verify(&self, report: &AttestationReport) -> Result<()> {
+        ensure!(self(report), "Attestation report is refused");
+        Ok(())
+    }
+}
+
+impl<P> AttestationReportPolicy for Arc<P>
+where
+    P: AttestationReportPolicy + ?Sized,
+{
+    fn verify(&self, report: &AttestationReport) -> Result<()> {
+        (**self).verify(report)
+    }
+}
 
 /// Type used to verify attestation reports (this can be set as a certificate
 /// verifier in `rustls::ClientConfig`).
@@ -36,31 +63,26 @@ pub struct AttestationReportVerifier {
     pub accepted_enclave_attrs: Vec<EnclaveAttr>,
     /// Root certificate of the attestation service provider (e.g., IAS).
     pub root_ca: Vec<u8>,
-    /// User defined function to verify the attestation report.
-    pub verifier: AttestationReportVerificationFn,
-}
-
-/// Checks if he quote's status is not `UnknownBadStatus`
-pub fn universal_quote_verifier(report: &AttestationReport) -> bool {
-    debug!("report.sgx_quote_status: {:?}", report.sgx_quote_status);
-    report.sgx_quote_status != crate::report::SgxQuoteStatus::UnknownBadStatus
+    /// Policy further deciding whether to accept the attestation report.
+    pub policy: Arc<dyn AttestationReportPolicy>,
 }
 
 impl AttestationReportVerifier {
     pub fn new(
         accepted_enclave_attrs: Vec<EnclaveAttr>,
         root_ca: &[u8],
-        verifier: AttestationReportVerificationFn,
+        policy: impl AttestationReportPolicy + 'static,
     ) -> Self {
         Self {
             accepted_enclave_attrs,
             root_ca: root_ca.to_vec(),
-            verifier,
+            policy: Arc::new(policy),
         }
     }
 
     /// Verify whether the `MR_SIGNER` and `MR_ENCLAVE` in the attestation report is
-    /// accepted by us, which are defined in `accepted_enclave_attrs`.
+    /// accepted by us, which are defined in `accepted_enclave_attrs`, and the
+    /// `MR_ENCLAVE` is not revoked by the installed revocation list.
     fn verify_measures(&self, attestation_report: &AttestationReport) -> bool {
         debug!("verify measures");
         let this_mr_signer = attestation_report
@@ -72,6 +94,11 @@ impl AttestationReportVerifier {
             .isv_enclave_report
             .mr_enclave;
 
+        if revocation::current().is_revoked(&this_mr_enclave, attestation_report.timestamp) {
+            error!("enclave {} is revoked", hex::encode(this_mr_enclave));
+            return false;
+        }
+
         self.accepted_enclave_attrs.iter().any(|a| {
             a.measurement.mr_signer == this_mr_signer && a.measurement.mr_enclave == this_mr_enclave
         })
@@ -80,7 +107,9 @@ impl AttestationReportVerifier {
     /// Verify TLS certificate.
     fn verify_cert(&self, cert_der: &[u8]) -> bool {
         debug!("verify cert");
-        if cfg!(sgx_sim) {
+        // Enclaves in simulation mode are not attested unless they use the
+        // mock attestation service backend
+        if cfg!(sgx_sim) && AttestationReport::is_unattested_cert(cert_der) {
             return true;
         }
 
@@ -94,11 +123,17 @@ impl AttestationReportVerifier {
 
         // Enclave measures are not tested in test mode since we have
         // a dedicated test enclave not known to production enclaves
-        if cfg!(test_mode) {
-            return (self.verifier)(&report);
+        if !cfg!(test_mode) && !self.verify_measures(&report) {
+            return false;
         }
 
-        self.verify_measures(&report) && (self.verifier)(&report)
+        match self.policy.verify(&report) {
+            Ok(()) => true,
+            Err(e) => {
+                error!("attestation report refused by policy: {:?}", e);
+                false
+            }
+        }
     }
 }
 
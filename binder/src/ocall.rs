@@ -112,3 +112,65 @@ pub extern "C" fn ocall_sgx_get_quote(
         )
     }
 }
+
+#[cfg(dcap)]
+#[link(name = "sgx_dcap_quoteverify")]
+extern "C" {
+    fn sgx_qv_verify_quote(
+        p_quote: *const u8,
+        quote_size: u32,
+        p_quote_collateral: *const sgx_ql_qve_collateral_t,
+        expiration_check_date: i64,
+        p_collateral_expiration_status: *mut u32,
+        p_quote_verification_result: *mut sgx_ql_qv_result_t,
+        p_qve_report_info: *mut sgx_ql_qe_report_info_t,
+        supplemental_data_size: u32,
+        p_supplemental_data: *mut u8,
+    ) -> quote3_error_t;
+}
+
+#[cfg(dcap)]
+#[no_mangle]
+pub extern "C" fn ocall_sgx_verify_quote(
+    p_quote: *const u8,
+    quote_size: u32,
+    expiration_check_date: i64,
+    p_collateral_expiration_status: *mut u32,
+    p_quote_verification_result: *mut u32,
+    p_qve_report_info: *mut sgx_qe_report_info_t,
+) -> sgx_status_t {
+    // Collateral is fetched by the quote provider library of the platform.
+    let ret = unsafe {
+        sgx_qv_verify_quote(
+            p_quote,
+            quote_size,
+            ptr::null(),
+            expiration_check_date,
+            p_collateral_expiration_status,
+            p_quote_verification_result as _,
+            p_qve_report_info as _,
+            0,
+            ptr::null_mut(),
+        )
+    };
+
+    if ret != quote3_error_t::SGX_QL_SUCCESS {
+        log::error!("sgx_qv_verify_quote failed: {:?}", ret);
+        return sgx_status_t::SGX_ERROR_UNEXPECTED;
+    }
+
+    sgx_status_t::SGX_SUCCESS
+}
+
+#[cfg(not(dcap))]
+#[no_mangle]
+pub extern "C" fn ocall_sgx_verify_quote(
+    _p_quote: *const u8,
+    _quote_size: u32,
+    _expiration_check_date: i64,
+    _p_collateral_expiration_status: *mut u32,
+    _p_quote_verification_result: *mut u32,
+    _p_qve_report_info: *mut sgx_qe_report_info_t,
+) -> sgx_status_t {
+    sgx_status_t::SGX_ERROR_FEATURE_NOT_SUPPORTED
+}
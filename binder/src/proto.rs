@@ -24,6 +24,7 @@ pub enum ECallCommand {
     InitEnclave,
     FinalizeEnclave,
     RunTest,
+    GetMetrics,
     Unimplemented,
 }
 
@@ -35,6 +36,7 @@ impl From<u32> for ECallCommand {
             0x0000_1001 => ECallCommand::InitEnclave,
             0x0000_1002 => ECallCommand::FinalizeEnclave,
             0x0000_1003 => ECallCommand::RunTest,
+            0x0000_1004 => ECallCommand::GetMetrics,
             _ => ECallCommand::Unimplemented,
         }
     }
@@ -48,6 +50,7 @@ impl Into<u32> for ECallCommand {
             ECallCommand::InitEnclave => 0x0000_1001,
             ECallCommand::FinalizeEnclave => 0x0000_1002,
             ECallCommand::RunTest => 0x0000_1003,
+            ECallCommand::GetMetrics => 0x0000_1004,
             ECallCommand::Unimplemented => 0xffff_ffff,
         }
     }
@@ -92,3 +95,18 @@ impl RunTestInput {
 
 #[derive(Serialize, Deserialize, Debug)]
 pub struct RunTestOutput;
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct GetMetricsInput;
+
+/// Metrics of the service in the Prometheus text format
+#[derive(Serialize, Deserialize, Debug)]
+pub struct GetMetricsOutput {
+    pub metrics: String,
+}
+
+impl GetMetricsOutput {
+    pub fn new(metrics: String) -> Self {
+        Self { metrics }
+    }
+}
@@ -0,0 +1,293 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License.
+
+use crate::config::ClientConfig;
+use anyhow::{anyhow, bail, ensure, Context, Result};
+use rustls::Session;
+use std::fs;
+use std::net::TcpStream;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use structopt::StructOpt;
+use teaclave_attestation::policy::AttestationPolicy;
+use teaclave_attestation::report::AttestationReport;
+use teaclave_attestation::revocation::RevocationList;
+use teaclave_attestation::verifier::AttestationReportPolicy;
+use teaclave_config::build::{AS_ROOT_CA_CERT, AUDITOR_PUBLIC_KEYS};
+use teaclave_config::AttestationPolicyConfig;
+use teaclave_types::EnclaveInfo;
+
+#[derive(Debug, StructOpt)]
+pub(crate) struct AttestOpt {
+    /// Address of the service to fetch the certificate from, e.g.,
+    /// "localhost:7777"
+    #[structopt(long, required_unless = "cert", conflicts_with = "cert")]
+    address: Option<String>,
+
+    /// Path of a certificate in DER format, instead of connecting to a service
+    #[structopt(long)]
+    cert: Option<PathBuf>,
+
+    /// Root CA certificate of the attestation service in PEM format, the one
+    /// built in by default
+    #[structopt(long = "as-root-ca-cert")]
+    as_root_ca_cert: Option<PathBuf>,
+
+    /// Path of enclave_info.toml to check the measurements against, the one
+    /// of the config file by default
+    #[structopt(long = "enclave-info")]
+    enclave_info: Option<PathBuf>,
+
+    /// Auditor signature of the enclave info, can be repeated, the ones of
+    /// the config file by default
+    #[structopt(long = "auditor-signature")]
+    auditor_signatures: Vec<PathBuf>,
+
+    /// Enclave the measurements are expected to match, e.g.,
+    /// "teaclave_frontend_service"
+    #[structopt(long)]
+    service: Option<String>,
+
+    /// Path of a revocation list to check the measurements against
+    #[structopt(long = "revocation-list")]
+    revocation_list: Option<PathBuf>,
+
+    /// Auditor signature of the revocation list, can be repeated
+    #[structopt(long = "revocation-list-signature", requires = "revocation-list")]
+    revocation_list_signatures: Vec<PathBuf>,
+}
+
+/// Accepts any server certificate and keeps it for inspection.
+#[derive(Default)]
+struct CertCollector {
+    cert: Mutex<Option<Vec<u8>>>,
+}
+
+impl rustls::ServerCertVerifier for CertCollector {
+    fn verify_server_cert(
+        &self,
+        _roots: &rustls::RootCertStore,
+        certs: &[rustls::Certificate],
+        _hostname: webpki::DNSNameRef<'_>,
+        _ocsp: &[u8],
+    ) -> Result<rustls::ServerCertVerified, rustls::TLSError> {
+        if let Some(cert) = certs.first() {
+            *self.cert.lock().unwrap() = Some(cert.0.clone());
+        }
+        Ok(rustls::ServerCertVerified::assertion())
+    }
+}
+
+fn fetch_cert(address: &str) -> Result<Vec<u8>> {
+    let collector = Arc::new(CertCollector::default());
+    let mut config = rustls::ClientConfig::new();
+    config
+        .dangerous()
+        .set_certificate_verifier(collector.clone());
+    config.versions = vec![rustls::ProtocolVersion::TLSv1_2];
+
+    let hostname = address.rsplitn(2, ':').last().unwrap_or(address);
+    let hostname = webpki::DNSNameRef::try_from_ascii_str(hostname)
+        .map_err(|_| anyhow!("Invalid hostname: {}", hostname))?;
+    let mut session = rustls::ClientSession::new(&Arc::new(config), hostname);
+    let mut stream = TcpStream::connect(address)?;
+
+    // Services requiring mutual attestation abort the handshake without our
+    // certificate, but only after presenting theirs.
+    while session.is_handshaking() {
+        if session.complete_io(&mut stream).is_err() {
+            break;
+        }
+    }
+
+    let cert = collector.cert.lock().unwrap().take();
+    cert.ok_or_else(|| anyhow!("No certificate presented by {}", address))
+}
+
+fn print_report(report: &AttestationReport) {
+    let quote = &report.sgx_quote_body;
+    let enclave_report = &quote.isv_enclave_report;
+    println!("Quote status:   {:?}", report.sgx_quote_status);
+    println!("Advisory IDs:   {}", report.advisory_ids.join(", "));
+    if let Some(number) = report.tcb_evaluation_data_number {
+        println!("TCB eval. data: {}", number);
+    }
+    if let Some(date) = &report.tcb_date {
+        println!("TCB date:       {}", date);
+    }
+    println!("Report age:     {}s", report.freshness.as_secs());
+    println!("Quote version:  {:?}", quote.version);
+    println!("EPID group ID:  {:#010x}", quote.gid);
+    println!("QE ISV SVN:     {}", quote.isv_svn_qe);
+    println!("PCE ISV SVN:    {}", quote.isv_svn_pce);
+    println!("QE vendor ID:   {}", quote.qe_vendor_id);
+    println!("MR_ENCLAVE:     {}", hex::encode(enclave_report.mr_enclave));
+    println!("MR_SIGNER:      {}", hex::encode(enclave_report.mr_signer));
+    println!("CPU SVN:        {}", hex::encode(enclave_report.cpu_svn));
+    println!("Attributes:     {}", hex::encode(enclave_report.attributes));
+    println!("Misc select:    {:#010x}", enclave_report.misc_select);
+    println!("ISV product ID: {}", enclave_report.isv_prod_id);
+    println!("ISV SVN:        {}", enclave_report.isv_svn);
+    println!(
+        "Report data:    {}",
+        hex::encode(&enclave_report.report_data[..])
+    );
+}
+
+/// Checks the report against the attestation policy of the client config, or
+/// the default policy if there is no config file.
+fn check_policy(report: &AttestationReport, config: Option<&ClientConfig>) -> Result<()> {
+    let policy_config = match config {
+        Some(config) => config.attestation_policy.clone(),
+        None => AttestationPolicyConfig::default(),
+    };
+    AttestationPolicy::from_config(&policy_config)?
+        .verify(report)
+        .context("Attestation report is refused by the policy")?;
+    println!("Policy:         accepted");
+    Ok(())
+}
+
+/// Checks the enclave is not revoked like the services do: the revocation
+/// list must be signed by every built-in auditor.
+fn check_revocation(report: &AttestationReport, opt: &AttestOpt) -> Result<()> {
+    let path = match &opt.revocation_list {
+        Some(path) => path,
+        None => return Ok(()),
+    };
+    let list = fs::read(path).with_context(|| format!("Cannot read revocation list {:?}", path))?;
+    let signatures = read_signatures(&opt.revocation_list_signatures)?;
+    let list = RevocationList::verify_and_new(&list, AUDITOR_PUBLIC_KEYS, &signatures)
+        .context("Revocation list is not signed by the auditors")?;
+
+    let mr_enclave = &report.sgx_quote_body.isv_enclave_report.mr_enclave;
+    ensure!(
+        !list.is_revoked(mr_enclave, report.timestamp),
+        "Enclave is revoked by the revocation list version {}",
+        list.version
+    );
+    println!("Revocation:     not revoked, list version {}", list.version);
+    Ok(())
+}
+
+fn read_signatures(paths: &[PathBuf]) -> Result<Vec<Vec<u8>>> {
+    let mut signatures = Vec::new();
+    for path in paths {
+        let signature =
+            fs::read(path).with_context(|| format!("Cannot read signature {:?}", path))?;
+        signatures.push(signature);
+    }
+    Ok(signatures)
+}
+
+/// Checks the measurements like the services do: the enclave info must be
+/// signed by every built-in auditor and list the enclave. The enclave info and
+/// its signatures default to the ones of the client config.
+fn check_measurements(
+    report: &AttestationReport,
+    opt: &AttestOpt,
+    config: Option<&ClientConfig>,
+) -> Result<()> {
+    let (path, signature_paths) = match (&opt.enclave_info, config) {
+        (Some(path), _) => (path.clone(), opt.auditor_signatures.clone()),
+        (None, Some(config)) if opt.auditor_signatures.is_empty() => (
+            config.resolve(&config.enclave_info),
+            config
+                .auditor_signatures
+                .iter()
+                .map(|path| config.resolve(path))
+                .collect(),
+        ),
+        (None, Some(config)) => (
+            config.resolve(&config.enclave_info),
+            opt.auditor_signatures.clone(),
+        ),
+        // Passing only the checks above does not tell which enclave it is.
+        (None, None) => {
+            bail!("Measurements are not checked without --enclave-info or a config file")
+        }
+    };
+    let enclave_info_bytes =
+        fs::read(&path).with_context(|| format!("Cannot read enclave info {:?}", path))?;
+    let signatures = read_signatures(&signature_paths)?;
+    ensure!(
+        signatures.len() == AUDITOR_PUBLIC_KEYS.len(),
+        "Expecting {} auditor signatures, got {}",
+        AUDITOR_PUBLIC_KEYS.len(),
+        signatures.len()
+    );
+    let enclave_info =
+        EnclaveInfo::verify_and_new(&enclave_info_bytes, AUDITOR_PUBLIC_KEYS, &signatures)
+            .context("Enclave info is not signed by the auditors")?;
+    println!(
+        "Enclave info:   {} auditor signature(s) verified",
+        signatures.len()
+    );
+
+    let enclave_report = &report.sgx_quote_body.isv_enclave_report;
+    let mut matched: Vec<&String> = enclave_info
+        .measurements
+        .iter()
+        .filter(|(_, m)| {
+            m.mr_enclave == enclave_report.mr_enclave && m.mr_signer == enclave_report.mr_signer
+        })
+        .map(|(name, _)| name)
+        .collect();
+    matched.sort();
+
+    match &opt.service {
+        Some(service) if !matched.contains(&service) => {
+            bail!("Measurements do not match {}", service)
+        }
+        _ if matched.is_empty() => bail!("Measurements do not match any enclave"),
+        _ => println!(
+            "Matched:        {}",
+            matched
+                .iter()
+                .map(|s| s.as_str())
+                .collect::<Vec<_>>()
+                .join(", ")
+        ),
+    }
+
+    Ok(())
+}
+
+pub(crate) fn run(opt: AttestOpt, config: &Path) -> Result<()> {
+    let cert = match (&opt.address, &opt.cert) {
+        (Some(address), _) => fetch_cert(address)?,
+        (None, Some(path)) => fs::read(path)?,
+        (None, None) => bail!("Either --address or --cert is required"),
+    };
+    let root_ca = match &opt.as_root_ca_cert {
+        Some(path) => pem::parse(fs::read(path)?)?.contents,
+        None => AS_ROOT_CA_CERT.to_vec(),
+    };
+
+    let report = AttestationReport::from_cert(&cert, &root_ca)
+        .context("Cannot verify the attestation report in the certificate")?;
+    print_report(&report);
+
+    let config = if config.exists() {
+        Some(ClientConfig::load(config)?)
+    } else {
+        None
+    };
+    check_policy(&report, config.as_ref())?;
+    check_revocation(&report, &opt)?;
+    check_measurements(&report, &opt, config.as_ref())
+}
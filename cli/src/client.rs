@@ -0,0 +1,78 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License.
+
+use crate::config::ClientConfig;
+use anyhow::{anyhow, Result};
+use std::collections::HashMap;
+use std::fs;
+use teaclave_attestation::policy::AttestationPolicy;
+use teaclave_proto::teaclave_authentication_service::TeaclaveAuthenticationApiClient;
+use teaclave_proto::teaclave_frontend_service::TeaclaveFrontendClient;
+use teaclave_rpc::config::SgxTrustedTlsClientConfig;
+use teaclave_rpc::endpoint::Endpoint;
+use teaclave_types::EnclaveInfo;
+
+/// Accepts only the enclave of `service_name` listed in the enclave info,
+/// with a report signed by the attestation service and accepted by the
+/// attestation policy.
+fn create_tls_config(
+    config: &ClientConfig,
+    service_name: &str,
+) -> Result<SgxTrustedTlsClientConfig> {
+    let enclave_info = fs::read(config.resolve(&config.enclave_info))?;
+    let enclave_attr = EnclaveInfo::from_bytes(&enclave_info)
+        .get_enclave_attr(service_name)
+        .ok_or_else(|| anyhow!("Cannot find {} in the enclave info", service_name))?;
+
+    let as_root_ca_cert = fs::read(config.resolve(&config.as_root_ca_cert))?;
+    let as_root_ca_cert = pem::parse(as_root_ca_cert)?.contents;
+    let attestation_policy = AttestationPolicy::from_config(&config.attestation_policy)?;
+
+    let tls_config = SgxTrustedTlsClientConfig::new().attestation_report_verifier(
+        vec![enclave_attr],
+        &as_root_ca_cert,
+        attestation_policy,
+    );
+    Ok(tls_config)
+}
+
+pub(crate) fn connect_authentication_service(
+    config: &ClientConfig,
+) -> Result<TeaclaveAuthenticationApiClient> {
+    let tls_config = create_tls_config(config, "teaclave_authentication_service")?;
+    let channel = Endpoint::new(&config.authentication_service_address)
+        .config(tls_config)
+        .connect()?;
+    TeaclaveAuthenticationApiClient::new(channel)
+}
+
+/// Connects with the credential saved by `user login`.
+pub(crate) fn connect_frontend_service(config: &ClientConfig) -> Result<TeaclaveFrontendClient> {
+    let credential = config
+        .credential
+        .as_ref()
+        .ok_or_else(|| anyhow!("Not logged in, please run `user login` first"))?;
+    let tls_config = create_tls_config(config, "teaclave_frontend_service")?;
+    let channel = Endpoint::new(&config.frontend_service_address)
+        .config(tls_config)
+        .connect()?;
+
+    let mut metadata = HashMap::new();
+    metadata.insert("id".to_string(), credential.id.clone());
+    metadata.insert("token".to_string(), credential.token.clone());
+    TeaclaveFrontendClient::new_with_metadata(channel, metadata)
+}
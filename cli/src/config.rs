@@ -0,0 +1,126 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License.
+
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use std::os::unix::fs::OpenOptionsExt;
+use std::path::{Path, PathBuf};
+use teaclave_config::AttestationPolicyConfig;
+
+/// Settings of the subcommands talking to the platform. Relative paths are
+/// resolved against the directory of the config file.
+#[derive(Debug, Serialize, Deserialize)]
+pub(crate) struct ClientConfig {
+    /// Address of the authentication service, e.g., "localhost:7776".
+    pub authentication_service_address: String,
+    /// Address of the frontend service, e.g., "localhost:7777".
+    pub frontend_service_address: String,
+    /// Root CA certificate of the attestation service in PEM format.
+    pub as_root_ca_cert: PathBuf,
+    /// Measurements of the service enclaves, i.e., enclave_info.toml.
+    pub enclave_info: PathBuf,
+    /// Auditor signatures of the enclave info, checked by `attest`.
+    #[serde(default)]
+    pub auditor_signatures: Vec<PathBuf>,
+    /// Attestation reports accepted from the services, same as the
+    /// `[attestation.policy]` section of the runtime config.
+    #[serde(default)]
+    pub attestation_policy: AttestationPolicyConfig,
+    /// Credential saved by `user login`.
+    pub credential: Option<Credential>,
+    #[serde(skip)]
+    path: PathBuf,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub(crate) struct Credential {
+    pub id: String,
+    pub token: String,
+}
+
+impl ClientConfig {
+    pub(crate) fn load(path: &Path) -> Result<Self> {
+        let content = fs::read_to_string(path)
+            .with_context(|| format!("Cannot read config file {:?}", path))?;
+        let mut config: Self =
+            toml::from_str(&content).with_context(|| format!("Invalid config file {:?}", path))?;
+        config.path = path.to_owned();
+        Ok(config)
+    }
+
+    /// Writes the config back, readable by the current user only since it
+    /// contains the login token.
+    pub(crate) fn save(&self) -> Result<()> {
+        let content = toml::to_string(self)?;
+        let mut file = OpenOptions::new()
+            .write(true)
+            .create(true)
+            .truncate(true)
+            .mode(0o600)
+            .open(&self.path)
+            .with_context(|| format!("Cannot write config file {:?}", self.path))?;
+        file.write_all(content.as_bytes())?;
+        Ok(())
+    }
+
+    pub(crate) fn resolve(&self, path: &Path) -> PathBuf {
+        match self.path.parent() {
+            Some(dir) => dir.join(path),
+            None => path.to_owned(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config_at(path: &str) -> ClientConfig {
+        let mut config: ClientConfig = toml::from_str(
+            r#"
+            authentication_service_address = "localhost:7776"
+            frontend_service_address = "localhost:7777"
+            as_root_ca_cert = "keys/ias_root_ca_cert.pem"
+            enclave_info = "enclave_info.toml"
+            "#,
+        )
+        .unwrap();
+        config.path = PathBuf::from(path);
+        config
+    }
+
+    #[test]
+    fn test_resolve() {
+        let config = config_at("/etc/teaclave/teaclave_cli.toml");
+        assert_eq!(
+            config.resolve(&config.as_root_ca_cert),
+            Path::new("/etc/teaclave/keys/ias_root_ca_cert.pem")
+        );
+        assert_eq!(
+            config.resolve(Path::new("/tmp/enclave_info.toml")),
+            Path::new("/tmp/enclave_info.toml")
+        );
+
+        let config = config_at("teaclave_cli.toml");
+        assert_eq!(
+            config.resolve(&config.enclave_info),
+            Path::new("enclave_info.toml")
+        );
+    }
+}
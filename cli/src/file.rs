@@ -0,0 +1,286 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License.
+
+use crate::client::connect_frontend_service;
+use crate::config::ClientConfig;
+use anyhow::{bail, Result};
+use serde_json::json;
+use std::convert::TryFrom;
+use structopt::StructOpt;
+use teaclave_proto::teaclave_frontend_service::{
+    GetInputFileRequest, GetOutputFileRequest, RegisterInputFileRequest, RegisterOutputFileRequest,
+    UpdateInputFileRequest, UpdateOutputFileRequest,
+};
+use teaclave_types::{ExternalID, FileAuthTag, FileCrypto, FileHash};
+use url::Url;
+
+type KeyVec = Vec<u8>;
+
+fn decode_hex(src: &str) -> Result<Vec<u8>, hex::FromHexError> {
+    hex::decode(src)
+}
+
+#[derive(Debug, StructOpt)]
+pub(crate) struct CryptoOpt {
+    /// Crypto algorithm of the file, same as the ones of encrypt/decrypt
+    #[structopt(short, long)]
+    algorithm: String,
+
+    /// Key in hex format
+    #[structopt(short, long, parse(try_from_str = decode_hex))]
+    key: KeyVec,
+
+    /// IV in hex format, only for the algorithms requiring one
+    #[structopt(long, parse(try_from_str = decode_hex), default_value = "")]
+    iv: KeyVec,
+}
+
+impl CryptoOpt {
+    fn file_crypto(&self) -> Result<FileCrypto> {
+        FileCrypto::new(&self.algorithm, &self.key, &self.iv)
+    }
+}
+
+#[derive(Debug, StructOpt)]
+pub(crate) struct RegisterInputOpt {
+    /// URL of the encrypted file
+    #[structopt(long)]
+    url: Url,
+
+    /// CMAC of the encrypted file in hex format
+    #[structopt(long)]
+    cmac: String,
+
+    /// Hash of the plaintext as "algorithm:hex", e.g., "sha256:9f86..."
+    #[structopt(long)]
+    hash: Option<String>,
+
+    #[structopt(flatten)]
+    crypto: CryptoOpt,
+}
+
+#[derive(Debug, StructOpt)]
+pub(crate) struct RegisterOutputOpt {
+    /// URL where the encrypted output will be written
+    #[structopt(long)]
+    url: Url,
+
+    /// Crypto algorithm of the file, same as the ones of encrypt/decrypt
+    #[structopt(short, long, required_unless = "recipient-public-key")]
+    algorithm: Option<String>,
+
+    /// Key in hex format
+    #[structopt(short, long, parse(try_from_str = decode_hex), required_unless = "recipient-public-key")]
+    key: Option<KeyVec>,
+
+    /// IV in hex format, only for the algorithms requiring one
+    #[structopt(long, parse(try_from_str = decode_hex), default_value = "")]
+    iv: KeyVec,
+
+    /// Public key in hex format to which the platform wraps a key it
+    /// generates for the output, instead of using the given key
+    #[structopt(
+        long = "recipient-public-key",
+        parse(try_from_str = decode_hex),
+        conflicts_with_all = &["algorithm", "key"]
+    )]
+    recipient_public_key: Option<KeyVec>,
+}
+
+impl RegisterOutputOpt {
+    fn into_request(self) -> Result<RegisterOutputFileRequest> {
+        let request = match (self.recipient_public_key, self.algorithm, self.key) {
+            (Some(public_key), None, None) => {
+                RegisterOutputFileRequest::with_recipient(self.url, public_key)
+            }
+            (None, Some(algorithm), Some(key)) => RegisterOutputFileRequest::new(
+                self.url,
+                FileCrypto::new(&algorithm, &key, &self.iv)?,
+            ),
+            _ => bail!("Expecting either an algorithm and a key, or a recipient public key"),
+        };
+        Ok(request)
+    }
+}
+
+#[derive(Debug, StructOpt)]
+pub(crate) enum FileCommand {
+    /// Register an input file and print its ID
+    #[structopt(name = "register-input")]
+    RegisterInput(RegisterInputOpt),
+
+    /// Register an output file and print its ID
+    #[structopt(name = "register-output")]
+    RegisterOutput(RegisterOutputOpt),
+
+    /// Point a registered input or output file to a new URL
+    #[structopt(name = "update")]
+    Update {
+        /// File ID
+        data_id: String,
+
+        /// New URL of the file
+        #[structopt(long)]
+        url: Url,
+    },
+
+    /// Print an input or output file in JSON
+    #[structopt(name = "get")]
+    Get {
+        /// File ID
+        data_id: String,
+    },
+}
+
+fn parse_hash(hash: &str) -> Result<FileHash> {
+    match hash.find(':') {
+        Some(pos) => FileHash::new(&hash[..pos], &hash[pos + 1..]),
+        None => bail!("Invalid hash, expecting \"algorithm:hex\": {}", hash),
+    }
+}
+
+pub(crate) fn run(command: FileCommand, config: ClientConfig) -> Result<()> {
+    let mut client = connect_frontend_service(&config)?;
+    match command {
+        FileCommand::RegisterInput(opt) => {
+            let cmac = FileAuthTag::from_hex(&opt.cmac)?;
+            let mut request =
+                RegisterInputFileRequest::new(opt.url, cmac, opt.crypto.file_crypto()?);
+            if let Some(hash) = opt.hash {
+                request = request.hash(parse_hash(&hash)?);
+            }
+            let response = client.register_input_file(request)?;
+            println!("{}", response.data_id.to_string());
+        }
+        FileCommand::RegisterOutput(opt) => {
+            let response = client.register_output_file(opt.into_request()?)?;
+            println!("{}", response.data_id.to_string());
+        }
+        FileCommand::Update { data_id, url } => {
+            let data_id = ExternalID::try_from(data_id)?;
+            let response = match data_id.prefix.as_str() {
+                "input" => {
+                    client
+                        .update_input_file(UpdateInputFileRequest::new(data_id, url))?
+                        .data_id
+                }
+                "output" => {
+                    client
+                        .update_output_file(UpdateOutputFileRequest::new(data_id, url))?
+                        .data_id
+                }
+                _ => bail!("Invalid file ID: {}", data_id.to_string()),
+            };
+            println!("{}", response.to_string());
+        }
+        FileCommand::Get { data_id } => {
+            let data_id = ExternalID::try_from(data_id)?;
+            let file = match data_id.prefix.as_str() {
+                "input" => {
+                    let response = client.get_input_file(GetInputFileRequest::new(data_id))?;
+                    json!({
+                        "owner": Vec::<String>::from(response.owner),
+                        "cmac": response.cmac.to_hex(),
+                        "privacy_budget": response.privacy_budget,
+                    })
+                }
+                "output" => {
+                    let response = client.get_output_file(GetOutputFileRequest::new(data_id))?;
+                    json!({
+                        "owner": Vec::<String>::from(response.owner),
+                        "cmac": response.cmac.map(|cmac| cmac.to_hex()),
+                        "crypto_info": response.crypto_info,
+                    })
+                }
+                _ => bail!("Invalid file ID: {}", data_id.to_string()),
+            };
+            println!("{}", serde_json::to_string_pretty(&file)?);
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_hash() {
+        let hex = "9f86d081884c7d659a2feaa0c55ad015a3bf4f1b2b0b822cd15d6c15b0f00a08";
+        let hash = parse_hash(&format!("sha256:{}", hex.to_uppercase())).unwrap();
+        assert_eq!(hash, FileHash::Sha256(hex.to_string()));
+        assert_eq!(hash.algorithm(), "sha256");
+
+        assert!(parse_hash(hex).is_err());
+        assert!(parse_hash(&format!("sha1:{}", hex)).is_err());
+        assert!(parse_hash("sha256:9f86").is_err());
+        assert!(parse_hash("md5:").is_err());
+    }
+
+    #[test]
+    fn test_register_output_request() {
+        let opt = RegisterOutputOpt::from_iter_safe(&[
+            "register-output",
+            "--url",
+            "https://localhost/output",
+            "--recipient-public-key",
+            "0102",
+        ])
+        .unwrap();
+        let request = opt.into_request().unwrap();
+        assert_eq!(request.recipient_public_key, Some(vec![1, 2]));
+        assert!(request.crypto_info.is_none());
+
+        let key = "00".repeat(16);
+        let iv = "00".repeat(12);
+        let opt = RegisterOutputOpt::from_iter_safe(&[
+            "register-output",
+            "--url",
+            "https://localhost/output",
+            "-a",
+            "aes-gcm-128",
+            "-k",
+            &key,
+            "--iv",
+            &iv,
+        ])
+        .unwrap();
+        let request = opt.into_request().unwrap();
+        assert!(request.recipient_public_key.is_none());
+        assert!(request.crypto_info.is_some());
+
+        let missing_key = RegisterOutputOpt::from_iter_safe(&[
+            "register-output",
+            "--url",
+            "https://localhost/output",
+            "-a",
+            "aes-gcm-128",
+        ]);
+        assert!(missing_key.is_err());
+        let both = RegisterOutputOpt::from_iter_safe(&[
+            "register-output",
+            "--url",
+            "https://localhost/output",
+            "-k",
+            &key,
+            "--recipient-public-key",
+            "0102",
+        ]);
+        assert!(both.is_err());
+    }
+}
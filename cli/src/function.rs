@@ -0,0 +1,157 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License.
+
+use crate::client::connect_frontend_service;
+use crate::config::ClientConfig;
+use anyhow::Result;
+use serde_json::json;
+use std::convert::TryFrom;
+use std::fs;
+use std::path::PathBuf;
+use structopt::StructOpt;
+use teaclave_proto::teaclave_frontend_service::{GetFunctionRequest, RegisterFunctionRequest};
+use teaclave_types::{ExecutorType, ExternalID, FunctionInput, FunctionOutput};
+
+// A slot is given as "name" or "name:description".
+fn split_slot(slot: &str) -> (&str, &str) {
+    match slot.find(':') {
+        Some(pos) => (&slot[..pos], &slot[pos + 1..]),
+        None => (slot, ""),
+    }
+}
+
+#[derive(Debug, StructOpt)]
+pub(crate) struct RegisterOpt {
+    /// Function name, e.g., "builtin-echo"
+    #[structopt(short, long)]
+    name: String,
+
+    /// Function description
+    #[structopt(short, long, default_value = "")]
+    description: String,
+
+    /// Executor type, "builtin" or "python"
+    #[structopt(short, long = "executor-type", default_value = "builtin")]
+    executor_type: String,
+
+    /// Path of the function payload, e.g., a Python script
+    #[structopt(long)]
+    payload: Option<PathBuf>,
+
+    /// Only the owner can use a private function
+    #[structopt(long)]
+    private: bool,
+
+    /// Argument name, can be repeated
+    #[structopt(short, long = "argument")]
+    arguments: Vec<String>,
+
+    /// Input file slot as "name" or "name:description", can be repeated
+    #[structopt(short, long = "input")]
+    inputs: Vec<String>,
+
+    /// Output file slot as "name" or "name:description", can be repeated
+    #[structopt(short, long = "output")]
+    outputs: Vec<String>,
+}
+
+#[derive(Debug, StructOpt)]
+pub(crate) enum FunctionCommand {
+    /// Register a function and print its ID
+    #[structopt(name = "register")]
+    Register(RegisterOpt),
+
+    /// Print a function in JSON
+    #[structopt(name = "get")]
+    Get {
+        /// Function ID
+        function_id: String,
+    },
+}
+
+pub(crate) fn run(command: FunctionCommand, config: ClientConfig) -> Result<()> {
+    let mut client = connect_frontend_service(&config)?;
+    match command {
+        FunctionCommand::Register(opt) => {
+            let payload = match opt.payload {
+                Some(path) => fs::read(path)?,
+                None => Vec::new(),
+            };
+            let inputs = opt
+                .inputs
+                .iter()
+                .map(|slot| {
+                    let (name, description) = split_slot(slot);
+                    FunctionInput::new(name, description)
+                })
+                .collect();
+            let outputs = opt
+                .outputs
+                .iter()
+                .map(|slot| {
+                    let (name, description) = split_slot(slot);
+                    FunctionOutput::new(name, description)
+                })
+                .collect();
+            let request = RegisterFunctionRequest::new()
+                .name(opt.name)
+                .description(opt.description)
+                .executor_type(ExecutorType::try_from(opt.executor_type)?)
+                .payload(payload)
+                .public(!opt.private)
+                .arguments(opt.arguments)
+                .inputs(inputs)
+                .outputs(outputs);
+            let response = client.register_function(request)?;
+            println!("{}", response.function_id.to_string());
+        }
+        FunctionCommand::Get { function_id } => {
+            let request = GetFunctionRequest::new(ExternalID::try_from(function_id)?);
+            let response = client.get_function(request)?;
+            let function = json!({
+                "name": response.name,
+                "description": response.description,
+                "owner": response.owner.to_string(),
+                "public": response.public,
+                "executor_type": response.executor_type.to_string(),
+                "payload": String::from_utf8_lossy(&response.payload),
+                "arguments": response.arguments,
+                "inputs": response.inputs,
+                "outputs": response.outputs,
+                "version": response.version,
+                "revision_hash": response.revision_hash,
+                "previous_revision": response.previous_revision.map(|id| id.to_string()),
+            });
+            println!("{}", serde_json::to_string_pretty(&function)?);
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_split_slot() {
+        assert_eq!(split_slot("input"), ("input", ""));
+        assert_eq!(split_slot("input:Input file"), ("input", "Input file"));
+        assert_eq!(split_slot("input:a:b"), ("input", "a:b"));
+        assert_eq!(split_slot(":"), ("", ""));
+    }
+}
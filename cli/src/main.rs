@@ -15,13 +15,26 @@
 // specific language governing permissions and limitations
 // under the License.
 
+mod attest;
+mod client;
+mod config;
+mod file;
+mod function;
+mod task;
+mod user;
+
 use anyhow::bail;
 use anyhow::Result;
-use std::fs;
+use std::fs::{self, File};
+use std::io::{self, BufReader, BufWriter, Write};
 use std::path::PathBuf;
 use structopt::StructOpt;
 
-use teaclave_crypto::{AesGcm128Key, AesGcm256Key, TeaclaveFile128Key};
+use crate::config::ClientConfig;
+use teaclave_crypto::{
+    AesGcm128StreamKey, AesGcm256StreamKey, StreamDecryptor, StreamEncryptor, TeaclaveFile128Key,
+};
+use teaclave_types::FileCrypto;
 
 const FILE_AUTH_TAG_LENGTH: usize = 16;
 type CMac = [u8; FILE_AUTH_TAG_LENGTH];
@@ -34,7 +47,8 @@ fn decode_hex(src: &str) -> Result<Vec<u8>, hex::FromHexError> {
 #[derive(Debug, StructOpt)]
 struct EncryptDecryptOpt {
     /// Crypto algorithm, supported algorithms are "aes-gcm-128", "aes-gcm-256",
-    /// "teaclave-file-128".
+    /// "chacha20-poly1305", "aes-gcm-siv", "teaclave-file-128",
+    /// "aes-gcm-128-stream", "aes-gcm-256-stream".
     #[structopt(short, long)]
     algorithm: String,
 
@@ -42,7 +56,8 @@ struct EncryptDecryptOpt {
     #[structopt(short, long, parse(try_from_str = decode_hex))]
     key: KeyVec,
 
-    /// IV for AES keys in hex format
+    /// IV for AES-GCM, AES-GCM-SIV and ChaCha20-Poly1305 keys in hex format.
+    /// ChaCha20-Poly1305 files carry their IV in front of the ciphertext.
     #[structopt(long, parse(try_from_str = decode_hex))]
     iv: Option<KeyVec>,
 
@@ -57,6 +72,10 @@ struct EncryptDecryptOpt {
     /// Whether to print cmac
     #[structopt(short, long)]
     cmac_flag: bool,
+
+    /// Plaintext bytes per chunk for the stream algorithms
+    #[structopt(long = "chunk-size", default_value = "65536")]
+    chunk_size: usize,
 }
 
 #[derive(Debug, StructOpt)]
@@ -68,35 +87,64 @@ enum Command {
     /// Decrypt file
     #[structopt(name = "decrypt")]
     Decrypt(EncryptDecryptOpt),
+
+    /// Inspect and verify the attestation report of a service
+    #[structopt(name = "attest")]
+    Attest(attest::AttestOpt),
+
+    /// Register users and log in
+    #[structopt(name = "user")]
+    User(user::UserCommand),
+
+    /// Register and get functions
+    #[structopt(name = "function")]
+    Function(function::FunctionCommand),
+
+    /// Register, update and get input/output files
+    #[structopt(name = "file")]
+    File(file::FileCommand),
+
+    /// Create, assign, approve, invoke and watch tasks
+    #[structopt(name = "task")]
+    Task(task::TaskCommand),
 }
 
 #[derive(Debug, StructOpt)]
 #[structopt(name = "teaclave_cli", about = "Teaclave command line tool.")]
 struct Opt {
+    /// Path of the client config file used by the platform subcommands
+    #[structopt(long, default_value = "teaclave_cli.toml")]
+    config: PathBuf,
+
     #[structopt(subcommand)]
     command: Command,
 }
 
+fn decrypt_stream(
+    mut decryptor: StreamDecryptor<BufReader<File>>,
+    output: PathBuf,
+) -> Result<CMac> {
+    let mut output = BufWriter::new(File::create(output)?);
+    io::copy(&mut decryptor, &mut output)?;
+    output.flush()?;
+    match decryptor.tag() {
+        Some(cmac) => Ok(cmac),
+        None => bail!("Incomplete stream"),
+    }
+}
+
+fn encrypt_stream(mut encryptor: StreamEncryptor<BufWriter<File>>, input: PathBuf) -> Result<CMac> {
+    let mut input = BufReader::new(File::open(input)?);
+    io::copy(&mut input, &mut encryptor)?;
+    let (mut output, cmac) = encryptor.finish()?;
+    output.flush()?;
+    Ok(cmac)
+}
+
 fn decrypt(opt: EncryptDecryptOpt) -> Result<CMac> {
     let key = opt.key;
     let mut cmac: CMac = [0u8; FILE_AUTH_TAG_LENGTH];
     match opt.algorithm.as_str() {
-        AesGcm128Key::SCHEMA => {
-            let iv = opt.iv.expect("IV is required.");
-            let key = AesGcm128Key::new(&key, &iv)?;
-            let mut content = fs::read(opt.input_file)?;
-            let res = key.decrypt(&mut content)?;
-            cmac.copy_from_slice(&res);
-            fs::write(opt.output_file, content)?;
-        }
-        AesGcm256Key::SCHEMA => {
-            let iv = opt.iv.expect("IV is required.");
-            let key = AesGcm256Key::new(&key, &iv)?;
-            let mut content = fs::read(opt.input_file)?;
-            let res = key.decrypt(&mut content)?;
-            cmac.copy_from_slice(&res);
-            fs::write(opt.output_file, content)?;
-        }
         TeaclaveFile128Key::SCHEMA => {
             let key = TeaclaveFile128Key::new(&key)?;
             let mut content = vec![];
@@ -104,7 +152,23 @@ fn decrypt(opt: EncryptDecryptOpt) -> Result<CMac> {
             cmac.copy_from_slice(&res);
             fs::write(opt.output_file, content)?;
         }
-        _ => bail!("Invalid crypto algorithm"),
+        AesGcm128StreamKey::SCHEMA => {
+            let key = AesGcm128StreamKey::new(&key)?;
+            let decryptor = key.decryptor(BufReader::new(File::open(opt.input_file)?))?;
+            cmac = decrypt_stream(decryptor, opt.output_file)?;
+        }
+        AesGcm256StreamKey::SCHEMA => {
+            let key = AesGcm256StreamKey::new(&key)?;
+            let decryptor = key.decryptor(BufReader::new(File::open(opt.input_file)?))?;
+            cmac = decrypt_stream(decryptor, opt.output_file)?;
+        }
+        algorithm => {
+            let iv = opt.iv.unwrap_or_default();
+            let crypto = FileCrypto::new(algorithm, &key, &iv)?;
+            let mut content = fs::read(opt.input_file)?;
+            cmac = crypto.decrypt(&mut content)?.into();
+            fs::write(opt.output_file, content)?;
+        }
     }
 
     Ok(cmac)
@@ -114,29 +178,31 @@ fn encrypt(opt: EncryptDecryptOpt) -> Result<CMac> {
     let key = opt.key;
     let mut cmac: CMac = [0u8; FILE_AUTH_TAG_LENGTH];
     match opt.algorithm.as_str() {
-        AesGcm128Key::SCHEMA => {
-            let iv = opt.iv.expect("IV is required.");
-            let key = AesGcm128Key::new(&key, &iv)?;
-            let mut content = fs::read(opt.input_file)?;
-            let res = key.encrypt(&mut content)?;
-            cmac.copy_from_slice(&res);
-            fs::write(opt.output_file, content)?;
-        }
-        AesGcm256Key::SCHEMA => {
-            let iv = opt.iv.expect("IV is required.");
-            let key = AesGcm256Key::new(&key, &iv)?;
-            let mut content = fs::read(opt.input_file)?;
-            let res = key.encrypt(&mut content)?;
-            cmac.copy_from_slice(&res);
-            fs::write(opt.output_file, content)?;
-        }
         TeaclaveFile128Key::SCHEMA => {
             let key = TeaclaveFile128Key::new(&key)?;
             let content = fs::read(opt.input_file)?;
             let res = key.encrypt(opt.output_file, &content)?;
             cmac.copy_from_slice(&res);
         }
-        _ => bail!("Invalid crypto algorithm"),
+        AesGcm128StreamKey::SCHEMA => {
+            let key = AesGcm128StreamKey::new(&key)?;
+            let output = BufWriter::new(File::create(opt.output_file)?);
+            let encryptor = key.encryptor(output, opt.chunk_size)?;
+            cmac = encrypt_stream(encryptor, opt.input_file)?;
+        }
+        AesGcm256StreamKey::SCHEMA => {
+            let key = AesGcm256StreamKey::new(&key)?;
+            let output = BufWriter::new(File::create(opt.output_file)?);
+            let encryptor = key.encryptor(output, opt.chunk_size)?;
+            cmac = encrypt_stream(encryptor, opt.input_file)?;
+        }
+        algorithm => {
+            let iv = opt.iv.unwrap_or_default();
+            let crypto = FileCrypto::new(algorithm, &key, &iv)?;
+            let mut content = fs::read(opt.input_file)?;
+            cmac = crypto.encrypt(&mut content)?.into();
+            fs::write(opt.output_file, content)?;
+        }
     }
 
     Ok(cmac)
@@ -161,6 +227,11 @@ fn main() -> Result<()> {
                 println!("{}", cmac_string);
             }
         }
+        Command::Attest(opt) => attest::run(opt, &args.config)?,
+        Command::User(command) => user::run(command, ClientConfig::load(&args.config)?)?,
+        Command::Function(command) => function::run(command, ClientConfig::load(&args.config)?)?,
+        Command::File(command) => file::run(command, ClientConfig::load(&args.config)?)?,
+        Command::Task(command) => task::run(command, ClientConfig::load(&args.config)?)?,
     };
 
     Ok(())
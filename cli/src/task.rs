@@ -0,0 +1,278 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License.
+
+use crate::client::connect_frontend_service;
+use crate::config::ClientConfig;
+use anyhow::{bail, Context, Result};
+use serde_json::{json, Value};
+use std::collections::HashMap;
+use std::convert::TryFrom;
+use std::thread;
+use std::time::{Duration, Instant};
+use structopt::StructOpt;
+use teaclave_proto::teaclave_frontend_service::{
+    ApproveTaskRequest, AssignDataRequest, CreateTaskRequest, GetTaskRequest, InvokeTaskRequest,
+};
+use teaclave_types::{
+    Executor, ExternalID, FunctionArguments, OwnerList, TaskFileOwners, TaskResult, TaskStatus,
+};
+
+// Splits a "key=value" pair given on the command line.
+fn split_pair(pair: &str) -> Result<(&str, &str)> {
+    match pair.find('=') {
+        Some(pos) => Ok((&pair[..pos], &pair[pos + 1..])),
+        None => bail!("Invalid pair, expecting \"key=value\": {}", pair),
+    }
+}
+
+fn parse_owners(specs: &[String]) -> Result<TaskFileOwners> {
+    let mut owners = HashMap::new();
+    for spec in specs {
+        let (name, users) = split_pair(spec)?;
+        let users: Vec<&str> = users.split(',').filter(|u| !u.is_empty()).collect();
+        owners.insert(name.to_string(), OwnerList::from(users));
+    }
+    Ok(owners.into())
+}
+
+fn parse_files(specs: &[String]) -> Result<HashMap<String, ExternalID>> {
+    let mut files = HashMap::new();
+    for spec in specs {
+        let (name, data_id) = split_pair(spec)?;
+        files.insert(name.to_string(), ExternalID::try_from(data_id)?);
+    }
+    Ok(files)
+}
+
+#[derive(Debug, StructOpt)]
+pub(crate) struct CreateOpt {
+    /// Function ID
+    #[structopt(short, long = "function-id")]
+    function_id: String,
+
+    /// Executor, e.g., "builtin", "mesapy"
+    #[structopt(short, long, default_value = "builtin")]
+    executor: String,
+
+    /// Function arguments as a JSON object
+    #[structopt(short, long, default_value = "{}")]
+    arguments: String,
+
+    /// Owners of an input file as "name=user1,user2", can be repeated
+    #[structopt(short, long = "input")]
+    inputs: Vec<String>,
+
+    /// Owners of an output file as "name=user1,user2", can be repeated
+    #[structopt(short, long = "output")]
+    outputs: Vec<String>,
+}
+
+#[derive(Debug, StructOpt)]
+pub(crate) struct AssignOpt {
+    /// Task ID
+    task_id: String,
+
+    /// Input file assigned as "name=data_id", can be repeated
+    #[structopt(short, long = "input")]
+    inputs: Vec<String>,
+
+    /// Output file assigned as "name=data_id", can be repeated
+    #[structopt(short, long = "output")]
+    outputs: Vec<String>,
+}
+
+#[derive(Debug, StructOpt)]
+pub(crate) enum TaskCommand {
+    /// Create a task and print its ID
+    #[structopt(name = "create")]
+    Create(CreateOpt),
+
+    /// Assign files owned by the current user to a task
+    #[structopt(name = "assign")]
+    Assign(AssignOpt),
+
+    /// Approve a task as a participant
+    #[structopt(name = "approve")]
+    Approve {
+        /// Task ID
+        task_id: String,
+    },
+
+    /// Invoke an approved task
+    #[structopt(name = "invoke")]
+    Invoke {
+        /// Task ID
+        task_id: String,
+    },
+
+    /// Print a task in JSON
+    #[structopt(name = "get")]
+    Get {
+        /// Task ID
+        task_id: String,
+    },
+
+    /// Wait for a task to finish and print its return value
+    #[structopt(name = "wait")]
+    Wait {
+        /// Task ID
+        task_id: String,
+
+        /// Seconds between two polls
+        #[structopt(long, default_value = "1")]
+        interval: u64,
+
+        /// Seconds to wait before giving up
+        #[structopt(long, default_value = "600")]
+        timeout: u64,
+    },
+}
+
+fn result_to_json(result: &TaskResult) -> Value {
+    match result {
+        TaskResult::NotReady => Value::Null,
+        TaskResult::Ok(outputs) => {
+            let tags: HashMap<&String, String> = outputs
+                .tags_map
+                .iter()
+                .map(|(name, tag)| (name, tag.to_hex()))
+                .collect();
+            json!({
+                "return_value": String::from_utf8_lossy(&outputs.return_value),
+                "tags_map": tags,
+                "log": outputs.log,
+            })
+        }
+        TaskResult::Err(failure) => json!({ "failure": failure }),
+    }
+}
+
+pub(crate) fn run(command: TaskCommand, config: ClientConfig) -> Result<()> {
+    let mut client = connect_frontend_service(&config)?;
+    match command {
+        TaskCommand::Create(opt) => {
+            let arguments = FunctionArguments::try_from(opt.arguments)
+                .context("Function arguments must be a JSON object")?;
+            let request = CreateTaskRequest::new()
+                .function_id(ExternalID::try_from(opt.function_id)?)
+                .function_arguments(arguments)
+                .executor(Executor::try_from(opt.executor)?)
+                .inputs_ownership(parse_owners(&opt.inputs)?)
+                .outputs_ownership(parse_owners(&opt.outputs)?);
+            let response = client.create_task(request)?;
+            println!("{}", response.task_id.to_string());
+        }
+        TaskCommand::Assign(opt) => {
+            let request = AssignDataRequest::new(
+                ExternalID::try_from(opt.task_id)?,
+                parse_files(&opt.inputs)?,
+                parse_files(&opt.outputs)?,
+            );
+            client.assign_data(request)?;
+        }
+        TaskCommand::Approve { task_id } => {
+            let request = ApproveTaskRequest::new(ExternalID::try_from(task_id)?);
+            client.approve_task(request)?;
+        }
+        TaskCommand::Invoke { task_id } => {
+            let request = InvokeTaskRequest::new(ExternalID::try_from(task_id)?);
+            client.invoke_task(request)?;
+        }
+        TaskCommand::Get { task_id } => {
+            let request = GetTaskRequest::new(ExternalID::try_from(task_id)?);
+            let response = client.get_task(request)?;
+            let to_strings = |files: HashMap<String, ExternalID>| -> HashMap<String, String> {
+                files
+                    .into_iter()
+                    .map(|(name, id)| (name, id.to_string()))
+                    .collect()
+            };
+            let task = json!({
+                "task_id": response.task_id.to_string(),
+                "creator": response.creator,
+                "function_id": response.function_id.to_string(),
+                "function_owner": response.function_owner,
+                "function_arguments": response.function_arguments,
+                "participants": Vec::<String>::from(response.participants),
+                "approved_users": Vec::<String>::from(response.approved_users),
+                "assigned_inputs": to_strings(response.assigned_inputs),
+                "assigned_outputs": to_strings(response.assigned_outputs),
+                "status": response.status,
+                "result": result_to_json(&response.result),
+            });
+            println!("{}", serde_json::to_string_pretty(&task)?);
+        }
+        TaskCommand::Wait {
+            task_id,
+            interval,
+            timeout,
+        } => {
+            let task_id = ExternalID::try_from(task_id)?;
+            let deadline = Instant::now() + Duration::from_secs(timeout);
+            loop {
+                let response = client.get_task(GetTaskRequest::new(task_id.clone()))?;
+                if response.status == TaskStatus::Finished {
+                    match response.result {
+                        TaskResult::Ok(outputs) => {
+                            println!("{}", String::from_utf8_lossy(&outputs.return_value));
+                            break;
+                        }
+                        TaskResult::Err(failure) => bail!("Task failed: {}", failure.reason),
+                        TaskResult::NotReady => bail!("Task finished without a result"),
+                    }
+                }
+                if Instant::now() >= deadline {
+                    bail!(
+                        "Task is still {:?} after {} seconds",
+                        response.status,
+                        timeout
+                    );
+                }
+                thread::sleep(Duration::from_secs(interval));
+            }
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_split_pair() {
+        assert_eq!(split_pair("input=alice").unwrap(), ("input", "alice"));
+        assert_eq!(split_pair("input=").unwrap(), ("input", ""));
+        assert_eq!(split_pair("url=a=b").unwrap(), ("url", "a=b"));
+        assert!(split_pair("input").is_err());
+    }
+
+    #[test]
+    fn test_parse_owners() {
+        let specs = vec!["input=alice,bob".to_string(), "output=,alice,".to_string()];
+        let owners = parse_owners(&specs).unwrap();
+        assert_eq!(
+            owners.get("input"),
+            Some(&OwnerList::from(vec!["alice", "bob"]))
+        );
+        assert_eq!(owners.get("output"), Some(&OwnerList::from(vec!["alice"])));
+        assert_eq!(owners.get("other"), None);
+
+        assert!(parse_owners(&["input".to_string()]).is_err());
+    }
+}
@@ -0,0 +1,110 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License.
+
+use crate::client::connect_authentication_service;
+use crate::config::{ClientConfig, Credential};
+use anyhow::{Context, Result};
+use std::env;
+use std::io::{self, BufRead};
+use structopt::StructOpt;
+use teaclave_proto::teaclave_authentication_service::{UserLoginRequest, UserRegisterRequest};
+
+// The password is never taken as an argument, which would leave it in the
+// shell history and the process list.
+const PASSWORD_ENV: &str = "TEACLAVE_PASSWORD";
+
+#[derive(Debug, StructOpt)]
+pub(crate) struct UserOpt {
+    /// User ID
+    #[structopt(short, long)]
+    user_id: String,
+
+    /// Read the password from the first line of stdin. Otherwise, it is read
+    /// from the TEACLAVE_PASSWORD environment variable, or prompted for.
+    #[structopt(long = "password-stdin")]
+    password_stdin: bool,
+}
+
+impl UserOpt {
+    fn password(&self) -> Result<String> {
+        if self.password_stdin {
+            return read_password_line(io::stdin().lock());
+        }
+        if let Ok(password) = env::var(PASSWORD_ENV) {
+            return Ok(password);
+        }
+        rpassword::read_password_from_tty(Some("Password: ")).context("Cannot read password")
+    }
+}
+
+fn read_password_line(mut input: impl BufRead) -> Result<String> {
+    let mut line = String::new();
+    input
+        .read_line(&mut line)
+        .context("Cannot read password from stdin")?;
+    Ok(line
+        .trim_end_matches(|c| c == '\r' || c == '\n')
+        .to_string())
+}
+
+#[derive(Debug, StructOpt)]
+pub(crate) enum UserCommand {
+    /// Register a new user
+    #[structopt(name = "register")]
+    Register(UserOpt),
+
+    /// Log in and save the token to the config file
+    #[structopt(name = "login")]
+    Login(UserOpt),
+}
+
+pub(crate) fn run(command: UserCommand, mut config: ClientConfig) -> Result<()> {
+    let mut client = connect_authentication_service(&config)?;
+    match command {
+        UserCommand::Register(opt) => {
+            let password = opt.password()?;
+            let request = UserRegisterRequest::new(opt.user_id, password);
+            client.user_register(request)?;
+        }
+        UserCommand::Login(opt) => {
+            let password = opt.password()?;
+            let request = UserLoginRequest::new(&opt.user_id, password);
+            let response = client.user_login(request)?;
+            config.credential = Some(Credential {
+                id: opt.user_id,
+                token: response.token,
+            });
+            config.save()?;
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_read_password_line() {
+        let password = read_password_line("secret\r\nrest\n".as_bytes()).unwrap();
+        assert_eq!(password, "secret");
+        let password = read_password_line("  with spaces ".as_bytes()).unwrap();
+        assert_eq!(password, "  with spaces ");
+        assert_eq!(read_password_line("".as_bytes()).unwrap(), "");
+    }
+}
@@ -26,6 +26,13 @@ if [ ! "$LIBENCLAVE_PATH" -nt "$SIGNED_PATH" ] \
     exit 0
 fi
 
+# The trusted verification library of DCAP checks the QvE report when a peer
+# quote is verified in the enclave.
+DCAP_TVL_LIB=""
+if [ "${DCAP}" == "ON" ]; then
+    DCAP_TVL_LIB="-lsgx_dcap_tvl"
+fi
+
 cd ${TEACLAVE_OUT_DIR}
 ${CMAKE_C_COMPILER} "lib${edl_lib_name}.o" -o \
     ${TEACLAVE_OUT_DIR}/${CUR_PKG_NAME}.so ${SGX_COMMON_CFLAGS} \
@@ -33,7 +40,7 @@ ${CMAKE_C_COMPILER} "lib${edl_lib_name}.o" -o \
     -L${SGX_LIBRARY_PATH} -Wl,--whole-archive -l${Trts_Library_Name} \
     -Wl,--no-whole-archive -Wl,--start-group \
     -l${Service_Library_Name} -lsgx_tprotected_fs -lsgx_tkey_exchange \
-    -lsgx_tstdc -lsgx_tcxx -lsgx_tservice -lsgx_tcrypto \
+    -lsgx_tstdc -lsgx_tcxx -lsgx_tservice -lsgx_tcrypto ${DCAP_TVL_LIB} \
     -L${TEACLAVE_OUT_DIR} -lpycomponent ffi.o -lpypy-c -lsgx_tlibc_ext -lffi \
     -L${TRUSTED_TARGET_DIR}/${TARGET} -l${CUR_PKG_NAME} -Wl,--end-group \
     -Wl,-Bstatic -Wl,-Bsymbolic -Wl,--no-undefined \
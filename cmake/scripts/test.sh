@@ -24,6 +24,7 @@ echo_title() {
 
 start_storage_server() {
   python3 ${TEACLAVE_PROJECT_ROOT}/tests/scripts/simple_http_server.py 6789 &
+  python3 ${TEACLAVE_PROJECT_ROOT}/tests/scripts/simple_s3_server.py 6790 &
 }
 
 run_unit_tests() {
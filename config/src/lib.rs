@@ -33,4 +33,4 @@ pub(crate) enum ConfigSource {
 pub mod build;
 mod runtime;
 
-pub use runtime::RuntimeConfig;
+pub use runtime::{AttestationPolicyConfig, RevocationListConfig, RuntimeConfig};
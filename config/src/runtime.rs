@@ -38,6 +38,10 @@ pub struct RuntimeConfig {
     pub audit: AuditConfig,
     pub attestation: AttestationServiceConfig,
     pub mount: MountConfig,
+    #[serde(default)]
+    pub execution: ExecutionConfig,
+    #[serde(default)]
+    pub metrics: MetricsConfig,
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
@@ -77,14 +81,118 @@ pub struct AuditConfig {
     pub enclave_info_bytes: Vec<u8>,
     #[serde(default = "Default::default")]
     pub auditor_signatures_bytes: Vec<Vec<u8>>,
+    /// Signed list of revoked enclave measurements, polled by the services.
+    #[serde(default)]
+    pub revocation_list: Option<RevocationListConfig>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct RevocationListConfig {
+    #[serde(rename(serialize = "list", deserialize = "list"))]
+    list_source: ConfigSource,
+    #[serde(rename(serialize = "signatures", deserialize = "signatures"))]
+    signatures_source: Vec<ConfigSource>,
+    /// Interval in seconds to read the list again.
+    #[serde(default = "default_revocation_list_poll_interval")]
+    pub poll_interval: u64,
+    /// File keeping the version of the installed list sealed, so that a
+    /// restarted service refuses lists of lower versions.
+    #[serde(default = "default_revocation_list_version_path")]
+    pub version_path: PathBuf,
+}
+
+fn default_revocation_list_poll_interval() -> u64 {
+    60
+}
+
+fn default_revocation_list_version_path() -> PathBuf {
+    PathBuf::from("revocation_list.version")
+}
+
+impl RevocationListConfig {
+    /// Read the current list and its auditor signatures. The files are read
+    /// on every call, so an updated list is picked up without restarting.
+    pub fn read(&self) -> Result<(Vec<u8>, Vec<Vec<u8>>)> {
+        let list = match &self.list_source {
+            ConfigSource::Path(ref path) => fs::read(path)
+                .with_context(|| format!("Cannot read revocation list from {:?}", path))?,
+        };
+
+        let mut signatures: Vec<Vec<u8>> = vec![];
+        for source in &self.signatures_source {
+            let signature = match source {
+                ConfigSource::Path(ref path) => fs::read(path).with_context(|| {
+                    format!("Cannot read revocation list signature from {:?}", path)
+                })?,
+            };
+            signatures.push(signature);
+        }
+
+        Ok((list, signatures))
+    }
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct AttestationServiceConfig {
     pub algorithm: String,
+    /// Backend endorsing the quotes: "ias" (IAS or a service with the same
+    /// API), "dcap_direct" or "mock".
+    #[serde(default = "default_attestation_backend")]
+    pub backend: String,
     pub url: String,
     pub key: String,
     pub spid: String,
+    #[serde(default)]
+    pub policy: AttestationPolicyConfig,
+}
+
+fn default_attestation_backend() -> String {
+    "ias".to_string()
+}
+
+/// Attestation reports accepted from peer enclaves. Like the rest of the
+/// runtime config, this file is not trusted, so the policy only lets an
+/// operator refuse more reports; the enclave measurements are always checked
+/// against the audited enclave info.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct AttestationPolicyConfig {
+    /// Accepted quote statuses as named by the attestation service, e.g.,
+    /// "OK", "SW_HARDENING_NEEDED".
+    #[serde(default = "default_accepted_quote_statuses")]
+    pub accepted_quote_statuses: Vec<String>,
+    /// Minimum ISV SVN of the enclave.
+    #[serde(default)]
+    pub min_isv_svn: u16,
+    /// Minimum CPU SVN in hex, compared component-wise.
+    #[serde(default)]
+    pub min_cpu_svn: Option<String>,
+    /// Whether to refuse enclaves launched in debug mode.
+    #[serde(default)]
+    pub reject_debug_enclave: bool,
+    /// Maximum age of an attestation report in seconds.
+    #[serde(default)]
+    pub max_report_age: Option<u64>,
+}
+
+// Platforms missing TCB recovery or configuration are refused unless the
+// operator opts in to their statuses.
+fn default_accepted_quote_statuses() -> Vec<String> {
+    ["OK", "SW_HARDENING_NEEDED"]
+        .iter()
+        .map(|s| s.to_string())
+        .collect()
+}
+
+impl Default for AttestationPolicyConfig {
+    fn default() -> Self {
+        Self {
+            accepted_quote_statuses: default_accepted_quote_statuses(),
+            min_isv_svn: 0,
+            min_cpu_svn: None,
+            reject_debug_enclave: false,
+            max_report_age: None,
+        }
+    }
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
@@ -92,6 +200,72 @@ pub struct MountConfig {
     pub fusion_base_dir: PathBuf,
 }
 
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct ExecutionConfig {
+    /// Number of tasks an execution service runs concurrently. It is capped
+    /// by the number of TCS of the execution enclave.
+    pub worker_num: usize,
+    /// Whether raw inputs, and inputs and outputs in the chunked stream format,
+    /// are streamed from and to their remote location instead of staged on
+    /// local disk.
+    #[serde(default = "default_file_streaming")]
+    pub file_streaming: bool,
+    /// How the file agent retries failed transfers of task files.
+    #[serde(default)]
+    pub transfer_retry: TransferRetryConfig,
+}
+
+fn default_file_streaming() -> bool {
+    true
+}
+
+impl Default for ExecutionConfig {
+    fn default() -> Self {
+        Self {
+            worker_num: 1,
+            file_streaming: default_file_streaming(),
+            transfer_retry: TransferRetryConfig::default(),
+        }
+    }
+}
+
+/// The delay before the n-th retry is `initial_backoff_ms * 2^(n-1)`, capped
+/// by `max_backoff_ms`.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct TransferRetryConfig {
+    pub max_retries: u32,
+    pub initial_backoff_ms: u64,
+    pub max_backoff_ms: u64,
+}
+
+impl Default for TransferRetryConfig {
+    fn default() -> Self {
+        Self {
+            max_retries: 3,
+            initial_backoff_ms: 500,
+            max_backoff_ms: 10_000,
+        }
+    }
+}
+
+/// HTTP endpoints exporting metrics of the services in the Prometheus text
+/// format. Metrics of a service are not exported if its endpoint is not set.
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+pub struct MetricsConfig {
+    pub frontend: Option<MetricsEndpoint>,
+    pub authentication: Option<MetricsEndpoint>,
+    pub access_control: Option<MetricsEndpoint>,
+    pub management: Option<MetricsEndpoint>,
+    pub storage: Option<MetricsEndpoint>,
+    pub execution: Option<MetricsEndpoint>,
+    pub scheduler: Option<MetricsEndpoint>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct MetricsEndpoint {
+    pub listen_address: net::SocketAddr,
+}
+
 impl RuntimeConfig {
     pub fn from_toml<T: AsRef<Path>>(path: T) -> Result<Self> {
         let contents = fs::read_to_string(path.as_ref())
@@ -126,11 +300,15 @@ impl RuntimeConfig {
             let url = env::var("AS_URL").unwrap();
             let spid = env::var("AS_SPID").unwrap();
             let key = env::var("AS_KEY").unwrap();
+            let backend =
+                env::var("AS_BACKEND").unwrap_or_else(|_| config.attestation.backend.clone());
             config.attestation = AttestationServiceConfig {
                 algorithm,
+                backend,
                 url,
                 key,
                 spid,
+                policy: config.attestation.policy,
             };
         }
 
@@ -154,6 +332,13 @@ fn validate_config(config: &RuntimeConfig) -> Result<()> {
         ),
     }
 
+    match config.attestation.backend.as_str() {
+        "ias" | "mock" => (),
+        "dcap_direct" if config.attestation.algorithm == "sgx_ecdsa" => (),
+        "dcap_direct" => bail!("The dcap_direct attestation backend requires sgx_ecdsa"),
+        _ => bail!("Invalid attestation backend {}", config.attestation.backend),
+    }
+
     if config.attestation.spid.len() != 32 || config.attestation.key.len() != 32 {
         bail!("Cannot find Attestation Service SPID/key or format error");
     }
@@ -162,5 +347,19 @@ fn validate_config(config: &RuntimeConfig) -> Result<()> {
         bail!("Invalid URL of attestation service");
     }
 
+    if config.attestation.policy.accepted_quote_statuses.is_empty() {
+        bail!("No quote status is accepted by the attestation policy");
+    }
+
+    if let Some(revocation_list) = &config.audit.revocation_list {
+        if revocation_list.poll_interval == 0 {
+            bail!("Poll interval of the revocation list should be at least one second");
+        }
+    }
+
+    if config.execution.worker_num == 0 {
+        bail!("Number of execution workers should be at least one");
+    }
+
     Ok(())
 }
@@ -0,0 +1,187 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License.
+
+//! Envelope encryption of file keys to an X25519 public key.
+//!
+//! A key is wrapped by agreeing on a shared secret between a fresh ephemeral
+//! key pair and the recipient's public key, deriving a one-time AES-256-GCM
+//! key from it with HKDF-SHA256 (salted by both public keys), and sealing the
+//! key with it. Only the holder of the recipient's private key can unwrap it.
+
+#[cfg(feature = "mesalock_sgx")]
+use std::prelude::v1::*;
+
+use anyhow::{anyhow, ensure, Result};
+use ring::{aead, hkdf};
+use serde::{Deserialize, Serialize};
+use x25519_dalek::{PublicKey, StaticSecret};
+
+pub const KEY_WRAPPING_PUBLIC_KEY_LENGTH: usize = 32;
+const KEY_WRAPPING_PRIVATE_KEY_LENGTH: usize = 32;
+const KEY_WRAPPING_INFO: &[u8] = b"teaclave key wrapping v1";
+
+/// X25519 key pair to which file keys are wrapped.
+#[derive(Clone)]
+pub struct KeyWrappingKey {
+    secret: StaticSecret,
+}
+
+impl KeyWrappingKey {
+    pub fn random() -> Self {
+        Self {
+            secret: StaticSecret::new(rand::thread_rng()),
+        }
+    }
+
+    pub fn from_bytes(in_key: &[u8]) -> Result<Self> {
+        ensure!(
+            in_key.len() == KEY_WRAPPING_PRIVATE_KEY_LENGTH,
+            "Invalid key length for KeyWrappingKey: {}",
+            in_key.len()
+        );
+        let mut key = [0u8; KEY_WRAPPING_PRIVATE_KEY_LENGTH];
+        key.copy_from_slice(in_key);
+        Ok(Self {
+            secret: StaticSecret::from(key),
+        })
+    }
+
+    pub fn to_bytes(&self) -> [u8; KEY_WRAPPING_PRIVATE_KEY_LENGTH] {
+        self.secret.to_bytes()
+    }
+
+    pub fn public_key(&self) -> [u8; KEY_WRAPPING_PUBLIC_KEY_LENGTH] {
+        *PublicKey::from(&self.secret).as_bytes()
+    }
+
+    pub fn unwrap(&self, wrapped: &WrappedKey) -> Result<Vec<u8>> {
+        let ephemeral_public_key = PublicKey::from(wrapped.ephemeral_public_key);
+        let shared = self.secret.diffie_hellman(&ephemeral_public_key);
+        let key = derive_key(
+            shared.as_bytes(),
+            &wrapped.ephemeral_public_key,
+            &self.public_key(),
+        )?;
+
+        let mut in_out = wrapped.ciphertext.clone();
+        let plaintext_len = key
+            .open_in_place(zero_nonce(), aead::Aad::empty(), &mut in_out)
+            .map_err(|_| anyhow!("Cannot unwrap key"))?
+            .len();
+        in_out.truncate(plaintext_len);
+        Ok(in_out)
+    }
+}
+
+// Only the public key is printed so that the private key never ends up in logs.
+impl std::fmt::Debug for KeyWrappingKey {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("KeyWrappingKey")
+            .field("public_key", &hex::encode(self.public_key()))
+            .finish()
+    }
+}
+
+/// A key sealed to the public key of a `KeyWrappingKey`.
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
+pub struct WrappedKey {
+    pub ephemeral_public_key: [u8; KEY_WRAPPING_PUBLIC_KEY_LENGTH],
+    pub ciphertext: Vec<u8>,
+}
+
+impl WrappedKey {
+    pub fn new(in_ephemeral_public_key: &[u8], ciphertext: Vec<u8>) -> Result<Self> {
+        ensure!(
+            in_ephemeral_public_key.len() == KEY_WRAPPING_PUBLIC_KEY_LENGTH,
+            "Invalid ephemeral public key length: {}",
+            in_ephemeral_public_key.len()
+        );
+        let mut ephemeral_public_key = [0u8; KEY_WRAPPING_PUBLIC_KEY_LENGTH];
+        ephemeral_public_key.copy_from_slice(in_ephemeral_public_key);
+        Ok(Self {
+            ephemeral_public_key,
+            ciphertext,
+        })
+    }
+
+    /// Wraps `plaintext` to `recipient`, the public key of a `KeyWrappingKey`.
+    pub fn wrap(recipient: &[u8], plaintext: &[u8]) -> Result<Self> {
+        ensure!(
+            recipient.len() == KEY_WRAPPING_PUBLIC_KEY_LENGTH,
+            "Invalid recipient public key length: {}",
+            recipient.len()
+        );
+        let mut recipient_key = [0u8; KEY_WRAPPING_PUBLIC_KEY_LENGTH];
+        recipient_key.copy_from_slice(recipient);
+
+        let ephemeral = StaticSecret::new(rand::thread_rng());
+        let ephemeral_public_key = *PublicKey::from(&ephemeral).as_bytes();
+        let shared = ephemeral.diffie_hellman(&PublicKey::from(recipient_key));
+        let key = derive_key(shared.as_bytes(), &ephemeral_public_key, &recipient_key)?;
+
+        let mut ciphertext = plaintext.to_vec();
+        key.seal_in_place_append_tag(zero_nonce(), aead::Aad::empty(), &mut ciphertext)
+            .map_err(|_| anyhow!("Cannot wrap key"))?;
+        Ok(Self {
+            ephemeral_public_key,
+            ciphertext,
+        })
+    }
+}
+
+fn derive_key(
+    shared: &[u8],
+    ephemeral_public_key: &[u8],
+    recipient: &[u8],
+) -> Result<aead::LessSafeKey> {
+    let mut salt = ephemeral_public_key.to_vec();
+    salt.extend_from_slice(recipient);
+    let prk = hkdf::Salt::new(hkdf::HKDF_SHA256, &salt).extract(shared);
+    let okm = prk
+        .expand(&[KEY_WRAPPING_INFO], &aead::AES_256_GCM)
+        .map_err(|_| anyhow!("Key wrapping derivation error"))?;
+    Ok(aead::LessSafeKey::new(aead::UnboundKey::from(okm)))
+}
+
+// Each derived key seals exactly one message, so a fixed nonce is safe.
+fn zero_nonce() -> aead::Nonce {
+    aead::Nonce::assume_unique_for_key([0u8; aead::NONCE_LEN])
+}
+
+#[cfg(feature = "enclave_unit_test")]
+pub mod tests {
+    use super::*;
+
+    pub fn test_wrap_then_unwrap() {
+        let recipient = KeyWrappingKey::random();
+        let file_key = [0x42u8; 16];
+
+        let wrapped = WrappedKey::wrap(&recipient.public_key(), &file_key).unwrap();
+        assert_ne!(&wrapped.ciphertext[..file_key.len()], &file_key[..]);
+        assert_eq!(recipient.unwrap(&wrapped).unwrap(), file_key.to_vec());
+
+        let restored = KeyWrappingKey::from_bytes(&recipient.to_bytes()).unwrap();
+        assert_eq!(restored.unwrap(&wrapped).unwrap(), file_key.to_vec());
+
+        let other = KeyWrappingKey::random();
+        assert!(other.unwrap(&wrapped).is_err());
+
+        let mut tampered = wrapped;
+        tampered.ciphertext[0] ^= 1;
+        assert!(recipient.unwrap(&tampered).is_err());
+    }
+}
@@ -22,6 +22,8 @@ extern crate sgx_tstd as std;
 #[cfg(feature = "mesalock_sgx")]
 use std::prelude::v1::*;
 
+use aes_gcm_siv::aead::{AeadInPlace, NewAead};
+use aes_gcm_siv::Aes256GcmSiv;
 use anyhow::{anyhow, ensure, Context, Result};
 use protected_fs::ProtectedFile;
 use rand::prelude::RngCore;
@@ -30,11 +32,24 @@ use serde::{Deserialize, Serialize};
 use std::format;
 use std::path::Path;
 
+mod envelope;
+pub use envelope::{KeyWrappingKey, WrappedKey, KEY_WRAPPING_PUBLIC_KEY_LENGTH};
+mod stream;
+pub use stream::{
+    Aes128, Aes256, AesGcm128StreamKey, AesGcm256StreamKey, AesGcmStreamKey, StreamDecryptor,
+    StreamEncryptor, StreamKeySize, STREAM_DEFAULT_CHUNK_SIZE, STREAM_HEADER_LENGTH,
+    STREAM_MAX_CHUNK_SIZE,
+};
+
 const AES_GCM_128_KEY_LENGTH: usize = 16;
 const AES_GCM_128_IV_LENGTH: usize = 12;
 
 const AES_GCM_256_KEY_LENGTH: usize = 32;
 const AES_GCM_256_IV_LENGTH: usize = 12;
+const CHACHA20_POLY1305_KEY_LENGTH: usize = 32;
+const CHACHA20_POLY1305_IV_LENGTH: usize = 12;
+const AES_GCM_SIV_KEY_LENGTH: usize = 32;
+const AES_GCM_SIV_IV_LENGTH: usize = 12;
 const TEACLAVE_FILE_128_ROOT_KEY_LENGTH: usize = 16;
 const CMAC_LENGTH: usize = 16;
 type CMac = [u8; CMAC_LENGTH];
@@ -177,6 +192,190 @@ impl Default for AesGcm128Key {
     }
 }
 
+#[derive(Copy, Clone, Debug, Serialize, Deserialize, PartialEq)]
+pub struct ChaCha20Poly1305Key {
+    pub key: [u8; CHACHA20_POLY1305_KEY_LENGTH],
+    pub iv: [u8; CHACHA20_POLY1305_IV_LENGTH],
+}
+
+impl ChaCha20Poly1305Key {
+    pub const SCHEMA: &'static str = "chacha20-poly1305";
+
+    pub fn new(in_key: &[u8], in_iv: &[u8]) -> Result<Self> {
+        ensure!(
+            in_key.len() == CHACHA20_POLY1305_KEY_LENGTH,
+            "Invalid key length for ChaCha20Poly1305: {}",
+            in_key.len()
+        );
+        ensure!(
+            in_iv.len() == CHACHA20_POLY1305_IV_LENGTH,
+            "Invalid iv length for ChaCha20Poly1305: {}",
+            in_iv.len()
+        );
+        let mut key = [0u8; CHACHA20_POLY1305_KEY_LENGTH];
+        let mut iv = [0u8; CHACHA20_POLY1305_IV_LENGTH];
+        key.copy_from_slice(in_key);
+        iv.copy_from_slice(in_iv);
+
+        Ok(ChaCha20Poly1305Key { key, iv })
+    }
+
+    pub fn from_hex(in_key: impl AsRef<str>, in_iv: impl AsRef<str>) -> Result<Self> {
+        let key = hex::decode(in_key.as_ref()).context("Illegal Key")?;
+        let iv = hex::decode(in_iv.as_ref()).context("Illegal IV")?;
+        Self::new(&key, &iv)
+    }
+
+    pub fn random() -> Self {
+        Self::default()
+    }
+
+    pub fn decrypt(&self, in_out: &mut Vec<u8>) -> Result<CMac> {
+        let plaintext_len =
+            aead_decrypt(&aead::CHACHA20_POLY1305, in_out, &self.key, &self.iv)?.len();
+        let mut cmac: CMac = [0u8; CMAC_LENGTH];
+        cmac.copy_from_slice(&in_out[plaintext_len..]);
+        in_out.truncate(plaintext_len);
+        Ok(cmac)
+    }
+
+    pub fn encrypt(&self, in_out: &mut Vec<u8>) -> Result<CMac> {
+        aead_encrypt(&aead::CHACHA20_POLY1305, in_out, &self.key, &self.iv)?;
+        let mut cmac: CMac = [0u8; CMAC_LENGTH];
+        let n = in_out.len();
+        let cybertext_len = n - CMAC_LENGTH;
+        cmac.copy_from_slice(&in_out[cybertext_len..]);
+        Ok(cmac)
+    }
+
+    /// Encrypts into the file format of the schema, where the iv is written
+    /// in front of the ciphertext, so that a file can be decrypted without
+    /// knowing which iv it was encrypted with.
+    pub fn encrypt_with_prefixed_iv(&self, in_out: &mut Vec<u8>) -> Result<CMac> {
+        let cmac = self.encrypt(in_out)?;
+        let mut content = self.iv.to_vec();
+        content.extend_from_slice(in_out);
+        *in_out = content;
+        Ok(cmac)
+    }
+
+    /// Encrypts into the file format with a random iv instead of the iv of
+    /// the key, for content encrypted more than once with the same key, e.g.,
+    /// the output of every task run.
+    pub fn encrypt_with_random_iv(&self, in_out: &mut Vec<u8>) -> Result<CMac> {
+        let mut iv = [0u8; CHACHA20_POLY1305_IV_LENGTH];
+        rand::thread_rng().fill_bytes(&mut iv);
+        ChaCha20Poly1305Key { key: self.key, iv }.encrypt_with_prefixed_iv(in_out)
+    }
+
+    /// Decrypts a file in the format of `encrypt_with_prefixed_iv` with the
+    /// iv in front of it.
+    pub fn decrypt_with_prefixed_iv(&self, in_out: &mut Vec<u8>) -> Result<CMac> {
+        ensure!(
+            in_out.len() >= CHACHA20_POLY1305_IV_LENGTH + CMAC_LENGTH,
+            "Invalid length for ChaCha20Poly1305: {}",
+            in_out.len()
+        );
+        let mut ciphertext = in_out.split_off(CHACHA20_POLY1305_IV_LENGTH);
+        let cmac = ChaCha20Poly1305Key::new(&self.key, in_out)?.decrypt(&mut ciphertext)?;
+        *in_out = ciphertext;
+        Ok(cmac)
+    }
+}
+
+impl Default for ChaCha20Poly1305Key {
+    fn default() -> Self {
+        let mut key = [0u8; CHACHA20_POLY1305_KEY_LENGTH];
+        let mut iv = [0u8; CHACHA20_POLY1305_IV_LENGTH];
+        let mut rng = rand::thread_rng();
+        rng.fill_bytes(&mut key);
+        rng.fill_bytes(&mut iv);
+
+        Self { key, iv }
+    }
+}
+
+/// AES-256-GCM-SIV key. Encrypting different files under the same key and
+/// iv only reveals whether they are identical, so the iv may be reused.
+#[derive(Copy, Clone, Debug, Serialize, Deserialize, PartialEq)]
+pub struct AesGcmSivKey {
+    pub key: [u8; AES_GCM_SIV_KEY_LENGTH],
+    pub iv: [u8; AES_GCM_SIV_IV_LENGTH],
+}
+
+impl AesGcmSivKey {
+    pub const SCHEMA: &'static str = "aes-gcm-siv";
+
+    pub fn new(in_key: &[u8], in_iv: &[u8]) -> Result<Self> {
+        ensure!(
+            in_key.len() == AES_GCM_SIV_KEY_LENGTH,
+            "Invalid key length for AesGcmSiv: {}",
+            in_key.len()
+        );
+        ensure!(
+            in_iv.len() == AES_GCM_SIV_IV_LENGTH,
+            "Invalid iv length for AesGcmSiv: {}",
+            in_iv.len()
+        );
+        let mut key = [0u8; AES_GCM_SIV_KEY_LENGTH];
+        let mut iv = [0u8; AES_GCM_SIV_IV_LENGTH];
+        key.copy_from_slice(in_key);
+        iv.copy_from_slice(in_iv);
+
+        Ok(AesGcmSivKey { key, iv })
+    }
+
+    pub fn from_hex(in_key: impl AsRef<str>, in_iv: impl AsRef<str>) -> Result<Self> {
+        let key = hex::decode(in_key.as_ref()).context("Illegal Key")?;
+        let iv = hex::decode(in_iv.as_ref()).context("Illegal IV")?;
+        Self::new(&key, &iv)
+    }
+
+    pub fn random() -> Self {
+        Self::default()
+    }
+
+    pub fn decrypt(&self, in_out: &mut Vec<u8>) -> Result<CMac> {
+        ensure!(
+            in_out.len() >= CMAC_LENGTH,
+            "AesGcmSiv ciphertext too short"
+        );
+        let plaintext_len = in_out.len() - CMAC_LENGTH;
+        let mut cmac: CMac = [0u8; CMAC_LENGTH];
+        cmac.copy_from_slice(&in_out[plaintext_len..]);
+        in_out.truncate(plaintext_len);
+
+        let cipher = Aes256GcmSiv::new(&self.key.into());
+        cipher
+            .decrypt_in_place_detached(&self.iv.into(), &[0u8; 8], in_out, &cmac.into())
+            .map_err(|_| anyhow!("AesGcmSiv decrypt error"))?;
+        Ok(cmac)
+    }
+
+    pub fn encrypt(&self, in_out: &mut Vec<u8>) -> Result<CMac> {
+        let cipher = Aes256GcmSiv::new(&self.key.into());
+        let tag = cipher
+            .encrypt_in_place_detached(&self.iv.into(), &[0u8; 8], in_out)
+            .map_err(|_| anyhow!("AesGcmSiv encrypt error"))?;
+        let mut cmac: CMac = [0u8; CMAC_LENGTH];
+        cmac.copy_from_slice(&tag);
+        in_out.extend_from_slice(&cmac);
+        Ok(cmac)
+    }
+}
+
+impl Default for AesGcmSivKey {
+    fn default() -> Self {
+        let mut key = [0u8; AES_GCM_SIV_KEY_LENGTH];
+        let mut iv = [0u8; AES_GCM_SIV_IV_LENGTH];
+        let mut rng = rand::thread_rng();
+        rng.fill_bytes(&mut key);
+        rng.fill_bytes(&mut iv);
+
+        Self { key, iv }
+    }
+}
+
 #[derive(Copy, Clone, Debug, Serialize, Deserialize, PartialEq)]
 pub struct TeaclaveFile128Key {
     pub key: [u8; TEACLAVE_FILE_128_ROOT_KEY_LENGTH],
@@ -272,7 +471,16 @@ pub mod tests {
     use teaclave_test_utils::*;
 
     pub fn run_tests() -> bool {
-        run_tests!(test_aead_enc_then_dec, test_crypto_info,)
+        run_tests!(
+            test_aead_enc_then_dec,
+            test_crypto_info,
+            test_chacha20_poly1305,
+            test_aes_gcm_siv,
+            stream::tests::test_stream_enc_then_dec,
+            stream::tests::test_stream_tampering,
+            stream::tests::test_stream_256,
+            envelope::tests::test_wrap_then_unwrap,
+        )
     }
 
     fn test_aead_enc_then_dec() {
@@ -300,4 +508,60 @@ pub mod tests {
         crypto_info.decrypt(&mut buf).unwrap();
         assert_eq!(&buf[..], &plain_text[..]);
     }
+
+    fn test_chacha20_poly1305() {
+        let crypto_info = ChaCha20Poly1305Key::random();
+        let plain_text: [u8; 5] = [0xde, 0xff, 0xab, 0xcd, 0x90];
+        let mut buf = plain_text.to_vec();
+
+        let cmac = crypto_info.encrypt(&mut buf).unwrap();
+        assert_ne!(&buf[..], &plain_text[..]);
+        assert_eq!(crypto_info.decrypt(&mut buf).unwrap(), cmac);
+        assert_eq!(&buf[..], &plain_text[..]);
+
+        // The file format carries the iv.
+        let mut file = plain_text.to_vec();
+        let cmac = crypto_info.encrypt_with_prefixed_iv(&mut file).unwrap();
+        assert_eq!(&file[..CHACHA20_POLY1305_IV_LENGTH], &crypto_info.iv[..]);
+        assert_eq!(
+            crypto_info.decrypt_with_prefixed_iv(&mut file).unwrap(),
+            cmac
+        );
+        assert_eq!(&file[..], &plain_text[..]);
+
+        // Each encryption with a random iv uses another iv.
+        let mut first = plain_text.to_vec();
+        let mut second = plain_text.to_vec();
+        let cmac = crypto_info.encrypt_with_random_iv(&mut first).unwrap();
+        crypto_info.encrypt_with_random_iv(&mut second).unwrap();
+        assert_eq!(
+            first.len(),
+            CHACHA20_POLY1305_IV_LENGTH + plain_text.len() + CMAC_LENGTH
+        );
+        assert_ne!(first, second);
+        assert_eq!(
+            crypto_info.decrypt_with_prefixed_iv(&mut first).unwrap(),
+            cmac
+        );
+        assert_eq!(&first[..], &plain_text[..]);
+
+        second[0] ^= 1;
+        assert!(crypto_info.decrypt_with_prefixed_iv(&mut second).is_err());
+    }
+
+    fn test_aes_gcm_siv() {
+        let crypto_info = AesGcmSivKey::random();
+        let plain_text: [u8; 5] = [0xde, 0xff, 0xab, 0xcd, 0x90];
+        let mut buf = plain_text.to_vec();
+
+        let cmac = crypto_info.encrypt(&mut buf).unwrap();
+        assert_eq!(buf.len(), plain_text.len() + CMAC_LENGTH);
+        assert_eq!(crypto_info.decrypt(&mut buf).unwrap(), cmac);
+        assert_eq!(&buf[..], &plain_text[..]);
+
+        let mut tampered = plain_text.to_vec();
+        crypto_info.encrypt(&mut tampered).unwrap();
+        tampered[0] ^= 1;
+        assert!(crypto_info.decrypt(&mut tampered).is_err());
+    }
 }
@@ -0,0 +1,555 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License.
+
+//! A segmented AEAD format for encrypting files of any size chunk by chunk.
+//!
+//! The file starts with a header of `magic (4) || chunk size (4, big endian)
+//! || nonce prefix (7)`, followed by chunks of at most `chunk size` bytes of
+//! plaintext, each sealed with AES-GCM and its 16-byte tag appended. The nonce
+//! of the i-th chunk is `nonce prefix || i (4, big endian) || last`, where
+//! `last` is 1 for the final chunk and 0 otherwise, and the header is the
+//! associated data of every chunk. Reordering, dropping or appending chunks
+//! fails authentication, and so does truncating the file as the final flag
+//! would be missing. The tag of the final chunk identifies the file.
+
+#[cfg(feature = "mesalock_sgx")]
+use std::prelude::v1::*;
+
+use anyhow::{anyhow, ensure, Result};
+use rand::prelude::RngCore;
+use ring::aead;
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use std::fmt::Debug;
+use std::io::{self, Read, Write};
+use std::marker::PhantomData;
+
+use crate::{CMac, CMAC_LENGTH};
+
+const STREAM_MAGIC: [u8; 4] = *b"TCS\x01";
+const NONCE_PREFIX_LENGTH: usize = 7;
+pub const STREAM_HEADER_LENGTH: usize = 4 + 4 + NONCE_PREFIX_LENGTH;
+pub const STREAM_DEFAULT_CHUNK_SIZE: usize = 64 * 1024;
+pub const STREAM_MAX_CHUNK_SIZE: usize = 16 * 1024 * 1024;
+
+/// The key size of an AES-GCM stream, selecting its key type, schema and
+/// AEAD algorithm.
+pub trait StreamKeySize: Copy + Clone + Debug + PartialEq {
+    type Key: AsRef<[u8]>
+        + AsMut<[u8]>
+        + Copy
+        + Clone
+        + Debug
+        + Default
+        + PartialEq
+        + Serialize
+        + DeserializeOwned;
+
+    const SCHEMA: &'static str;
+
+    fn algorithm() -> &'static aead::Algorithm;
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Aes128;
+
+impl StreamKeySize for Aes128 {
+    type Key = [u8; 16];
+
+    const SCHEMA: &'static str = "aes-gcm-128-stream";
+
+    fn algorithm() -> &'static aead::Algorithm {
+        &aead::AES_128_GCM
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Aes256;
+
+impl StreamKeySize for Aes256 {
+    type Key = [u8; 32];
+
+    const SCHEMA: &'static str = "aes-gcm-256-stream";
+
+    fn algorithm() -> &'static aead::Algorithm {
+        &aead::AES_256_GCM
+    }
+}
+
+pub type AesGcm128StreamKey = AesGcmStreamKey<Aes128>;
+pub type AesGcm256StreamKey = AesGcmStreamKey<Aes256>;
+
+#[derive(Copy, Clone, Debug, Serialize, Deserialize, PartialEq)]
+#[serde(bound = "")]
+pub struct AesGcmStreamKey<S: StreamKeySize> {
+    pub key: S::Key,
+    #[serde(skip)]
+    size: PhantomData<S>,
+}
+
+impl<S: StreamKeySize> AesGcmStreamKey<S> {
+    pub const SCHEMA: &'static str = S::SCHEMA;
+
+    pub fn new(in_key: &[u8]) -> Result<Self> {
+        let mut key = S::Key::default();
+        ensure!(
+            in_key.len() == key.as_ref().len(),
+            "Invalid key length for {}: {}",
+            S::SCHEMA,
+            in_key.len()
+        );
+        key.as_mut().copy_from_slice(in_key);
+        Ok(AesGcmStreamKey {
+            key,
+            size: PhantomData,
+        })
+    }
+
+    pub fn random() -> Self {
+        Self::default()
+    }
+
+    /// Returns a writer encrypting everything written to it into `inner` in
+    /// chunks of `chunk_size` bytes. The stream must be completed with
+    /// `finish`.
+    pub fn encryptor<W: Write>(&self, inner: W, chunk_size: usize) -> Result<StreamEncryptor<W>> {
+        StreamEncryptor::new(S::algorithm(), self.key.as_ref(), inner, chunk_size)
+    }
+
+    /// Returns a reader decrypting and authenticating the stream in `inner`
+    /// chunk by chunk.
+    pub fn decryptor<R: Read>(&self, inner: R) -> Result<StreamDecryptor<R>> {
+        StreamDecryptor::new(S::algorithm(), self.key.as_ref(), inner)
+    }
+
+    pub fn decrypt(&self, in_out: &mut Vec<u8>) -> Result<CMac> {
+        let mut plaintext = Vec::new();
+        let cmac = {
+            let mut decryptor = self.decryptor(&in_out[..])?;
+            decryptor.read_to_end(&mut plaintext)?;
+            decryptor.tag()
+        };
+        let cmac = cmac.ok_or_else(|| anyhow!("Incomplete stream"))?;
+        *in_out = plaintext;
+        Ok(cmac)
+    }
+
+    pub fn encrypt(&self, in_out: &mut Vec<u8>) -> Result<CMac> {
+        let mut encryptor = self.encryptor(Vec::new(), STREAM_DEFAULT_CHUNK_SIZE)?;
+        encryptor.write_all(in_out)?;
+        let (ciphertext, cmac) = encryptor.finish()?;
+        *in_out = ciphertext;
+        Ok(cmac)
+    }
+}
+
+impl<S: StreamKeySize> Default for AesGcmStreamKey<S> {
+    fn default() -> Self {
+        let mut key = S::Key::default();
+        let mut rng = rand::thread_rng();
+        rng.fill_bytes(key.as_mut());
+
+        AesGcmStreamKey {
+            key,
+            size: PhantomData,
+        }
+    }
+}
+
+fn chunk_nonce(prefix: &[u8], counter: u32, last: bool) -> aead::Nonce {
+    let mut nonce = [0u8; aead::NONCE_LEN];
+    nonce[..NONCE_PREFIX_LENGTH].copy_from_slice(prefix);
+    nonce[NONCE_PREFIX_LENGTH..NONCE_PREFIX_LENGTH + 4].copy_from_slice(&counter.to_be_bytes());
+    nonce[aead::NONCE_LEN - 1] = last as u8;
+    aead::Nonce::assume_unique_for_key(nonce)
+}
+
+fn invalid_data(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+pub struct StreamEncryptor<W: Write> {
+    inner: W,
+    key: aead::LessSafeKey,
+    header: [u8; STREAM_HEADER_LENGTH],
+    header_written: bool,
+    chunk_size: usize,
+    counter: u32,
+    buffer: Vec<u8>,
+    tag: Option<CMac>,
+}
+
+impl<W: Write> StreamEncryptor<W> {
+    pub fn new(
+        alg: &'static aead::Algorithm,
+        key: &[u8],
+        inner: W,
+        chunk_size: usize,
+    ) -> Result<Self> {
+        ensure!(
+            chunk_size > 0 && chunk_size <= STREAM_MAX_CHUNK_SIZE,
+            "Invalid chunk size: {}",
+            chunk_size
+        );
+        let key =
+            aead::UnboundKey::new(alg, key).map_err(|_| anyhow!("Aead unbound key init error"))?;
+
+        let mut header = [0u8; STREAM_HEADER_LENGTH];
+        header[..4].copy_from_slice(&STREAM_MAGIC);
+        header[4..8].copy_from_slice(&(chunk_size as u32).to_be_bytes());
+        rand::thread_rng().fill_bytes(&mut header[8..]);
+
+        Ok(Self {
+            inner,
+            key: aead::LessSafeKey::new(key),
+            header,
+            header_written: false,
+            chunk_size,
+            counter: 0,
+            buffer: Vec::with_capacity(chunk_size + CMAC_LENGTH),
+            tag: None,
+        })
+    }
+
+    fn seal_chunk(&mut self, last: bool) -> io::Result<()> {
+        if self.tag.is_some() {
+            return Err(io::Error::new(
+                io::ErrorKind::Other,
+                "Stream is already finished",
+            ));
+        }
+        if !self.header_written {
+            self.inner.write_all(&self.header)?;
+            self.header_written = true;
+        }
+
+        let nonce = chunk_nonce(&self.header[8..], self.counter, last);
+        let aad = aead::Aad::from(self.header);
+        self.key
+            .seal_in_place_append_tag(nonce, aad, &mut self.buffer)
+            .map_err(|_| io::Error::new(io::ErrorKind::Other, "Aead seal error"))?;
+        self.inner.write_all(&self.buffer)?;
+
+        if last {
+            let mut tag = [0u8; CMAC_LENGTH];
+            tag.copy_from_slice(&self.buffer[self.buffer.len() - CMAC_LENGTH..]);
+            self.tag = Some(tag);
+        } else {
+            self.counter = self
+                .counter
+                .checked_add(1)
+                .ok_or_else(|| io::Error::new(io::ErrorKind::Other, "Too many chunks"))?;
+        }
+        self.buffer.clear();
+        Ok(())
+    }
+
+    /// Seals the buffered data as the final chunk and returns the tag of the
+    /// stream. Calling it again returns the same tag.
+    pub fn finalize(&mut self) -> Result<CMac> {
+        if let Some(tag) = self.tag {
+            return Ok(tag);
+        }
+        self.seal_chunk(true)?;
+        self.inner.flush()?;
+        self.tag.ok_or_else(|| anyhow!("Stream is not finished"))
+    }
+
+    /// The writer the ciphertext goes to.
+    pub fn get_mut(&mut self) -> &mut W {
+        &mut self.inner
+    }
+
+    pub fn finish(mut self) -> Result<(W, CMac)> {
+        let tag = self.finalize()?;
+        Ok((self.inner, tag))
+    }
+}
+
+impl<W: Write> Write for StreamEncryptor<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let mut written = 0;
+        while written < buf.len() {
+            // A full chunk is only sealed once more data arrives, as the last
+            // chunk must be sealed with the final flag.
+            if self.buffer.len() == self.chunk_size {
+                self.seal_chunk(false)?;
+            }
+            let n = std::cmp::min(self.chunk_size - self.buffer.len(), buf.len() - written);
+            self.buffer.extend_from_slice(&buf[written..written + n]);
+            written += n;
+        }
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+pub struct StreamDecryptor<R: Read> {
+    inner: R,
+    key: aead::LessSafeKey,
+    header: [u8; STREAM_HEADER_LENGTH],
+    chunk_size: usize,
+    counter: u32,
+    chunk: Vec<u8>,
+    plaintext_len: usize,
+    pos: usize,
+    lookahead: Option<u8>,
+    tag: Option<CMac>,
+    expected_tag: Option<CMac>,
+}
+
+impl<R: Read> StreamDecryptor<R> {
+    pub fn new(alg: &'static aead::Algorithm, key: &[u8], mut inner: R) -> Result<Self> {
+        let key =
+            aead::UnboundKey::new(alg, key).map_err(|_| anyhow!("Aead unbound key init error"))?;
+
+        let mut header = [0u8; STREAM_HEADER_LENGTH];
+        inner
+            .read_exact(&mut header)
+            .map_err(|_| anyhow!("Invalid stream header"))?;
+        ensure!(header[..4] == STREAM_MAGIC, "Invalid stream header");
+        let mut chunk_size = [0u8; 4];
+        chunk_size.copy_from_slice(&header[4..8]);
+        let chunk_size = u32::from_be_bytes(chunk_size) as usize;
+        ensure!(
+            chunk_size > 0 && chunk_size <= STREAM_MAX_CHUNK_SIZE,
+            "Invalid chunk size: {}",
+            chunk_size
+        );
+
+        Ok(Self {
+            inner,
+            key: aead::LessSafeKey::new(key),
+            header,
+            chunk_size,
+            counter: 0,
+            chunk: Vec::with_capacity(chunk_size + CMAC_LENGTH),
+            plaintext_len: 0,
+            pos: 0,
+            lookahead: None,
+            tag: None,
+            expected_tag: None,
+        })
+    }
+
+    /// Fails the read of the final chunk if the tag of the stream differs.
+    pub fn expect_tag(mut self, tag: impl Into<CMac>) -> Self {
+        self.expected_tag = Some(tag.into());
+        self
+    }
+
+    /// The tag of the stream, available once the final chunk has been read.
+    pub fn tag(&self) -> Option<CMac> {
+        self.tag
+    }
+
+    // Reads up to a full chunk, returns whether it is the last one.
+    fn fill_chunk(&mut self) -> io::Result<bool> {
+        let record_len = self.chunk_size + CMAC_LENGTH;
+        self.chunk.clear();
+        self.chunk.resize(record_len, 0);
+
+        let mut filled = 0;
+        if let Some(byte) = self.lookahead.take() {
+            self.chunk[0] = byte;
+            filled = 1;
+        }
+        while filled < record_len {
+            match self.inner.read(&mut self.chunk[filled..]) {
+                Ok(0) => break,
+                Ok(n) => filled += n,
+                Err(ref e) if e.kind() == io::ErrorKind::Interrupted => continue,
+                Err(e) => return Err(e),
+            }
+        }
+        self.chunk.truncate(filled);
+        if filled < record_len {
+            return Ok(true);
+        }
+
+        let mut next = [0u8; 1];
+        loop {
+            match self.inner.read(&mut next) {
+                Ok(0) => return Ok(true),
+                Ok(_) => {
+                    self.lookahead = Some(next[0]);
+                    return Ok(false);
+                }
+                Err(ref e) if e.kind() == io::ErrorKind::Interrupted => continue,
+                Err(e) => return Err(e),
+            }
+        }
+    }
+
+    fn open_chunk(&mut self) -> io::Result<()> {
+        let last = self.fill_chunk()?;
+        if self.chunk.len() < CMAC_LENGTH {
+            return Err(invalid_data("Truncated stream"));
+        }
+
+        let nonce = chunk_nonce(&self.header[8..], self.counter, last);
+        let aad = aead::Aad::from(self.header);
+        let mut tag = [0u8; CMAC_LENGTH];
+        tag.copy_from_slice(&self.chunk[self.chunk.len() - CMAC_LENGTH..]);
+        self.plaintext_len = self
+            .key
+            .open_in_place(nonce, aad, &mut self.chunk)
+            .map_err(|_| invalid_data("Corrupted or truncated stream"))?
+            .len();
+        self.pos = 0;
+
+        if last {
+            if let Some(expected) = self.expected_tag {
+                if expected != tag {
+                    return Err(invalid_data("Stream tag mismatch"));
+                }
+            }
+            self.tag = Some(tag);
+        } else {
+            self.counter = self
+                .counter
+                .checked_add(1)
+                .ok_or_else(|| invalid_data("Too many chunks"))?;
+        }
+        Ok(())
+    }
+}
+
+impl<R: Read> Read for StreamDecryptor<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        while self.pos == self.plaintext_len {
+            if self.tag.is_some() {
+                return Ok(0);
+            }
+            self.open_chunk()?;
+        }
+        let n = std::cmp::min(buf.len(), self.plaintext_len - self.pos);
+        buf[..n].copy_from_slice(&self.chunk[self.pos..self.pos + n]);
+        self.pos += n;
+        Ok(n)
+    }
+}
+
+#[cfg(feature = "enclave_unit_test")]
+pub mod tests {
+    use super::*;
+
+    pub fn test_stream_enc_then_dec() {
+        let key = AesGcm128StreamKey::random();
+        for len in [0, 1, 100, 1000, 1024, 4096].iter() {
+            let plaintext: Vec<u8> = (0..*len).map(|i| (i % 251) as u8).collect();
+
+            let mut encryptor = key.encryptor(Vec::new(), 100).unwrap();
+            // Write in uneven pieces to cross chunk boundaries.
+            for piece in plaintext.chunks(37) {
+                encryptor.write_all(piece).unwrap();
+            }
+            let (ciphertext, tag) = encryptor.finish().unwrap();
+
+            let mut decryptor = key.decryptor(&ciphertext[..]).unwrap().expect_tag(tag);
+            let mut decrypted = Vec::new();
+            decryptor.read_to_end(&mut decrypted).unwrap();
+            assert_eq!(decrypted, plaintext);
+            assert_eq!(decryptor.tag(), Some(tag));
+        }
+    }
+
+    pub fn test_stream_tampering() {
+        let key = AesGcm128StreamKey::random();
+        let plaintext = vec![0x5au8; 1000];
+        let mut encryptor = key.encryptor(Vec::new(), 100).unwrap();
+        encryptor.write_all(&plaintext).unwrap();
+        let (ciphertext, tag) = encryptor.finish().unwrap();
+
+        let decrypt = |bytes: &[u8]| -> io::Result<Vec<u8>> {
+            let mut decrypted = Vec::new();
+            key.decryptor(bytes)
+                .map_err(|e| invalid_data(&e.to_string()))?
+                .read_to_end(&mut decrypted)?;
+            Ok(decrypted)
+        };
+
+        // Truncated at a chunk boundary
+        let record_len = 100 + CMAC_LENGTH;
+        assert!(decrypt(&ciphertext[..STREAM_HEADER_LENGTH + 3 * record_len]).is_err());
+        // Flipped bit
+        let mut flipped = ciphertext.clone();
+        flipped[STREAM_HEADER_LENGTH + 5] ^= 1;
+        assert!(decrypt(&flipped).is_err());
+        // Appended data
+        let mut appended = ciphertext.clone();
+        appended.push(0);
+        assert!(decrypt(&appended).is_err());
+        // Swapped chunks
+        let mut swapped = ciphertext.clone();
+        let (first, second) = swapped[STREAM_HEADER_LENGTH..].split_at_mut(record_len);
+        first.swap_with_slice(&mut second[..record_len]);
+        assert!(decrypt(&swapped).is_err());
+        // Wrong file tag
+        let mut wrong_tag = tag;
+        wrong_tag[0] ^= 1;
+        let mut decrypted = Vec::new();
+        assert!(key
+            .decryptor(&ciphertext[..])
+            .unwrap()
+            .expect_tag(wrong_tag)
+            .read_to_end(&mut decrypted)
+            .is_err());
+
+        assert_eq!(decrypt(&ciphertext).unwrap(), plaintext);
+    }
+
+    pub fn test_stream_256() {
+        let key = AesGcm256StreamKey::random();
+        let plaintext = b"Hello Teaclave!".to_vec();
+
+        let mut buf = plaintext.clone();
+        let tag = key.encrypt(&mut buf).unwrap();
+        assert_eq!(
+            buf.len(),
+            STREAM_HEADER_LENGTH + plaintext.len() + CMAC_LENGTH
+        );
+        assert_eq!(&buf[buf.len() - CMAC_LENGTH..], &tag[..]);
+
+        // The single chunk opens with plain AES-256-GCM under the final nonce.
+        let mut header = [0u8; STREAM_HEADER_LENGTH];
+        header.copy_from_slice(&buf[..STREAM_HEADER_LENGTH]);
+        let mut chunk = buf[STREAM_HEADER_LENGTH..].to_vec();
+        let raw_key = aead::UnboundKey::new(&aead::AES_256_GCM, &key.key).unwrap();
+        let opened = aead::LessSafeKey::new(raw_key)
+            .open_in_place(
+                chunk_nonce(&header[8..], 0, true),
+                aead::Aad::from(header),
+                &mut chunk,
+            )
+            .unwrap();
+        assert_eq!(opened, &plaintext[..]);
+
+        assert_eq!(key.decrypt(&mut buf).unwrap(), tag);
+        assert_eq!(buf, plaintext);
+
+        // A stream is bound to its algorithm and key.
+        let mut buf = plaintext.clone();
+        key.encrypt(&mut buf).unwrap();
+        let other = AesGcm128StreamKey::new(&key.key[..16]).unwrap();
+        assert!(other.decrypt(&mut buf).is_err());
+        assert!(AesGcm256StreamKey::new(&key.key[..16]).is_err());
+    }
+}
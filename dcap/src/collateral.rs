@@ -0,0 +1,421 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License.
+
+//! Quote verification collateral (PCK CRLs, TCB info and QE identity) fetched
+//! with the quote provider library and cached per platform, so that quotes are
+//! not verified with collateral fetched on every request.
+
+use std::collections::HashMap;
+use std::convert::TryInto;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use sgx_types::*;
+
+/// Length of the quote header and the ISV enclave report body
+const QUOTE_BODY_LEN: usize = 432;
+/// DER encoded OID of the FMSPC extension in PCK certificates, i.e.,
+/// 1.2.840.113741.1.13.1.4
+const FMSPC_OID: &[u8] = &[
+    0x06, 0x0a, 0x2a, 0x86, 0x48, 0x86, 0xf8, 0x4d, 0x01, 0x0d, 0x01, 0x04,
+];
+const FMSPC_LEN: usize = 6;
+const PCK_PLATFORM_CA: &[u8] = b"Intel SGX PCK Platform CA";
+/// Certification data type of the concatenated PCK certificate chain in PEM
+const PCK_CERT_CHAIN_TYPE: u16 = 5;
+
+#[link(name = "dcap_quoteprov")]
+extern "C" {
+    #[allow(improper_ctypes)]
+    fn sgx_ql_get_quote_verification_collateral(
+        fmspc: *const u8,
+        fmspc_size: u16,
+        pck_ca: *const libc::c_char,
+        pp_quote_collateral: *mut *mut sgx_ql_qve_collateral_t,
+    ) -> sgx_quote3_error_t;
+
+    #[allow(improper_ctypes)]
+    fn sgx_ql_free_quote_verification_collateral(
+        p_quote_collateral: *const sgx_ql_qve_collateral_t,
+    ) -> sgx_quote3_error_t;
+}
+
+/// Platform which the collateral is issued for, identified by the FMSPC and
+/// the CA issuing its PCK certificate.
+#[derive(Clone, Debug, Hash, PartialEq, Eq)]
+pub struct PlatformId {
+    fmspc: [u8; FMSPC_LEN],
+    pck_ca: &'static str,
+}
+
+impl PlatformId {
+    /// Get the platform from the PCK certificate in the certification data
+    /// of an ECDSA quote.
+    pub fn from_quote(quote: &[u8]) -> Result<Self, String> {
+        let pck_cert_chain = pck_cert_chain(quote)?;
+        let pck_cert = pem::parse_many(pck_cert_chain)
+            .into_iter()
+            .next()
+            .ok_or_else(|| "Missing PCK certificate in the quote".to_string())?
+            .contents;
+
+        let fmspc_pos = find(&pck_cert, FMSPC_OID)
+            .ok_or_else(|| "Missing FMSPC in the PCK certificate".to_string())?
+            + FMSPC_OID.len();
+        // An octet string of the FMSPC follows the OID
+        let fmspc = match pck_cert.get(fmspc_pos..fmspc_pos + 2 + FMSPC_LEN) {
+            Some([0x04, 0x06, fmspc @ ..]) => {
+                let mut buf = [0u8; FMSPC_LEN];
+                buf.copy_from_slice(fmspc);
+                buf
+            }
+            _ => return Err("Invalid FMSPC in the PCK certificate".to_string()),
+        };
+
+        let pck_ca = if find(&pck_cert, PCK_PLATFORM_CA).is_some() {
+            "platform"
+        } else {
+            "processor"
+        };
+
+        Ok(Self { fmspc, pck_ca })
+    }
+}
+
+/// Get the PCK certificate chain from the certification data of the quote
+/// signature data, whose layout is: signature (64), attestation key (64), QE
+/// report (384), QE report signature (64), QE authentication data (2 + n),
+/// certification data type (2), size (4) and data.
+fn pck_cert_chain<'a>(quote: &'a [u8]) -> Result<&'a [u8], String> {
+    let mut pos = QUOTE_BODY_LEN + 4 + 64 + 64 + 384 + 64;
+    let mut take = |n: usize| -> Result<&'a [u8], String> {
+        let ret = quote
+            .get(pos..pos + n)
+            .ok_or_else(|| "Truncated quote signature data".to_string())?;
+        pos += n;
+        Ok(ret)
+    };
+
+    let auth_data_len = u16::from_le_bytes(take(2)?.try_into().unwrap()) as usize;
+    take(auth_data_len)?;
+    let cert_data_type = u16::from_le_bytes(take(2)?.try_into().unwrap());
+    if cert_data_type != PCK_CERT_CHAIN_TYPE {
+        return Err(format!(
+            "Unsupported certification data type {}",
+            cert_data_type
+        ));
+    }
+    let cert_data_len = u32::from_le_bytes(take(4)?.try_into().unwrap()) as usize;
+
+    take(cert_data_len)
+}
+
+fn find(haystack: &[u8], needle: &[u8]) -> Option<usize> {
+    haystack.windows(needle.len()).position(|w| w == needle)
+}
+
+/// Collateral returned by the quote provider library, freed on drop.
+pub struct Collateral {
+    ptr: *mut sgx_ql_qve_collateral_t,
+    fetched_at: Instant,
+}
+
+// The collateral is never modified after it is fetched.
+unsafe impl Send for Collateral {}
+unsafe impl Sync for Collateral {}
+
+impl Collateral {
+    fn fetch(platform: &PlatformId) -> Result<Self, String> {
+        let pck_ca = std::ffi::CString::new(platform.pck_ca).unwrap();
+        let mut ptr: *mut sgx_ql_qve_collateral_t = std::ptr::null_mut();
+        let ret = unsafe {
+            sgx_ql_get_quote_verification_collateral(
+                platform.fmspc.as_ptr(),
+                FMSPC_LEN as u16,
+                pck_ca.as_ptr(),
+                &mut ptr as _,
+            )
+        };
+        if ret != sgx_quote3_error_t::SGX_QL_SUCCESS || ptr.is_null() {
+            return Err(format!("Failed to fetch collateral: {:?}", ret));
+        }
+
+        Ok(Self {
+            ptr,
+            fetched_at: Instant::now(),
+        })
+    }
+
+    pub fn as_ptr(&self) -> *const sgx_ql_qve_collateral_t {
+        self.ptr
+    }
+
+    /// Find the TCB level of the platform with its raw TCB in the TCB info of
+    /// the collateral.
+    pub fn tcb_level(&self, cpu_svn: &[u8; 16], pce_svn: u16) -> Option<TcbLevel> {
+        let tcb_info = unsafe {
+            let c = &*self.ptr;
+            std::slice::from_raw_parts(c.tcb_info as *const u8, c.tcb_info_size as usize)
+        };
+
+        tcb_level(tcb_info, cpu_svn, pce_svn)
+    }
+}
+
+/// Find the TCB level of the platform with its raw TCB, i.e., the first level
+/// in the TCB info whose components are all lower than or equal to the
+/// corresponding components of the platform.
+fn tcb_level(tcb_info: &[u8], cpu_svn: &[u8; 16], pce_svn: u16) -> Option<TcbLevel> {
+    // The size may include the terminating null
+    let tcb_info = match tcb_info.split_last() {
+        Some((0, rest)) => rest,
+        _ => tcb_info,
+    };
+    let tcb_info: serde_json::Value = serde_json::from_slice(tcb_info).ok()?;
+    let tcb_info = &tcb_info["tcbInfo"];
+
+    let level = tcb_info["tcbLevels"].as_array()?.iter().find(|level| {
+        let tcb = &level["tcb"];
+        let cpu_svn_ok = cpu_svn.iter().enumerate().all(|(i, svn)| {
+            let key = format!("sgxtcbcomp{:02}svn", i + 1);
+            tcb[&key].as_u64().map_or(false, |v| v <= *svn as u64)
+        });
+        let pce_svn_ok = tcb["pcesvn"]
+            .as_u64()
+            .map_or(false, |v| v <= pce_svn as u64);
+        cpu_svn_ok && pce_svn_ok
+    })?;
+
+    Some(TcbLevel {
+        evaluation_data_number: tcb_info["tcbEvaluationDataNumber"].as_u64(),
+        date: level["tcbDate"].as_str().map(|s| s.to_string()),
+        status: level["tcbStatus"].as_str().map(|s| s.to_string()),
+        advisory_ids: level["advisoryIDs"]
+            .as_array()
+            .map(|ids| {
+                ids.iter()
+                    .filter_map(|id| id.as_str().map(|s| s.to_string()))
+                    .collect()
+            })
+            .unwrap_or_default(),
+    })
+}
+
+impl Drop for Collateral {
+    fn drop(&mut self) {
+        unsafe {
+            sgx_ql_free_quote_verification_collateral(self.ptr);
+        }
+    }
+}
+
+/// TCB level of a platform from the TCB info in the collateral
+#[derive(Debug, PartialEq)]
+pub struct TcbLevel {
+    pub evaluation_data_number: Option<u64>,
+    pub date: Option<String>,
+    pub status: Option<String>,
+    pub advisory_ids: Vec<String>,
+}
+
+/// Cached collateral of platforms, refreshed when older than the refresh
+/// interval or when it has expired.
+pub struct CollateralCache {
+    refresh_interval: Duration,
+    cache: Mutex<HashMap<PlatformId, Arc<Collateral>>>,
+    /// Error of the last fetch, cleared when a fetch succeeds
+    last_error: Mutex<Option<String>>,
+}
+
+impl CollateralCache {
+    pub fn new(refresh_interval: Duration) -> Self {
+        Self {
+            refresh_interval,
+            cache: Mutex::new(HashMap::new()),
+            last_error: Mutex::new(None),
+        }
+    }
+
+    /// Get the collateral of the platform, from the cache if it is not due
+    /// for a refresh.
+    pub fn get(&self, platform: &PlatformId) -> Result<Arc<Collateral>, String> {
+        if let Some(collateral) = self.cache.lock().unwrap().get(platform) {
+            if collateral.fetched_at.elapsed() < self.refresh_interval {
+                return Ok(collateral.clone());
+            }
+        }
+
+        self.refresh(platform)
+    }
+
+    /// Fetch the collateral of the platform again, e.g., when the cached one
+    /// has expired.
+    pub fn refresh(&self, platform: &PlatformId) -> Result<Arc<Collateral>, String> {
+        let collateral = match Collateral::fetch(platform) {
+            Ok(collateral) => Arc::new(collateral),
+            Err(e) => {
+                *self.last_error.lock().unwrap() = Some(e.clone());
+                return Err(e);
+            }
+        };
+        *self.last_error.lock().unwrap() = None;
+        self.cache
+            .lock()
+            .unwrap()
+            .insert(platform.clone(), collateral.clone());
+
+        Ok(collateral)
+    }
+
+    /// Number of platforms whose collateral is cached
+    pub fn platforms(&self) -> usize {
+        self.cache.lock().unwrap().len()
+    }
+
+    /// Error of the last fetch of collateral if it has failed, i.e., the
+    /// provisioning certification service cannot be reached.
+    pub fn last_error(&self) -> Option<String> {
+        self.last_error.lock().unwrap().clone()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const FMSPC: [u8; FMSPC_LEN] = [0x00, 0x90, 0x6e, 0xa1, 0x00, 0x00];
+
+    /// Builds an ECDSA quote whose certification data is the given type and
+    /// data, with QE authentication data of the given length.
+    fn ecdsa_quote(auth_data_len: usize, cert_data_type: u16, cert_data: &[u8]) -> Vec<u8> {
+        let mut quote = vec![0u8; QUOTE_BODY_LEN + 4 + 64 + 64 + 384 + 64];
+        quote.extend_from_slice(&(auth_data_len as u16).to_le_bytes());
+        quote.extend(std::iter::repeat(0xaa).take(auth_data_len));
+        quote.extend_from_slice(&cert_data_type.to_le_bytes());
+        quote.extend_from_slice(&(cert_data.len() as u32).to_le_bytes());
+        quote.extend_from_slice(cert_data);
+        quote
+    }
+
+    /// Builds a PEM "certificate" carrying the FMSPC extension and the name
+    /// of the issuing CA, which is all the platform is identified by.
+    fn pck_cert_pem(fmspc: &[u8], pck_ca: &[u8]) -> Vec<u8> {
+        let mut der = vec![0x30, 0x82, 0x01, 0x00];
+        der.extend_from_slice(pck_ca);
+        der.extend_from_slice(FMSPC_OID);
+        der.extend_from_slice(&[0x04, fmspc.len() as u8]);
+        der.extend_from_slice(fmspc);
+        pem::encode(&pem::Pem {
+            tag: "CERTIFICATE".to_string(),
+            contents: der,
+        })
+        .into_bytes()
+    }
+
+    #[test]
+    fn test_pck_cert_chain() {
+        let quote = ecdsa_quote(32, PCK_CERT_CHAIN_TYPE, b"chain");
+        assert_eq!(pck_cert_chain(&quote).unwrap(), b"chain");
+
+        let quote = ecdsa_quote(0, PCK_CERT_CHAIN_TYPE, b"");
+        assert_eq!(pck_cert_chain(&quote).unwrap(), b"");
+
+        let quote = ecdsa_quote(32, 6, b"chain");
+        assert!(pck_cert_chain(&quote).is_err());
+
+        let mut quote = ecdsa_quote(32, PCK_CERT_CHAIN_TYPE, b"chain");
+        quote.pop();
+        assert!(pck_cert_chain(&quote).is_err());
+        assert!(pck_cert_chain(&[0u8; QUOTE_BODY_LEN]).is_err());
+    }
+
+    #[test]
+    fn test_platform_id_from_quote() {
+        let cert = pck_cert_pem(&FMSPC, PCK_PLATFORM_CA);
+        let platform = PlatformId::from_quote(&ecdsa_quote(0, PCK_CERT_CHAIN_TYPE, &cert)).unwrap();
+        assert_eq!(platform.fmspc, FMSPC);
+        assert_eq!(platform.pck_ca, "platform");
+
+        let cert = pck_cert_pem(&FMSPC, b"Intel SGX PCK Processor CA");
+        let platform = PlatformId::from_quote(&ecdsa_quote(0, PCK_CERT_CHAIN_TYPE, &cert)).unwrap();
+        assert_eq!(platform.pck_ca, "processor");
+
+        // FMSPC of a wrong length
+        let cert = pck_cert_pem(&FMSPC[..4], PCK_PLATFORM_CA);
+        assert!(PlatformId::from_quote(&ecdsa_quote(0, PCK_CERT_CHAIN_TYPE, &cert)).is_err());
+        // No certificate
+        assert!(PlatformId::from_quote(&ecdsa_quote(0, PCK_CERT_CHAIN_TYPE, b"")).is_err());
+    }
+
+    fn tcb_info() -> Vec<u8> {
+        let mut tcb_info = serde_json::json!({
+            "tcbInfo": {
+                "tcbEvaluationDataNumber": 5,
+                "tcbLevels": [
+                    {
+                        "tcb": tcb(3, 10),
+                        "tcbDate": "2020-05-28T00:00:00Z",
+                        "tcbStatus": "UpToDate",
+                    },
+                    {
+                        "tcb": tcb(2, 9),
+                        "tcbDate": "2019-11-13T00:00:00Z",
+                        "tcbStatus": "OutOfDate",
+                        "advisoryIDs": ["INTEL-SA-00334", "INTEL-SA-00320"],
+                    },
+                ],
+            },
+        })
+        .to_string()
+        .into_bytes();
+        tcb_info.push(0);
+        tcb_info
+    }
+
+    fn tcb(cpu_svn: u8, pce_svn: u16) -> serde_json::Value {
+        let mut tcb = serde_json::json!({ "pcesvn": pce_svn });
+        for i in 1..=16 {
+            tcb[format!("sgxtcbcomp{:02}svn", i)] = serde_json::json!(cpu_svn);
+        }
+        tcb
+    }
+
+    #[test]
+    fn test_tcb_level() {
+        let tcb_info = tcb_info();
+
+        let level = tcb_level(&tcb_info, &[3; 16], 10).unwrap();
+        assert_eq!(level.evaluation_data_number, Some(5));
+        assert_eq!(level.status.as_deref(), Some("UpToDate"));
+        assert!(level.advisory_ids.is_empty());
+
+        // A lower PCE SVN falls to the next level
+        let level = tcb_level(&tcb_info, &[3; 16], 9).unwrap();
+        assert_eq!(level.status.as_deref(), Some("OutOfDate"));
+        assert_eq!(level.date.as_deref(), Some("2019-11-13T00:00:00Z"));
+        assert_eq!(level.advisory_ids, vec!["INTEL-SA-00334", "INTEL-SA-00320"]);
+
+        // Every CPU SVN component counts
+        let mut cpu_svn = [3; 16];
+        cpu_svn[15] = 2;
+        let level = tcb_level(&tcb_info, &cpu_svn, 10).unwrap();
+        assert_eq!(level.status.as_deref(), Some("OutOfDate"));
+
+        assert_eq!(tcb_level(&tcb_info, &[1; 16], 10), None);
+        assert_eq!(tcb_level(b"not json", &[3; 16], 10), None);
+    }
+}
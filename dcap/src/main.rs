@@ -23,6 +23,7 @@ extern crate rocket;
 extern crate lazy_static;
 extern crate chrono;
 extern crate libc;
+extern crate log;
 extern crate rand;
 extern crate ring;
 extern crate serde_json;
@@ -31,7 +32,10 @@ extern crate sgx_ucrypto;
 extern crate untrusted;
 extern crate uuid;
 
+mod collateral;
+
 use chrono::prelude::*;
+use collateral::{Collateral, CollateralCache, PlatformId, TcbLevel};
 use rand::{RngCore, SeedableRng};
 use ring::signature;
 use rocket::{http, response};
@@ -40,11 +44,23 @@ use sgx_types::*;
 const REPORT_SIGNING_CERT: &str = include_str!("../../keys/dcap_server_cert.pem");
 const REPORT_SIGNING_KEY: &str = include_str!("../../keys/dcap_server_key.pem");
 
+/// Length of the quote header and the ISV enclave report body
+const QUOTE_BODY_LEN: usize = 432;
+/// Default interval to refresh the cached collateral of a platform
+const DEFAULT_COLLATERAL_REFRESH_SECS: u64 = 24 * 60 * 60;
+
 lazy_static! {
     static ref SIGNER: signature::RsaKeyPair = {
         let der = pem::parse(REPORT_SIGNING_KEY).unwrap().contents;
         signature::RsaKeyPair::from_pkcs8(&der).unwrap()
     };
+    static ref COLLATERAL_CACHE: CollateralCache = {
+        let secs = std::env::var("DCAP_COLLATERAL_REFRESH_SECS")
+            .ok()
+            .and_then(|s| s.parse().ok())
+            .unwrap_or(DEFAULT_COLLATERAL_REFRESH_SECS);
+        CollateralCache::new(std::time::Duration::from_secs(secs))
+    };
 }
 
 #[link(name = "sgx_dcap_quoteverify")]
@@ -63,25 +79,72 @@ extern "C" {
         supplemental_data_size: u32,
         p_supplemental_data: *mut u8,
     ) -> sgx_quote3_error_t;
+
+    fn sgx_qv_get_quote_supplemental_data_size(p_data_size: *mut u32) -> sgx_quote3_error_t;
+}
+
+/// Versions of the supplemental data sharing the layout of
+/// `SupplementalData`, later versions only append fields to it.
+const SUPPLEMENTAL_DATA_VERSIONS: &[u32] = &[2, 3];
+
+/// Supplemental data of the quote verification (`sgx_ql_qv_supplemental_t`)
+#[allow(dead_code)]
+#[repr(C)]
+#[derive(Clone, Copy)]
+struct SupplementalData {
+    version: u32,
+    earliest_issue_date: time_t,
+    latest_issue_date: time_t,
+    earliest_expiration_date: time_t,
+    tcb_level_date_tag: time_t,
+    pck_crl_num: u32,
+    root_ca_crl_num: u32,
+    tcb_eval_ref_num: u32,
+    root_key_id: [u8; 48],
+    pck_ppid: [u8; 16],
+    tcb_cpusvn: [u8; 16],
+    tcb_pce_isvsvn: u16,
+    pce_id: u16,
+}
+
+impl SupplementalData {
+    /// Parse the supplemental data returned by the quote verification library,
+    /// which is only trusted to have this layout with a known version.
+    fn parse(data: &[u8]) -> Result<Self, String> {
+        if data.len() < std::mem::size_of::<Self>() {
+            return Err(format!("Supplemental data of {} bytes", data.len()));
+        }
+        let version = u32::from_le_bytes([data[0], data[1], data[2], data[3]]);
+        if !SUPPLEMENTAL_DATA_VERSIONS.contains(&version) {
+            return Err(format!("Unsupported supplemental data version {}", version));
+        }
+
+        Ok(unsafe { std::ptr::read_unaligned(data.as_ptr() as *const Self) })
+    }
 }
 
 enum QuoteVerificationResponse {
-    BadRequest,
-    InternalError,
+    Error(http::Status, String),
     AcceptedRequest(QuoteVerificationResult),
 }
 
 struct QuoteVerificationResult {
     pub quote_status: sgx_ql_qv_result_t,
     pub isv_enclave_quote: String,
+    pub tcb_level: Option<TcbLevel>,
 }
 
 impl QuoteVerificationResponse {
-    fn accept(quote_status: sgx_ql_qv_result_t, isv_enclave_quote: String) -> Self {
-        Self::AcceptedRequest(QuoteVerificationResult {
-            quote_status,
-            isv_enclave_quote,
-        })
+    fn bad_request(message: impl Into<String>) -> Self {
+        Self::Error(http::Status::BadRequest, message.into())
+    }
+
+    fn unavailable(message: impl Into<String>) -> Self {
+        Self::Error(http::Status::ServiceUnavailable, message.into())
+    }
+
+    fn internal_error(message: impl Into<String>) -> Self {
+        Self::Error(http::Status::InternalServerError, message.into())
     }
 }
 
@@ -96,43 +159,76 @@ fn to_report(rst: sgx_ql_qv_result_t) -> &'static str {
         SGX_QL_QV_RESULT_OUT_OF_DATE_CONFIG_NEEDED => "OUT_OF_DATE_CONFIGURATION_NEEDED",
         SGX_QL_QV_RESULT_INVALID_SIGNATURE => "SIGNATURE_INVALID",
         SGX_QL_QV_RESULT_REVOKED => "KEY_REVOKED",
-        SGX_QL_QV_RESULT_UNSPECIFIED => "UNSPECIFIED",
         SGX_QL_QV_RESULT_SW_HARDENING_NEEDED => "SW_HARDENING_NEEDED",
         SGX_QL_QV_RESULT_CONFIG_AND_SW_HARDENING_NEEDED => "CONFIGURATION_AND_SW_HARDENING_NEEDED",
-        _ => panic!(),
+        _ => "UNSPECIFIED",
     }
 }
 
 impl QuoteVerificationResult {
     pub fn to_json(&self) -> String {
-        serde_json::json!({
+        let mut report = serde_json::json!({
             "id": uuid::Uuid::new_v4().to_simple().to_string(),
             "version": 4,
             "timestamp": Utc::now().format("%Y-%m-%dT%H:%M:%S%.f").to_string(),
             "isvEnclaveQuoteStatus": to_report(self.quote_status),
             "isvEnclaveQuoteBody": self.isv_enclave_quote,
-        })
-        .to_string()
+        });
+
+        if let Some(tcb_level) = &self.tcb_level {
+            // Like IAS, advisory IDs are only given when there are any
+            if !tcb_level.advisory_ids.is_empty() {
+                report["advisoryIDs"] = serde_json::json!(tcb_level.advisory_ids);
+            }
+            if let Some(number) = tcb_level.evaluation_data_number {
+                report["tcbEvaluationDataNumber"] = serde_json::json!(number);
+            }
+            if let Some(date) = &tcb_level.date {
+                report["tcbDate"] = serde_json::json!(date);
+            }
+        }
+
+        report.to_string()
     }
 }
 
+fn json_response<'r>(status: http::Status, body: String) -> response::Result<'r> {
+    response::Response::build()
+        .status(status)
+        .header(http::ContentType::JSON)
+        .sized_body(std::io::Cursor::new(body))
+        .ok()
+}
+
+fn error_body(message: &str) -> String {
+    serde_json::json!({ "error": message }).to_string()
+}
+
 impl<'r> response::Responder<'r> for QuoteVerificationResponse {
     fn respond_to(self, _: &rocket::Request) -> response::Result<'r> {
         match self {
-            Self::BadRequest => response::Result::Err(http::Status::BadRequest),
-            Self::InternalError => response::Result::Err(http::Status::InternalServerError),
+            Self::Error(status, message) => {
+                log::error!("{}: {}", status, message);
+                json_response(status, error_body(&message))
+            }
             Self::AcceptedRequest(qvr) => {
                 let payload = qvr.to_json();
                 let mut signature = vec![0; SIGNER.public_modulus_len()];
                 let rng = ring::rand::SystemRandom::new();
-                SIGNER
+                if SIGNER
                     .sign(
                         &signature::RSA_PKCS1_SHA256,
                         &rng,
                         payload.as_bytes(),
                         &mut signature,
                     )
-                    .unwrap();
+                    .is_err()
+                {
+                    return json_response(
+                        http::Status::InternalServerError,
+                        error_body("Failed to sign the report"),
+                    );
+                }
                 response::Response::build()
                     .header(http::ContentType::JSON)
                     .header(http::hyper::header::Connection::close())
@@ -151,6 +247,104 @@ impl<'r> response::Responder<'r> for QuoteVerificationResponse {
     }
 }
 
+/// Outcome of verifying a quote with the collateral
+struct Verification {
+    quote_status: sgx_ql_qv_result_t,
+    collateral_expired: bool,
+    supplemental: Option<SupplementalData>,
+}
+
+fn verify(
+    quote: &[u8],
+    collateral: &Collateral,
+) -> Result<Verification, QuoteVerificationResponse> {
+    let mut collateral_exp_status = 1u32;
+    let mut quote_verification_result = sgx_ql_qv_result_t::SGX_QL_QV_RESULT_UNSPECIFIED;
+    let mut qve_report_info = sgx_ql_qe_report_info_t::default();
+
+    let mut nonce = sgx_quote_nonce_t::default();
+    let mut rng = rand::rngs::StdRng::from_entropy();
+    rng.fill_bytes(&mut nonce.rand);
+    qve_report_info.nonce = nonce;
+
+    let mut supplemental_size = 0u32;
+    let ret = unsafe { sgx_qv_get_quote_supplemental_data_size(&mut supplemental_size as _) };
+    if ret != sgx_quote3_error_t::SGX_QL_SUCCESS {
+        supplemental_size = 0;
+    }
+    let mut supplemental = vec![0u8; supplemental_size as usize];
+
+    let mut expiration_check_date: time_t = 0;
+    let ret = unsafe {
+        sgx_qv_verify_quote(
+            quote.as_ptr(),
+            quote.len() as _,
+            collateral.as_ptr(),
+            libc::time(&mut expiration_check_date),
+            &mut collateral_exp_status as _,
+            &mut quote_verification_result as _,
+            &mut qve_report_info as _,
+            supplemental_size,
+            if supplemental.is_empty() {
+                std::ptr::null_mut()
+            } else {
+                supplemental.as_mut_ptr()
+            },
+        )
+    };
+
+    if ret != sgx_quote3_error_t::SGX_QL_SUCCESS {
+        return Err(QuoteVerificationResponse::bad_request(format!(
+            "Failed to verify the quote: {:?}",
+            ret
+        )));
+    };
+
+    let hash = (|| {
+        let sha256 = sgx_ucrypto::SgxShaHandle::new();
+        sha256.init()?;
+        sha256.update_msg(&nonce.rand)?;
+        sha256.update_slice(&quote)?;
+        sha256.update_msg(&expiration_check_date)?;
+        sha256.update_msg(&collateral_exp_status)?;
+        sha256.update_msg(&(quote_verification_result as u32))?;
+        sha256.get_hash()
+    })()
+    .map_err(|e| {
+        QuoteVerificationResponse::internal_error(format!("Failed to hash the result: {:?}", e))
+    })?;
+
+    // This check isn't quote necessary if we are verifying the nonce in
+    // an untrusted environment
+    if hash != qve_report_info.qe_report.body.report_data.d[..32]
+        || [0u8; 32] != qve_report_info.qe_report.body.report_data.d[32..]
+    {
+        // Something wrong with out SW stack, probably compromised
+        return Err(QuoteVerificationResponse::internal_error(
+            "Quote verification result is not bound to the request",
+        ));
+    }
+
+    // The TCB level is only reported with supplemental data of a known layout
+    let supplemental = if supplemental.is_empty() {
+        None
+    } else {
+        match SupplementalData::parse(&supplemental) {
+            Ok(supplemental) => Some(supplemental),
+            Err(e) => {
+                log::warn!("Ignoring supplemental data: {}", e);
+                None
+            }
+        }
+    };
+
+    Ok(Verification {
+        quote_status: quote_verification_result,
+        collateral_expired: collateral_exp_status != 0,
+        supplemental,
+    })
+}
+
 #[post(
     "/sgx/dev/attestation/v4/report",
     format = "application/json",
@@ -159,74 +353,139 @@ impl<'r> response::Responder<'r> for QuoteVerificationResponse {
 fn verify_quote(request: String) -> QuoteVerificationResponse {
     let v = match serde_json::from_str::<serde_json::Value>(&request) {
         Ok(v) => v,
-        Err(_) => return QuoteVerificationResponse::BadRequest,
+        Err(e) => return QuoteVerificationResponse::bad_request(format!("Invalid JSON: {}", e)),
     };
 
-    if let serde_json::Value::String(base64_quote) = &v["isvEnclaveQuote"] {
-        let quote = match base64::decode(&base64_quote) {
-            Ok(v) => v,
-            Err(_) => return QuoteVerificationResponse::BadRequest,
+    let base64_quote = match &v["isvEnclaveQuote"] {
+        serde_json::Value::String(base64_quote) => base64_quote,
+        _ => return QuoteVerificationResponse::bad_request("Missing isvEnclaveQuote"),
+    };
+    let quote = match base64::decode(&base64_quote) {
+        Ok(v) if v.len() > QUOTE_BODY_LEN => v,
+        _ => return QuoteVerificationResponse::bad_request("Invalid isvEnclaveQuote"),
+    };
+
+    let platform = match PlatformId::from_quote(&quote) {
+        Ok(platform) => platform,
+        Err(e) => return QuoteVerificationResponse::bad_request(e),
+    };
+    let mut collateral = match COLLATERAL_CACHE.get(&platform) {
+        Ok(collateral) => collateral,
+        Err(e) => return QuoteVerificationResponse::unavailable(e),
+    };
+
+    let mut verification = match verify(&quote, &collateral) {
+        Ok(verification) => verification,
+        Err(response) => return response,
+    };
+
+    // The cached collateral may have expired before its refresh is due
+    if verification.collateral_expired {
+        collateral = match COLLATERAL_CACHE.refresh(&platform) {
+            Ok(collateral) => collateral,
+            Err(e) => return QuoteVerificationResponse::unavailable(e),
         };
-        let mut collateral_exp_status = 1u32;
-        let mut quote_verification_result = sgx_ql_qv_result_t::SGX_QL_QV_RESULT_UNSPECIFIED;
-        let mut qve_report_info = sgx_ql_qe_report_info_t::default();
-
-        let mut nonce = sgx_quote_nonce_t::default();
-        let mut rng = rand::rngs::StdRng::from_entropy();
-        rng.fill_bytes(&mut nonce.rand);
-        qve_report_info.nonce = nonce;
-        let mut expiration_check_date: time_t = 0;
-        let ret = unsafe {
-            sgx_qv_verify_quote(
-                quote.as_ptr(),
-                quote.len() as _,
-                std::ptr::null() as _,
-                libc::time(&mut expiration_check_date),
-                &mut collateral_exp_status as _,
-                &mut quote_verification_result as _,
-                &mut qve_report_info as _,
-                0,
-                std::ptr::null_mut(),
-            )
+        verification = match verify(&quote, &collateral) {
+            Ok(verification) => verification,
+            Err(response) => return response,
         };
+        if verification.collateral_expired {
+            return QuoteVerificationResponse::unavailable(
+                "Collateral of the platform has expired",
+            );
+        }
+    }
 
-        if ret != sgx_quote3_error_t::SGX_QL_SUCCESS {
-            eprintln!("sgx_qv_verify_quote fialed: {:?}", ret);
-            return QuoteVerificationResponse::BadRequest;
-        };
+    let tcb_level = verification
+        .supplemental
+        .and_then(|s| collateral.tcb_level(&s.tcb_cpusvn, s.tcb_pce_isvsvn));
 
-        if collateral_exp_status != 0 {
-            eprintln!("collateral_exp_status fialed: {:?}", collateral_exp_status);
-            return QuoteVerificationResponse::BadRequest;
-        }
+    // strip off signature data; client won't need this
+    QuoteVerificationResponse::AcceptedRequest(QuoteVerificationResult {
+        quote_status: verification.quote_status,
+        isv_enclave_quote: base64::encode(&quote[..QUOTE_BODY_LEN]),
+        tcb_level,
+    })
+}
 
-        let sha256 = sgx_ucrypto::SgxShaHandle::new();
-        sha256.init().unwrap();
-        sha256.update_msg(&nonce.rand).unwrap();
-        sha256.update_slice(&quote.as_slice()).unwrap();
-        sha256.update_msg(&expiration_check_date).unwrap();
-        sha256.update_msg(&collateral_exp_status).unwrap();
-        sha256
-            .update_msg(&(quote_verification_result as u32))
-            .unwrap();
-
-        // This check isn't quote necessary if we are verifying the nonce in
-        // an untrusted environment
-        if sha256.get_hash().unwrap() != qve_report_info.qe_report.body.report_data.d[..32]
-            || [0u8; 32] != qve_report_info.qe_report.body.report_data.d[32..]
-        {
-            // Something wrong with out SW stack, probably compromised
-            return QuoteVerificationResponse::InternalError;
+/// Replies `503` when quotes cannot be verified, i.e., the quote verification
+/// library cannot be used or the last fetch of collateral has failed.
+#[get("/health")]
+fn health() -> response::Result<'static> {
+    let mut supplemental_size = 0u32;
+    let ret = unsafe { sgx_qv_get_quote_supplemental_data_size(&mut supplemental_size as _) };
+    let error = if ret != sgx_quote3_error_t::SGX_QL_SUCCESS {
+        Some(format!(
+            "Quote verification library is unavailable: {:?}",
+            ret
+        ))
+    } else {
+        COLLATERAL_CACHE
+            .last_error()
+            .map(|e| format!("Collateral cannot be fetched: {}", e))
+    };
+
+    let mut body = serde_json::json!({
+        "status": if error.is_none() { "ok" } else { "unavailable" },
+        "cachedPlatforms": COLLATERAL_CACHE.platforms(),
+    });
+    let status = match error {
+        Some(error) => {
+            log::warn!("Unhealthy: {}", error);
+            body["error"] = serde_json::json!(error);
+            http::Status::ServiceUnavailable
         }
+        None => http::Status::Ok,
+    };
 
-        // strip off signature data; client won't need this
-        let quote_body = base64::encode(&quote[..432]);
-        QuoteVerificationResponse::accept(quote_verification_result, quote_body)
-    } else {
-        QuoteVerificationResponse::BadRequest
-    }
+    json_response(status, body.to_string())
+}
+
+#[catch(400)]
+fn bad_request() -> response::content::Json<String> {
+    response::content::Json(error_body("Bad request"))
+}
+
+#[catch(404)]
+fn not_found() -> response::content::Json<String> {
+    response::content::Json(error_body("Not found"))
+}
+
+#[catch(500)]
+fn internal_error() -> response::content::Json<String> {
+    response::content::Json(error_body("Internal server error"))
 }
 
 fn main() {
-    rocket::ignite().mount("/", routes![verify_quote]).launch();
+    rocket::ignite()
+        .mount("/", routes![verify_quote, health])
+        .register(catchers![bad_request, not_found, internal_error])
+        .launch();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn supplemental_data(version: u32, len: usize) -> Vec<u8> {
+        let mut data = vec![0u8; len];
+        data[..4].copy_from_slice(&version.to_le_bytes());
+        data
+    }
+
+    #[test]
+    fn test_supplemental_data() {
+        let size = std::mem::size_of::<SupplementalData>();
+        for version in SUPPLEMENTAL_DATA_VERSIONS {
+            let data = supplemental_data(*version, size);
+            assert_eq!(SupplementalData::parse(&data).unwrap().version, *version);
+            // Later versions append fields
+            assert!(SupplementalData::parse(&supplemental_data(*version, size + 32)).is_ok());
+        }
+
+        assert!(SupplementalData::parse(&supplemental_data(1, size)).is_err());
+        assert!(SupplementalData::parse(&supplemental_data(4, size)).is_err());
+        assert!(SupplementalData::parse(&supplemental_data(3, size - 1)).is_err());
+        assert!(SupplementalData::parse(&[]).is_err());
+    }
 }
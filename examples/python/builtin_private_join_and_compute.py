@@ -87,14 +87,10 @@ class ConfigClient:
             executor_type="builtin",
             arguments=["num_user"],
             inputs=[
-                FunctionInput("input_data0", "Bank A data file."),
-                FunctionInput("input_data1", "Bank B data file."),
-                FunctionInput("input_data2", "Bank C data file.")
+                FunctionInput("input_data", "Bank data files.", repeated=True)
             ],
             outputs=[
-                FunctionOutput("output_data0", "Output data."),
-                FunctionOutput("output_data1", "Output data."),
-                FunctionOutput("output_data2", "Output date.")
+                FunctionOutput("output_data", "Output data.", repeated=True)
             ])
 
         print(f"[+] {self.user_id} creating task")
@@ -104,19 +100,19 @@ class ConfigClient:
                                      }),
                                      executor="builtin",
                                      inputs_ownership=[
-                                         OwnerList("input_data0",
+                                         OwnerList("input_data[0]",
                                                    [USER_DATA_0.user_id]),
-                                         OwnerList("input_data1",
+                                         OwnerList("input_data[1]",
                                                    [USER_DATA_1.user_id]),
-                                         OwnerList("input_data2",
+                                         OwnerList("input_data[2]",
                                                    [USER_DATA_2.user_id])
                                      ],
                                      outputs_ownership=[
-                                         OwnerList("output_data0",
+                                         OwnerList("output_data[0]",
                                                    [USER_DATA_0.user_id]),
-                                         OwnerList("output_data1",
+                                         OwnerList("output_data[1]",
                                                    [USER_DATA_1.user_id]),
-                                         OwnerList("output_data2",
+                                         OwnerList("output_data[2]",
                                                    [USER_DATA_2.user_id])
                                      ])
 
@@ -187,18 +183,18 @@ def main():
     ## USER 0, 1, 2 join the task and upload their data
     user0 = DataClient(USER_DATA_0.user_id, USER_DATA_0.password)
     user0.register_data(task_id, USER_DATA_0.input_url, USER_DATA_0.input_cmac,
-                        USER_DATA_0.output_url, USER_DATA_0.key, "input_data0",
-                        "output_data0")
+                        USER_DATA_0.output_url, USER_DATA_0.key, "input_data[0]",
+                        "output_data[0]")
 
     user1 = DataClient(USER_DATA_1.user_id, USER_DATA_1.password)
     user1.register_data(task_id, USER_DATA_1.input_url, USER_DATA_1.input_cmac,
-                        USER_DATA_1.output_url, USER_DATA_1.key, "input_data1",
-                        "output_data1")
+                        USER_DATA_1.output_url, USER_DATA_1.key, "input_data[1]",
+                        "output_data[1]")
 
     user2 = DataClient(USER_DATA_2.user_id, USER_DATA_2.password)
     user2.register_data(task_id, USER_DATA_2.input_url, USER_DATA_2.input_cmac,
-                        USER_DATA_2.output_url, USER_DATA_2.key, "input_data2",
-                        "output_data2")
+                        USER_DATA_2.output_url, USER_DATA_2.key, "input_data[2]",
+                        "output_data[2]")
 
     user0.approve_task(task_id)
     user1.approve_task(task_id)
@@ -19,8 +19,9 @@
 use std::prelude::v1::*;
 
 use teaclave_function::{
-    Echo, GbdtPredict, GbdtTrain, LogisticRegressionPredict, LogisticRegressionTrain,
-    OnlineDecrypt, PrivateJoinAndCompute, RsaSign,
+    DescriptiveStats, DpAggregate, Echo, GbdtPredict, GbdtTrain, KMeansPredict, KMeansTrain,
+    LinearRegressionPredict, LinearRegressionTrain, LogisticRegressionPredict,
+    LogisticRegressionTrain, OnlineDecrypt, PrivateJoinAndCompute, ReencryptFile, RsaSign,
 };
 use teaclave_types::{FunctionArguments, FunctionRuntime, TeaclaveExecutor};
 
@@ -38,12 +39,24 @@ impl TeaclaveExecutor for BuiltinFunctionExecutor {
         runtime: FunctionRuntime,
     ) -> Result<String> {
         match name.as_str() {
+            #[cfg(feature = "builtin_descriptive_stats")]
+            DescriptiveStats::NAME => DescriptiveStats::new().run(arguments, runtime),
+            #[cfg(feature = "builtin_dp_aggregate")]
+            DpAggregate::NAME => DpAggregate::new().run(arguments, runtime),
             #[cfg(feature = "builtin_echo")]
             Echo::NAME => Echo::new().run(arguments, runtime),
             #[cfg(feature = "builtin_gbdt_predict")]
             GbdtPredict::NAME => GbdtPredict::new().run(arguments, runtime),
             #[cfg(feature = "builtin_gbdt_train")]
             GbdtTrain::NAME => GbdtTrain::new().run(arguments, runtime),
+            #[cfg(feature = "builtin_kmeans_predict")]
+            KMeansPredict::NAME => KMeansPredict::new().run(arguments, runtime),
+            #[cfg(feature = "builtin_kmeans_train")]
+            KMeansTrain::NAME => KMeansTrain::new().run(arguments, runtime),
+            #[cfg(feature = "builtin_linear_regression_train")]
+            LinearRegressionTrain::NAME => LinearRegressionTrain::new().run(arguments, runtime),
+            #[cfg(feature = "builtin_linear_regression_predict")]
+            LinearRegressionPredict::NAME => LinearRegressionPredict::new().run(arguments, runtime),
             #[cfg(feature = "builtin_logistic_regression_train")]
             LogisticRegressionTrain::NAME => LogisticRegressionTrain::new().run(arguments, runtime),
             #[cfg(feature = "builtin_logistic_regression_predict")]
@@ -54,6 +67,8 @@ impl TeaclaveExecutor for BuiltinFunctionExecutor {
             OnlineDecrypt::NAME => OnlineDecrypt::new().run(arguments, runtime),
             #[cfg(feature = "builtin_private_join_and_compute")]
             PrivateJoinAndCompute::NAME => PrivateJoinAndCompute::new().run(arguments, runtime),
+            #[cfg(feature = "builtin_reencrypt_file")]
+            ReencryptFile::NAME => ReencryptFile::new().run(arguments, runtime),
             #[cfg(feature = "builtin_rsa_sign")]
             RsaSign::NAME => RsaSign::new().run(arguments, runtime),
             _ => bail!("Function not found."),
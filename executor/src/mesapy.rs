@@ -22,13 +22,24 @@ use crate::context::set_thread_context;
 use crate::context::Context;
 
 use std::ffi::CString;
+#[cfg(not(feature = "mesalock_sgx"))]
+use std::sync::Mutex;
+#[cfg(feature = "mesalock_sgx")]
+use std::sync::SgxMutex as Mutex;
 
+use lazy_static::lazy_static;
 use teaclave_types::{FunctionArguments, FunctionRuntime, TeaclaveExecutor};
 
 const MAXPYBUFLEN: usize = 20480;
 const MESAPY_ERROR_BUFFER_TOO_SHORT: i64 = -1i64;
 const MESAPY_EXEC_ERROR: i64 = -2i64;
 
+lazy_static! {
+    // The interpreter keeps global state and is not reentrant, so concurrent
+    // workers take turns to run Python functions.
+    static ref MESAPY_LOCK: Mutex<()> = Mutex::new(());
+}
+
 extern "C" {
     fn mesapy_exec(
         input: *const u8,
@@ -68,6 +79,9 @@ impl TeaclaveExecutor for MesaPy {
 
         let mut py_result = [0u8; MAXPYBUFLEN];
 
+        let _guard = MESAPY_LOCK
+            .lock()
+            .map_err(|_| anyhow::anyhow!("Cannot lock MesaPy"))?;
         set_thread_context(Context::new(runtime))?;
 
         let result = unsafe {
@@ -17,25 +17,161 @@
 
 use futures::future::join_all;
 use futures::TryFutureExt;
-use tokio::io::AsyncWriteExt;
+use lazy_static::lazy_static;
+use tokio::io::{AsyncReadExt, AsyncSeekExt, AsyncWriteExt};
 use tokio_util::codec;
 use url::Url;
 
+use crate::s3;
+
 use std::path::{Component, Path, PathBuf};
-use teaclave_types::{FileAgentRequest, HandleFileCommand, HandleFileInfo};
+use teaclave_types::{
+    redact_url, FileAgentRequest, FileAgentResponse, FileHash, HandleFileCommand, HandleFileError,
+    HandleFileInfo, HandleFileResult, ReadFileRangeRequest, TransferRetryPolicy,
+};
+
+/// Returned by the ocall when all files are handled, see `FileAgentResponse`
+/// for the result of each file.
+pub(crate) const FILE_AGENT_OK: u32 = 0;
+pub(crate) const FILE_AGENT_INVALID_REQUEST: u32 = 1;
+pub(crate) const FILE_AGENT_BUFFER_TOO_SMALL: u32 = 2;
+pub(crate) const FILE_AGENT_READ_FAILED: u32 = 3;
+pub(crate) const FILE_AGENT_WRITE_FAILED: u32 = 4;
+
+lazy_static! {
+    // Runs the transfers of every ocall, so that range reads and streamed
+    // uploads do not build a runtime each, and connections are kept alive
+    // across ocalls by the shared client.
+    static ref RUNTIME: tokio::runtime::Handle = spawn_runtime();
+    pub(crate) static ref HTTP_CLIENT: reqwest::Client = reqwest::Client::new();
+}
+
+fn spawn_runtime() -> tokio::runtime::Handle {
+    let (sender, receiver) = std::sync::mpsc::channel();
+    std::thread::spawn(move || {
+        let mut runtime = tokio::runtime::Builder::new()
+            .threaded_scheduler()
+            .enable_all()
+            .build()
+            .expect("Cannot build file agent runtime");
+        let _ = sender.send(runtime.handle().clone());
+        runtime.block_on(futures::future::pending::<()>());
+    });
+    receiver.recv().expect("Cannot start file agent runtime")
+}
+
+/// Runs `future` on the runtime shared by the ocalls and waits for its
+/// result.
+pub(crate) fn block_on<T, F>(future: F) -> anyhow::Result<T>
+where
+    T: Send + 'static,
+    F: std::future::Future<Output = anyhow::Result<T>> + Send + 'static,
+{
+    futures::executor::block_on(RUNTIME.spawn(future))?
+}
+
+#[derive(thiserror::Error, Debug)]
+#[error("HTTP status {0}")]
+pub(crate) struct HttpStatusError(pub(crate) http::StatusCode);
+
+#[derive(thiserror::Error, Debug)]
+#[error("{algorithm} mismatch: expected {expected}, got {actual}")]
+struct HashMismatchError {
+    algorithm: String,
+    expected: String,
+    actual: String,
+}
+
+// Transient failures worth another attempt: network errors, timeouts,
+// throttling and server errors.
+fn is_retryable(error: &anyhow::Error) -> bool {
+    let status = if let Some(e) = error.downcast_ref::<HttpStatusError>() {
+        Some(e.0)
+    } else if let Some(e) = error.downcast_ref::<reqwest::Error>() {
+        match e.status() {
+            Some(status) => Some(status),
+            None => return true,
+        }
+    } else {
+        None
+    };
+    match status {
+        Some(status) => {
+            status.is_server_error()
+                || status == http::StatusCode::REQUEST_TIMEOUT
+                || status == http::StatusCode::TOO_MANY_REQUESTS
+        }
+        None => false,
+    }
+}
+
+pub(crate) async fn with_retry<T, F, Fut>(
+    retry: &TransferRetryPolicy,
+    what: &str,
+    mut f: F,
+) -> anyhow::Result<T>
+where
+    F: FnMut() -> Fut,
+    Fut: std::future::Future<Output = anyhow::Result<T>>,
+{
+    let mut attempt = 0;
+    loop {
+        match f().await {
+            Ok(v) => return Ok(v),
+            Err(e) if attempt < retry.max_retries && is_retryable(&e) => {
+                attempt += 1;
+                warn!("[{}] Retry {}: {:?}", what, attempt, e);
+                tokio::time::delay_for(retry.backoff(attempt)).await;
+            }
+            Err(e) => return Err(e),
+        }
+    }
+}
+
+pub(crate) fn check_status(res: reqwest::Response) -> anyhow::Result<reqwest::Response> {
+    match res.status() {
+        status if status.is_success() => Ok(res),
+        status => Err(HttpStatusError(status).into()),
+    }
+}
 
 async fn download_remote_input_to_file(
     presigned_url: Url,
     dest: impl AsRef<std::path::Path>,
+    retry: &TransferRetryPolicy,
 ) -> anyhow::Result<()> {
-    let mut download = reqwest::get(presigned_url.as_str())
-        .await?
-        .error_for_status()?;
+    let client = &*HTTP_CLIENT;
+    download_to_file(|| Ok(client.get(presigned_url.as_str())), dest, retry).await
+}
 
+/// Downloads the response body of the requests built by `make_request` to
+/// `dest`, resuming with a ranged request after a transient failure.
+pub(crate) async fn download_to_file(
+    make_request: impl Fn() -> anyhow::Result<reqwest::RequestBuilder>,
+    dest: impl AsRef<std::path::Path>,
+    retry: &TransferRetryPolicy,
+) -> anyhow::Result<()> {
     let mut outfile = tokio::fs::File::create(dest).await?;
-
-    while let Some(chunk) = download.chunk().await? {
-        outfile.write(&chunk).await?;
+    let mut downloaded: u64 = 0;
+
+    let mut attempt = 0;
+    loop {
+        let result = match make_request() {
+            Ok(request) => download_remaining(request, &mut outfile, &mut downloaded).await,
+            Err(e) => Err(e),
+        };
+        match result {
+            Ok(()) => break,
+            Err(e) if attempt < retry.max_retries && is_retryable(&e) => {
+                attempt += 1;
+                warn!(
+                    "[Download] Retry {} after {} bytes: {:?}",
+                    attempt, downloaded, e
+                );
+                tokio::time::delay_for(retry.backoff(attempt)).await;
+            }
+            Err(e) => return Err(e),
+        }
     }
 
     // Must flush tokio::io::BufWriter manually.
@@ -45,6 +181,71 @@ async fn download_remote_input_to_file(
     Ok(())
 }
 
+// Downloads the rest of a file after `downloaded` bytes with a ranged request.
+// Starts over if the server does not support ranges.
+async fn download_remaining(
+    mut request: reqwest::RequestBuilder,
+    outfile: &mut tokio::fs::File,
+    downloaded: &mut u64,
+) -> anyhow::Result<()> {
+    if *downloaded > 0 {
+        request = request.header(reqwest::header::RANGE, format!("bytes={}-", downloaded));
+    }
+    let mut download = check_status(request.send().await?)?;
+
+    if *downloaded > 0 && download.status() != http::StatusCode::PARTIAL_CONTENT {
+        outfile.set_len(0).await?;
+        outfile.seek(std::io::SeekFrom::Start(0)).await?;
+        *downloaded = 0;
+    }
+
+    while let Some(chunk) = download.chunk().await? {
+        outfile.write_all(&chunk).await?;
+        *downloaded += chunk.len() as u64;
+    }
+
+    Ok(())
+}
+
+async fn verify_file_hash(path: impl AsRef<Path>, expected: &FileHash) -> anyhow::Result<()> {
+    enum Hasher {
+        Md5(md5::Context),
+        Sha256(ring::digest::Context),
+    }
+
+    let mut hasher = match expected {
+        FileHash::Md5(_) => Hasher::Md5(md5::Context::new()),
+        FileHash::Sha256(_) => Hasher::Sha256(ring::digest::Context::new(&ring::digest::SHA256)),
+    };
+
+    let mut file = tokio::fs::File::open(path).await?;
+    let mut buf = vec![0u8; 64 * 1024];
+    loop {
+        let n = file.read(&mut buf).await?;
+        if n == 0 {
+            break;
+        }
+        match hasher {
+            Hasher::Md5(ref mut ctx) => ctx.consume(&buf[..n]),
+            Hasher::Sha256(ref mut ctx) => ctx.update(&buf[..n]),
+        }
+    }
+
+    let actual = match hasher {
+        Hasher::Md5(ctx) => format!("{:x}", ctx.compute()),
+        Hasher::Sha256(ctx) => hex::encode(ctx.finish().as_ref()),
+    };
+    if actual != expected.hex() {
+        return Err(HashMismatchError {
+            algorithm: expected.algorithm().to_string(),
+            expected: expected.hex().to_string(),
+            actual,
+        }
+        .into());
+    }
+    Ok(())
+}
+
 async fn copy_file(
     src: impl AsRef<std::path::Path>,
     dst: impl AsRef<std::path::Path>,
@@ -56,33 +257,62 @@ async fn copy_file(
 async fn upload_output_file_to_remote(
     src: impl AsRef<std::path::Path>,
     presigned_url: Url,
+    retry: &TransferRetryPolicy,
+) -> anyhow::Result<()> {
+    let client = &*HTTP_CLIENT;
+    let src = src.as_ref();
+    with_retry(retry, "Upload", move || {
+        upload_file(client.put(presigned_url.as_str()), src)
+    })
+    .await
+}
+
+/// Sends the content of `src` as the body of `request`.
+pub(crate) async fn upload_file(
+    request: reqwest::RequestBuilder,
+    src: &Path,
 ) -> anyhow::Result<()> {
-    let metadata = std::fs::metadata(&src)?;
+    let metadata = std::fs::metadata(src)?;
     let file_len = metadata.len();
 
-    let stream = tokio::fs::File::open(src.as_ref().to_path_buf())
+    let stream = tokio::fs::File::open(src.to_path_buf())
         .map_ok(|file| codec::FramedRead::new(file, codec::BytesCodec::new()))
         .try_flatten_stream();
 
     let body = reqwest::Body::wrap_stream(stream);
 
-    let client = reqwest::Client::new();
-    let res = client
-        .put(presigned_url.as_str())
+    let res = request
         .header(reqwest::header::CONTENT_TYPE, "application/x-binary")
         .header(reqwest::header::CONTENT_LENGTH, file_len.to_string())
         .body(body)
         .send()
         .await?;
-    match res.status() {
-        http::StatusCode::OK => Ok(()),
-        status => anyhow::bail!("{}", status),
-    }
+    check_status(res)?;
+    Ok(())
+}
+
+// fusion:///TEACLAVE_FUSION_BASE/path is resolved to $fusion_base/path
+pub(crate) fn fusion_path(remote: &Url, fusion_base: &Path) -> anyhow::Result<PathBuf> {
+    let path = remote
+        .to_file_path()
+        .map_err(|e| anyhow::anyhow!("Cannot convert fusion:// to path: {:?}", e))?;
+    let components = path.components().collect::<Vec<_>>();
+    anyhow::ensure!(
+        components.len() > 2
+            && (components[0] == Component::RootDir)
+            && (components[1] == Component::Normal("TEACLAVE_FUSION_BASE".as_ref())),
+        "Fusion data format error: {:?}",
+        components
+    );
+
+    let relative_path: PathBuf = components[2..].iter().collect();
+    Ok(fusion_base.join(relative_path))
 }
 
 async fn handle_download(
     info: HandleFileInfo,
     fusion_base: impl AsRef<Path>,
+    retry: &TransferRetryPolicy,
 ) -> anyhow::Result<()> {
     anyhow::ensure!(
         !info.local.exists(),
@@ -94,7 +324,10 @@ async fn handle_download(
 
     match remote.scheme() {
         "https" | "http" => {
-            download_remote_input_to_file(remote, dst).await?;
+            download_remote_input_to_file(remote, &dst, retry).await?;
+        }
+        "s3" => {
+            s3::handle_s3_download(&remote, &dst, retry).await?;
         }
         "file" => {
             let src = remote
@@ -105,36 +338,32 @@ async fn handle_download(
                 "[Download] Src local file: {:?} doesn't exist.",
                 src
             );
-            copy_file(src, dst).await?;
+            copy_file(src, &dst).await?;
         }
         "fusion" => {
-            let path = remote
-                .to_file_path()
-                .map_err(|e| anyhow::anyhow!("Cannot convert fusion:// to path: {:?}", e))?;
-            let components = path.components().collect::<Vec<_>>();
-            anyhow::ensure!(
-                (components[0] == Component::RootDir)
-                    && (components[1] == Component::Normal("TEACLAVE_FUSION_BASE".as_ref())),
-                "[Download] Fusion data format error: {:?}",
-                components
-            );
-
-            let relative_path: PathBuf = components[2..].iter().collect();
-            let src: PathBuf = fusion_base.as_ref().join(relative_path);
+            let src = fusion_path(&remote, fusion_base.as_ref())?;
 
             anyhow::ensure!(
                 src.exists(),
                 "[Download] Src local file: {:?} doesn't exist.",
                 src
             );
-            copy_file(src, dst).await?;
+            copy_file(src, &dst).await?;
         }
         _ => anyhow::bail!("Scheme not supported"),
     }
+
+    if let Some(hash) = info.hash {
+        verify_file_hash(&dst, &hash).await?;
+    }
     Ok(())
 }
 
-async fn handle_upload(info: HandleFileInfo, fusion_base: impl AsRef<Path>) -> anyhow::Result<()> {
+async fn handle_upload(
+    info: HandleFileInfo,
+    fusion_base: impl AsRef<Path>,
+    retry: &TransferRetryPolicy,
+) -> anyhow::Result<()> {
     anyhow::ensure!(
         info.local.exists(),
         "[Upload] Src local file: {:?} doesn't exist.",
@@ -144,7 +373,10 @@ async fn handle_upload(info: HandleFileInfo, fusion_base: impl AsRef<Path>) -> a
 
     match info.remote.scheme() {
         "https" | "http" => {
-            upload_output_file_to_remote(src, info.remote).await?;
+            upload_output_file_to_remote(src, info.remote, retry).await?;
+        }
+        "s3" => {
+            s3::handle_s3_upload(&src, &info.remote, retry).await?;
         }
         "file" => {
             let dst = info
@@ -155,20 +387,7 @@ async fn handle_upload(info: HandleFileInfo, fusion_base: impl AsRef<Path>) -> a
             copy_file(src, dst).await?;
         }
         "fusion" => {
-            let path = info
-                .remote
-                .to_file_path()
-                .map_err(|e| anyhow::anyhow!("Cannot convert fusion:// to path: {:?}", e))?;
-            let components = path.components().collect::<Vec<_>>();
-            anyhow::ensure!(
-                (components[0] == Component::RootDir)
-                    && (components[1] == Component::Normal("TEACLAVE_FUSION_BASE".as_ref())),
-                "[Upload] Fusion data format error: {:?}",
-                components
-            );
-
-            let relative_path: PathBuf = components[2..].iter().collect();
-            let dst: PathBuf = fusion_base.as_ref().join(relative_path);
+            let dst = fusion_path(&info.remote, fusion_base.as_ref())?;
 
             anyhow::ensure!(
                 !dst.exists(),
@@ -182,61 +401,239 @@ async fn handle_upload(info: HandleFileInfo, fusion_base: impl AsRef<Path>) -> a
     Ok(())
 }
 
-fn handle_file_request(bytes: &[u8]) -> anyhow::Result<()> {
+/// Reads up to `len` bytes of the response body of `request` from `offset`
+/// on with a ranged request. Skips to `offset` if the server ignores ranges.
+pub(crate) async fn read_range(
+    request: reqwest::RequestBuilder,
+    offset: u64,
+    len: u32,
+) -> anyhow::Result<Vec<u8>> {
+    let len = len as usize;
+    if len == 0 {
+        return Ok(Vec::new());
+    }
+    let end = offset + len as u64 - 1;
+    let res = request
+        .header(reqwest::header::RANGE, format!("bytes={}-{}", offset, end))
+        .send()
+        .await?;
+    if res.status() == http::StatusCode::RANGE_NOT_SATISFIABLE {
+        return Ok(Vec::new());
+    }
+    let mut res = check_status(res)?;
+
+    let mut skip = if res.status() == http::StatusCode::PARTIAL_CONTENT {
+        0
+    } else {
+        offset
+    };
+    let mut data = Vec::with_capacity(len);
+    while let Some(chunk) = res.chunk().await? {
+        let start = std::cmp::min(skip, chunk.len() as u64) as usize;
+        skip -= start as u64;
+        let n = std::cmp::min(len - data.len(), chunk.len() - start);
+        data.extend_from_slice(&chunk[start..start + n]);
+        if data.len() == len {
+            break;
+        }
+    }
+    Ok(data)
+}
+
+async fn read_local_range(
+    path: impl AsRef<Path>,
+    offset: u64,
+    len: u32,
+) -> anyhow::Result<Vec<u8>> {
+    let mut file = tokio::fs::File::open(path.as_ref()).await?;
+    file.seek(std::io::SeekFrom::Start(offset)).await?;
+    let mut data = Vec::with_capacity(len as usize);
+    file.take(len as u64).read_to_end(&mut data).await?;
+    Ok(data)
+}
+
+async fn read_file_range(req: ReadFileRangeRequest) -> anyhow::Result<Vec<u8>> {
+    let remote = &req.remote;
+    match remote.scheme() {
+        "https" | "http" => {
+            let client = &*HTTP_CLIENT;
+            with_retry(&req.retry, "Read", || {
+                read_range(client.get(remote.as_str()), req.offset, req.len)
+            })
+            .await
+        }
+        "s3" => s3::read_s3_range(remote, req.offset, req.len, &req.retry).await,
+        "file" => {
+            let path = remote
+                .to_file_path()
+                .map_err(|e| anyhow::anyhow!("Cannot convert file:// to path: {:?}", e))?;
+            read_local_range(path, req.offset, req.len).await
+        }
+        "fusion" => {
+            let path = fusion_path(remote, &req.fusion_base)?;
+            read_local_range(path, req.offset, req.len).await
+        }
+        _ => anyhow::bail!("Scheme not supported"),
+    }
+}
+
+pub(crate) fn to_handle_file_error(error: anyhow::Error) -> HandleFileError {
+    if let Some(e) = error.downcast_ref::<HttpStatusError>() {
+        return HandleFileError::Http {
+            status: e.0.as_u16(),
+        };
+    }
+    // The URL of a request may be presigned, so it is left out of the message.
+    let error = match error.downcast::<reqwest::Error>() {
+        Ok(e) => {
+            return match e.status() {
+                Some(status) => HandleFileError::Http {
+                    status: status.as_u16(),
+                },
+                None => HandleFileError::Network {
+                    message: e.without_url().to_string(),
+                },
+            };
+        }
+        Err(error) => error,
+    };
+    if let Some(e) = error.downcast_ref::<HashMismatchError>() {
+        return HandleFileError::HashMismatch {
+            algorithm: e.algorithm.clone(),
+            expected: e.expected.clone(),
+            actual: e.actual.clone(),
+        };
+    }
+    if let Some(e) = error.downcast_ref::<std::io::Error>() {
+        return HandleFileError::Io {
+            kind: format!("{:?}", e.kind()),
+            message: e.to_string(),
+        };
+    }
+    HandleFileError::Other {
+        message: error.to_string(),
+    }
+}
+
+async fn handle_file(
+    cmd: &HandleFileCommand,
+    info: HandleFileInfo,
+    fusion_base: PathBuf,
+    retry: &TransferRetryPolicy,
+) -> HandleFileResult {
+    let result = match cmd {
+        HandleFileCommand::Download => handle_download(info.clone(), fusion_base, retry).await,
+        HandleFileCommand::Upload => handle_upload(info.clone(), fusion_base, retry).await,
+    };
+    let error = result.err().map(to_handle_file_error);
+    HandleFileResult::new(info, error)
+}
+
+fn handle_file_request(bytes: &[u8]) -> anyhow::Result<FileAgentResponse> {
     let req: FileAgentRequest = serde_json::from_slice(bytes)?;
-    let results = tokio::runtime::Builder::new()
-        .threaded_scheduler()
-        .enable_all()
-        .build()?
-        .block_on(async {
-            let fusion_base = req.fusion_base.clone();
-            match req.cmd {
-                HandleFileCommand::Download => {
-                    let futures: Vec<_> = req
-                        .info
-                        .into_iter()
-                        .map(|info| {
-                            let fusion_base = fusion_base.clone();
-                            tokio::spawn(async { handle_download(info, fusion_base).await })
-                        })
-                        .collect();
-                    join_all(futures).await
-                }
-                HandleFileCommand::Upload => {
-                    let futures: Vec<_> = req
-                        .info
-                        .into_iter()
-                        .map(|info| {
-                            let fusion_base = fusion_base.clone();
-                            tokio::spawn(async { handle_upload(info, fusion_base).await })
-                        })
-                        .collect();
-                    join_all(futures).await
-                }
+    let cmd = req.cmd;
+    let retry = req.retry;
+    let fusion_base = req.fusion_base;
+    let infos = req.info.clone();
+    let results = block_on(async move {
+        let futures: Vec<_> = req
+            .info
+            .into_iter()
+            .map(|info| {
+                let fusion_base = fusion_base.clone();
+                tokio::spawn(async move { handle_file(&cmd, info, fusion_base, &retry).await })
+            })
+            .collect();
+        Ok(join_all(futures).await)
+    })?;
+
+    let results: Vec<HandleFileResult> = results
+        .into_iter()
+        .zip(infos)
+        .map(|(result, info)| match result {
+            Ok(result) => result,
+            Err(e) => {
+                let error = HandleFileError::Other {
+                    message: format!("Spawned task join error: {}", e),
+                };
+                HandleFileResult::new(info, Some(error))
             }
-        });
+        })
+        .collect();
 
-    let (task_results, errs): (Vec<_>, Vec<_>) = results.into_iter().partition(Result::is_ok);
+    debug!("{:?}", results);
+    Ok(FileAgentResponse::new(results))
+}
 
-    debug!("{:?}, errs: {:?}", task_results, errs);
-    if !errs.is_empty() {
-        anyhow::bail!("Spawned task join error!");
+/// Handles a serialized `FileAgentRequest` and writes a serialized
+/// `FileAgentResponse` with the result of each file into `out_buf`.
+#[no_mangle]
+#[allow(clippy::not_unsafe_ptr_arg_deref)]
+pub extern "C" fn ocall_handle_file_request(
+    in_buf: *const u8,
+    in_len: u32,
+    out_buf: *mut u8,
+    out_max: u32,
+    out_len: *mut u32,
+) -> u32 {
+    let input_buf: &[u8] = unsafe { std::slice::from_raw_parts(in_buf, in_len as usize) };
+    let response = match handle_file_request(input_buf) {
+        Ok(response) => response,
+        Err(e) => {
+            error!("Invalid file agent request: {:?}", e);
+            return FILE_AGENT_INVALID_REQUEST;
+        }
+    };
+    let bytes = match serde_json::to_vec(&response) {
+        Ok(bytes) => bytes,
+        Err(_) => return FILE_AGENT_INVALID_REQUEST,
+    };
+    if bytes.len() > out_max as usize {
+        return FILE_AGENT_BUFFER_TOO_SMALL;
     }
-    anyhow::ensure!(
-        task_results.into_iter().all(|x| x.unwrap().is_ok()),
-        "Some handle file task failed"
-    );
-    Ok(())
+
+    let output_buf: &mut [u8] = unsafe { std::slice::from_raw_parts_mut(out_buf, bytes.len()) };
+    output_buf.copy_from_slice(&bytes);
+    unsafe { *out_len = bytes.len() as u32 };
+    FILE_AGENT_OK
 }
 
+/// Reads the file range of a serialized `ReadFileRangeRequest` into
+/// `out_buf`, which must be large enough for the requested length.
 #[no_mangle]
 #[allow(clippy::not_unsafe_ptr_arg_deref)]
-pub extern "C" fn ocall_handle_file_request(in_buf: *const u8, in_len: u32) -> u32 {
+pub extern "C" fn ocall_read_file_range(
+    in_buf: *const u8,
+    in_len: u32,
+    out_buf: *mut u8,
+    out_max: u32,
+    out_len: *mut u32,
+) -> u32 {
     let input_buf: &[u8] = unsafe { std::slice::from_raw_parts(in_buf, in_len as usize) };
-    match handle_file_request(input_buf) {
-        Ok(_) => 0,
-        Err(_) => 1,
+    let req: ReadFileRangeRequest = match serde_json::from_slice(input_buf) {
+        Ok(req) => req,
+        Err(e) => {
+            error!("Invalid read file range request: {:?}", e);
+            return FILE_AGENT_INVALID_REQUEST;
+        }
+    };
+    if req.len > out_max {
+        return FILE_AGENT_BUFFER_TOO_SMALL;
     }
+
+    let remote = redact_url(&req.remote);
+    let data = match block_on(read_file_range(req)) {
+        Ok(data) => data,
+        Err(e) => {
+            error!("Cannot read {}: {}", remote, to_handle_file_error(e));
+            return FILE_AGENT_READ_FAILED;
+        }
+    };
+
+    let output_buf: &mut [u8] = unsafe { std::slice::from_raw_parts_mut(out_buf, data.len()) };
+    output_buf.copy_from_slice(&data);
+    unsafe { *out_len = data.len() as u32 };
+    FILE_AGENT_OK
 }
 
 #[cfg(test)]
@@ -269,7 +666,7 @@ mod tests {
         let req = FileAgentRequest::new(HandleFileCommand::Download, vec![info], "");
 
         let bytes = serde_json::to_vec(&req).unwrap();
-        handle_file_request(&bytes).unwrap();
+        handle_file_request(&bytes).unwrap().into_result().unwrap();
 
         std::fs::remove_file(&dest).unwrap();
     }
@@ -289,7 +686,7 @@ mod tests {
         let req = FileAgentRequest::new(HandleFileCommand::Upload, vec![info], "");
 
         let bytes = serde_json::to_vec(&req).unwrap();
-        handle_file_request(&bytes).unwrap();
+        handle_file_request(&bytes).unwrap().into_result().unwrap();
 
         std::fs::remove_file(&src).unwrap();
     }
@@ -310,7 +707,7 @@ mod tests {
         let req = FileAgentRequest::new(HandleFileCommand::Download, info_list, "");
 
         let bytes = serde_json::to_vec(&req).unwrap();
-        handle_file_request(&bytes).unwrap();
+        handle_file_request(&bytes).unwrap().into_result().unwrap();
 
         std::fs::remove_dir_all(&base).unwrap();
     }
@@ -339,7 +736,7 @@ mod tests {
         let req = FileAgentRequest::new(HandleFileCommand::Upload, info_list, "");
 
         let bytes = serde_json::to_vec(&req).unwrap();
-        handle_file_request(&bytes).unwrap();
+        handle_file_request(&bytes).unwrap().into_result().unwrap();
 
         std::fs::remove_file(&src).unwrap();
     }
@@ -364,7 +761,7 @@ mod tests {
         let req = FileAgentRequest::new(HandleFileCommand::Upload, vec![info], "");
 
         let bytes = serde_json::to_vec(&req).unwrap();
-        handle_file_request(&bytes).unwrap();
+        handle_file_request(&bytes).unwrap().into_result().unwrap();
 
         // test local download
         let dest = base.join("d2.txt");
@@ -372,8 +769,176 @@ mod tests {
         let req = FileAgentRequest::new(HandleFileCommand::Download, vec![info], "");
 
         let bytes = serde_json::to_vec(&req).unwrap();
-        handle_file_request(&bytes).unwrap();
+        handle_file_request(&bytes).unwrap().into_result().unwrap();
+
+        std::fs::remove_dir_all(&base).unwrap();
+    }
+
+    #[test]
+    fn test_file_errors() {
+        let dest = PathBuf::from("/tmp/file_agent_missing_test.txt");
+        let s = "http://localhost:6789/fixtures/functions/not_found.txt?token=secret";
+        let http_info = HandleFileInfo::new(&dest, &Url::parse(s).unwrap());
+        let s = "file:///tmp/file_agent_not_found.txt";
+        let file_info = HandleFileInfo::new(&dest, &Url::parse(s).unwrap());
+        let req =
+            FileAgentRequest::new(HandleFileCommand::Download, vec![http_info, file_info], "");
+
+        let bytes = serde_json::to_vec(&req).unwrap();
+        let response = handle_file_request(&bytes).unwrap();
+        assert_eq!(
+            response.results[0].error,
+            Some(HandleFileError::Http { status: 404 })
+        );
+        assert!(response.results[1].error.is_some());
+
+        let error = response.into_result().unwrap_err();
+        assert_eq!(error.failures.len(), 2);
+        assert!(!error.to_string().contains("secret"));
+
+        // Nothing listens on the port, so the request fails without a status.
+        let s = "http://localhost:1/not_found.txt?token=secret";
+        let info = HandleFileInfo::new(&dest, &Url::parse(s).unwrap());
+        let req = FileAgentRequest::new(HandleFileCommand::Download, vec![info], "");
+        let bytes = serde_json::to_vec(&req).unwrap();
+        let response = handle_file_request(&bytes).unwrap();
+        match &response.results[0].error {
+            Some(HandleFileError::Network { message }) => assert!(!message.contains("secret")),
+            e => panic!("unexpected error: {:?}", e),
+        }
+    }
+
+    #[test]
+    fn test_verify_file_hash() {
+        let base_str = "/tmp/file_agent_verify_hash";
+        let base = PathBuf::from(&base_str);
+        std::fs::create_dir_all(&base).unwrap();
+
+        let src = base.join("src.txt");
+        {
+            let mut file = std::fs::File::create(&src).unwrap();
+            file.write_all(b"Hello Teaclave!").unwrap();
+        }
+        let url = Url::parse(&format!("file://{}/src.txt", base_str)).unwrap();
+
+        let digest = ring::digest::digest(&ring::digest::SHA256, b"Hello Teaclave!");
+        let hash = FileHash::new("sha256", &hex::encode(digest.as_ref())).unwrap();
+        let info = HandleFileInfo::new(&base.join("d1.txt"), &url).hash(Some(hash));
+        let req = FileAgentRequest::new(HandleFileCommand::Download, vec![info], "");
+        let bytes = serde_json::to_vec(&req).unwrap();
+        handle_file_request(&bytes).unwrap().into_result().unwrap();
+
+        let hash = FileHash::new("md5", &format!("{:x}", md5::compute(b"Hello"))).unwrap();
+        let info = HandleFileInfo::new(&base.join("d2.txt"), &url).hash(Some(hash));
+        let req = FileAgentRequest::new(HandleFileCommand::Download, vec![info], "");
+        let bytes = serde_json::to_vec(&req).unwrap();
+        let response = handle_file_request(&bytes).unwrap();
+        match &response.results[0].error {
+            Some(HandleFileError::HashMismatch { algorithm, .. }) => assert_eq!(algorithm, "md5"),
+            e => panic!("unexpected result: {:?}", e),
+        }
+
+        std::fs::remove_dir_all(&base).unwrap();
+    }
+
+    #[test]
+    fn test_read_file_range() {
+        let base = PathBuf::from("/tmp/file_agent_read_range");
+        std::fs::create_dir_all(&base).unwrap();
+        let src = base.join("src.txt");
+        std::fs::write(&src, b"Hello Teaclave Range!").unwrap();
+
+        let url = Url::parse(&format!("file://{}", src.display())).unwrap();
+        let req = ReadFileRangeRequest::new(&url, 6, 8, "");
+        let data = block_on(read_file_range(req)).unwrap();
+        assert_eq!(data, b"Teaclave");
+
+        let req = ReadFileRangeRequest::new(&url, 15, 100, "");
+        let data = block_on(read_file_range(req)).unwrap();
+        assert_eq!(data, b"Range!");
+
+        let s = "http://localhost:6789/fixtures/functions/mesapy/input.txt";
+        let url = Url::parse(s).unwrap();
+        let whole = block_on(read_file_range(ReadFileRangeRequest::new(
+            &url, 0, 4096, "",
+        )))
+        .unwrap();
+        let part = block_on(read_file_range(ReadFileRangeRequest::new(&url, 2, 3, ""))).unwrap();
+        assert_eq!(&part[..], &whole[2..5]);
 
         std::fs::remove_dir_all(&base).unwrap();
     }
+
+    // Reads the head of a request and returns its Range header, if any.
+    fn read_range_header(stream: &std::net::TcpStream) -> Option<String> {
+        use std::io::BufRead;
+        let mut reader = std::io::BufReader::new(stream);
+        let mut range = None;
+        loop {
+            let mut line = String::new();
+            reader.read_line(&mut line).unwrap();
+            if line == "\r\n" || line.is_empty() {
+                return range;
+            }
+            let mut parts = line.splitn(2, ':');
+            if parts.next().unwrap().eq_ignore_ascii_case("range") {
+                range = parts.next().map(|v| v.trim().to_string());
+            }
+        }
+    }
+
+    #[test]
+    fn test_resume_download() {
+        let content = b"Hello Teaclave Resume!";
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = Url::parse(&format!(
+            "http://{}/input.txt",
+            listener.local_addr().unwrap()
+        ))
+        .unwrap();
+        let server = std::thread::spawn(move || {
+            // The connection breaks after the first half of the body.
+            let (mut stream, _) = listener.accept().unwrap();
+            assert_eq!(read_range_header(&stream), None);
+            let head = format!(
+                "HTTP/1.1 200 OK\r\nContent-Length: {}\r\n\r\n",
+                content.len()
+            );
+            stream.write_all(head.as_bytes()).unwrap();
+            stream.write_all(&content[..10]).unwrap();
+            drop(stream);
+
+            let (mut stream, _) = listener.accept().unwrap();
+            let range = read_range_header(&stream);
+            let head = format!(
+                "HTTP/1.1 206 Partial Content\r\nContent-Length: {}\r\nContent-Range: bytes 10-{}/{}\r\n\r\n",
+                content.len() - 10,
+                content.len() - 1,
+                content.len()
+            );
+            stream.write_all(head.as_bytes()).unwrap();
+            stream.write_all(&content[10..]).unwrap();
+            range
+        });
+
+        let dest = PathBuf::from("/tmp/file_agent_resume_test.txt");
+        let retry = TransferRetryPolicy {
+            max_retries: 1,
+            initial_backoff_ms: 10,
+            max_backoff_ms: 10,
+        };
+        let client = reqwest::Client::new();
+        tokio::runtime::Runtime::new()
+            .unwrap()
+            .block_on(download_to_file(
+                || Ok(client.get(url.as_str())),
+                &dest,
+                &retry,
+            ))
+            .unwrap();
+
+        assert_eq!(server.join().unwrap(), Some("bytes=10-".to_string()));
+        assert_eq!(std::fs::read(&dest).unwrap(), &content[..]);
+        std::fs::remove_file(&dest).unwrap();
+    }
 }
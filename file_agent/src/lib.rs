@@ -19,4 +19,7 @@
 extern crate log;
 
 mod agent;
-pub use agent::ocall_handle_file_request;
+mod s3;
+mod upload;
+pub use agent::{ocall_handle_file_request, ocall_read_file_range};
+pub use upload::{ocall_close_upload, ocall_open_upload, ocall_write_upload};
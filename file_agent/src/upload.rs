@@ -0,0 +1,309 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License.
+
+//! Uploads of outputs streamed out of the enclave. An upload is opened with
+//! the remote file, written piece by piece and closed, and its content goes
+//! to the remote file as it is written instead of to a local file.
+
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+
+use futures::channel::mpsc;
+use futures::SinkExt;
+use lazy_static::lazy_static;
+use tokio::io::AsyncWriteExt;
+
+use crate::agent::{
+    block_on, check_status, fusion_path, to_handle_file_error, FILE_AGENT_INVALID_REQUEST,
+    FILE_AGENT_OK, FILE_AGENT_WRITE_FAILED, HTTP_CLIENT,
+};
+use crate::s3::S3StreamUpload;
+use teaclave_types::{redact_url, OpenUploadRequest};
+
+// Pieces queued for the body of an HTTP upload.
+const HTTP_UPLOAD_QUEUE_LENGTH: usize = 4;
+
+lazy_static! {
+    static ref UPLOADS: Mutex<HashMap<u64, Upload>> = Mutex::new(HashMap::new());
+}
+
+static NEXT_UPLOAD_ID: AtomicU64 = AtomicU64::new(1);
+
+type HttpUploadResult = tokio::task::JoinHandle<anyhow::Result<()>>;
+
+enum Upload {
+    // The body of a single PUT request, sent as it is written. The body is
+    // not kept, so the request is not retried.
+    Http {
+        body: mpsc::Sender<std::io::Result<Vec<u8>>>,
+        response: Option<HttpUploadResult>,
+    },
+    S3(S3StreamUpload),
+    Local {
+        file: tokio::fs::File,
+        path: PathBuf,
+    },
+}
+
+impl Upload {
+    async fn open(req: OpenUploadRequest) -> anyhow::Result<Self> {
+        let remote = &req.remote;
+        let path = match remote.scheme() {
+            "https" | "http" => {
+                let (body, receiver) = mpsc::channel(HTTP_UPLOAD_QUEUE_LENGTH);
+                let request = HTTP_CLIENT
+                    .put(remote.as_str())
+                    .header(reqwest::header::CONTENT_TYPE, "application/x-binary")
+                    .body(reqwest::Body::wrap_stream(receiver));
+                let response = tokio::spawn(async move {
+                    check_status(request.send().await?)?;
+                    Ok(())
+                });
+                return Ok(Upload::Http {
+                    body,
+                    response: Some(response),
+                });
+            }
+            "s3" => return Ok(Upload::S3(S3StreamUpload::new(remote, &req.retry)?)),
+            "file" => remote
+                .to_file_path()
+                .map_err(|e| anyhow::anyhow!("Cannot convert to path: {:?}", e))?,
+            "fusion" => fusion_path(remote, &req.fusion_base)?,
+            _ => anyhow::bail!("Scheme not supported"),
+        };
+
+        anyhow::ensure!(
+            !path.exists(),
+            "[Upload] Dest local file: {:?} exists.",
+            path
+        );
+        let file = tokio::fs::File::create(&path).await?;
+        Ok(Upload::Local { file, path })
+    }
+
+    async fn write(&mut self, data: Vec<u8>) -> anyhow::Result<()> {
+        match self {
+            Upload::Http { body, response } => {
+                if body.send(Ok(data)).await.is_err() {
+                    // The request has ended early, its result tells why.
+                    if let Some(response) = response.take() {
+                        response.await??;
+                    }
+                    anyhow::bail!("Upload is closed by the remote");
+                }
+                Ok(())
+            }
+            Upload::S3(upload) => upload.write(&data).await,
+            Upload::Local { file, .. } => Ok(file.write_all(&data).await?),
+        }
+    }
+
+    async fn finish(self) -> anyhow::Result<()> {
+        match self {
+            Upload::Http { body, response } => {
+                // The end of the body is the end of the channel.
+                drop(body);
+                match response {
+                    Some(response) => response.await?,
+                    None => anyhow::bail!("Upload is closed by the remote"),
+                }
+            }
+            Upload::S3(upload) => upload.finish().await,
+            Upload::Local { mut file, .. } => {
+                file.flush().await?;
+                Ok(())
+            }
+        }
+    }
+
+    async fn abort(self) {
+        match self {
+            Upload::Http { mut body, response } => {
+                let error = std::io::Error::new(std::io::ErrorKind::Other, "Upload aborted");
+                let _ = body.send(Err(error)).await;
+                drop(body);
+                if let Some(response) = response {
+                    let _ = response.await;
+                }
+            }
+            Upload::S3(mut upload) => upload.abort().await,
+            Upload::Local { file, path } => {
+                drop(file);
+                let _ = tokio::fs::remove_file(path).await;
+            }
+        }
+    }
+}
+
+fn take_upload(upload_id: u64) -> Option<Upload> {
+    UPLOADS.lock().ok()?.remove(&upload_id)
+}
+
+fn put_upload(upload_id: u64, upload: Upload) -> anyhow::Result<()> {
+    UPLOADS
+        .lock()
+        .map_err(|_| anyhow::anyhow!("Cannot lock uploads"))?
+        .insert(upload_id, upload);
+    Ok(())
+}
+
+/// Opens an upload of a serialized `OpenUploadRequest`, whose ID is written
+/// into `upload_id`.
+#[no_mangle]
+#[allow(clippy::not_unsafe_ptr_arg_deref)]
+pub extern "C" fn ocall_open_upload(in_buf: *const u8, in_len: u32, upload_id: *mut u64) -> u32 {
+    let input_buf: &[u8] = unsafe { std::slice::from_raw_parts(in_buf, in_len as usize) };
+    let req: OpenUploadRequest = match serde_json::from_slice(input_buf) {
+        Ok(req) => req,
+        Err(e) => {
+            error!("Invalid open upload request: {:?}", e);
+            return FILE_AGENT_INVALID_REQUEST;
+        }
+    };
+
+    let remote = redact_url(&req.remote);
+    let id = NEXT_UPLOAD_ID.fetch_add(1, Ordering::Relaxed);
+    let result = block_on(Upload::open(req)).and_then(|upload| put_upload(id, upload));
+    if let Err(e) = result {
+        error!("Cannot upload {}: {}", remote, to_handle_file_error(e));
+        return FILE_AGENT_WRITE_FAILED;
+    }
+    unsafe { *upload_id = id };
+    FILE_AGENT_OK
+}
+
+/// Sends the next `len` bytes of an open upload, which is aborted if they
+/// cannot be sent.
+#[no_mangle]
+#[allow(clippy::not_unsafe_ptr_arg_deref)]
+pub extern "C" fn ocall_write_upload(upload_id: u64, buf: *const u8, len: u32) -> u32 {
+    let mut upload = match take_upload(upload_id) {
+        Some(upload) => upload,
+        None => return FILE_AGENT_INVALID_REQUEST,
+    };
+    let data = unsafe { std::slice::from_raw_parts(buf, len as usize) }.to_vec();
+    let result = block_on(async move {
+        match upload.write(data).await {
+            Ok(()) => Ok(upload),
+            Err(e) => {
+                upload.abort().await;
+                Err(e)
+            }
+        }
+    })
+    .and_then(|upload| put_upload(upload_id, upload));
+    if let Err(e) = result {
+        error!(
+            "Cannot write upload {}: {}",
+            upload_id,
+            to_handle_file_error(e)
+        );
+        return FILE_AGENT_WRITE_FAILED;
+    }
+    FILE_AGENT_OK
+}
+
+/// Completes an upload, or drops what has been sent so far if `abort` is
+/// set.
+#[no_mangle]
+pub extern "C" fn ocall_close_upload(upload_id: u64, abort: u32) -> u32 {
+    let upload = match take_upload(upload_id) {
+        Some(upload) => upload,
+        None => return FILE_AGENT_INVALID_REQUEST,
+    };
+    if abort != 0 {
+        let _ = block_on(async move {
+            upload.abort().await;
+            Ok(())
+        });
+        return FILE_AGENT_OK;
+    }
+    if let Err(e) = block_on(upload.finish()) {
+        error!(
+            "Cannot finish upload {}: {}",
+            upload_id,
+            to_handle_file_error(e)
+        );
+        return FILE_AGENT_WRITE_FAILED;
+    }
+    FILE_AGENT_OK
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use url::Url;
+
+    fn upload(remote: &Url, pieces: &[&[u8]]) -> u32 {
+        let req = OpenUploadRequest::new(remote, "");
+        let bytes = serde_json::to_vec(&req).unwrap();
+        let mut upload_id = 0;
+        let rt = ocall_open_upload(bytes.as_ptr(), bytes.len() as u32, &mut upload_id);
+        if rt != FILE_AGENT_OK {
+            return rt;
+        }
+        for piece in pieces {
+            let rt = ocall_write_upload(upload_id, piece.as_ptr(), piece.len() as u32);
+            assert_eq!(rt, FILE_AGENT_OK);
+        }
+        ocall_close_upload(upload_id, 0)
+    }
+
+    #[test]
+    fn test_local_upload() {
+        let base = PathBuf::from("/tmp/file_agent_upload");
+        std::fs::create_dir_all(&base).unwrap();
+        let dest = base.join("output.txt");
+        let url = Url::parse(&format!("file://{}", dest.display())).unwrap();
+
+        let rt = upload(&url, &[b"Hello ", b"Teaclave ", b"Upload!"]);
+        assert_eq!(rt, FILE_AGENT_OK);
+        assert_eq!(std::fs::read(&dest).unwrap(), b"Hello Teaclave Upload!");
+
+        // An existing file is not overwritten.
+        assert_eq!(upload(&url, &[b"Again"]), FILE_AGENT_WRITE_FAILED);
+        assert_eq!(std::fs::read(&dest).unwrap(), b"Hello Teaclave Upload!");
+
+        // An aborted upload leaves nothing behind.
+        let aborted = base.join("aborted.txt");
+        let url = Url::parse(&format!("file://{}", aborted.display())).unwrap();
+        let bytes = serde_json::to_vec(&OpenUploadRequest::new(&url, "")).unwrap();
+        let mut upload_id = 0;
+        let rt = ocall_open_upload(bytes.as_ptr(), bytes.len() as u32, &mut upload_id);
+        assert_eq!(rt, FILE_AGENT_OK);
+        let rt = ocall_write_upload(upload_id, b"Partial".as_ptr(), 7);
+        assert_eq!(rt, FILE_AGENT_OK);
+        assert_eq!(ocall_close_upload(upload_id, 1), FILE_AGENT_OK);
+        assert!(!aborted.exists());
+        assert_eq!(
+            ocall_write_upload(upload_id, b"Late".as_ptr(), 4),
+            FILE_AGENT_INVALID_REQUEST
+        );
+
+        std::fs::remove_dir_all(&base).unwrap();
+    }
+
+    #[test]
+    fn test_http_upload() {
+        let url = "http://localhost:6789/fixtures/functions/mesapy/stream_result.txt";
+        let url = Url::parse(url).unwrap();
+        let rt = upload(&url, &[b"Hello Teaclave ", b"Streamed Results!"]);
+        assert_eq!(rt, FILE_AGENT_OK);
+    }
+}
@@ -0,0 +1,339 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License.
+
+#[cfg(feature = "mesalock_sgx")]
+use std::prelude::v1::*;
+
+use std::convert::TryFrom;
+use std::format;
+use std::io::{self, BufRead, BufReader, Write};
+
+use teaclave_types::{FunctionArguments, FunctionRuntime};
+
+const INPUT_DATA: &str = "data_file";
+const RESULT: &str = "result_file";
+
+#[derive(Default)]
+pub struct DescriptiveStats;
+
+#[derive(serde::Deserialize)]
+struct DescriptiveStatsArguments {
+    #[serde(default = "default_quantiles")]
+    quantiles: Vec<f64>,
+}
+
+fn default_quantiles() -> Vec<f64> {
+    vec![0.25, 0.5, 0.75]
+}
+
+impl TryFrom<FunctionArguments> for DescriptiveStatsArguments {
+    type Error = anyhow::Error;
+
+    fn try_from(arguments: FunctionArguments) -> Result<Self, Self::Error> {
+        use anyhow::Context;
+        serde_json::from_str(&arguments.into_string()).context("Cannot deserialize arguments")
+    }
+}
+
+#[derive(serde::Serialize, serde::Deserialize)]
+struct ColumnStats {
+    column: usize,
+    count: usize,
+    mean: f64,
+    stddev: f64,
+    min: f64,
+    max: f64,
+    quantiles: Vec<f64>,
+}
+
+#[derive(serde::Serialize, serde::Deserialize)]
+struct Summary {
+    columns: Vec<ColumnStats>,
+    /// Pearson correlation coefficients of every pair of columns, none for a
+    /// column of constant values.
+    correlations: Vec<Vec<Option<f64>>>,
+}
+
+impl DescriptiveStats {
+    pub const NAME: &'static str = "builtin-descriptive-stats";
+
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    pub fn run(
+        &self,
+        arguments: FunctionArguments,
+        runtime: FunctionRuntime,
+    ) -> anyhow::Result<String> {
+        let args = DescriptiveStatsArguments::try_from(arguments)?;
+        for q in args.quantiles.iter() {
+            anyhow::ensure!(*q >= 0.0 && *q <= 1.0, "Invalid quantile: {}", q);
+        }
+
+        // The input files are joined row by row, i.e., the i-th rows of all
+        // files make up the i-th row of the joined data.
+        let file_count = runtime.input_count(INPUT_DATA);
+        anyhow::ensure!(file_count > 0, "No input data");
+        let mut columns: Vec<Vec<f64>> = Vec::new();
+        for i in 0..file_count {
+            let input = runtime.open_input_at(INPUT_DATA, i)?;
+            let file_columns = parse_columns(input)?;
+            if let Some(first) = columns.first() {
+                anyhow::ensure!(
+                    file_columns[0].len() == first.len(),
+                    "Data format error: row count of file {} = {}, expected = {}",
+                    i,
+                    file_columns[0].len(),
+                    first.len()
+                );
+            }
+            columns.extend(file_columns);
+        }
+        let row_count = columns[0].len();
+
+        let correlations = correlations(&columns);
+        let stats: Vec<ColumnStats> = columns
+            .into_iter()
+            .enumerate()
+            .map(|(i, column)| column_stats(i, column, &args.quantiles))
+            .collect();
+        let column_count = stats.len();
+
+        let summary = Summary {
+            columns: stats,
+            correlations,
+        };
+        let summary_json = serde_json::to_string(&summary)?;
+        let mut output = runtime.create_output(RESULT)?;
+        output.write_all(summary_json.as_bytes())?;
+
+        Ok(format!(
+            "Summarized {} lines of data with {} columns.",
+            row_count, column_count
+        ))
+    }
+}
+
+fn column_stats(column: usize, mut values: Vec<f64>, quantiles: &[f64]) -> ColumnStats {
+    let count = values.len();
+    let mean = values.iter().sum::<f64>() / count as f64;
+    // Sample standard deviation, zero for a single value.
+    let stddev = if count > 1 {
+        let sum_sq: f64 = values.iter().map(|x| (x - mean) * (x - mean)).sum();
+        (sum_sq / (count - 1) as f64).sqrt()
+    } else {
+        0.0
+    };
+
+    values.sort_by(|a, b| a.partial_cmp(b).unwrap_or(std::cmp::Ordering::Equal));
+    let quantiles = quantiles.iter().map(|q| quantile(&values, *q)).collect();
+
+    ColumnStats {
+        column,
+        count,
+        mean,
+        stddev,
+        min: values[0],
+        max: values[count - 1],
+        quantiles,
+    }
+}
+
+fn correlations(columns: &[Vec<f64>]) -> Vec<Vec<Option<f64>>> {
+    let deviations: Vec<Vec<f64>> = columns
+        .iter()
+        .map(|column| {
+            let mean = column.iter().sum::<f64>() / column.len() as f64;
+            column.iter().map(|x| x - mean).collect()
+        })
+        .collect();
+    let dot = |a: &[f64], b: &[f64]| a.iter().zip(b.iter()).map(|(x, y)| x * y).sum::<f64>();
+    let norms: Vec<f64> = deviations.iter().map(|d| dot(d, d).sqrt()).collect();
+
+    deviations
+        .iter()
+        .zip(norms.iter())
+        .map(|(a, norm_a)| {
+            deviations
+                .iter()
+                .zip(norms.iter())
+                .map(|(b, norm_b)| {
+                    if *norm_a > 0.0 && *norm_b > 0.0 {
+                        Some(dot(a, b) / (norm_a * norm_b))
+                    } else {
+                        None
+                    }
+                })
+                .collect()
+        })
+        .collect()
+}
+
+// Linear interpolation between the closest ranks of the sorted values.
+fn quantile(sorted: &[f64], q: f64) -> f64 {
+    let pos = q * (sorted.len() - 1) as f64;
+    let lower = pos.floor() as usize;
+    let upper = pos.ceil() as usize;
+    sorted[lower] + (sorted[upper] - sorted[lower]) * (pos - lower as f64)
+}
+
+fn parse_columns(input: impl io::Read) -> anyhow::Result<Vec<Vec<f64>>> {
+    let reader = BufReader::new(input);
+    let mut columns: Vec<Vec<f64>> = Vec::new();
+
+    for line_result in reader.lines() {
+        let line = line_result?;
+        let trimed_line = line.trim();
+        anyhow::ensure!(!trimed_line.is_empty(), "Empty line");
+
+        let v: Vec<f64> = trimed_line
+            .split(',')
+            .map(|x| x.trim().parse::<f64>())
+            .collect::<std::result::Result<_, _>>()?;
+        // Non-finite values have no order, and would break the sorting of
+        // the values for min, max and quantiles.
+        anyhow::ensure!(
+            v.iter().all(|x| x.is_finite()),
+            "Data format error: non-finite value"
+        );
+
+        if columns.is_empty() {
+            columns.resize(v.len(), Vec::new());
+        }
+        anyhow::ensure!(
+            v.len() == columns.len(),
+            "Data format error: column len = {}, expected = {}",
+            v.len(),
+            columns.len()
+        );
+        for (column, x) in columns.iter_mut().zip(v) {
+            column.push(x);
+        }
+    }
+
+    anyhow::ensure!(!columns.is_empty(), "Empty input data");
+    Ok(columns)
+}
+
+#[cfg(feature = "enclave_unit_test")]
+pub mod tests {
+    use super::*;
+    use serde_json::json;
+    use std::path::Path;
+    use std::untrusted::fs;
+    use teaclave_crypto::*;
+    use teaclave_runtime::*;
+    use teaclave_test_utils::*;
+    use teaclave_types::*;
+
+    pub fn run_tests() -> bool {
+        run_tests!(
+            test_descriptive_stats,
+            test_joined_descriptive_stats,
+            test_parse_non_finite_columns
+        )
+    }
+
+    fn run_descriptive_stats(
+        arguments: FunctionArguments,
+        inputs: &[&str],
+        plain_output: &Path,
+    ) -> (String, Summary) {
+        let base = Path::new("fixtures/functions/descriptive_stats");
+        let input_files: StagedFiles = inputs
+            .iter()
+            .enumerate()
+            .map(|(i, input)| {
+                (
+                    indexed_file_name(INPUT_DATA, i),
+                    StagedFileInfo::new(
+                        &base.join(input),
+                        TeaclaveFile128Key::random(),
+                        FileAuthTag::mock(),
+                    ),
+                )
+            })
+            .collect();
+
+        let output_files = StagedFiles::new(hashmap!(
+            RESULT =>
+            StagedFileInfo::new(plain_output, TeaclaveFile128Key::random(), FileAuthTag::mock())
+        ));
+
+        let runtime = Box::new(RawIoRuntime::new(input_files, output_files));
+
+        let summary = DescriptiveStats::new().run(arguments, runtime).unwrap();
+        let summary_json = fs::read_to_string(plain_output).unwrap();
+        (summary, serde_json::from_str(&summary_json).unwrap())
+    }
+
+    fn test_descriptive_stats() {
+        let arguments = FunctionArguments::from_json(json!({
+            "quantiles": [0.1, 0.5]
+        }))
+        .unwrap();
+        let plain_output = Path::new("fixtures/functions/descriptive_stats/stats.txt.out");
+
+        let (summary, stats) = run_descriptive_stats(arguments, &["input.txt"], plain_output);
+        assert_eq!(summary, "Summarized 5 lines of data with 2 columns.");
+
+        let columns = &stats.columns;
+        assert_eq!(columns.len(), 2);
+        assert_eq!(columns[0].count, 5);
+        assert_eq!(columns[0].mean, 3.0);
+        assert!((columns[0].stddev - 2.5f64.sqrt()).abs() < 1e-9);
+        assert_eq!(columns[0].min, 1.0);
+        assert_eq!(columns[0].max, 5.0);
+        assert!((columns[0].quantiles[0] - 1.4).abs() < 1e-9);
+        assert_eq!(columns[0].quantiles[1], 3.0);
+        assert_eq!(columns[1].mean, 30.0);
+        assert_eq!(columns[1].quantiles[1], 30.0);
+    }
+
+    fn test_joined_descriptive_stats() {
+        let arguments = FunctionArguments::default();
+        let plain_output = Path::new("fixtures/functions/descriptive_stats/joined_stats.txt.out");
+
+        let (summary, stats) =
+            run_descriptive_stats(arguments, &["input.txt", "joined_input.txt"], plain_output);
+        assert_eq!(summary, "Summarized 5 lines of data with 4 columns.");
+
+        let columns = &stats.columns;
+        assert_eq!(columns.len(), 4);
+        assert_eq!(columns[0].quantiles, vec![2.0, 3.0, 4.0]);
+        assert_eq!(columns[2].mean, 3.0);
+        assert_eq!(columns[3].stddev, 0.0);
+
+        let correlations = &stats.correlations;
+        assert_eq!(correlations.len(), 4);
+        let close = |c: Option<f64>, expected: f64| (c.unwrap() - expected).abs() < 1e-9;
+        assert!(close(correlations[0][0], 1.0));
+        assert!(close(correlations[0][1], 1.0));
+        assert!(close(correlations[0][2], -1.0));
+        assert!(close(correlations[2][1], -1.0));
+        assert!(correlations[0][3].is_none());
+        assert!(correlations[3][3].is_none());
+    }
+
+    fn test_parse_non_finite_columns() {
+        assert!(parse_columns("1.0, 2.0\n3.0, 4.0\n".as_bytes()).is_ok());
+        for input in &["1.0, NaN\n", "inf, 2.0\n", "1e400, 2.0\n"] {
+            assert!(parse_columns(input.as_bytes()).is_err());
+        }
+    }
+}
@@ -0,0 +1,275 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License.
+
+#[cfg(feature = "mesalock_sgx")]
+use std::prelude::v1::*;
+
+use anyhow::{anyhow, bail, ensure, Result};
+use std::collections::HashMap;
+use std::convert::TryFrom;
+use std::format;
+use std::io::Write;
+
+use ring::rand::{SecureRandom, SystemRandom};
+use teaclave_types::{FunctionArguments, FunctionRuntime};
+
+const IN_DATA: &str = "input_data";
+const OUT_RESULT: &str = "output_data";
+
+#[derive(Default)]
+pub struct DpAggregate;
+
+#[derive(serde::Deserialize)]
+#[serde(rename_all = "lowercase")]
+enum Aggregation {
+    Count,
+    Sum,
+    Mean,
+}
+
+#[derive(serde::Deserialize)]
+#[serde(rename_all = "lowercase")]
+enum Mechanism {
+    Laplace,
+    Gaussian,
+}
+
+#[derive(serde::Deserialize)]
+struct DpAggregateArguments {
+    aggregation: Aggregation,
+    mechanism: Mechanism,
+    epsilon: f64,
+    delta: f64,
+    lower_bound: f64,
+    upper_bound: f64,
+}
+
+impl TryFrom<FunctionArguments> for DpAggregateArguments {
+    type Error = anyhow::Error;
+
+    fn try_from(arguments: FunctionArguments) -> Result<Self, Self::Error> {
+        use anyhow::Context;
+        serde_json::from_str(&arguments.into_string()).context("Cannot deserialize arguments")
+    }
+}
+
+impl DpAggregate {
+    pub const NAME: &'static str = teaclave_types::DP_AGGREGATE_FUNCTION_NAME;
+
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    pub fn run(
+        &self,
+        arguments: FunctionArguments,
+        runtime: FunctionRuntime,
+    ) -> anyhow::Result<String> {
+        let args = DpAggregateArguments::try_from(arguments)?;
+        ensure!(args.epsilon > 0.0, "Invalid epsilon: {}", args.epsilon);
+        ensure!(
+            args.lower_bound <= args.upper_bound,
+            "Invalid clipping bounds: [{}, {}]",
+            args.lower_bound,
+            args.upper_bound
+        );
+        if let Mechanism::Gaussian = args.mechanism {
+            ensure!(
+                args.delta > 0.0 && args.delta < 1.0,
+                "Invalid delta for the Gaussian mechanism: {}",
+                args.delta
+            );
+        }
+
+        let num_user = runtime.input_count(IN_DATA);
+        ensure!(num_user > 0, "No input data");
+
+        let mut joined = read_input(0, &runtime)?;
+        for i in 1..num_user {
+            let input = read_input(i, &runtime)?;
+            joined = joined
+                .into_iter()
+                .filter_map(|(identity, value)| input.get(&identity).map(|v| (identity, value + v)))
+                .collect();
+        }
+
+        // Each identity contributes a single clipped value, which bounds the
+        // sensitivity of the sum regardless of the data.
+        let count = joined.len() as f64;
+        let sum: f64 = joined
+            .values()
+            .map(|v| v.max(args.lower_bound).min(args.upper_bound))
+            .sum();
+        let sum_sensitivity = args.lower_bound.abs().max(args.upper_bound.abs());
+
+        let noise = NoiseGenerator::new(args.mechanism);
+        let result = match args.aggregation {
+            Aggregation::Count => count + noise.sample(1.0, args.epsilon, args.delta)?,
+            Aggregation::Sum => sum + noise.sample(sum_sensitivity, args.epsilon, args.delta)?,
+            Aggregation::Mean => {
+                // Split the budget evenly between the noisy sum and count.
+                let (epsilon, delta) = (args.epsilon / 2.0, args.delta / 2.0);
+                let noisy_sum = sum + noise.sample(sum_sensitivity, epsilon, delta)?;
+                let noisy_count = count + noise.sample(1.0, epsilon, delta)?;
+                noisy_sum / noisy_count.max(1.0)
+            }
+        };
+
+        let output = format!("{}\n", result);
+        for i in 0..runtime.output_count(OUT_RESULT) {
+            let mut f = runtime.create_output_at(OUT_RESULT, i)?;
+            f.write_all(output.as_bytes())?;
+        }
+
+        Ok(format!(
+            "Aggregated data of {} users with epsilon = {}, delta = {}.",
+            num_user, args.epsilon, args.delta
+        ))
+    }
+}
+
+struct NoiseGenerator {
+    mechanism: Mechanism,
+    rng: SystemRandom,
+}
+
+impl NoiseGenerator {
+    fn new(mechanism: Mechanism) -> Self {
+        Self {
+            mechanism,
+            rng: SystemRandom::new(),
+        }
+    }
+
+    fn sample(&self, sensitivity: f64, epsilon: f64, delta: f64) -> Result<f64> {
+        let noise = match self.mechanism {
+            Mechanism::Laplace => {
+                let scale = sensitivity / epsilon;
+                let u = self.uniform()? - 0.5;
+                -scale * u.signum() * (1.0 - 2.0 * u.abs()).ln()
+            }
+            Mechanism::Gaussian => {
+                let sigma = sensitivity * (2.0 * (1.25 / delta).ln()).sqrt() / epsilon;
+                // Box-Muller transform
+                let (u1, u2) = (self.uniform()?, self.uniform()?);
+                sigma * (-2.0 * u1.ln()).sqrt() * (2.0 * std::f64::consts::PI * u2).cos()
+            }
+        };
+        Ok(noise)
+    }
+
+    // Returns a uniformly distributed number in the open interval (0, 1).
+    fn uniform(&self) -> Result<f64> {
+        let mut buf = [0u8; 8];
+        self.rng
+            .fill(&mut buf)
+            .map_err(|_| anyhow!("Failed to generate random numbers"))?;
+        let bits = u64::from_le_bytes(buf) >> 11;
+        Ok((bits as f64 + 0.5) / (1u64 << 53) as f64)
+    }
+}
+
+fn read_input(index: usize, runtime: &FunctionRuntime) -> Result<HashMap<String, f64>> {
+    let mut data = String::new();
+    let mut input = runtime.open_input_at(IN_DATA, index)?;
+    input.read_to_string(&mut data)?;
+
+    let mut ret = HashMap::new();
+    for line in data.lines() {
+        let line = line.trim();
+        if line.is_empty() {
+            continue;
+        }
+        let kv_pair: Vec<&str> = line.split(':').collect();
+        if kv_pair.len() != 2 {
+            bail!("Data format error: {}", line);
+        }
+        let value = kv_pair[1].trim().parse::<f64>()?;
+        ret.insert(kv_pair[0].trim().to_string(), value);
+    }
+    Ok(ret)
+}
+
+#[cfg(feature = "enclave_unit_test")]
+pub mod tests {
+    use super::*;
+    use serde_json::json;
+    use std::path::Path;
+    use std::untrusted::fs;
+    use teaclave_crypto::*;
+    use teaclave_runtime::*;
+    use teaclave_test_utils::*;
+    use teaclave_types::*;
+
+    pub fn run_tests() -> bool {
+        run_tests!(test_dp_aggregate)
+    }
+
+    fn test_dp_aggregate() {
+        // A huge epsilon makes the noise negligible so that the result can be
+        // compared with the exact one.
+        let arguments = FunctionArguments::from_json(json!({
+            "aggregation": "sum",
+            "mechanism": "laplace",
+            "epsilon": 1e9,
+            "delta": 0.0,
+            "lower_bound": 0.0,
+            "upper_bound": 1000000.0
+        }))
+        .unwrap();
+
+        let base = Path::new("fixtures/functions/private_join_and_compute/three_party_data");
+        let user0_input = base.join("bank_a.txt");
+        let user1_input = base.join("bank_b.txt");
+        let user2_input = base.join("bank_c.txt");
+        let user0_output = base.join("user0_dp_output.txt.out");
+        let user1_output = base.join("user1_dp_output.txt.out");
+        let user2_output = base.join("user2_dp_output.txt.out");
+
+        let input_files = StagedFiles::new(hashmap!(
+            "input_data[0]" =>
+            StagedFileInfo::new(&user0_input, TeaclaveFile128Key::random(), FileAuthTag::mock()),
+            "input_data[1]" =>
+            StagedFileInfo::new(&user1_input, TeaclaveFile128Key::random(), FileAuthTag::mock()),
+            "input_data[2]" =>
+            StagedFileInfo::new(&user2_input, TeaclaveFile128Key::random(), FileAuthTag::mock())
+        ));
+
+        let output_files = StagedFiles::new(hashmap!(
+            "output_data[0]" =>
+            StagedFileInfo::new(&user0_output, TeaclaveFile128Key::random(), FileAuthTag::mock()),
+            "output_data[1]" =>
+            StagedFileInfo::new(&user1_output, TeaclaveFile128Key::random(), FileAuthTag::mock()),
+            "output_data[2]" =>
+            StagedFileInfo::new(&user2_output, TeaclaveFile128Key::random(), FileAuthTag::mock())
+        ));
+
+        let runtime = Box::new(RawIoRuntime::new(input_files, output_files));
+
+        let summary = DpAggregate::new().run(arguments, runtime).unwrap();
+        assert_eq!(
+            summary,
+            "Aggregated data of 3 users with epsilon = 1000000000, delta = 0."
+        );
+
+        // Identities "c" and "e" are in all inputs, the sum of "e" is clipped.
+        for output in &[user0_output, user1_output, user2_output] {
+            let result: f64 = fs::read_to_string(output).unwrap().trim().parse().unwrap();
+            assert!((result - 1470000.0).abs() < 1.0);
+        }
+    }
+}
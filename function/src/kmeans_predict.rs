@@ -0,0 +1,115 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License.
+
+#[cfg(feature = "mesalock_sgx")]
+use std::prelude::v1::*;
+
+use std::format;
+use std::io::Write;
+
+use crate::kmeans_train::{parse_csv_rows, KMeansModel};
+use teaclave_types::{FunctionArguments, FunctionRuntime};
+
+const MODEL_FILE: &str = "model_file";
+const INPUT_DATA: &str = "data_file";
+const RESULT: &str = "result_file";
+
+#[derive(Default)]
+pub struct KMeansPredict;
+
+impl KMeansPredict {
+    pub const NAME: &'static str = "builtin-kmeans-predict";
+
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    pub fn run(
+        &self,
+        _arguments: FunctionArguments,
+        runtime: FunctionRuntime,
+    ) -> anyhow::Result<String> {
+        let mut model_json = String::new();
+        let mut f = runtime.open_input(MODEL_FILE)?;
+        f.read_to_string(&mut model_json)?;
+
+        let model: KMeansModel = serde_json::from_str(&model_json)?;
+        anyhow::ensure!(!model.centroids.is_empty(), "Model has no centroids");
+        let feature_size = model.feature_size();
+
+        let input = runtime.open_input(INPUT_DATA)?;
+        let points = parse_csv_rows(input)?;
+        anyhow::ensure!(
+            points[0].len() == feature_size,
+            "Data format error: column len = {}, expected = {}",
+            points[0].len(),
+            feature_size
+        );
+
+        let mut output = runtime.create_output(RESULT)?;
+        for point in points.iter() {
+            writeln!(&mut output, "{}", model.nearest(point))?;
+        }
+        Ok(format!("Predicted {} lines of data.", points.len()))
+    }
+}
+
+#[cfg(feature = "enclave_unit_test")]
+pub mod tests {
+    use super::*;
+    use std::path::Path;
+    use std::untrusted::fs;
+    use teaclave_crypto::*;
+    use teaclave_runtime::*;
+    use teaclave_test_utils::*;
+    use teaclave_types::*;
+
+    pub fn run_tests() -> bool {
+        run_tests!(test_kmeans_predict)
+    }
+
+    fn test_kmeans_predict() {
+        let arguments = FunctionArguments::default();
+
+        let base = Path::new("fixtures/functions/kmeans_prediction");
+        let model = base.join("model.txt");
+        let plain_input = base.join("predict_input.txt");
+        let plain_output = base.join("predict_result.txt.out");
+        let expected_output = base.join("expected_result.txt");
+
+        let input_files = StagedFiles::new(hashmap!(
+            MODEL_FILE =>
+            StagedFileInfo::new(&model, TeaclaveFile128Key::random(), FileAuthTag::mock()),
+            INPUT_DATA =>
+            StagedFileInfo::new(&plain_input, TeaclaveFile128Key::random(), FileAuthTag::mock()),
+        ));
+
+        let output_files = StagedFiles::new(hashmap!(
+            RESULT =>
+            StagedFileInfo::new(&plain_output, TeaclaveFile128Key::random(), FileAuthTag::mock())
+        ));
+
+        let runtime = Box::new(RawIoRuntime::new(input_files, output_files));
+
+        let summary = KMeansPredict::new().run(arguments, runtime).unwrap();
+        assert_eq!(summary, "Predicted 4 lines of data.");
+
+        let result = fs::read_to_string(&plain_output).unwrap();
+        let expected = fs::read_to_string(&expected_output).unwrap();
+        assert_eq!(&result[..], &expected[..]);
+    }
+}
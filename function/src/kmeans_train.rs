@@ -0,0 +1,342 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License.
+
+#[cfg(feature = "mesalock_sgx")]
+use std::prelude::v1::*;
+
+use std::convert::TryFrom;
+use std::format;
+use std::io::{self, BufRead, BufReader, Write};
+
+use teaclave_types::{FunctionArguments, FunctionRuntime};
+
+const TRAINING_DATA: &str = "training_data";
+const OUT_MODEL_FILE: &str = "model_file";
+const OUT_ASSIGNMENT_FILE: &str = "assignment_file";
+
+#[derive(Default)]
+pub struct KMeansTrain;
+
+#[derive(serde::Deserialize)]
+struct KMeansTrainArguments {
+    k: usize,
+    max_iters: usize,
+    tolerance: f64,
+    seed: u64,
+}
+
+impl TryFrom<FunctionArguments> for KMeansTrainArguments {
+    type Error = anyhow::Error;
+
+    fn try_from(arguments: FunctionArguments) -> Result<Self, Self::Error> {
+        use anyhow::Context;
+        serde_json::from_str(&arguments.into_string()).context("Cannot deserialize arguments")
+    }
+}
+
+/// Centroids of the clusters, shared by the train and predict functions.
+#[derive(serde::Serialize, serde::Deserialize)]
+pub(crate) struct KMeansModel {
+    pub(crate) centroids: Vec<Vec<f64>>,
+}
+
+impl KMeansModel {
+    pub(crate) fn feature_size(&self) -> usize {
+        self.centroids.first().map_or(0, |c| c.len())
+    }
+
+    pub(crate) fn nearest(&self, point: &[f64]) -> usize {
+        let mut nearest = 0;
+        let mut min_distance = std::f64::INFINITY;
+        for (i, centroid) in self.centroids.iter().enumerate() {
+            let distance = squared_distance(point, centroid);
+            if distance < min_distance {
+                min_distance = distance;
+                nearest = i;
+            }
+        }
+        nearest
+    }
+}
+
+impl KMeansTrain {
+    pub const NAME: &'static str = "builtin-kmeans-train";
+
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    pub fn run(
+        &self,
+        arguments: FunctionArguments,
+        runtime: FunctionRuntime,
+    ) -> anyhow::Result<String> {
+        let args = KMeansTrainArguments::try_from(arguments)?;
+
+        let input = runtime.open_input(TRAINING_DATA)?;
+        let points = parse_csv_rows(input)?;
+        anyhow::ensure!(
+            args.k > 0 && args.k <= points.len(),
+            "Invalid k: {}, number of rows = {}",
+            args.k,
+            points.len()
+        );
+
+        let mut rng = SplitMix64::new(args.seed);
+        let mut model = KMeansModel {
+            centroids: init_centroids(&points, args.k, &mut rng),
+        };
+        let mut assignments = vec![0; points.len()];
+        let mut iters = 0;
+        while iters < args.max_iters {
+            iters += 1;
+            for (point, assignment) in points.iter().zip(assignments.iter_mut()) {
+                *assignment = model.nearest(point);
+            }
+            let shift = update_centroids(&mut model, &points, &assignments);
+            if shift <= args.tolerance {
+                break;
+            }
+        }
+        for (point, assignment) in points.iter().zip(assignments.iter_mut()) {
+            *assignment = model.nearest(point);
+        }
+
+        let model_json = serde_json::to_string(&model)?;
+        let mut model_file = runtime.create_output(OUT_MODEL_FILE)?;
+        model_file.write_all(model_json.as_bytes())?;
+
+        let mut assignment_file = runtime.create_output(OUT_ASSIGNMENT_FILE)?;
+        for assignment in assignments.iter() {
+            writeln!(&mut assignment_file, "{}", assignment)?;
+        }
+
+        Ok(format!(
+            "Trained {} lines of data into {} clusters in {} iterations.",
+            points.len(),
+            args.k,
+            iters
+        ))
+    }
+}
+
+// Chooses the initial centroids with k-means++, i.e., each centroid is picked
+// with probability proportional to its squared distance to the nearest
+// centroid chosen so far.
+fn init_centroids(points: &[Vec<f64>], k: usize, rng: &mut SplitMix64) -> Vec<Vec<f64>> {
+    let mut centroids = Vec::with_capacity(k);
+    centroids.push(points[rng.next_index(points.len())].clone());
+
+    let mut distances: Vec<f64> = points
+        .iter()
+        .map(|p| squared_distance(p, &centroids[0]))
+        .collect();
+    while centroids.len() < k {
+        let total: f64 = distances.iter().sum();
+        let index = if total > 0.0 {
+            let mut target = rng.next_f64() * total;
+            let mut index = points.len() - 1;
+            for (i, d) in distances.iter().enumerate() {
+                if target < *d {
+                    index = i;
+                    break;
+                }
+                target -= d;
+            }
+            index
+        } else {
+            rng.next_index(points.len())
+        };
+
+        let centroid = points[index].clone();
+        for (p, d) in points.iter().zip(distances.iter_mut()) {
+            *d = d.min(squared_distance(p, &centroid));
+        }
+        centroids.push(centroid);
+    }
+
+    centroids
+}
+
+// Moves each centroid to the mean of its assigned points and returns the
+// largest distance a centroid moved. A centroid without points stays put.
+fn update_centroids(model: &mut KMeansModel, points: &[Vec<f64>], assignments: &[usize]) -> f64 {
+    let feature_size = model.feature_size();
+    let k = model.centroids.len();
+    let mut sums = vec![vec![0.0; feature_size]; k];
+    let mut counts = vec![0usize; k];
+    for (point, &cluster) in points.iter().zip(assignments.iter()) {
+        counts[cluster] += 1;
+        for (s, x) in sums[cluster].iter_mut().zip(point.iter()) {
+            *s += x;
+        }
+    }
+
+    let mut max_shift: f64 = 0.0;
+    for ((centroid, sum), count) in model.centroids.iter_mut().zip(sums).zip(counts) {
+        if count == 0 {
+            continue;
+        }
+        let new_centroid: Vec<f64> = sum.iter().map(|s| s / count as f64).collect();
+        max_shift = max_shift.max(squared_distance(centroid, &new_centroid).sqrt());
+        *centroid = new_centroid;
+    }
+
+    max_shift
+}
+
+pub(crate) fn squared_distance(a: &[f64], b: &[f64]) -> f64 {
+    a.iter().zip(b.iter()).map(|(x, y)| (x - y) * (x - y)).sum()
+}
+
+pub(crate) fn parse_csv_rows(input: impl io::Read) -> anyhow::Result<Vec<Vec<f64>>> {
+    let reader = BufReader::new(input);
+    let mut rows: Vec<Vec<f64>> = Vec::new();
+
+    for line_result in reader.lines() {
+        let line = line_result?;
+        let trimed_line = line.trim();
+        anyhow::ensure!(!trimed_line.is_empty(), "Empty line");
+
+        let v: Vec<f64> = trimed_line
+            .split(',')
+            .map(|x| x.trim().parse::<f64>())
+            .collect::<std::result::Result<_, _>>()?;
+        // A NaN is never nearer to any centroid, and would silently be
+        // assigned to the first cluster.
+        anyhow::ensure!(
+            v.iter().all(|x| x.is_finite()),
+            "Data format error: non-finite value"
+        );
+
+        if let Some(first) = rows.first() {
+            anyhow::ensure!(
+                v.len() == first.len(),
+                "Data format error: column len = {}, expected = {}",
+                v.len(),
+                first.len()
+            );
+        }
+        rows.push(v);
+    }
+
+    anyhow::ensure!(!rows.is_empty(), "Empty input data");
+    Ok(rows)
+}
+
+// A small deterministic PRNG so that training is reproducible with a seed.
+struct SplitMix64 {
+    state: u64,
+}
+
+impl SplitMix64 {
+    fn new(seed: u64) -> Self {
+        Self { state: seed }
+    }
+
+    fn next_u64(&mut self) -> u64 {
+        self.state = self.state.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        let mut z = self.state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        z ^ (z >> 31)
+    }
+
+    fn next_f64(&mut self) -> f64 {
+        (self.next_u64() >> 11) as f64 / (1u64 << 53) as f64
+    }
+
+    fn next_index(&mut self, len: usize) -> usize {
+        (self.next_u64() % len as u64) as usize
+    }
+}
+
+#[cfg(feature = "enclave_unit_test")]
+pub mod tests {
+    use super::*;
+    use serde_json::json;
+    use std::path::Path;
+    use std::untrusted::fs;
+    use teaclave_crypto::*;
+    use teaclave_runtime::*;
+    use teaclave_test_utils::*;
+    use teaclave_types::*;
+
+    pub fn run_tests() -> bool {
+        run_tests!(test_kmeans_train, test_parse_non_finite_rows)
+    }
+
+    fn test_kmeans_train() {
+        let arguments = FunctionArguments::from_json(json!({
+            "k": 3,
+            "max_iters": 100,
+            "tolerance": 0.0001,
+            "seed": 42
+        }))
+        .unwrap();
+
+        let base = Path::new("fixtures/functions/kmeans_training");
+        let training_data = base.join("train.txt");
+        let plain_model = base.join("model.txt.out");
+        let plain_assignments = base.join("assignments.txt.out");
+
+        let input_files = StagedFiles::new(hashmap!(
+            TRAINING_DATA =>
+            StagedFileInfo::new(&training_data, TeaclaveFile128Key::random(), FileAuthTag::mock()),
+        ));
+
+        let output_files = StagedFiles::new(hashmap!(
+            OUT_MODEL_FILE =>
+            StagedFileInfo::new(&plain_model, TeaclaveFile128Key::random(), FileAuthTag::mock()),
+            OUT_ASSIGNMENT_FILE =>
+            StagedFileInfo::new(&plain_assignments, TeaclaveFile128Key::random(), FileAuthTag::mock())
+        ));
+
+        let runtime = Box::new(RawIoRuntime::new(input_files, output_files));
+
+        let summary = KMeansTrain::new().run(arguments, runtime).unwrap();
+        assert!(summary.starts_with("Trained 12 lines of data into 3 clusters"));
+
+        let model_json = fs::read_to_string(&plain_model).unwrap();
+        let model: KMeansModel = serde_json::from_str(&model_json).unwrap();
+        assert_eq!(model.centroids.len(), 3);
+
+        // The training data consists of three well separated groups of four
+        // rows, rows of the same group must end up in the same cluster.
+        let assignments: Vec<usize> = fs::read_to_string(&plain_assignments)
+            .unwrap()
+            .lines()
+            .map(|l| l.parse().unwrap())
+            .collect();
+        assert_eq!(assignments.len(), 12);
+        for group in assignments.chunks(4) {
+            assert!(group.iter().all(|&c| c == group[0]));
+        }
+        assert_ne!(assignments[0], assignments[4]);
+        assert_ne!(assignments[4], assignments[8]);
+        assert_ne!(assignments[0], assignments[8]);
+    }
+
+    fn test_parse_non_finite_rows() {
+        let rows = parse_csv_rows("1.0, 2.0\n3.0, 4.0\n".as_bytes()).unwrap();
+        assert_eq!(rows, vec![vec![1.0, 2.0], vec![3.0, 4.0]]);
+
+        for input in &["1.0, NaN\n", "inf, 2.0\n", "1.0, -inf\n", "1e400, 2.0\n"] {
+            assert!(parse_csv_rows(input.as_bytes()).is_err());
+        }
+    }
+}
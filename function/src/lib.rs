@@ -22,22 +22,36 @@ extern crate sgx_tstd as std;
 #[cfg(feature = "mesalock_sgx")]
 use std::prelude::v1::*;
 
+mod descriptive_stats;
+mod dp_aggregate;
 mod echo;
 mod gbdt_predict;
 mod gbdt_train;
+mod kmeans_predict;
+mod kmeans_train;
+mod linear_regression_predict;
+mod linear_regression_train;
 mod logistic_regression_predict;
 mod logistic_regression_train;
 mod online_decrypt;
 mod private_join_and_compute;
+mod reencrypt_file;
 mod rsa_sign;
 
+pub use descriptive_stats::DescriptiveStats;
+pub use dp_aggregate::DpAggregate;
 pub use echo::Echo;
 pub use gbdt_predict::GbdtPredict;
 pub use gbdt_train::GbdtTrain;
+pub use kmeans_predict::KMeansPredict;
+pub use kmeans_train::KMeansTrain;
+pub use linear_regression_predict::LinearRegressionPredict;
+pub use linear_regression_train::LinearRegressionTrain;
 pub use logistic_regression_predict::LogisticRegressionPredict;
 pub use logistic_regression_train::LogisticRegressionTrain;
 pub use online_decrypt::OnlineDecrypt;
 pub use private_join_and_compute::PrivateJoinAndCompute;
+pub use reencrypt_file::ReencryptFile;
 pub use rsa_sign::RsaSign;
 
 #[cfg(feature = "enclave_unit_test")]
@@ -47,13 +61,20 @@ pub mod tests {
 
     pub fn run_tests() -> bool {
         check_all_passed!(
+            descriptive_stats::tests::run_tests(),
+            dp_aggregate::tests::run_tests(),
             echo::tests::run_tests(),
             gbdt_train::tests::run_tests(),
             gbdt_predict::tests::run_tests(),
+            kmeans_train::tests::run_tests(),
+            kmeans_predict::tests::run_tests(),
+            linear_regression_train::tests::run_tests(),
+            linear_regression_predict::tests::run_tests(),
             logistic_regression_train::tests::run_tests(),
             logistic_regression_predict::tests::run_tests(),
             online_decrypt::tests::run_tests(),
             private_join_and_compute::tests::run_tests(),
+            reencrypt_file::tests::run_tests(),
             rsa_sign::tests::run_tests(),
         )
     }
@@ -15,7 +15,7 @@
 // specific language governing permissions and limitations
 // under the License.
 
-use anyhow::{bail, Result};
+use anyhow::{bail, ensure, Result};
 use std::collections::HashMap;
 use std::convert::TryFrom;
 use std::fmt;
@@ -62,6 +62,11 @@ impl PrivateJoinAndCompute {
         if num_user < 2 {
             bail!("The demo requires at least two parties!");
         }
+        ensure!(
+            runtime.input_count(IN_DATA) == num_user
+                && runtime.output_count(OUT_RESULT) == num_user,
+            "The number of input and output files should equal to num_user"
+        );
 
         let mut output = String::new();
         let data_0 = get_data(0, &runtime)?;
@@ -81,8 +86,7 @@ impl PrivateJoinAndCompute {
         let output_bytes = output.as_bytes();
 
         for i in 0..num_user {
-            let output_file_name = format!("{}{}", OUT_RESULT, i);
-            let mut output = runtime.create_output(&output_file_name)?;
+            let mut output = runtime.create_output_at(OUT_RESULT, i)?;
             output.write_all(&output_bytes)?;
         }
 
@@ -93,8 +97,7 @@ impl PrivateJoinAndCompute {
 
 fn get_data(user_id: usize, runtime: &FunctionRuntime) -> anyhow::Result<Vec<u8>> {
     let mut data: Vec<u8> = Vec::new();
-    let input_file_name = format!("{}{}", IN_DATA, user_id);
-    let mut input_io = runtime.open_input(&input_file_name)?;
+    let mut input_io = runtime.open_input_at(IN_DATA, user_id)?;
     input_io.read_to_end(&mut data)?;
     Ok(data)
 }
@@ -168,20 +171,20 @@ pub mod tests {
             "fixtures/functions/private_join_and_compute/three_party_data/user2_output.txt";
 
         let input_files = StagedFiles::new(hashmap!(
-            "input_data0" =>
+            "input_data[0]" =>
             StagedFileInfo::new(user0_input, TeaclaveFile128Key::random(), FileAuthTag::mock()),
-            "input_data1" =>
+            "input_data[1]" =>
             StagedFileInfo::new(user1_input, TeaclaveFile128Key::random(), FileAuthTag::mock()),
-            "input_data2" =>
+            "input_data[2]" =>
             StagedFileInfo::new(user2_input, TeaclaveFile128Key::random(), FileAuthTag::mock())
         ));
 
         let output_files = StagedFiles::new(hashmap!(
-            "output_data0" =>
+            "output_data[0]" =>
             StagedFileInfo::new(user0_output, TeaclaveFile128Key::random(), FileAuthTag::mock()),
-            "output_data1" =>
+            "output_data[1]" =>
             StagedFileInfo::new(user1_output, TeaclaveFile128Key::random(), FileAuthTag::mock()),
-            "output_data2" =>
+            "output_data[2]" =>
             StagedFileInfo::new(user2_output, TeaclaveFile128Key::random(), FileAuthTag::mock())
         ));
 
@@ -0,0 +1,92 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License.
+
+#[cfg(feature = "mesalock_sgx")]
+use std::prelude::v1::*;
+
+use std::format;
+use std::io;
+
+use teaclave_types::{
+    FunctionArguments, FunctionRuntime, REENCRYPT_FILE_INPUT, REENCRYPT_FILE_OUTPUT,
+};
+
+/// Copies the input file to the output file. Both files are decrypted and
+/// encrypted by the platform, so running it with an output under a new key
+/// re-encrypts the data without exposing it outside of the enclave.
+#[derive(Default)]
+pub struct ReencryptFile;
+
+impl ReencryptFile {
+    pub const NAME: &'static str = teaclave_types::REENCRYPT_FILE_FUNCTION_NAME;
+
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    pub fn run(
+        &self,
+        _arguments: FunctionArguments,
+        runtime: FunctionRuntime,
+    ) -> anyhow::Result<String> {
+        let mut input = runtime.open_input(REENCRYPT_FILE_INPUT)?;
+        let mut output = runtime.create_output(REENCRYPT_FILE_OUTPUT)?;
+        let n = io::copy(&mut input, &mut output)?;
+        Ok(format!("Re-encrypted {} bytes.", n))
+    }
+}
+
+#[cfg(feature = "enclave_unit_test")]
+pub mod tests {
+    use super::*;
+    use std::path::Path;
+    use std::untrusted::fs;
+    use teaclave_crypto::*;
+    use teaclave_runtime::*;
+    use teaclave_test_utils::*;
+    use teaclave_types::*;
+
+    pub fn run_tests() -> bool {
+        run_tests!(test_reencrypt_file)
+    }
+
+    fn test_reencrypt_file() {
+        let base = Path::new("fixtures/functions/descriptive_stats");
+        let plain_input = base.join("input.txt");
+        let plain_output = base.join("input.txt.reencrypted.out");
+
+        let input_files = StagedFiles::new(hashmap!(
+            REENCRYPT_FILE_INPUT =>
+            StagedFileInfo::new(&plain_input, TeaclaveFile128Key::random(), FileAuthTag::mock()),
+        ));
+
+        let output_files = StagedFiles::new(hashmap!(
+            REENCRYPT_FILE_OUTPUT =>
+            StagedFileInfo::new(&plain_output, TeaclaveFile128Key::random(), FileAuthTag::mock())
+        ));
+
+        let runtime = Box::new(RawIoRuntime::new(input_files, output_files));
+
+        let summary = ReencryptFile::new()
+            .run(FunctionArguments::default(), runtime)
+            .unwrap();
+
+        let expected = fs::read(&plain_input).unwrap();
+        assert_eq!(summary, format!("Re-encrypted {} bytes.", expected.len()));
+        assert_eq!(fs::read(&plain_output).unwrap(), expected);
+    }
+}
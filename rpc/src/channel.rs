@@ -16,51 +16,187 @@
 // under the License.
 
 use crate::config::SgxTrustedTlsClientConfig;
-use crate::transport::{ClientTransport, SgxTrustedTlsTransport};
+use crate::endpoint::ChannelOptions;
+use crate::pool::{Connection, ConnectionPool};
+use crate::protocol::{Encoding, ProtobufMessage};
+use crate::transport::ClientTransport;
 use crate::Request;
-use anyhow::anyhow;
 use anyhow::Result;
-use http::Uri;
+use log::debug;
 use serde::{Deserialize, Serialize};
+use std::prelude::v1::*;
 use std::sync::Arc;
+use teaclave_types::TeaclaveServiceResponseError;
 
+/// A channel over one connection at a time. The connection is taken from the
+/// pool of the endpoint, replaced if it is closed or broken, and returned to
+/// the pool when the channel is dropped. Cloned channels share the pool and
+/// connect on their first request.
 pub struct SgxTrustedTlsChannel<U, V>
 where
-    U: Serialize + std::fmt::Debug,
-    V: for<'de> Deserialize<'de> + std::fmt::Debug,
+    U: Serialize + ProtobufMessage + std::fmt::Debug,
+    V: for<'de> Deserialize<'de> + ProtobufMessage + std::fmt::Debug,
 {
-    transport: SgxTrustedTlsTransport<rustls::ClientSession>,
+    pool: Arc<ConnectionPool>,
+    connection: Option<Connection>,
+    encoding: Encoding,
     maker: std::marker::PhantomData<(U, V)>,
 }
 
 impl<U, V> SgxTrustedTlsChannel<U, V>
 where
-    U: Serialize + std::fmt::Debug,
-    V: for<'de> Deserialize<'de> + std::fmt::Debug,
+    U: Serialize + ProtobufMessage + std::fmt::Debug,
+    V: for<'de> Deserialize<'de> + ProtobufMessage + std::fmt::Debug,
 {
     pub fn new(
         address: &str,
         client_config: &SgxTrustedTlsClientConfig,
     ) -> Result<SgxTrustedTlsChannel<U, V>> {
-        let uri = address.parse::<Uri>()?;
-        let hostname = uri.host().ok_or_else(|| anyhow!("Invalid hostname."))?;
-        let stream = std::net::TcpStream::connect(address)?;
-        let hostname = webpki::DNSNameRef::try_from_ascii_str(hostname)?;
-        let session =
-            rustls::ClientSession::new(&Arc::new(client_config.client_config.clone()), hostname);
-        let tls_stream = rustls::StreamOwned::new(session, stream);
-        let transport = SgxTrustedTlsTransport::new(tls_stream);
+        let pool = ConnectionPool::new(address, client_config, ChannelOptions::default())?;
+        Self::with_pool(Arc::new(pool))
+    }
+
+    pub(crate) fn with_pool(pool: Arc<ConnectionPool>) -> Result<SgxTrustedTlsChannel<U, V>> {
+        let connection = pool.get()?.encoding(Encoding::default());
 
         Ok(Self {
-            transport,
+            pool,
+            connection: Some(connection),
+            encoding: Encoding::default(),
             maker: std::marker::PhantomData::<(U, V)>,
         })
     }
 
+    /// Set the encoding of requests, JSON by default.
+    pub fn encoding(mut self, encoding: Encoding) -> Self {
+        self.encoding = encoding;
+        self.connection = self.connection.take().map(|c| c.encoding(encoding));
+        self
+    }
+
+    /// The connection of the channel, connecting a new one if there is none
+    /// or the server has closed it.
+    fn connection(&mut self) -> Result<&mut Connection> {
+        if self.connection.as_mut().map_or(false, |c| c.is_closed()) {
+            debug!("Connection closed by the server, reconnecting");
+            self.connection = None;
+        }
+        if self.connection.is_none() {
+            self.connection = Some(self.pool.get()?.encoding(self.encoding));
+        }
+
+        Ok(self.connection.as_mut().expect("connection"))
+    }
+
+    /// Send a request through the connection, which is dropped if the request
+    /// fails with a connection error, as the connection is in an unknown state.
+    fn send(
+        &mut self,
+        send: impl FnOnce(&mut Connection) -> teaclave_types::TeaclaveServiceResponseResult<V>,
+    ) -> teaclave_types::TeaclaveServiceResponseResult<V> {
+        let connection = self
+            .connection()
+            .map_err(|e| TeaclaveServiceResponseError::ConnectionError(format!("{:?}", e)))?;
+        let result = send(connection);
+        if let Err(TeaclaveServiceResponseError::ConnectionError(_)) = result {
+            self.connection = None;
+        }
+
+        result
+    }
+
+    /// Invoke a method. The request is sent once, since a request failed with
+    /// a connection error may still have been handled by the service.
     pub fn invoke(
         &mut self,
         input: Request<U>,
     ) -> teaclave_types::TeaclaveServiceResponseResult<V> {
-        self.transport.send(input)
+        self.send(|c| c.send(input))
+    }
+
+    /// Invoke an idempotent method, resending the request on a new connection
+    /// following the request retry policy of the endpoint if it fails with a
+    /// connection error.
+    pub fn invoke_idempotent(
+        &mut self,
+        input: Request<U>,
+    ) -> teaclave_types::TeaclaveServiceResponseResult<V>
+    where
+        U: Clone,
+    {
+        let policy = self.pool.options().request_retry;
+        let mut input = Some(input);
+        let mut retries = 0;
+        loop {
+            let backoff = policy.backoff(retries);
+            // Only keep a copy of the request if it may be resent.
+            let request = match backoff {
+                Some(_) => input.clone(),
+                None => input.take(),
+            }
+            .expect("request");
+            match (self.send(|c| c.send(request)), backoff) {
+                (Err(TeaclaveServiceResponseError::ConnectionError(e)), Some(backoff)) => {
+                    debug!("Request failed: {}, retry {}", e, retries);
+                    std::thread::sleep(backoff);
+                    retries += 1;
+                }
+                (result, _) => return result,
+            }
+        }
+    }
+
+    /// Invoke a client streaming method, sending the stream read from
+    /// `upload` after the request.
+    pub fn invoke_upload(
+        &mut self,
+        input: Request<U>,
+        upload: &mut dyn std::io::Read,
+    ) -> teaclave_types::TeaclaveServiceResponseResult<V> {
+        self.send(|c| c.send_stream(input, Some(upload), None))
+    }
+
+    /// Invoke a server streaming method, writing the stream received before
+    /// the response to `download`.
+    pub fn invoke_download(
+        &mut self,
+        input: Request<U>,
+        download: &mut dyn std::io::Write,
+    ) -> teaclave_types::TeaclaveServiceResponseResult<V> {
+        self.send(|c| c.send_stream(input, None, Some(download)))
+    }
+
+    /// Keying material exported from the TLS session of the current
+    /// connection, the same as `PeerInfo::tls_exporter_secret` of requests on
+    /// the server.
+    pub fn tls_exporter_secret(&mut self) -> Result<Vec<u8>> {
+        self.connection()?.tls_exporter_secret()
+    }
+}
+
+impl<U, V> Clone for SgxTrustedTlsChannel<U, V>
+where
+    U: Serialize + ProtobufMessage + std::fmt::Debug,
+    V: for<'de> Deserialize<'de> + ProtobufMessage + std::fmt::Debug,
+{
+    fn clone(&self) -> Self {
+        Self {
+            pool: self.pool.clone(),
+            connection: None,
+            encoding: self.encoding,
+            maker: std::marker::PhantomData::<(U, V)>,
+        }
+    }
+}
+
+impl<U, V> Drop for SgxTrustedTlsChannel<U, V>
+where
+    U: Serialize + ProtobufMessage + std::fmt::Debug,
+    V: for<'de> Deserialize<'de> + ProtobufMessage + std::fmt::Debug,
+{
+    fn drop(&mut self) {
+        if let Some(connection) = self.connection.take() {
+            self.pool.put(connection);
+        }
     }
 }
@@ -30,8 +30,7 @@ use std::time::SystemTime;
 #[cfg(feature = "mesalock_sgx")]
 use std::untrusted::time::SystemTimeEx;
 
-use teaclave_attestation::report::AttestationReport;
-use teaclave_attestation::verifier::AttestationReportVerifier;
+use teaclave_attestation::verifier::{AttestationReportPolicy, AttestationReportVerifier};
 use teaclave_attestation::AttestedTlsConfig;
 use teaclave_types::EnclaveAttr;
 
@@ -39,6 +38,7 @@ use teaclave_types::EnclaveAttr;
 pub struct SgxTrustedTlsServerConfig {
     server_config: rustls::ServerConfig,
     attested_tls_config: Option<Arc<RwLock<AttestedTlsConfig>>>,
+    report_ca_cert: Option<Vec<u8>>,
     time: std::time::SystemTime,
     validity: std::time::Duration,
 }
@@ -53,6 +53,7 @@ impl Default for SgxTrustedTlsServerConfig {
         Self {
             server_config,
             attested_tls_config: None,
+            report_ca_cert: None,
             time,
             validity,
         }
@@ -90,15 +91,16 @@ impl SgxTrustedTlsServerConfig {
         mut self,
         accepted_enclave_attrs: Vec<EnclaveAttr>,
         root_ca: &[u8],
-        verifier: fn(&AttestationReport) -> bool,
+        policy: impl AttestationReportPolicy + 'static,
     ) -> Result<Self> {
         let verifier = Arc::new(AttestationReportVerifier::new(
             accepted_enclave_attrs,
             root_ca,
-            verifier,
+            policy,
         ));
 
         self.server_config.set_client_certificate_verifier(verifier);
+        self.report_ca_cert = Some(root_ca.to_vec());
         Ok(Self { ..self })
     }
 
@@ -106,6 +108,12 @@ impl SgxTrustedTlsServerConfig {
         Arc::new(self.server_config.clone())
     }
 
+    /// Root CA certificate of attestation reports of clients, if they are
+    /// attested.
+    pub fn report_ca_cert(&self) -> Option<&[u8]> {
+        self.report_ca_cert.as_deref()
+    }
+
     pub fn need_refresh(&self) -> bool {
         let current_time = SystemTime::now();
         let elapsed_time = current_time
@@ -197,12 +205,12 @@ impl SgxTrustedTlsClientConfig {
         mut self,
         accepted_enclave_attrs: Vec<EnclaveAttr>,
         root_ca: &[u8],
-        verifier: fn(&AttestationReport) -> bool,
+        policy: impl AttestationReportPolicy + 'static,
     ) -> Self {
         let verifier = Arc::new(AttestationReportVerifier::new(
             accepted_enclave_attrs,
             root_ca,
-            verifier,
+            policy,
         ));
         self.client_config
             .dangerous()
@@ -17,13 +17,113 @@
 
 use crate::channel::SgxTrustedTlsChannel;
 use crate::config::SgxTrustedTlsClientConfig;
-use anyhow::Result;
+use crate::pool::ConnectionPool;
+use crate::protocol::{Encoding, ProtobufMessage};
+use anyhow::{anyhow, Result};
 use serde::{Deserialize, Serialize};
 use std::prelude::v1::*;
+use std::sync::Arc;
+#[cfg(not(feature = "mesalock_sgx"))]
+use std::sync::Mutex;
+#[cfg(feature = "mesalock_sgx")]
+use std::sync::SgxMutex as Mutex;
+use std::time::Duration;
 
+/// Maximum number of idle connections kept in the pool of an endpoint
+const DEFAULT_POOL_SIZE: usize = 8;
+
+/// Policy of retrying connecting to an endpoint or sending a request.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RetryPolicy {
+    max_retries: u32,
+    initial_backoff: Duration,
+    max_backoff: Duration,
+    multiplier: u32,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        RetryPolicy::none()
+    }
+}
+
+impl RetryPolicy {
+    /// Never retry.
+    pub fn none() -> Self {
+        RetryPolicy::fixed(0, Duration::default())
+    }
+
+    /// Retry at most `max_retries` times, waiting `interval` before each
+    /// retry.
+    pub fn fixed(max_retries: u32, interval: Duration) -> Self {
+        Self {
+            max_retries,
+            initial_backoff: interval,
+            max_backoff: interval,
+            multiplier: 1,
+        }
+    }
+
+    /// Retry at most `max_retries` times, waiting `initial_backoff` before the
+    /// first retry and twice as long before each following one, up to
+    /// `max_backoff`.
+    pub fn exponential(max_retries: u32, initial_backoff: Duration, max_backoff: Duration) -> Self {
+        Self {
+            max_retries,
+            initial_backoff,
+            max_backoff,
+            multiplier: 2,
+        }
+    }
+
+    /// Time to wait before the retry after `retries` retries, or `None` if no
+    /// more retry is allowed.
+    pub(crate) fn backoff(&self, retries: u32) -> Option<Duration> {
+        if retries >= self.max_retries {
+            return None;
+        }
+        let backoff = self
+            .multiplier
+            .checked_pow(retries)
+            .and_then(|m| self.initial_backoff.checked_mul(m))
+            .map_or(self.max_backoff, |b| b.min(self.max_backoff));
+
+        Some(backoff)
+    }
+}
+
+/// Options of connections to an endpoint, shared by its channels.
+#[derive(Debug, Clone)]
+pub(crate) struct ChannelOptions {
+    pub(crate) connect_timeout: Option<Duration>,
+    pub(crate) request_timeout: Option<Duration>,
+    pub(crate) connect_retry: RetryPolicy,
+    pub(crate) request_retry: RetryPolicy,
+    pub(crate) pool_size: usize,
+}
+
+impl Default for ChannelOptions {
+    fn default() -> Self {
+        Self {
+            connect_timeout: None,
+            request_timeout: None,
+            connect_retry: RetryPolicy::none(),
+            request_retry: RetryPolicy::none(),
+            pool_size: DEFAULT_POOL_SIZE,
+        }
+    }
+}
+
+/// An endpoint to connect channels to. Channels connected to the same
+/// endpoint share a pool of connections: a channel takes a connection from
+/// the pool (or connects a new one), reconnects if the connection is broken,
+/// and returns the connection to the pool when dropped.
 pub struct Endpoint {
     url: String,
     config: SgxTrustedTlsClientConfig,
+    encoding: Encoding,
+    options: ChannelOptions,
+    pool: Mutex<Option<Arc<ConnectionPool>>>,
 }
 
 impl Endpoint {
@@ -32,21 +132,151 @@ impl Endpoint {
         Self {
             url: url.to_string(),
             config,
+            encoding: Encoding::default(),
+            options: ChannelOptions::default(),
+            pool: Mutex::new(None),
         }
     }
 
+    /// Connect a channel, with a connection taken from the pool of the
+    /// endpoint or newly connected following the connect retry policy.
     pub fn connect<U, V>(&self) -> Result<SgxTrustedTlsChannel<U, V>>
     where
-        U: Serialize + std::fmt::Debug,
-        V: for<'de> Deserialize<'de> + std::fmt::Debug,
+        U: Serialize + ProtobufMessage + std::fmt::Debug,
+        V: for<'de> Deserialize<'de> + ProtobufMessage + std::fmt::Debug,
     {
-        SgxTrustedTlsChannel::<U, V>::new(&self.url, &self.config)
+        let pool = {
+            let mut pool = self.pool.lock().map_err(|_| anyhow!("lock error"))?;
+            match &*pool {
+                Some(pool) => pool.clone(),
+                None => {
+                    let new_pool = Arc::new(ConnectionPool::new(
+                        &self.url,
+                        &self.config,
+                        self.options.clone(),
+                    )?);
+                    *pool = Some(new_pool.clone());
+                    new_pool
+                }
+            }
+        };
+        let channel = SgxTrustedTlsChannel::<U, V>::with_pool(pool)?;
+        Ok(channel.encoding(self.encoding))
     }
 
     pub fn config(self, config: SgxTrustedTlsClientConfig) -> Self {
+        let options = self.options.clone();
+        Self { config, ..self }.with_options(options)
+    }
+
+    /// Set the encoding of requests sent through channels to the endpoint.
+    pub fn encoding(self, encoding: Encoding) -> Self {
+        Self { encoding, ..self }
+    }
+
+    /// Set the timeout of connecting, no timeout by default.
+    pub fn connect_timeout(self, timeout: Duration) -> Self {
+        let options = ChannelOptions {
+            connect_timeout: Some(timeout),
+            ..self.options.clone()
+        };
+        self.with_options(options)
+    }
+
+    /// Set the timeout of each read and write of requests and responses, no
+    /// timeout by default. A request timed out fails with a connection error,
+    /// and its connection is closed.
+    pub fn request_timeout(self, timeout: Duration) -> Self {
+        let options = ChannelOptions {
+            request_timeout: Some(timeout),
+            ..self.options.clone()
+        };
+        self.with_options(options)
+    }
+
+    /// Set the policy of retrying connecting, never retry by default.
+    pub fn connect_retry(self, policy: RetryPolicy) -> Self {
+        let options = ChannelOptions {
+            connect_retry: policy,
+            ..self.options.clone()
+        };
+        self.with_options(options)
+    }
+
+    /// Set the policy of resending requests failed with connection errors on
+    /// a new connection, never retry by default. The peer may have handled a
+    /// request before the connection failed, so only enable it for
+    /// idempotent requests. Streaming requests are never retried.
+    pub fn request_retry(self, policy: RetryPolicy) -> Self {
+        let options = ChannelOptions {
+            request_retry: policy,
+            ..self.options.clone()
+        };
+        self.with_options(options)
+    }
+
+    /// Set the maximum number of idle connections kept in the pool.
+    pub fn pool_size(self, pool_size: usize) -> Self {
+        let options = ChannelOptions {
+            pool_size,
+            ..self.options.clone()
+        };
+        self.with_options(options)
+    }
+
+    // Connections in the pool are made with the previous options, so the pool
+    // is dropped and created again on the next connect.
+    fn with_options(self, options: ChannelOptions) -> Self {
         Self {
-            url: self.url,
-            config,
+            options,
+            pool: Mutex::new(None),
+            ..self
         }
     }
 }
+
+#[cfg(feature = "enclave_unit_test")]
+pub mod tests {
+    use super::*;
+
+    fn backoffs(policy: RetryPolicy) -> Vec<Option<Duration>> {
+        (0..=policy.max_retries)
+            .map(|r| policy.backoff(r))
+            .collect()
+    }
+
+    pub fn test_retry_policy_none() {
+        assert_eq!(RetryPolicy::default(), RetryPolicy::none());
+        assert_eq!(RetryPolicy::none().backoff(0), None);
+    }
+
+    pub fn test_retry_policy_fixed() {
+        let interval = Duration::from_secs(3);
+        let policy = RetryPolicy::fixed(3, interval);
+        assert_eq!(
+            backoffs(policy),
+            vec![Some(interval), Some(interval), Some(interval), None]
+        );
+    }
+
+    pub fn test_retry_policy_exponential() {
+        let ms = Duration::from_millis;
+        let policy = RetryPolicy::exponential(5, ms(100), ms(1_000));
+        assert_eq!(
+            backoffs(policy),
+            vec![
+                Some(ms(100)),
+                Some(ms(200)),
+                Some(ms(400)),
+                Some(ms(800)),
+                Some(ms(1_000)),
+                None
+            ]
+        );
+
+        // The backoff stays at the maximum instead of overflowing.
+        let policy = RetryPolicy::exponential(u32::max_value(), ms(100), ms(1_000));
+        assert_eq!(policy.backoff(64), Some(ms(1_000)));
+        assert_eq!(policy.backoff(u32::max_value() - 1), Some(ms(1_000)));
+    }
+}
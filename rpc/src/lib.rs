@@ -32,15 +32,43 @@ where
         &self,
         request: Request<V>,
     ) -> std::result::Result<U, TeaclaveServiceResponseError>;
+
+    /// Handle a request with the stream of the connection. Only services with
+    /// streaming methods need to read or write the stream.
+    fn handle_stream_request(
+        &self,
+        request: Request<V>,
+        _stream: &mut stream::Stream,
+    ) -> std::result::Result<U, TeaclaveServiceResponseError> {
+        self.handle_request(request)
+    }
 }
 
 pub mod channel;
 pub mod config;
 pub mod endpoint;
-mod protocol;
+mod pool;
+pub mod protocol;
 mod request;
-pub use request::{IntoRequest, Request};
+pub use request::{IntoRequest, PeerInfo, Request};
 pub use teaclave_rpc_proc_macro::into_request;
 pub mod server;
+pub mod stream;
 mod transport;
-mod utils;
+pub mod utils;
+
+#[cfg(feature = "enclave_unit_test")]
+pub mod tests {
+    use super::*;
+    use teaclave_test_utils::*;
+
+    pub fn run_tests() -> bool {
+        run_tests!(
+            endpoint::tests::test_retry_policy_none,
+            endpoint::tests::test_retry_policy_fixed,
+            endpoint::tests::test_retry_policy_exponential,
+            request::tests::test_peer_info,
+            request::tests::test_peer_info_not_serialized,
+        )
+    }
+}
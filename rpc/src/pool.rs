@@ -0,0 +1,141 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License.
+
+use crate::config::SgxTrustedTlsClientConfig;
+use crate::endpoint::ChannelOptions;
+use crate::transport::SgxTrustedTlsTransport;
+use anyhow::{anyhow, Result};
+use http::Uri;
+use log::debug;
+use std::net::{TcpStream, ToSocketAddrs};
+use std::prelude::v1::*;
+use std::sync::Arc;
+#[cfg(not(feature = "mesalock_sgx"))]
+use std::sync::Mutex;
+#[cfg(feature = "mesalock_sgx")]
+use std::sync::SgxMutex as Mutex;
+use std::time::Duration;
+
+pub(crate) type Connection = SgxTrustedTlsTransport<rustls::ClientSession>;
+
+/// Idle connections to an endpoint, and how to connect new ones.
+pub(crate) struct ConnectionPool {
+    address: String,
+    hostname: String,
+    client_config: Arc<rustls::ClientConfig>,
+    options: ChannelOptions,
+    idle: Mutex<Vec<Connection>>,
+}
+
+impl ConnectionPool {
+    pub(crate) fn new(
+        address: &str,
+        client_config: &SgxTrustedTlsClientConfig,
+        options: ChannelOptions,
+    ) -> Result<Self> {
+        let uri = address.parse::<Uri>()?;
+        let hostname = uri.host().ok_or_else(|| anyhow!("Invalid hostname."))?;
+        webpki::DNSNameRef::try_from_ascii_str(hostname)?;
+
+        Ok(Self {
+            address: address.to_string(),
+            hostname: hostname.to_string(),
+            client_config: Arc::new(client_config.client_config.clone()),
+            options,
+            idle: Mutex::new(Vec::new()),
+        })
+    }
+
+    pub(crate) fn options(&self) -> &ChannelOptions {
+        &self.options
+    }
+
+    /// Take an idle connection still open, or connect a new one.
+    pub(crate) fn get(&self) -> Result<Connection> {
+        loop {
+            let connection = match self.idle.lock() {
+                Ok(mut idle) => idle.pop(),
+                Err(_) => None,
+            };
+            match connection {
+                Some(mut connection) => {
+                    if !connection.is_closed() {
+                        return Ok(connection);
+                    }
+                    debug!("Drop closed connection to {}", self.address);
+                }
+                None => return self.connect(),
+            }
+        }
+    }
+
+    /// Return a connection to the pool, or close it if the pool is full.
+    pub(crate) fn put(&self, connection: Connection) {
+        if let Ok(mut idle) = self.idle.lock() {
+            if idle.len() < self.options.pool_size {
+                idle.push(connection);
+            }
+        }
+    }
+
+    fn connect(&self) -> Result<Connection> {
+        let mut retries = 0;
+        loop {
+            match self.connect_once() {
+                Ok(connection) => return Ok(connection),
+                Err(e) => match self.options.connect_retry.backoff(retries) {
+                    Some(backoff) => {
+                        debug!(
+                            "Failed to connect to {}: {:?}, retry {}",
+                            self.address, e, retries
+                        );
+                        std::thread::sleep(backoff);
+                        retries += 1;
+                    }
+                    None => return Err(e.context(format!("failed to connect to {}", self.address))),
+                },
+            }
+        }
+    }
+
+    fn connect_once(&self) -> Result<Connection> {
+        let stream = match self.options.connect_timeout {
+            Some(timeout) => connect_timeout(&self.address, timeout)?,
+            None => TcpStream::connect(self.address.as_str())?,
+        };
+        stream.set_read_timeout(self.options.request_timeout)?;
+        stream.set_write_timeout(self.options.request_timeout)?;
+        let hostname = webpki::DNSNameRef::try_from_ascii_str(&self.hostname)?;
+        let session = rustls::ClientSession::new(&self.client_config, hostname);
+        let tls_stream = rustls::StreamOwned::new(session, stream);
+
+        Ok(SgxTrustedTlsTransport::new(tls_stream))
+    }
+}
+
+/// Connect to the first resolved address accepting the connection in time.
+fn connect_timeout(address: &str, timeout: Duration) -> Result<TcpStream> {
+    let mut error = anyhow!("Cannot resolve {}", address);
+    for addr in address.to_socket_addrs()? {
+        match TcpStream::connect_timeout(&addr, timeout) {
+            Ok(stream) => return Ok(stream),
+            Err(e) => error = e.into(),
+        }
+    }
+
+    Err(error)
+}
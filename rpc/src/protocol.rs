@@ -15,10 +15,11 @@
 // specific language governing permissions and limitations
 // under the License.
 
+use crate::Request;
 use log::debug;
 use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
 use std::io;
-use std::mem::transmute;
 use std::prelude::v1::*;
 use std::vec::Vec;
 use teaclave_types::TeaclaveServiceResponseError;
@@ -50,34 +51,273 @@ impl From<ProtocolError> for TeaclaveServiceResponseError {
     }
 }
 
-pub(crate) struct JsonProtocol<'a, T>
+/// Encoding of messages in a frame. The encoding is marked in the most
+/// significant byte of the frame header, which is always zero for JSON, so
+/// peers only speaking JSON are still compatible.
+///
+/// The frame header is 8 bytes in big endian: the encoding, the kind of the
+/// frame and the length of the following payload in the rest 6 bytes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Encoding {
+    Json,
+    Protobuf,
+}
+
+impl Default for Encoding {
+    fn default() -> Self {
+        Encoding::Json
+    }
+}
+
+impl Encoding {
+    fn marker(self) -> u8 {
+        match self {
+            Encoding::Json => 0,
+            Encoding::Protobuf => 1,
+        }
+    }
+
+    fn from_marker(marker: u8) -> Option<Self> {
+        match marker {
+            0 => Some(Encoding::Json),
+            1 => Some(Encoding::Protobuf),
+            _ => None,
+        }
+    }
+}
+
+/// Messages which can be encoded in protobuf, i.e., request and response
+/// enums generated from the `.proto` definitions of services.
+pub trait ProtobufMessage: Sized {
+    fn encode_protobuf(&self, buf: &mut Vec<u8>) -> anyhow::Result<()>;
+    fn decode_protobuf(buf: &[u8]) -> anyhow::Result<Self>;
+}
+
+/// Messages written in frames of the protocol, i.e., requests on clients and
+/// results of responses on servers.
+pub trait EncodeFrame {
+    fn encode_frame(&self, encoding: Encoding) -> Result<Vec<u8>, ProtocolError>;
+}
+
+/// Messages read from frames of the protocol, i.e., requests on servers and
+/// results of responses on clients.
+pub trait DecodeFrame: Sized {
+    fn decode_frame(buf: &[u8], encoding: Encoding) -> Result<Self, ProtocolError>;
+}
+
+#[derive(Clone, PartialEq, prost::Message)]
+struct RequestEnvelope {
+    #[prost(map = "string, string", tag = "1")]
+    metadata: HashMap<String, String>,
+    #[prost(bytes, tag = "2")]
+    message: Vec<u8>,
+}
+
+#[derive(Clone, PartialEq, prost::Message)]
+struct ResponseEnvelope {
+    #[prost(bytes, optional, tag = "1")]
+    ok: Option<Vec<u8>>,
+    #[prost(uint32, tag = "2")]
+    error_kind: u32,
+    #[prost(string, tag = "3")]
+    error_message: String,
+}
+
+fn encode_envelope<M: prost::Message>(envelope: M) -> Result<Vec<u8>, ProtocolError> {
+    let mut buf = Vec::with_capacity(envelope.encoded_len());
+    envelope.encode(&mut buf).map_err(anyhow::Error::new)?;
+    Ok(buf)
+}
+
+fn decode_envelope<M: prost::Message + Default>(buf: &[u8]) -> Result<M, ProtocolError> {
+    Ok(M::decode(buf).map_err(anyhow::Error::new)?)
+}
+
+impl<T> EncodeFrame for Request<T>
+where
+    T: Serialize + ProtobufMessage,
+{
+    fn encode_frame(&self, encoding: Encoding) -> Result<Vec<u8>, ProtocolError> {
+        match encoding {
+            Encoding::Json => Ok(serde_json::to_vec(self)?),
+            Encoding::Protobuf => {
+                let mut message = Vec::new();
+                self.message.encode_protobuf(&mut message)?;
+                encode_envelope(RequestEnvelope {
+                    metadata: self.metadata.clone(),
+                    message,
+                })
+            }
+        }
+    }
+}
+
+impl<T> DecodeFrame for Request<T>
+where
+    T: for<'de> Deserialize<'de> + ProtobufMessage,
+{
+    fn decode_frame(buf: &[u8], encoding: Encoding) -> Result<Self, ProtocolError> {
+        match encoding {
+            Encoding::Json => Ok(serde_json::from_slice(buf)?),
+            Encoding::Protobuf => {
+                let envelope: RequestEnvelope = decode_envelope(buf)?;
+                let mut request = Request::new(T::decode_protobuf(&envelope.message)?);
+                request.metadata = envelope.metadata;
+                Ok(request)
+            }
+        }
+    }
+}
+
+impl<T> EncodeFrame for ProtocolResult<T, TeaclaveServiceResponseError>
+where
+    T: Serialize + ProtobufMessage,
+{
+    fn encode_frame(&self, encoding: Encoding) -> Result<Vec<u8>, ProtocolError> {
+        if encoding == Encoding::Json {
+            return Ok(serde_json::to_vec(self)?);
+        }
+
+        let envelope = match self {
+            ProtocolResult::Ok(message) => {
+                let mut buf = Vec::new();
+                message.encode_protobuf(&mut buf)?;
+                ResponseEnvelope {
+                    ok: Some(buf),
+                    ..Default::default()
+                }
+            }
+            ProtocolResult::Err(e) => {
+                let (error_kind, error_message) = match e {
+                    TeaclaveServiceResponseError::RequestError(m) => (0, m),
+                    TeaclaveServiceResponseError::ConnectionError(m) => (1, m),
+                    TeaclaveServiceResponseError::InternalError(m) => (2, m),
+                    TeaclaveServiceResponseError::NotFound(m) => (3, m),
+                };
+                ResponseEnvelope {
+                    ok: None,
+                    error_kind,
+                    error_message: error_message.to_string(),
+                }
+            }
+        };
+        encode_envelope(envelope)
+    }
+}
+
+impl<T> DecodeFrame for ProtocolResult<T, TeaclaveServiceResponseError>
+where
+    T: for<'de> Deserialize<'de> + ProtobufMessage,
+{
+    fn decode_frame(buf: &[u8], encoding: Encoding) -> Result<Self, ProtocolError> {
+        if encoding == Encoding::Json {
+            return Ok(serde_json::from_slice(buf)?);
+        }
+
+        let envelope: ResponseEnvelope = decode_envelope(buf)?;
+        if let Some(ok) = envelope.ok {
+            return Ok(ProtocolResult::Ok(T::decode_protobuf(&ok)?));
+        }
+        let message = envelope.error_message;
+        let error = match envelope.error_kind {
+            0 => TeaclaveServiceResponseError::RequestError(message),
+            1 => TeaclaveServiceResponseError::ConnectionError(message),
+            2 => TeaclaveServiceResponseError::InternalError(message),
+            3 => TeaclaveServiceResponseError::NotFound(message),
+            kind => return Err(anyhow::anyhow!("Unknown error kind {}", kind).into()),
+        };
+        Ok(ProtocolResult::Err(error))
+    }
+}
+
+/// Log payloads of messages. Payloads may contain secrets (e.g., credentials
+/// and keys of files), so only the length is logged unless the `log_payload`
+/// feature is enabled.
+fn log_frame(direction: &str, encoding: Encoding, buf: &[u8]) {
+    if cfg!(feature = "log_payload") && encoding == Encoding::Json {
+        debug!(
+            "{}: {}",
+            direction,
+            std::string::String::from_utf8_lossy(buf)
+        );
+    } else {
+        debug!("{}: {} bytes ({:?})", direction, buf.len(), encoding);
+    }
+}
+
+/// Kinds of frames, marked in the second most significant byte of the frame
+/// header, which is always zero for messages.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum FrameKind {
+    /// A request or a response.
+    Message,
+    /// A chunk of the stream of a streaming request.
+    Chunk,
+    /// The end of the stream.
+    End,
+    /// Number of chunks the receiver of a stream grants the sender to send.
+    WindowUpdate,
+}
+
+impl FrameKind {
+    fn marker(self) -> u8 {
+        match self {
+            FrameKind::Message => 0,
+            FrameKind::Chunk => 1,
+            FrameKind::End => 2,
+            FrameKind::WindowUpdate => 3,
+        }
+    }
+
+    fn from_marker(marker: u8) -> Option<Self> {
+        match marker {
+            0 => Some(FrameKind::Message),
+            1 => Some(FrameKind::Chunk),
+            2 => Some(FrameKind::End),
+            3 => Some(FrameKind::WindowUpdate),
+            _ => None,
+        }
+    }
+}
+
+pub struct Protocol<'a, T>
 where
     T: io::Read + io::Write,
 {
     pub transport: &'a mut T,
     max_frame_len: u64,
+    encoding: Encoding,
 }
 
-impl<'a, T> JsonProtocol<'a, T>
+impl<'a, T> Protocol<'a, T>
 where
     T: io::Read + io::Write,
 {
-    pub fn new(transport: &'a mut T) -> JsonProtocol<'a, T> {
+    /// Create a protocol writing messages in `encoding`. The encoding then
+    /// follows the peer, i.e., it is updated to the encoding of each message
+    /// read, so servers respond in the encoding of requests.
+    pub fn new(transport: &'a mut T, encoding: Encoding) -> Protocol<'a, T> {
         Self {
             transport,
             // Default max frame length is 8MB
             max_frame_len: 8 * 1_024 * 1_024,
+            encoding,
         }
     }
 
-    pub fn read_message<V>(&mut self) -> std::result::Result<V, ProtocolError>
-    where
-        V: for<'de> Deserialize<'de> + std::fmt::Debug,
-    {
+    pub(crate) fn read_frame(
+        &mut self,
+    ) -> std::result::Result<(FrameKind, Vec<u8>), ProtocolError> {
         let mut header = [0u8; 8];
 
         self.transport.read_exact(&mut header)?;
-        let buf_len = u64::from_be(unsafe { transmute::<[u8; 8], u64>(header) });
+        let encoding = Encoding::from_marker(header[0])
+            .ok_or_else(|| anyhow::anyhow!("Unknown encoding {}", header[0]))?;
+        let kind = FrameKind::from_marker(header[1])
+            .ok_or_else(|| anyhow::anyhow!("Unknown frame kind {}", header[1]))?;
+        header[0] = 0;
+        header[1] = 0;
+        let buf_len = u64::from_be_bytes(header);
 
         if buf_len > self.max_frame_len {
             return Err(ProtocolError::Other(anyhow::anyhow!(
@@ -88,53 +328,93 @@ where
         let mut recv_buf: Vec<u8> = vec![0u8; buf_len as usize];
         self.transport.read_exact(&mut recv_buf)?;
 
-        debug!("Recv: {}", std::string::String::from_utf8_lossy(&recv_buf));
-        let r: V = serde_json::from_slice(&recv_buf)?;
+        if kind == FrameKind::Message {
+            log_frame("Recv", encoding, &recv_buf);
+            self.encoding = encoding;
+        }
+
+        Ok((kind, recv_buf))
+    }
+
+    pub(crate) fn write_frame(
+        &mut self,
+        kind: FrameKind,
+        buf: &[u8],
+    ) -> std::result::Result<(), ProtocolError> {
+        let buf_len = buf.len() as u64;
+        if buf_len > self.max_frame_len {
+            return Err(ProtocolError::Other(anyhow::anyhow!(
+                "Exceed max frame length"
+            )));
+        }
+        let mut header = buf_len.to_be_bytes();
+        header[0] = self.encoding.marker();
+        header[1] = kind.marker();
+
+        self.transport.write_all(&header)?;
+        self.transport.write_all(buf)?;
+        self.transport.flush()?;
 
-        Ok(r)
+        Ok(())
     }
 
-    pub fn write_message<U>(&mut self, message: U) -> std::result::Result<(), ProtocolError>
+    /// Read the next message, discarding frames of streams which are not read,
+    /// e.g., chunks in flight when a stream is closed. No more chunks are
+    /// granted, since the sender stops at the response in place of the stream.
+    pub fn read_message<V>(&mut self) -> std::result::Result<V, ProtocolError>
     where
-        U: Serialize + std::fmt::Debug,
+        V: DecodeFrame,
     {
-        let send_buf = serde_json::to_vec(&message)?;
+        loop {
+            let (kind, buf) = self.read_frame()?;
+            match kind {
+                FrameKind::Message => return self.decode_message(&buf),
+                FrameKind::Chunk | FrameKind::End | FrameKind::WindowUpdate => (),
+            }
+        }
+    }
 
-        debug!("Send: {}", std::string::String::from_utf8_lossy(&send_buf));
+    pub(crate) fn decode_message<V>(&self, buf: &[u8]) -> std::result::Result<V, ProtocolError>
+    where
+        V: DecodeFrame,
+    {
+        V::decode_frame(buf, self.encoding)
+    }
 
-        let buf_len = send_buf.len() as u64;
-        let header = unsafe { transmute::<u64, [u8; 8]>(buf_len.to_be()) };
+    pub fn write_message<U>(&mut self, message: U) -> std::result::Result<(), ProtocolError>
+    where
+        U: EncodeFrame,
+    {
+        let send_buf = message.encode_frame(self.encoding)?;
 
-        self.transport.write(&header)?;
-        self.transport.write_all(&send_buf)?;
-        self.transport.flush()?;
+        log_frame("Send", self.encoding, &send_buf);
 
-        Ok(())
+        self.write_frame(FrameKind::Message, &send_buf)
     }
 }
 
 #[derive(Debug, Serialize, Deserialize)]
 #[serde(tag = "result")]
 #[serde(rename_all = "snake_case")]
-pub enum JsonProtocolResult<T, E> {
+pub enum ProtocolResult<T, E> {
     Ok(T),
     Err(E),
 }
 
-impl<T, E> From<std::result::Result<T, E>> for JsonProtocolResult<T, E> {
+impl<T, E> From<std::result::Result<T, E>> for ProtocolResult<T, E> {
     fn from(result: std::result::Result<T, E>) -> Self {
         match result {
-            Ok(t) => JsonProtocolResult::Ok(t),
-            Err(e) => JsonProtocolResult::Err(e),
+            Ok(t) => ProtocolResult::Ok(t),
+            Err(e) => ProtocolResult::Err(e),
         }
     }
 }
 
-impl<T, E> From<JsonProtocolResult<T, E>> for std::result::Result<T, E> {
-    fn from(result: JsonProtocolResult<T, E>) -> Self {
+impl<T, E> From<ProtocolResult<T, E>> for std::result::Result<T, E> {
+    fn from(result: ProtocolResult<T, E>) -> Self {
         match result {
-            JsonProtocolResult::Ok(t) => Ok(t),
-            JsonProtocolResult::Err(e) => Err(e),
+            ProtocolResult::Ok(t) => Ok(t),
+            ProtocolResult::Err(e) => Err(e),
         }
     }
 }
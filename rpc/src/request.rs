@@ -18,6 +18,22 @@
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 use std::prelude::v1::*;
+use std::sync::Arc;
+use teaclave_attestation::report::AttestationReport;
+
+/// Information on the peer of the connection a request is received from,
+/// which is set by the server and never sent over the wire.
+#[derive(Debug)]
+pub struct PeerInfo {
+    /// Attestation report of the peer verified in the handshake. `None` if the
+    /// peer is not attested, e.g., clients of API endpoints, or enclaves in
+    /// simulation mode.
+    pub attestation_report: Option<AttestationReport>,
+    /// Keying material exported from the TLS session (RFC 5705). Both ends of
+    /// the connection derive the same secret, so application-level messages
+    /// can be bound to this connection. Empty if it cannot be exported.
+    pub tls_exporter_secret: Vec<u8>,
+}
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct Request<T> {
@@ -26,6 +42,8 @@ pub struct Request<T> {
     pub metadata: HashMap<String, String>,
     #[serde(flatten)]
     pub message: T,
+    #[serde(skip)]
+    pub peer: Option<Arc<PeerInfo>>,
 }
 
 impl<T> Request<T> {
@@ -33,6 +51,7 @@ impl<T> Request<T> {
         Request {
             metadata: HashMap::<String, String>::default(),
             message,
+            peer: None,
         }
     }
 
@@ -45,6 +64,7 @@ impl<T> Request<T> {
         Request {
             metadata: self.metadata,
             message,
+            peer: self.peer,
         }
     }
 
@@ -55,6 +75,11 @@ impl<T> Request<T> {
     pub fn metadata_mut(&mut self) -> &mut HashMap<String, String> {
         &mut self.metadata
     }
+
+    /// Information on the peer, only available on the server.
+    pub fn peer(&self) -> Option<&PeerInfo> {
+        self.peer.as_deref()
+    }
 }
 
 pub trait IntoRequest<T> {
@@ -66,3 +91,43 @@ impl<T> IntoRequest<T> for T {
         Request::new(self)
     }
 }
+
+#[cfg(feature = "enclave_unit_test")]
+pub mod tests {
+    use super::*;
+
+    #[derive(Debug, Serialize, Deserialize, PartialEq)]
+    struct Message {
+        value: u32,
+    }
+
+    fn peer_info() -> Arc<PeerInfo> {
+        Arc::new(PeerInfo {
+            attestation_report: None,
+            tls_exporter_secret: vec![1; 32],
+        })
+    }
+
+    pub fn test_peer_info() {
+        let mut request = Request::new(Message { value: 1 });
+        assert!(request.peer().is_none());
+
+        request.peer = Some(peer_info());
+        let request = request.map(|m| Message { value: m.value + 1 });
+        assert_eq!(request.message, Message { value: 2 });
+        assert_eq!(request.peer().unwrap().tls_exporter_secret, vec![1; 32]);
+    }
+
+    pub fn test_peer_info_not_serialized() {
+        let mut request = Request::new(Message { value: 1 });
+        request.peer = Some(peer_info());
+
+        let json = serde_json::to_string(&request).unwrap();
+        assert_eq!(json, r#"{"value":1}"#);
+
+        // A peer cannot claim to be attested in the message.
+        let json = r#"{"value":1,"peer":{"tls_exporter_secret":[1]}}"#;
+        let request: Request<Message> = serde_json::from_str(json).unwrap();
+        assert!(request.peer().is_none());
+    }
+}
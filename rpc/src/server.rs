@@ -16,6 +16,7 @@
 // under the License.
 
 use crate::config::SgxTrustedTlsServerConfig;
+use crate::protocol::ProtobufMessage;
 use crate::transport::{ServerTransport, SgxTrustedTlsTransport};
 use crate::TeaclaveService;
 use anyhow::Result;
@@ -24,8 +25,8 @@ use serde::{Deserialize, Serialize};
 
 pub struct SgxTrustedTlsServer<U, V>
 where
-    U: Serialize + std::fmt::Debug,
-    V: for<'de> Deserialize<'de> + std::fmt::Debug,
+    U: Serialize + ProtobufMessage + std::fmt::Debug,
+    V: for<'de> Deserialize<'de> + ProtobufMessage + std::fmt::Debug,
 {
     addr: std::net::SocketAddr,
     tls_config: SgxTrustedTlsServerConfig,
@@ -36,8 +37,8 @@ where
 
 impl<U, V> SgxTrustedTlsServer<U, V>
 where
-    U: Serialize + std::fmt::Debug,
-    V: for<'de> Deserialize<'de> + std::fmt::Debug,
+    U: Serialize + ProtobufMessage + std::fmt::Debug,
+    V: for<'de> Deserialize<'de> + ProtobufMessage + std::fmt::Debug,
 {
     pub fn new(
         addr: std::net::SocketAddr,
@@ -90,7 +91,9 @@ where
                     }
                     let session = rustls::ServerSession::new(&tls_config_ref);
                     let tls_stream = rustls::StreamOwned::new(session, stream);
-                    let mut transport = SgxTrustedTlsTransport::new(tls_stream);
+                    let report_ca_cert = self.tls_config.report_ca_cert().map(<[u8]>::to_vec);
+                    let mut transport =
+                        SgxTrustedTlsTransport::new(tls_stream).report_ca_cert(report_ca_cert);
                     let service = service.clone();
                     pool.execute(move || match transport.serve(service) {
                         Ok(_) => (),
@@ -0,0 +1,264 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License.
+
+//! Streams of streaming requests. A streaming request is a request followed
+//! by a stream of chunks from the client (client streaming), or a response
+//! preceded by a stream of chunks from the server (server streaming). Streams
+//! are sent in chunk frames and terminated by an end frame, with credit-based
+//! flow control: the sender can only send chunks granted by the receiver with
+//! window update frames, besides the initial window.
+
+use crate::protocol::{FrameKind, Protocol, ProtocolError};
+use std::cmp;
+use std::convert::TryInto;
+use std::io;
+use std::prelude::v1::*;
+
+/// Max size of chunks in streams.
+pub const CHUNK_SIZE: usize = 512 * 1_024;
+/// Number of chunks a sender can send before it is granted more, i.e., at
+/// most 4MB of a stream is in flight.
+const INITIAL_WINDOW: u32 = 8;
+
+pub(crate) trait FrameIo {
+    fn read_frame(&mut self) -> Result<(FrameKind, Vec<u8>), ProtocolError>;
+    fn write_frame(&mut self, kind: FrameKind, buf: &[u8]) -> Result<(), ProtocolError>;
+}
+
+impl<'a, T> FrameIo for Protocol<'a, T>
+where
+    T: io::Read + io::Write,
+{
+    fn read_frame(&mut self) -> Result<(FrameKind, Vec<u8>), ProtocolError> {
+        Protocol::read_frame(self)
+    }
+
+    fn write_frame(&mut self, kind: FrameKind, buf: &[u8]) -> Result<(), ProtocolError> {
+        Protocol::write_frame(self, kind, buf)
+    }
+}
+
+fn to_io_error(error: ProtocolError) -> io::Error {
+    match error {
+        ProtocolError::IoError(e) => e,
+        e => io::Error::new(io::ErrorKind::InvalidData, e.to_string()),
+    }
+}
+
+/// Stream of the connection a streaming request is received from.
+pub struct Stream<'a> {
+    frames: &'a mut dyn FrameIo,
+}
+
+impl<'a> Stream<'a> {
+    pub(crate) fn new(frames: &'a mut dyn FrameIo) -> Self {
+        Self { frames }
+    }
+
+    /// Read the stream sent by the client after the request.
+    pub fn reader(&mut self) -> StreamReader<'_> {
+        StreamReader::new(self.frames)
+    }
+
+    /// Write a stream to the client before the response.
+    pub fn writer(&mut self) -> StreamWriter<'_> {
+        StreamWriter::new(self.frames)
+    }
+}
+
+/// Reader of a stream, which grants the sender a chunk for each chunk read.
+pub struct StreamReader<'a> {
+    frames: &'a mut dyn FrameIo,
+    chunk: Vec<u8>,
+    pos: usize,
+    finished: bool,
+    message: Option<Vec<u8>>,
+}
+
+impl<'a> StreamReader<'a> {
+    pub(crate) fn new(frames: &'a mut dyn FrameIo) -> Self {
+        Self {
+            frames,
+            chunk: Vec::new(),
+            pos: 0,
+            finished: false,
+            message: None,
+        }
+    }
+
+    /// Read the next chunk, returning false at the end of the stream.
+    fn next_chunk(&mut self) -> Result<bool, ProtocolError> {
+        while !self.finished {
+            let (kind, buf) = self.frames.read_frame()?;
+            match kind {
+                FrameKind::Chunk => {
+                    self.frames
+                        .write_frame(FrameKind::WindowUpdate, &1u32.to_be_bytes())?;
+                    self.chunk = buf;
+                    self.pos = 0;
+                    return Ok(true);
+                }
+                FrameKind::End => self.finished = true,
+                // A response sent without the stream, e.g., for an invalid
+                // request, also ends the stream.
+                FrameKind::Message => {
+                    self.message = Some(buf);
+                    self.finished = true;
+                }
+                FrameKind::WindowUpdate => (),
+            }
+        }
+
+        Ok(false)
+    }
+
+    /// Discard the rest of the stream, so the next message can be read.
+    pub fn drain(&mut self) -> Result<(), ProtocolError> {
+        while self.next_chunk()? {}
+        Ok(())
+    }
+
+    /// Stop reading the stream without granting the sender more chunks, e.g.,
+    /// to respond with an error right away. The sender stops once it receives
+    /// the response, and chunks already in flight are discarded when the next
+    /// message is read.
+    pub fn close(&mut self) {
+        self.finished = true;
+    }
+
+    /// The message received in place of the rest of the stream, if any.
+    pub(crate) fn take_message(&mut self) -> Option<Vec<u8>> {
+        self.message.take()
+    }
+}
+
+impl<'a> io::Read for StreamReader<'a> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        while self.pos == self.chunk.len() {
+            if !self.next_chunk().map_err(to_io_error)? {
+                return Ok(0);
+            }
+        }
+        let n = cmp::min(buf.len(), self.chunk.len() - self.pos);
+        buf[..n].copy_from_slice(&self.chunk[self.pos..self.pos + n]);
+        self.pos += n;
+
+        Ok(n)
+    }
+}
+
+/// Writer of a stream, which buffers written data in chunks and waits for the
+/// receiver to grant chunks to send.
+pub struct StreamWriter<'a> {
+    frames: &'a mut dyn FrameIo,
+    chunk: Vec<u8>,
+    credits: u32,
+    finished: bool,
+    message: Option<Vec<u8>>,
+}
+
+impl<'a> StreamWriter<'a> {
+    pub(crate) fn new(frames: &'a mut dyn FrameIo) -> Self {
+        Self {
+            frames,
+            chunk: Vec::with_capacity(CHUNK_SIZE),
+            credits: INITIAL_WINDOW,
+            finished: false,
+            message: None,
+        }
+    }
+
+    fn send_chunk(&mut self) -> Result<(), ProtocolError> {
+        while self.credits == 0 {
+            let (kind, buf) = self.frames.read_frame()?;
+            match kind {
+                FrameKind::WindowUpdate => {
+                    let credits: [u8; 4] = buf
+                        .as_slice()
+                        .try_into()
+                        .map_err(|_| anyhow::anyhow!("Invalid window update"))?;
+                    self.credits = self.credits.saturating_add(u32::from_be_bytes(credits));
+                }
+                // The receiver responds before reading the stream, e.g., for
+                // an invalid request, so the rest of the stream is not sent.
+                FrameKind::Message => {
+                    self.message = Some(buf);
+                    self.finished = true;
+                    return Err(anyhow::anyhow!("Stream closed by the receiver").into());
+                }
+                FrameKind::Chunk | FrameKind::End => (),
+            }
+        }
+        self.frames.write_frame(FrameKind::Chunk, &self.chunk)?;
+        self.credits -= 1;
+        self.chunk.clear();
+
+        Ok(())
+    }
+
+    /// Send the rest of the buffered data and the end of the stream.
+    pub fn finish(&mut self) -> Result<(), ProtocolError> {
+        if self.finished {
+            return Ok(());
+        }
+        if !self.chunk.is_empty() {
+            self.send_chunk()?;
+        }
+        self.frames.write_frame(FrameKind::End, &[])?;
+        self.finished = true;
+
+        Ok(())
+    }
+
+    /// End the stream without sending the buffered data, e.g., when the data
+    /// cannot be completed because of an error.
+    pub fn close(&mut self) -> Result<(), ProtocolError> {
+        self.chunk.clear();
+        self.finish()
+    }
+
+    /// The message received in place of window updates, if any.
+    pub(crate) fn take_message(&mut self) -> Option<Vec<u8>> {
+        self.message.take()
+    }
+}
+
+impl<'a> io::Write for StreamWriter<'a> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        if self.finished {
+            return Err(io::Error::new(
+                io::ErrorKind::BrokenPipe,
+                "Stream is finished",
+            ));
+        }
+        let n = cmp::min(buf.len(), CHUNK_SIZE - self.chunk.len());
+        self.chunk.extend_from_slice(&buf[..n]);
+        if self.chunk.len() == CHUNK_SIZE {
+            self.send_chunk().map_err(to_io_error)?;
+        }
+
+        Ok(n)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        if !self.finished && !self.chunk.is_empty() {
+            self.send_chunk().map_err(to_io_error)?;
+        }
+
+        Ok(())
+    }
+}
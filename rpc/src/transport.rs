@@ -15,13 +15,22 @@
 // specific language governing permissions and limitations
 // under the License.
 
-use crate::protocol;
+use crate::protocol::{self, Encoding, ProtobufMessage};
+use crate::request::PeerInfo;
+use crate::stream::{Stream, StreamReader, StreamWriter};
 use crate::Request;
 use crate::TeaclaveService;
-use anyhow::Result;
+use anyhow::{anyhow, Result};
 use log::debug;
 use serde::{Deserialize, Serialize};
+use std::io;
 use std::prelude::v1::*;
+use std::sync::Arc;
+use teaclave_attestation::report::AttestationReport;
+
+/// Label of the keying material exported from TLS sessions for `PeerInfo`
+const TLS_EXPORTER_LABEL: &[u8] = b"EXPORTER-teaclave-rpc";
+const TLS_EXPORTER_SECRET_LEN: usize = 32;
 
 pub(crate) trait ClientTransport {
     fn send<U, V>(
@@ -29,15 +38,28 @@ pub(crate) trait ClientTransport {
         request: Request<U>,
     ) -> teaclave_types::TeaclaveServiceResponseResult<V>
     where
-        U: Serialize + std::fmt::Debug,
-        V: for<'de> Deserialize<'de> + std::fmt::Debug;
+        U: Serialize + ProtobufMessage + std::fmt::Debug,
+        V: for<'de> Deserialize<'de> + ProtobufMessage + std::fmt::Debug;
+
+    /// Send a streaming request, with the stream to send after the request
+    /// read from `upload`, or the stream received before the response written
+    /// to `download`.
+    fn send_stream<U, V>(
+        &mut self,
+        request: Request<U>,
+        upload: Option<&mut dyn io::Read>,
+        download: Option<&mut dyn io::Write>,
+    ) -> teaclave_types::TeaclaveServiceResponseResult<V>
+    where
+        U: Serialize + ProtobufMessage + std::fmt::Debug,
+        V: for<'de> Deserialize<'de> + ProtobufMessage + std::fmt::Debug;
 }
 
 pub(crate) trait ServerTransport {
     fn serve<U, V, X>(&mut self, service: X) -> Result<()>
     where
-        U: Serialize + std::fmt::Debug,
-        V: for<'de> Deserialize<'de> + std::fmt::Debug,
+        U: Serialize + ProtobufMessage + std::fmt::Debug,
+        V: for<'de> Deserialize<'de> + ProtobufMessage + std::fmt::Debug,
         X: TeaclaveService<V, U>;
 }
 pub(crate) struct SgxTrustedTlsTransport<S>
@@ -45,6 +67,8 @@ where
     S: rustls::Session,
 {
     stream: rustls::StreamOwned<S, std::net::TcpStream>,
+    report_ca_cert: Option<Vec<u8>>,
+    encoding: Encoding,
 }
 
 impl<S> SgxTrustedTlsTransport<S>
@@ -52,7 +76,105 @@ where
     S: rustls::Session,
 {
     pub fn new(stream: rustls::StreamOwned<S, std::net::TcpStream>) -> SgxTrustedTlsTransport<S> {
-        SgxTrustedTlsTransport::<S> { stream }
+        SgxTrustedTlsTransport::<S> {
+            stream,
+            report_ca_cert: None,
+            encoding: Encoding::default(),
+        }
+    }
+
+    /// Set the encoding of requests sent by clients. Servers always respond
+    /// in the encoding of requests.
+    pub fn encoding(self, encoding: Encoding) -> Self {
+        Self { encoding, ..self }
+    }
+
+    /// Set the root CA certificate to read attestation reports in the peer
+    /// certificate with.
+    pub fn report_ca_cert(self, report_ca_cert: Option<Vec<u8>>) -> Self {
+        Self {
+            report_ca_cert,
+            ..self
+        }
+    }
+
+    /// Export keying material of the TLS session, completing the handshake
+    /// first if needed.
+    pub fn tls_exporter_secret(&mut self) -> Result<Vec<u8>> {
+        while self.stream.sess.is_handshaking() {
+            self.stream.sess.complete_io(&mut self.stream.sock)?;
+        }
+
+        export_tls_secret(&self.stream.sess)
+    }
+}
+
+impl SgxTrustedTlsTransport<rustls::ClientSession> {
+    /// Check without blocking whether the server has closed the connection,
+    /// e.g., restarted, before the connection is reused. Records pending on
+    /// the socket are processed by the TLS session, so that records sent after
+    /// the handshake (e.g., session tickets) are not mistaken for closing,
+    /// while a close notify alert, the end of the socket or unexpected data
+    /// from the server mean the connection cannot be reused.
+    pub fn is_closed(&mut self) -> bool {
+        use rustls::Session;
+        use std::io::Read;
+
+        let rustls::StreamOwned { sess, sock } = &mut self.stream;
+        if sock.set_nonblocking(true).is_err() {
+            return true;
+        }
+        let closed = loop {
+            // The session stops reading records once it has plaintext.
+            if !sess.wants_read() {
+                break true;
+            }
+            match sess.read_tls(sock) {
+                Ok(0) => break true,
+                Ok(_) => {
+                    if sess.process_new_packets().is_err() {
+                        break true;
+                    }
+                }
+                Err(ref e) if e.kind() == io::ErrorKind::WouldBlock => break false,
+                Err(ref e) if e.kind() == io::ErrorKind::Interrupted => (),
+                Err(_) => break true,
+            }
+        };
+        // Reading fails after a close notify alert, and reads nothing unless
+        // the server sent data without a request.
+        let closed = closed || !matches!(sess.read(&mut [0u8; 1]), Ok(0));
+
+        closed || sock.set_nonblocking(false).is_err()
+    }
+}
+
+fn export_tls_secret<S: rustls::Session>(session: &S) -> Result<Vec<u8>> {
+    let mut secret = vec![0u8; TLS_EXPORTER_SECRET_LEN];
+    session
+        .export_keying_material(&mut secret, TLS_EXPORTER_LABEL, None)
+        .map_err(|e| anyhow!("Cannot export keying material: {:?}", e))?;
+
+    Ok(secret)
+}
+
+/// Information on the peer of the session, after the handshake completes.
+fn peer_info<S: rustls::Session>(session: &S, report_ca_cert: Option<&[u8]>) -> PeerInfo {
+    // The certificate is verified in the handshake already, if there is any.
+    let attestation_report = match (session.get_peer_certificates(), report_ca_cert) {
+        (Some(certs), Some(report_ca_cert)) => certs
+            .first()
+            .and_then(|cert| AttestationReport::from_cert(&cert.0, report_ca_cert).ok()),
+        _ => None,
+    };
+    let tls_exporter_secret = export_tls_secret(session).unwrap_or_else(|e| {
+        debug!("{:?}", e);
+        Vec::new()
+    });
+
+    PeerInfo {
+        attestation_report,
+        tls_exporter_secret,
     }
 }
 
@@ -65,17 +187,57 @@ where
         request: Request<U>,
     ) -> teaclave_types::TeaclaveServiceResponseResult<V>
     where
-        U: Serialize + std::fmt::Debug,
-        V: for<'de> Deserialize<'de> + std::fmt::Debug,
+        U: Serialize + ProtobufMessage + std::fmt::Debug,
+        V: for<'de> Deserialize<'de> + ProtobufMessage + std::fmt::Debug,
     {
-        let mut protocol = protocol::JsonProtocol::new(&mut self.stream);
+        let mut protocol = protocol::Protocol::new(&mut self.stream, self.encoding);
         protocol.write_message(request)?;
-        protocol.read_message::<protocol::JsonProtocolResult<
+        protocol.read_message::<protocol::ProtocolResult<
                 V,
                 teaclave_types::TeaclaveServiceResponseError,
             >>()?
             .into()
     }
+
+    fn send_stream<U, V>(
+        &mut self,
+        request: Request<U>,
+        upload: Option<&mut dyn io::Read>,
+        download: Option<&mut dyn io::Write>,
+    ) -> teaclave_types::TeaclaveServiceResponseResult<V>
+    where
+        U: Serialize + ProtobufMessage + std::fmt::Debug,
+        V: for<'de> Deserialize<'de> + ProtobufMessage + std::fmt::Debug,
+    {
+        use crate::protocol::{ProtocolError, ProtocolResult};
+        use teaclave_types::TeaclaveServiceResponseError;
+
+        let mut protocol = protocol::Protocol::new(&mut self.stream, self.encoding);
+        protocol.write_message(request)?;
+
+        // The server may respond without reading or writing the stream.
+        let mut message = None;
+        if let Some(upload) = upload {
+            let mut writer = StreamWriter::new(&mut protocol);
+            let copied = io::copy(upload, &mut writer);
+            message = writer.take_message();
+            if message.is_none() {
+                copied.map_err(ProtocolError::from)?;
+                writer.finish()?;
+            }
+        }
+        if let Some(download) = download.filter(|_| message.is_none()) {
+            let mut reader = StreamReader::new(&mut protocol);
+            io::copy(&mut reader, download).map_err(ProtocolError::from)?;
+            message = reader.take_message();
+        }
+
+        let response: ProtocolResult<V, TeaclaveServiceResponseError> = match message {
+            Some(buf) => protocol.decode_message(&buf)?,
+            None => protocol.read_message()?,
+        };
+        response.into()
+    }
 }
 
 impl<S> ServerTransport for SgxTrustedTlsTransport<S>
@@ -84,16 +246,18 @@ where
 {
     fn serve<U, V, X>(&mut self, service: X) -> Result<()>
     where
-        U: Serialize + std::fmt::Debug,
-        V: for<'de> Deserialize<'de> + std::fmt::Debug,
+        U: Serialize + ProtobufMessage + std::fmt::Debug,
+        V: for<'de> Deserialize<'de> + ProtobufMessage + std::fmt::Debug,
         X: TeaclaveService<V, U>,
     {
-        use crate::protocol::{JsonProtocol, JsonProtocolResult};
+        use crate::protocol::{Protocol, ProtocolResult};
         use teaclave_types::TeaclaveServiceResponseError;
-        let mut protocol = JsonProtocol::new(&mut self.stream);
+        let report_ca_cert = self.report_ca_cert.clone();
+        let mut protocol = Protocol::new(&mut self.stream, self.encoding);
+        let mut peer = None;
 
         loop {
-            let request: Request<V> = match protocol.read_message::<Request<V>>() {
+            let mut request: Request<V> = match protocol.read_message::<Request<V>>() {
                 Ok(r) => r,
                 Err(e) => match e {
                     protocol::ProtocolError::IoError(_) => {
@@ -102,7 +266,7 @@ where
                     }
                     _ => {
                         debug!("{:?}", e);
-                        let response: JsonProtocolResult<U, TeaclaveServiceResponseError> =
+                        let response: ProtocolResult<U, TeaclaveServiceResponseError> =
                             Err(TeaclaveServiceResponseError::RequestError(
                                 "invalid request".to_string(),
                             ))
@@ -112,8 +276,17 @@ where
                     }
                 },
             };
-            let response: JsonProtocolResult<U, TeaclaveServiceResponseError> =
-                service.handle_request(request).into();
+            // The handshake has completed once a request is read.
+            if peer.is_none() {
+                let info = peer_info(&protocol.transport.sess, report_ca_cert.as_deref());
+                peer = Some(Arc::new(info));
+            }
+            request.peer = peer.clone();
+
+            let response: ProtocolResult<U, TeaclaveServiceResponseError> = {
+                let mut stream = Stream::new(&mut protocol);
+                service.handle_stream_request(request, &mut stream).into()
+            };
             protocol.write_message(response)?;
         }
     }
@@ -15,9 +15,9 @@
 // specific language governing permissions and limitations
 // under the License.
 
-#[allow(dead_code)]
+/// Returns the number of threads the enclave can run concurrently.
 #[cfg(feature = "mesalock_sgx")]
-pub(crate) fn get_tcs_num() -> usize {
+pub fn get_tcs_num() -> usize {
     if sgx_trts::enclave::rsgx_is_supported_EDMM() {
         sgx_trts::enclave::SgxGlobalData::new().get_dyn_tcs_num() as usize
     } else {
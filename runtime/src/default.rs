@@ -59,4 +59,12 @@ impl TeaclaveRuntime for DefaultRuntime {
         let writable = file_info.create_writable_io()?;
         Ok(writable)
     }
+
+    fn input_count(&self, identifier: &str) -> usize {
+        self.input_files.slot_len(identifier)
+    }
+
+    fn output_count(&self, identifier: &str) -> usize {
+        self.output_files.slot_len(identifier)
+    }
 }
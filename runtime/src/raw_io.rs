@@ -58,4 +58,12 @@ impl TeaclaveRuntime for RawIoRuntime {
         let f = File::create(&file_info.path)?;
         Ok(Box::new(f))
     }
+
+    fn input_count(&self, identifier: &str) -> usize {
+        self.input_files.slot_len(identifier)
+    }
+
+    fn output_count(&self, identifier: &str) -> usize {
+        self.output_files.slot_len(identifier)
+    }
 }
@@ -19,6 +19,11 @@ from typing import Tuple, Dict, List, Any
 
 from cryptography import x509
 from cryptography.hazmat.backends import default_backend
+from cryptography.hazmat.primitives import hashes, serialization
+from cryptography.hazmat.primitives.asymmetric.x25519 import (X25519PrivateKey,
+                                                              X25519PublicKey)
+from cryptography.hazmat.primitives.ciphers.aead import AESGCM
+from cryptography.hazmat.primitives.kdf.hkdf import HKDF
 
 from OpenSSL.crypto import load_certificate, FILETYPE_PEM, FILETYPE_ASN1
 from OpenSSL.crypto import X509Store, X509StoreContext
@@ -39,10 +44,19 @@ class FunctionInput:
     Args:
         name: Name of input data.
         description: Description of the input data.
+        optional: Whether the input can be left out when creating a task.
+        repeated: Whether the input is a list of files, which are named
+            "name[0]", "name[1]", ... in the task.
     """
-    def __init__(self, name: str, description: str):
+    def __init__(self,
+                 name: str,
+                 description: str,
+                 optional: bool = False,
+                 repeated: bool = False):
         self.name = name
         self.description = description
+        self.optional = optional
+        self.repeated = repeated
 
 
 class FunctionOutput:
@@ -51,10 +65,19 @@ class FunctionOutput:
     Args:
         name: Name of output data.
         description: Description of the output data.
+        optional: Whether the output can be left out when creating a task.
+        repeated: Whether the output is a list of files, which are named
+            "name[0]", "name[1]", ... in the task.
     """
-    def __init__(self, name: str, description: str):
+    def __init__(self,
+                 name: str,
+                 description: str,
+                 optional: bool = False,
+                 repeated: bool = False):
         self.name = name
         self.description = description
+        self.optional = optional
+        self.repeated = repeated
 
 
 class OwnerList:
@@ -85,15 +108,69 @@ class CryptoInfo:
     """Cryptographic information for the input/output data.
 
     Args:
-        schema: Encryption algorithms for the input/output data.
+        schema: Encryption algorithms for the input/output data, e.g.,
+            "aes-gcm-128", "aes-gcm-256", "chacha20-poly1305", "aes-gcm-siv",
+            "teaclave-file-128" or "aes-gcm-128-stream". Outputs can use
+            "teaclave-file-128", "aes-gcm-siv" or a stream schema.
         key: Key for encryption and decryption, bytes in list.
-        iv: IV, bytes in list.
+        iv: IV, bytes in list, empty for the stream schemas.
     """
     def __init__(self, schema: str, key: List[int], iv: List[int]):
         self.schema = schema
         self.key = key
         self.iv = iv
 
+    def wrap(self, public_key: List[int]) -> "CryptoInfo":
+        """Wraps the key and IV to an X25519 public key, e.g., the one
+        returned by FrontendClient.get_key_wrapping_key, so that they are not
+        sent in plaintext.
+        """
+        recipient = bytes(public_key)
+        ephemeral = X25519PrivateKey.generate()
+        ephemeral_public_key = ephemeral.public_key().public_bytes(
+            serialization.Encoding.Raw, serialization.PublicFormat.Raw)
+        shared = ephemeral.exchange(
+            X25519PublicKey.from_public_bytes(recipient))
+        key = HKDF(algorithm=hashes.SHA256(),
+                   length=32,
+                   salt=ephemeral_public_key + recipient,
+                   info=b"teaclave key wrapping v1",
+                   backend=default_backend()).derive(shared)
+        plaintext = bytes([len(self.key)] + self.key + self.iv)
+        ciphertext = AESGCM(key).encrypt(bytes(12), plaintext, None)
+
+        wrapped = CryptoInfo(self.schema, [], [])
+        wrapped.wrapped_key = {
+            "ephemeral_public_key": list(ephemeral_public_key),
+            "ciphertext": list(ciphertext)
+        }
+        return wrapped
+
+
+class PrivacyBudget:
+    """Differential privacy budget of an input file.
+
+    Args:
+        epsilon: Total epsilon that tasks on the file may spend.
+        delta: Total delta that tasks on the file may spend.
+    """
+    def __init__(self, epsilon: float, delta: float):
+        self.epsilon = epsilon
+        self.delta = delta
+
+
+class FileHash:
+    """Hash of the (encrypted) file stored at the remote URL, verified by the
+    file agent after downloading.
+
+    Args:
+        algorithm: "md5" or "sha256".
+        value: Hex-encoded digest.
+    """
+    def __init__(self, algorithm: str, value: str):
+        self.algorithm = algorithm
+        self.value = value
+
 
 class UserRegisterReqeust:
     def __init__(self, user_id: str, user_password: str):
@@ -243,7 +320,7 @@ class RegisterFunctionRequest:
     def __init__(self, metadata: Metadata, name: str, description: str,
                  executor_type: str, public: bool, payload: List[int],
                  arguments: List[str], inputs: List[FunctionInput],
-                 outputs: List[FunctionOutput]):
+                 outputs: List[FunctionOutput], version: str):
         self.request = "register_function"
         self.metadata = metadata
         self.name = name
@@ -254,24 +331,62 @@ class RegisterFunctionRequest:
         self.arguments = arguments
         self.inputs = inputs
         self.outputs = outputs
+        self.version = version
+
+
+class UpdateFunctionRequest:
+    def __init__(self, metadata: Metadata, function_id: str, version: str,
+                 description: str, executor_type: str, public: bool,
+                 payload: List[int], arguments: List[str],
+                 inputs: List[FunctionInput], outputs: List[FunctionOutput]):
+        self.request = "update_function"
+        self.metadata = metadata
+        self.function_id = function_id
+        self.version = version
+        self.description = description
+        self.executor_type = executor_type
+        self.public = public
+        self.payload = payload
+        self.arguments = arguments
+        self.inputs = inputs
+        self.outputs = outputs
+
+
+class GetFunctionRequest:
+    def __init__(self, metadata: Metadata, function_id: str):
+        self.request = "get_function"
+        self.metadata = metadata
+        self.function_id = function_id
 
 
 class RegisterInputFileRequest:
-    def __init__(self, metadata: Metadata, url: str, cmac: str,
-                 crypto_info: CryptoInfo):
+    def __init__(self,
+                 metadata: Metadata,
+                 url: str,
+                 cmac: str,
+                 crypto_info: CryptoInfo,
+                 privacy_budget: PrivacyBudget = None,
+                 hash: FileHash = None):
         self.request = "register_input_file"
         self.metadata = metadata
         self.url = url
         self.cmac = cmac
         self.crypto_info = crypto_info
+        self.privacy_budget = privacy_budget
+        self.hash = hash
 
 
 class RegisterOutputFileRequest:
-    def __init__(self, metadata: Metadata, url: str, crypto_info: CryptoInfo):
+    def __init__(self,
+                 metadata: Metadata,
+                 url: str,
+                 crypto_info: CryptoInfo = None,
+                 recipient_public_key: List[int] = []):
         self.request = "register_output_file"
         self.metadata = metadata
         self.url = url
         self.crypto_info = crypto_info
+        self.recipient_public_key = recipient_public_key
 
 
 class UpdateInputFileRequest:
@@ -335,6 +450,29 @@ class GetTaskRequest:
         self.task_id = task_id
 
 
+class GetOutputFileRequest:
+    def __init__(self, metadata: Metadata, data_id: str):
+        self.request = "get_output_file"
+        self.metadata = metadata
+        self.data_id = data_id
+
+
+class GetKeyWrappingKeyRequest:
+    def __init__(self, metadata: Metadata):
+        self.request = "get_key_wrapping_key"
+        self.metadata = metadata
+
+
+class RotateFileKeyRequest:
+    def __init__(self, metadata: Metadata, data_id: str, url: str,
+                 crypto_info: CryptoInfo):
+        self.request = "rotate_file_key"
+        self.metadata = metadata
+        self.data_id = data_id
+        self.url = url
+        self.crypto_info = crypto_info
+
+
 class FrontendClient:
     def __init__(self, channel: ssl.SSLSocket, metadata: Metadata = None):
         self.channel = channel
@@ -348,18 +486,55 @@ class FrontendClient:
                           payload: List[int] = [],
                           arguments: List[str] = [],
                           inputs: List[FunctionInput] = [],
-                          outputs: List[FunctionOutput] = []):
+                          outputs: List[FunctionOutput] = [],
+                          version: str = ""):
         request = RegisterFunctionRequest(self.metadata, name, description,
                                           executor_type, public, payload,
-                                          arguments, inputs, outputs)
+                                          arguments, inputs, outputs, version)
         _write_message(self.channel, request)
         response = _read_message(self.channel)
         return response["content"]["function_id"]
 
-    def register_input_file(self, url: str, schema: str, key: List[int],
-                            iv: List[int], cmac: str):
+    def update_function(self,
+                        function_id: str,
+                        version: str,
+                        description: str,
+                        executor_type: str,
+                        public: bool = True,
+                        payload: List[int] = [],
+                        arguments: List[str] = [],
+                        inputs: List[FunctionInput] = [],
+                        outputs: List[FunctionOutput] = []):
+        """Create a new revision of a function, the old revision is kept.
+
+        Returns:
+            Tuple[str, str]: Function id and revision hash of the new revision.
+        """
+        request = UpdateFunctionRequest(self.metadata, function_id, version,
+                                        description, executor_type, public,
+                                        payload, arguments, inputs, outputs)
+        _write_message(self.channel, request)
+        response = _read_message(self.channel)
+        return (response["content"]["function_id"],
+                response["content"]["revision_hash"])
+
+    def get_function(self, function_id: str):
+        request = GetFunctionRequest(self.metadata, function_id)
+        _write_message(self.channel, request)
+        response = _read_message(self.channel)
+        return response["content"]
+
+    def register_input_file(self,
+                            url: str,
+                            schema: str,
+                            key: List[int],
+                            iv: List[int],
+                            cmac: str,
+                            privacy_budget: PrivacyBudget = None,
+                            hash: FileHash = None):
         request = RegisterInputFileRequest(self.metadata, url, cmac,
-                                           CryptoInfo(schema, key, iv))
+                                           CryptoInfo(schema, key, iv),
+                                           privacy_budget, hash)
         _write_message(self.channel, request)
         response = _read_message(self.channel)
         return response["content"]["data_id"]
@@ -372,6 +547,45 @@ class FrontendClient:
         response = _read_message(self.channel)
         return response["content"]["data_id"]
 
+    def register_output_file_for_recipient(self, url: str,
+                                           recipient_public_key: List[int]):
+        """Registers an output whose key is generated by the platform and
+        wrapped to the recipient's X25519 public key, see get_output_file.
+        """
+        request = RegisterOutputFileRequest(
+            self.metadata, url, recipient_public_key=recipient_public_key)
+        _write_message(self.channel, request)
+        response = _read_message(self.channel)
+        return response["content"]["data_id"]
+
+    def get_output_file(self, data_id: str):
+        request = GetOutputFileRequest(self.metadata, data_id)
+        _write_message(self.channel, request)
+        response = _read_message(self.channel)
+        return response["content"]
+
+    def get_key_wrapping_key(self) -> List[int]:
+        """Returns the public key to wrap file keys to. It comes from the
+        attested frontend service over the verified TLS channel.
+        """
+        request = GetKeyWrappingKeyRequest(self.metadata)
+        _write_message(self.channel, request)
+        response = _read_message(self.channel)
+        return response["content"]["public_key"]
+
+    def rotate_file_key(self, data_id: str, url: str, schema: str,
+                        key: List[int], iv: List[int]):
+        """Re-encrypts an input file under a new key to a new location.
+
+        The file keeps its id and is pointed to the new location once the
+        returned task has finished, see get_task.
+        """
+        request = RotateFileKeyRequest(self.metadata, data_id, url,
+                                       CryptoInfo(schema, key, iv))
+        _write_message(self.channel, request)
+        response = _read_message(self.channel)
+        return response["content"]["task_id"]
+
     def create_task(self,
                     function_id: str,
                     function_arguments: Dict[str, Any],
@@ -25,10 +25,11 @@ extern crate log;
 use anyhow::{anyhow, Result};
 
 use std::prelude::v1::*;
-use teaclave_attestation::{verifier, AttestationConfig, RemoteAttestation};
+use teaclave_attestation::policy::AttestationPolicy;
+use teaclave_attestation::{AttestationConfig, RemoteAttestation};
 use teaclave_binder::proto::{
-    ECallCommand, FinalizeEnclaveInput, FinalizeEnclaveOutput, InitEnclaveInput, InitEnclaveOutput,
-    StartServiceInput, StartServiceOutput,
+    ECallCommand, FinalizeEnclaveInput, FinalizeEnclaveOutput, GetMetricsInput, GetMetricsOutput,
+    InitEnclaveInput, InitEnclaveOutput, StartServiceInput, StartServiceOutput,
 };
 use teaclave_binder::{handle_ecall, register_ecall_handler};
 use teaclave_config::build::{
@@ -40,7 +41,8 @@ use teaclave_proto::teaclave_access_control_service::{
 };
 use teaclave_rpc::config::SgxTrustedTlsServerConfig;
 use teaclave_rpc::server::SgxTrustedTlsServer;
-use teaclave_service_enclave_utils::ServiceEnclave;
+use teaclave_service_enclave_utils::metrics;
+use teaclave_service_enclave_utils::{watch_revocation_list, ServiceEnclave};
 use teaclave_types::{EnclaveInfo, TeeServiceError, TeeServiceResult};
 
 mod acs;
@@ -49,6 +51,8 @@ mod service;
 fn start_service(config: &RuntimeConfig) -> Result<()> {
     let listen_address = config.internal_endpoints.access_control.listen_address;
     let attestation_config = AttestationConfig::from_teaclave_config(&config)?;
+    let attestation_policy = AttestationPolicy::from_teaclave_config(&config)?;
+    watch_revocation_list(&config)?;
     let attested_tls_config = RemoteAttestation::new(attestation_config)
         .generate_and_endorse()?
         .attested_tls_config()
@@ -69,7 +73,7 @@ fn start_service(config: &RuntimeConfig) -> Result<()> {
         .attestation_report_verifier(
         accepted_enclave_attrs,
         AS_ROOT_CA_CERT,
-        verifier::universal_quote_verifier,
+        attestation_policy,
     )?;
 
     acs::init_acs()?;
@@ -110,11 +114,17 @@ fn handle_finalize_enclave(_: &FinalizeEnclaveInput) -> TeeServiceResult<Finaliz
     Ok(FinalizeEnclaveOutput)
 }
 
+#[handle_ecall]
+fn handle_get_metrics(_: &GetMetricsInput) -> TeeServiceResult<GetMetricsOutput> {
+    Ok(GetMetricsOutput::new(metrics::gather()))
+}
+
 register_ecall_handler!(
     type ECallCommand,
     (ECallCommand::StartService, StartServiceInput, StartServiceOutput),
     (ECallCommand::InitEnclave, InitEnclaveInput, InitEnclaveOutput),
     (ECallCommand::FinalizeEnclave, FinalizeEnclaveInput, FinalizeEnclaveOutput),
+    (ECallCommand::GetMetrics, GetMetricsInput, GetMetricsOutput),
 );
 
 #[cfg(feature = "enclave_unit_test")]
@@ -30,6 +30,12 @@ fn main() -> Result<()> {
         PACKAGE_NAME,
         "runtime.config.toml",
     )?);
+    if let Some(endpoint) = &launcher.config().metrics.authentication {
+        launcher
+            .clone()
+            .start_metrics_endpoint(endpoint.listen_address)
+            .context("Failed to start the metrics endpoint")?;
+    }
     let launcher_ref = launcher.clone();
     thread::spawn(move || {
         let _ = launcher_ref.start();
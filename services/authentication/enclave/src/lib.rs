@@ -29,10 +29,11 @@ use std::prelude::v1::*;
 use std::sync::{Arc, SgxRwLock as RwLock};
 use std::thread;
 
-use teaclave_attestation::{verifier, AttestationConfig, AttestedTlsConfig, RemoteAttestation};
+use teaclave_attestation::policy::AttestationPolicy;
+use teaclave_attestation::{AttestationConfig, AttestedTlsConfig, RemoteAttestation};
 use teaclave_binder::proto::{
-    ECallCommand, FinalizeEnclaveInput, FinalizeEnclaveOutput, InitEnclaveInput, InitEnclaveOutput,
-    StartServiceInput, StartServiceOutput,
+    ECallCommand, FinalizeEnclaveInput, FinalizeEnclaveOutput, GetMetricsInput, GetMetricsOutput,
+    InitEnclaveInput, InitEnclaveOutput, StartServiceInput, StartServiceOutput,
 };
 use teaclave_binder::{handle_ecall, register_ecall_handler};
 use teaclave_config::build::{
@@ -45,7 +46,8 @@ use teaclave_proto::teaclave_authentication_service::{
 };
 use teaclave_rpc::config::SgxTrustedTlsServerConfig;
 use teaclave_rpc::server::SgxTrustedTlsServer;
-use teaclave_service_enclave_utils::ServiceEnclave;
+use teaclave_service_enclave_utils::metrics;
+use teaclave_service_enclave_utils::{watch_revocation_list, ServiceEnclave};
 use teaclave_types::{EnclaveInfo, TeeServiceError, TeeServiceResult};
 
 mod api_service;
@@ -59,12 +61,13 @@ fn start_internal_endpoint(
     jwt_secret: Vec<u8>,
     attested_tls_config: Arc<RwLock<AttestedTlsConfig>>,
     accepted_enclave_attrs: Vec<teaclave_types::EnclaveAttr>,
+    attestation_policy: AttestationPolicy,
 ) -> Result<()> {
     let server_config = SgxTrustedTlsServerConfig::from_attested_tls_config(attested_tls_config)?
         .attestation_report_verifier(
         accepted_enclave_attrs,
         AS_ROOT_CA_CERT,
-        verifier::universal_quote_verifier,
+        attestation_policy,
     )?;
 
     let mut server = SgxTrustedTlsServer::<
@@ -124,6 +127,8 @@ fn start_service(config: &RuntimeConfig) -> Result<()> {
     let api_listen_address = config.api_endpoints.authentication.listen_address;
     let internal_listen_address = config.internal_endpoints.authentication.listen_address;
     let attestation_config = AttestationConfig::from_teaclave_config(&config)?;
+    let attestation_policy = AttestationPolicy::from_teaclave_config(&config)?;
+    watch_revocation_list(&config)?;
     let attested_tls_config = RemoteAttestation::new(attestation_config)
         .generate_and_endorse()?
         .attested_tls_config()
@@ -153,6 +158,7 @@ fn start_service(config: &RuntimeConfig) -> Result<()> {
             internal_jwt_secret,
             attested_tls_config,
             accepted_enclave_attrs,
+            attestation_policy,
         );
     });
 
@@ -189,11 +195,17 @@ fn handle_finalize_enclave(_: &FinalizeEnclaveInput) -> TeeServiceResult<Finaliz
     Ok(FinalizeEnclaveOutput)
 }
 
+#[handle_ecall]
+fn handle_get_metrics(_: &GetMetricsInput) -> TeeServiceResult<GetMetricsOutput> {
+    Ok(GetMetricsOutput::new(metrics::gather()))
+}
+
 register_ecall_handler!(
     type ECallCommand,
     (ECallCommand::StartService, StartServiceInput, StartServiceOutput),
     (ECallCommand::InitEnclave, InitEnclaveInput, InitEnclaveOutput),
     (ECallCommand::FinalizeEnclave, FinalizeEnclaveInput, FinalizeEnclaveOutput),
+    (ECallCommand::GetMetrics, GetMetricsInput, GetMetricsOutput),
 );
 
 #[cfg(feature = "enclave_unit_test")]
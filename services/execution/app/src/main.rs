@@ -22,7 +22,10 @@ use std::thread;
 use teaclave_service_app_utils::{register_signals, TeaclaveServiceLauncher};
 
 // Use to import ocall
-pub use teaclave_file_agent::ocall_handle_file_request;
+pub use teaclave_file_agent::{
+    ocall_close_upload, ocall_handle_file_request, ocall_open_upload, ocall_read_file_range,
+    ocall_write_upload,
+};
 
 const PACKAGE_NAME: &str = env!("CARGO_PKG_NAME");
 
@@ -33,6 +36,12 @@ fn main() -> Result<()> {
         PACKAGE_NAME,
         "runtime.config.toml",
     )?);
+    if let Some(endpoint) = &launcher.config().metrics.execution {
+        launcher
+            .clone()
+            .start_metrics_endpoint(endpoint.listen_address)
+            .context("Failed to start the metrics endpoint")?;
+    }
     let launcher_ref = launcher.clone();
     thread::spawn(move || {
         let _ = launcher_ref.start();
@@ -0,0 +1,312 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License.
+
+//! Staged files streamed between the enclave and their remote location, so
+//! that inputs are read by the function as they are downloaded and outputs
+//! are uploaded as they are written. Files in the chunked stream format are
+//! decrypted as they are read and encrypted as they are written, and neither
+//! kind touches the local disk.
+
+use crate::ocall::{close_upload, open_upload, read_file_range, write_upload};
+use anyhow::Result;
+use std::io::{self, Read, Write};
+use std::path::{Path, PathBuf};
+use std::prelude::v1::*;
+use std::sync::{Arc, SgxMutex as Mutex};
+use teaclave_crypto::StreamEncryptor;
+use teaclave_types::{
+    redact_url, FileAuthTag, FileCrypto, FileHash, FileHasher, OpenUploadRequest,
+    ReadFileRangeRequest, StagedFileStream, TransferRetryPolicy,
+};
+use url::Url;
+
+// Bytes read from or written to the remote file per ocall.
+const READ_CHUNK_SIZE: u32 = 1024 * 1024;
+const WRITE_CHUNK_SIZE: usize = 1024 * 1024;
+
+/// Reads a remote file sequentially with ranged reads through the file agent.
+struct RemoteFileReader {
+    remote: Url,
+    fusion_base: PathBuf,
+    retry: TransferRetryPolicy,
+    offset: u64,
+    buffer: Vec<u8>,
+    pos: usize,
+    eof: bool,
+}
+
+impl RemoteFileReader {
+    fn new(remote: &Url, fusion_base: &Path, retry: TransferRetryPolicy) -> Self {
+        Self {
+            remote: remote.to_owned(),
+            fusion_base: fusion_base.to_owned(),
+            retry,
+            offset: 0,
+            buffer: Vec::new(),
+            pos: 0,
+            eof: false,
+        }
+    }
+
+    fn fill_buffer(&mut self) -> io::Result<()> {
+        let request = ReadFileRangeRequest::new(
+            &self.remote,
+            self.offset,
+            READ_CHUNK_SIZE,
+            &self.fusion_base,
+        )
+        .retry(self.retry);
+        self.buffer = read_file_range(&request)
+            .map_err(|e| io::Error::new(io::ErrorKind::Other, e.to_string()))?;
+        self.pos = 0;
+        self.offset += self.buffer.len() as u64;
+        self.eof = self.buffer.len() < READ_CHUNK_SIZE as usize;
+        Ok(())
+    }
+}
+
+impl Read for RemoteFileReader {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if self.pos == self.buffer.len() {
+            if self.eof {
+                return Ok(0);
+            }
+            self.fill_buffer()?;
+        }
+        let n = std::cmp::min(buf.len(), self.buffer.len() - self.pos);
+        buf[..n].copy_from_slice(&self.buffer[self.pos..self.pos + n]);
+        self.pos += n;
+        Ok(n)
+    }
+}
+
+/// Whether files encrypted with `crypto` can be streamed, i.e., raw files and
+/// files in the chunked stream format. Other files are authenticated as a
+/// whole.
+pub(crate) fn is_streamable(crypto: &FileCrypto) -> bool {
+    match crypto {
+        FileCrypto::Raw => true,
+        crypto => crypto.is_stream(),
+    }
+}
+
+/// An input read by the function from its remote location, decrypted while
+/// it is read if it is in the chunked stream format.
+#[derive(Debug)]
+pub(crate) struct StreamingInput {
+    remote: Url,
+    fusion_base: PathBuf,
+    crypto: FileCrypto,
+    cmac: FileAuthTag,
+    retry: TransferRetryPolicy,
+}
+
+impl StreamingInput {
+    pub(crate) fn new(
+        remote: &Url,
+        fusion_base: impl AsRef<Path>,
+        crypto: FileCrypto,
+        cmac: FileAuthTag,
+        retry: TransferRetryPolicy,
+    ) -> Self {
+        Self {
+            remote: remote.to_owned(),
+            fusion_base: fusion_base.as_ref().to_owned(),
+            crypto,
+            cmac,
+            retry,
+        }
+    }
+}
+
+impl StagedFileStream for StreamingInput {
+    fn open_read(&self) -> Result<Box<dyn io::Read>> {
+        let reader = RemoteFileReader::new(&self.remote, &self.fusion_base, self.retry);
+        if let FileCrypto::Raw = self.crypto {
+            return Ok(Box::new(reader));
+        }
+        let decryptor = self.crypto.stream_decryptor(reader)?.expect_tag(self.cmac);
+        Ok(Box::new(decryptor))
+    }
+
+    fn open_write(&self) -> Result<Box<dyn io::Write>> {
+        anyhow::bail!("Input file is not writable: {}", redact_url(&self.remote))
+    }
+}
+
+type OutputTag = Arc<Mutex<Option<std::result::Result<(FileAuthTag, FileHash), String>>>>;
+
+/// Writes a remote file sequentially through an upload opened with the file
+/// agent. The upload is aborted unless it is closed.
+struct RemoteFileWriter {
+    upload_id: u64,
+    buffer: Vec<u8>,
+    hasher: FileHasher,
+    closed: bool,
+}
+
+impl RemoteFileWriter {
+    fn open(remote: &Url, fusion_base: &Path, retry: TransferRetryPolicy) -> Result<Self> {
+        let request = OpenUploadRequest::new(remote, fusion_base).retry(retry);
+        let upload_id = open_upload(&request)?;
+        Ok(Self {
+            upload_id,
+            buffer: Vec::with_capacity(WRITE_CHUNK_SIZE),
+            hasher: FileHasher::new(),
+            closed: false,
+        })
+    }
+
+    fn send_buffer(&mut self) -> io::Result<()> {
+        if !self.buffer.is_empty() {
+            self.hasher.update(&self.buffer);
+            write_upload(self.upload_id, &self.buffer)
+                .map_err(|e| io::Error::new(io::ErrorKind::Other, e.to_string()))?;
+            self.buffer.clear();
+        }
+        Ok(())
+    }
+
+    /// Sends the rest of the file and completes the upload, returning the
+    /// hash of what is uploaded.
+    fn close(&mut self) -> Result<FileHash> {
+        self.send_buffer()?;
+        self.closed = true;
+        close_upload(self.upload_id, false)?;
+        Ok(self.hasher.clone().finish())
+    }
+}
+
+impl Write for RemoteFileWriter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        if self.buffer.len() == WRITE_CHUNK_SIZE {
+            self.send_buffer()?;
+        }
+        let n = std::cmp::min(buf.len(), WRITE_CHUNK_SIZE - self.buffer.len());
+        self.buffer.extend_from_slice(&buf[..n]);
+        Ok(n)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.send_buffer()
+    }
+}
+
+impl Drop for RemoteFileWriter {
+    fn drop(&mut self) {
+        if !self.closed {
+            if let Err(e) = close_upload(self.upload_id, true) {
+                log::warn!("Cannot abort upload: {:?}", e);
+            }
+        }
+    }
+}
+
+/// An output encrypted in the chunked stream format while the function writes
+/// it, the ciphertext goes straight to its remote location.
+#[derive(Debug)]
+pub(crate) struct StreamingOutput {
+    remote: Url,
+    fusion_base: PathBuf,
+    crypto: FileCrypto,
+    retry: TransferRetryPolicy,
+    tag: OutputTag,
+}
+
+impl StreamingOutput {
+    pub(crate) fn new(
+        remote: &Url,
+        fusion_base: impl AsRef<Path>,
+        crypto: FileCrypto,
+        retry: TransferRetryPolicy,
+    ) -> Self {
+        Self {
+            remote: remote.to_owned(),
+            fusion_base: fusion_base.as_ref().to_owned(),
+            crypto,
+            retry,
+            tag: Arc::new(Mutex::new(None)),
+        }
+    }
+
+    /// The tag and hash of the output once the function has closed it and it
+    /// is uploaded.
+    pub(crate) fn uploaded(&self) -> Result<(FileAuthTag, FileHash)> {
+        let tag = self
+            .tag
+            .lock()
+            .map_err(|_| anyhow::anyhow!("Cannot lock output tag"))?;
+        match &*tag {
+            Some(Ok(tag)) => Ok(tag.clone()),
+            Some(Err(e)) => {
+                anyhow::bail!("Cannot finish output {}: {}", redact_url(&self.remote), e)
+            }
+            None => anyhow::bail!("Output is not written: {}", redact_url(&self.remote)),
+        }
+    }
+}
+
+impl StagedFileStream for StreamingOutput {
+    fn open_read(&self) -> Result<Box<dyn io::Read>> {
+        anyhow::bail!("Output file is not readable: {}", redact_url(&self.remote))
+    }
+
+    fn open_write(&self) -> Result<Box<dyn io::Write>> {
+        if let Ok(mut tag) = self.tag.lock() {
+            *tag = None;
+        }
+        let writer = RemoteFileWriter::open(&self.remote, &self.fusion_base, self.retry)?;
+        let encryptor = self.crypto.stream_encryptor(writer)?;
+        Ok(Box::new(StreamingOutputWriter {
+            encryptor,
+            tag: self.tag.clone(),
+        }))
+    }
+}
+
+// Seals the final chunk and completes the upload when the function drops the
+// writer, as `io::Write` has no way to tell the end of the output.
+struct StreamingOutputWriter {
+    encryptor: StreamEncryptor<RemoteFileWriter>,
+    tag: OutputTag,
+}
+
+impl Write for StreamingOutputWriter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.encryptor.write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.encryptor.flush()
+    }
+}
+
+impl Drop for StreamingOutputWriter {
+    fn drop(&mut self) {
+        let encryptor = &mut self.encryptor;
+        let result = encryptor
+            .finalize()
+            .and_then(|tag| {
+                let hash = encryptor.get_mut().close()?;
+                Ok((FileAuthTag::from(tag), hash))
+            })
+            .map_err(|e| e.to_string());
+        if let Ok(mut tag) = self.tag.lock() {
+            *tag = Some(result);
+        }
+    }
+}
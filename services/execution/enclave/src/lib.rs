@@ -26,24 +26,29 @@ use std::untrusted::path::PathEx;
 
 use anyhow::{anyhow, ensure, Result};
 
-use teaclave_attestation::{verifier, AttestationConfig, RemoteAttestation};
+use teaclave_attestation::policy::AttestationPolicy;
+use teaclave_attestation::{AttestationConfig, RemoteAttestation};
 use teaclave_binder::proto::{
-    ECallCommand, FinalizeEnclaveInput, FinalizeEnclaveOutput, InitEnclaveInput, InitEnclaveOutput,
-    StartServiceInput, StartServiceOutput,
+    ECallCommand, FinalizeEnclaveInput, FinalizeEnclaveOutput, GetMetricsInput, GetMetricsOutput,
+    InitEnclaveInput, InitEnclaveOutput, StartServiceInput, StartServiceOutput,
 };
 use teaclave_binder::{handle_ecall, register_ecall_handler};
 use teaclave_config::build::{AS_ROOT_CA_CERT, AUDITOR_PUBLIC_KEYS};
 use teaclave_config::RuntimeConfig;
 use teaclave_service_enclave_utils::create_trusted_scheduler_endpoint;
-use teaclave_service_enclave_utils::ServiceEnclave;
-use teaclave_types::{EnclaveInfo, TeeServiceError, TeeServiceResult};
+use teaclave_service_enclave_utils::metrics;
+use teaclave_service_enclave_utils::{watch_revocation_list, ServiceEnclave};
+use teaclave_types::{EnclaveInfo, TeeServiceError, TeeServiceResult, TransferRetryPolicy};
 
+mod file_stream;
 mod ocall;
 mod service;
 mod task_file_manager;
 
 fn start_service(config: &RuntimeConfig) -> Result<()> {
     let attestation_config = AttestationConfig::from_teaclave_config(&config)?;
+    let attestation_policy = AttestationPolicy::from_teaclave_config(&config)?;
+    watch_revocation_list(&config)?;
     let attested_tls_config = RemoteAttestation::new(attestation_config)
         .generate_and_endorse()?
         .attested_tls_config()
@@ -58,7 +63,7 @@ fn start_service(config: &RuntimeConfig) -> Result<()> {
         &scheduler_service_address,
         &enclave_info,
         AS_ROOT_CA_CERT,
-        verifier::universal_quote_verifier,
+        attestation_policy,
         attested_tls_config,
     )?;
 
@@ -75,9 +80,19 @@ fn start_service(config: &RuntimeConfig) -> Result<()> {
         fusion_base.display()
     );
 
-    let mut service =
-        service::TeaclaveExecutionService::new(scheduler_service_endpoint, fusion_base)?;
-    let _ = service.start();
+    let transfer_retry = &config.execution.transfer_retry;
+    let transfer_retry = TransferRetryPolicy {
+        max_retries: transfer_retry.max_retries,
+        initial_backoff_ms: transfer_retry.initial_backoff_ms,
+        max_backoff_ms: transfer_retry.max_backoff_ms,
+    };
+    let mut service = service::TeaclaveExecutionService::new(
+        scheduler_service_endpoint,
+        fusion_base,
+        config.execution.file_streaming,
+        transfer_retry,
+    )?;
+    let _ = service.start(config.execution.worker_num);
 
     Ok(())
 }
@@ -105,11 +120,17 @@ fn handle_finalize_enclave(_: &FinalizeEnclaveInput) -> TeeServiceResult<Finaliz
     Ok(FinalizeEnclaveOutput)
 }
 
+#[handle_ecall]
+fn handle_get_metrics(_: &GetMetricsInput) -> TeeServiceResult<GetMetricsOutput> {
+    Ok(GetMetricsOutput::new(metrics::gather()))
+}
+
 register_ecall_handler!(
     type ECallCommand,
     (ECallCommand::StartService, StartServiceInput, StartServiceOutput),
     (ECallCommand::InitEnclave, InitEnclaveInput, InitEnclaveOutput),
     (ECallCommand::FinalizeEnclave, FinalizeEnclaveInput, FinalizeEnclaveOutput),
+    (ECallCommand::GetMetrics, GetMetricsInput, GetMetricsOutput),
 );
 
 #[cfg(feature = "enclave_unit_test")]
@@ -120,9 +141,15 @@ pub mod tests {
     pub fn run_tests() -> bool {
         run_tests!(
             ocall::tests::test_handle_file_request,
+            service::tests::test_max_worker_num,
+            service::tests::test_pull_backoff,
             service::tests::test_invoke_echo,
             service::tests::test_invoke_gbdt_train,
             task_file_manager::tests::test_input,
+            task_file_manager::tests::test_stream_raw_input,
+            task_file_manager::tests::test_stream_files,
+            task_file_manager::tests::test_aes_gcm_siv_files,
+            task_file_manager::tests::test_name_failed_files,
         )
     }
 }
@@ -15,37 +15,147 @@
 // specific language governing permissions and limitations
 // under the License.
 
-use anyhow::ensure;
 use anyhow::Result;
+use anyhow::{bail, ensure};
 use sgx_types::sgx_status_t;
 use std::prelude::v1::*;
-use teaclave_types::FileAgentRequest;
+use teaclave_types::{
+    redact_url, FileAgentRequest, FileAgentResponse, OpenUploadRequest, ReadFileRangeRequest,
+};
 
 extern "C" {
     fn ocall_handle_file_request(
         p_retval: *mut u32,
         in_buf: *const u8,
         in_len: u32,
+        out_buf: *mut u8,
+        out_max: u32,
+        out_len: *mut u32,
     ) -> sgx_status_t;
+
+    fn ocall_read_file_range(
+        p_retval: *mut u32,
+        in_buf: *const u8,
+        in_len: u32,
+        out_buf: *mut u8,
+        out_max: u32,
+        out_len: *mut u32,
+    ) -> sgx_status_t;
+
+    fn ocall_open_upload(
+        p_retval: *mut u32,
+        in_buf: *const u8,
+        in_len: u32,
+        upload_id: *mut u64,
+    ) -> sgx_status_t;
+
+    fn ocall_write_upload(
+        p_retval: *mut u32,
+        upload_id: u64,
+        buf: *const u8,
+        len: u32,
+    ) -> sgx_status_t;
+
+    fn ocall_close_upload(p_retval: *mut u32, upload_id: u64, abort: u32) -> sgx_status_t;
 }
 
+// Return values of the ocall, see ocall_handle_file_request in the file agent.
+const FILE_AGENT_OK: u32 = 0;
+const FILE_AGENT_INVALID_REQUEST: u32 = 1;
+const FILE_AGENT_BUFFER_TOO_SMALL: u32 = 2;
+const FILE_AGENT_READ_FAILED: u32 = 3;
+const FILE_AGENT_WRITE_FAILED: u32 = 4;
+
+// Room for the response on top of the request it echoes back, i.e., the
+// error details of each file.
+const RESPONSE_EXTRA_LEN_PER_FILE: usize = 1024;
+
 #[allow(dead_code)]
 pub(crate) fn handle_file_request(request: FileAgentRequest) -> Result<()> {
-    let mut rt: u32 = 2;
+    let mut rt: u32 = FILE_AGENT_INVALID_REQUEST;
     let bytes = serde_json::to_vec(&request)?;
     let buf_len = bytes.len();
-    let res =
-        unsafe { ocall_handle_file_request(&mut rt as _, bytes.as_ptr() as _, buf_len as u32) };
+
+    let out_max = buf_len + RESPONSE_EXTRA_LEN_PER_FILE * (request.info.len() + 1);
+    let mut out_buf = vec![0u8; out_max];
+    let mut out_len: u32 = 0;
+    let res = unsafe {
+        ocall_handle_file_request(
+            &mut rt as _,
+            bytes.as_ptr() as _,
+            buf_len as u32,
+            out_buf.as_mut_ptr() as _,
+            out_max as u32,
+            &mut out_len as _,
+        )
+    };
 
     ensure!(
         res == sgx_status_t::SGX_SUCCESS,
         "ocall sgx_error = {:?}",
         res
     );
-    ensure!(rt == 0, "ocall error = {:?}", rt);
+    match rt {
+        FILE_AGENT_OK => (),
+        FILE_AGENT_INVALID_REQUEST => bail!("ocall error: invalid file agent request"),
+        FILE_AGENT_BUFFER_TOO_SMALL => bail!("ocall error: file agent response is too large"),
+        _ => bail!("ocall error = {:?}", rt),
+    }
+    ensure!(
+        out_len as usize <= out_max,
+        "ocall error: invalid response length {}",
+        out_len
+    );
+
+    let response: FileAgentResponse = serde_json::from_slice(&out_buf[..out_len as usize])?;
+    response.into_result()?;
     Ok(())
 }
 
+/// Reads a range of a remote file through the file agent, returns fewer bytes
+/// than requested at the end of the file.
+pub(crate) fn read_file_range(request: &ReadFileRangeRequest) -> Result<Vec<u8>> {
+    let mut rt: u32 = FILE_AGENT_INVALID_REQUEST;
+    let bytes = serde_json::to_vec(request)?;
+
+    let out_max = request.len;
+    let mut out_buf = vec![0u8; out_max as usize];
+    let mut out_len: u32 = 0;
+    let res = unsafe {
+        ocall_read_file_range(
+            &mut rt as _,
+            bytes.as_ptr() as _,
+            bytes.len() as u32,
+            out_buf.as_mut_ptr() as _,
+            out_max,
+            &mut out_len as _,
+        )
+    };
+
+    ensure!(
+        res == sgx_status_t::SGX_SUCCESS,
+        "ocall sgx_error = {:?}",
+        res
+    );
+    match rt {
+        FILE_AGENT_OK => (),
+        FILE_AGENT_INVALID_REQUEST => bail!("ocall error: invalid read file range request"),
+        FILE_AGENT_BUFFER_TOO_SMALL => bail!("ocall error: buffer too small"),
+        FILE_AGENT_READ_FAILED => {
+            bail!("ocall error: cannot read {}", redact_url(&request.remote))
+        }
+        _ => bail!("ocall error = {:?}", rt),
+    }
+    ensure!(
+        out_len <= out_max,
+        "ocall error: invalid response length {}",
+        out_len
+    );
+
+    out_buf.truncate(out_len as usize);
+    Ok(out_buf)
+}
+
 #[cfg(feature = "enclave_unit_test")]
 pub mod tests {
     use super::*;
@@ -71,3 +181,76 @@ pub mod tests {
         std::untrusted::fs::remove_file(&dest).unwrap();
     }
 }
+
+/// Opens an upload to a remote file through the file agent, returns the ID
+/// to write to it with.
+pub(crate) fn open_upload(request: &OpenUploadRequest) -> Result<u64> {
+    let mut rt: u32 = FILE_AGENT_INVALID_REQUEST;
+    let bytes = serde_json::to_vec(request)?;
+    let mut upload_id: u64 = 0;
+    let res = unsafe {
+        ocall_open_upload(
+            &mut rt as _,
+            bytes.as_ptr() as _,
+            bytes.len() as u32,
+            &mut upload_id as _,
+        )
+    };
+
+    ensure!(
+        res == sgx_status_t::SGX_SUCCESS,
+        "ocall sgx_error = {:?}",
+        res
+    );
+    match rt {
+        FILE_AGENT_OK => Ok(upload_id),
+        FILE_AGENT_INVALID_REQUEST => bail!("ocall error: invalid open upload request"),
+        FILE_AGENT_WRITE_FAILED => {
+            bail!("ocall error: cannot upload {}", redact_url(&request.remote))
+        }
+        _ => bail!("ocall error = {:?}", rt),
+    }
+}
+
+/// Sends the next piece of an upload, which the file agent aborts on failure.
+pub(crate) fn write_upload(upload_id: u64, data: &[u8]) -> Result<()> {
+    let mut rt: u32 = FILE_AGENT_INVALID_REQUEST;
+    let res = unsafe {
+        ocall_write_upload(
+            &mut rt as _,
+            upload_id,
+            data.as_ptr() as _,
+            data.len() as u32,
+        )
+    };
+
+    ensure!(
+        res == sgx_status_t::SGX_SUCCESS,
+        "ocall sgx_error = {:?}",
+        res
+    );
+    match rt {
+        FILE_AGENT_OK => Ok(()),
+        FILE_AGENT_INVALID_REQUEST => bail!("ocall error: unknown upload {}", upload_id),
+        FILE_AGENT_WRITE_FAILED => bail!("ocall error: cannot write upload {}", upload_id),
+        _ => bail!("ocall error = {:?}", rt),
+    }
+}
+
+/// Completes an upload, or drops what has been sent if `abort` is set.
+pub(crate) fn close_upload(upload_id: u64, abort: bool) -> Result<()> {
+    let mut rt: u32 = FILE_AGENT_INVALID_REQUEST;
+    let res = unsafe { ocall_close_upload(&mut rt as _, upload_id, abort as u32) };
+
+    ensure!(
+        res == sgx_status_t::SGX_SUCCESS,
+        "ocall sgx_error = {:?}",
+        res
+    );
+    match rt {
+        FILE_AGENT_OK => Ok(()),
+        FILE_AGENT_INVALID_REQUEST => bail!("ocall error: unknown upload {}", upload_id),
+        FILE_AGENT_WRITE_FAILED => bail!("ocall error: cannot finish upload {}", upload_id),
+        _ => bail!("ocall error = {:?}", rt),
+    }
+}
@@ -16,13 +16,18 @@
 // under the License.
 
 use std::collections::HashMap;
+use std::format;
 use std::path::{Path, PathBuf};
 use std::prelude::v1::*;
 use std::sync::{Arc, SgxMutex as Mutex};
+use std::time::Duration;
 
 use crate::task_file_manager::TaskFileManager;
+use teaclave_crypto::KeyWrappingKey;
 use teaclave_proto::teaclave_scheduler_service::*;
 use teaclave_rpc::endpoint::Endpoint;
+use teaclave_rpc::utils::get_tcs_num;
+use teaclave_service_enclave_utils::metrics::{Counter, Histogram, DURATION_BUCKETS};
 use teaclave_types::*;
 use teaclave_worker::Worker;
 
@@ -31,55 +36,105 @@ use uuid::Uuid;
 
 static WORKER_BASE_DIR: &str = "/tmp/teaclave_agent/";
 
+const PULL_BACKOFF_MIN: Duration = Duration::from_millis(100);
+const PULL_BACKOFF_MAX: Duration = Duration::from_secs(3);
+
+static TASK_DURATION: Histogram = Histogram::new(
+    "teaclave_execution_task_duration_seconds",
+    "Time to execute tasks, including staging files",
+    DURATION_BUCKETS,
+);
+static TASK_FAILURES: Counter = Counter::new(
+    "teaclave_execution_task_failures_total",
+    "Failed tasks by the stage of the failure",
+);
+
 #[derive(Clone)]
 pub(crate) struct TeaclaveExecutionService {
     worker: Arc<Worker>,
     scheduler_client: Arc<Mutex<TeaclaveSchedulerClient>>,
     fusion_base: PathBuf,
+    worker_base: PathBuf,
+    file_streaming: bool,
+    transfer_retry: TransferRetryPolicy,
+    // File keys of pulled tasks are wrapped to it by the scheduler. It is
+    // only kept in memory, as tasks are pulled again after a restart.
+    key_wrapping_key: Arc<KeyWrappingKey>,
 }
 
 impl TeaclaveExecutionService {
     pub(crate) fn new(
         scheduler_service_endpoint: Endpoint,
         fusion_base: impl AsRef<Path>,
+        file_streaming: bool,
+        transfer_retry: TransferRetryPolicy,
     ) -> Result<Self> {
-        let mut i = 0;
-        let channel = loop {
-            match scheduler_service_endpoint.connect() {
-                Ok(channel) => break channel,
-                Err(_) => {
-                    anyhow::ensure!(i < 10, "failed to connect to scheduler service");
-                    log::debug!("Failed to connect to scheduler service, retry {}", i);
-                    i += 1;
-                }
-            }
-            std::thread::sleep(std::time::Duration::from_secs(3));
-        };
+        let channel = scheduler_service_endpoint.connect()?;
         let scheduler_client = Arc::new(Mutex::new(TeaclaveSchedulerClient::new(channel)?));
 
         Ok(TeaclaveExecutionService {
             worker: Arc::new(Worker::default()),
             scheduler_client,
             fusion_base: fusion_base.as_ref().to_owned(),
+            worker_base: PathBuf::from(WORKER_BASE_DIR),
+            file_streaming,
+            transfer_retry,
+            key_wrapping_key: Arc::new(KeyWrappingKey::random()),
         })
     }
 
-    pub(crate) fn start(&mut self) -> Result<()> {
+    /// Runs `worker_num` workers pulling and executing tasks concurrently.
+    /// Each worker stages files of its tasks in its own directory.
+    pub(crate) fn start(&mut self, worker_num: usize) -> Result<()> {
+        let worker_num = max_worker_num(worker_num, get_tcs_num());
+        log::info!("Starting {} execution workers", worker_num);
+
+        let mut handles = Vec::with_capacity(worker_num);
+        for i in 0..worker_num {
+            let mut service = self.clone();
+            service.worker_base = Path::new(WORKER_BASE_DIR).join(format!("worker-{}", i));
+            handles.push(std::thread::spawn(move || service.run()));
+        }
+
+        for handle in handles {
+            let _ = handle.join();
+        }
+
+        Ok(())
+    }
+
+    fn run(&mut self) {
+        let mut backoff = PULL_BACKOFF_MIN;
         loop {
-            std::thread::sleep(std::time::Duration::from_secs(3));
             let staged_task = match self.pull_task() {
-                Ok(staged_task) => staged_task,
+                Ok(staged_task) => {
+                    backoff = PULL_BACKOFF_MIN;
+                    staged_task
+                }
                 Err(e) => {
-                    log::warn!("PullTask Error: {:?}", e);
+                    // Wait a bit longer each time before pulling again,
+                    // whether the queue is empty or pulling keeps failing.
+                    if is_idle(&e) {
+                        log::debug!("PullTask Error: {:?}", e);
+                    } else {
+                        log::error!("PullTask Error: {:?}", e);
+                    }
+                    std::thread::sleep(backoff);
+                    backoff = next_backoff(backoff);
                     continue;
                 }
             };
 
-            log::debug!("InvokeTask: {:?}", staged_task);
-            let result = self.invoke_task(&staged_task);
+            // The staged task is not logged as it holds the file keys.
+            let task_id = staged_task.task_id;
+            log::debug!("InvokeTask: {}", task_id);
+            let timer = TASK_DURATION.start_timer();
+            let result = self.invoke_task(staged_task);
+            let outcome = if result.is_ok() { "ok" } else { "error" };
+            timer.observe_duration(&[("result", outcome)]);
             log::debug!("InvokeTask result: {:?}", result);
 
-            match self.update_task_result(&staged_task.task_id, result) {
+            match self.update_task_result(&task_id, result) {
                 Ok(_) => (),
                 Err(e) => {
                     log::error!("UpdateResult Error: {:?}", e);
@@ -89,37 +144,67 @@ impl TeaclaveExecutionService {
         }
     }
 
-    fn pull_task(&mut self) -> Result<StagedTask> {
-        let request = PullTaskRequest {};
+    fn pull_task(&mut self) -> TeaclaveServiceResponseResult<StagedTask> {
+        let request = PullTaskRequest::new(&self.key_wrapping_key.public_key()[..]);
         let response = self
             .scheduler_client
             .clone()
             .lock()
-            .map_err(|_| anyhow::anyhow!("Cannot lock scheduler client"))?
+            .map_err(|_| {
+                TeaclaveServiceResponseError::InternalError("Cannot lock scheduler client".into())
+            })?
             .pull_task(request)?;
 
-        log::debug!("pull_stask response: {:?}", response);
         Ok(response.staged_task)
     }
 
-    fn invoke_task(&mut self, task: &StagedTask) -> Result<TaskOutputs> {
-        self.update_task_status(&task.task_id, TaskStatus::Running)?;
+    fn invoke_task(&mut self, task: StagedTask) -> Result<TaskOutputs> {
+        self.update_task_status(&task.task_id, TaskStatus::Running)
+            .map_err(failed("status"))?;
+
+        let task = task
+            .unwrap_file_keys(&self.key_wrapping_key)
+            .map_err(failed("prepare"))?;
 
         let file_mgr = TaskFileManager::new(
-            WORKER_BASE_DIR,
+            &self.worker_base,
             &self.fusion_base,
             &task.task_id,
             &task.input_data,
             &task.output_data,
-        )?;
-        let invocation = prepare_task(&task, &file_mgr)?;
-
-        log::debug!("Invoke function: {:?}", invocation);
-        let worker = Worker::default();
-        let summary = worker.invoke_function(invocation)?;
-
-        let outputs_tag = finalize_task(&file_mgr)?;
-        let task_outputs = TaskOutputs::new(summary.as_bytes(), outputs_tag);
+            self.file_streaming,
+            self.transfer_retry,
+        )
+        .map_err(failed("prepare"))?;
+        let invocation = prepare_task(&task, &file_mgr).map_err(failed("prepare"))?;
+        let mut log = vec![format!(
+            "Prepared {} input and {} output files",
+            invocation.input_files.len(),
+            invocation.output_files.len()
+        )];
+
+        log::debug!("Invoke function: {}", invocation.name);
+        log.push(format!(
+            "Invoking function {} with the {} executor",
+            invocation.name, invocation.executor_type
+        ));
+        let summary = self
+            .worker
+            .invoke_function(invocation)
+            .map_err(failed("function"))?;
+        log.push(format!("Function returned {} bytes", summary.len()));
+
+        let outputs = finalize_task(&file_mgr).map_err(failed("finalize"))?;
+        log.push(format!("Uploaded {} output files", outputs.len()));
+        let mut outputs_tag = HashMap::new();
+        let mut outputs_hash = HashMap::new();
+        for (name, (tag, hash)) in outputs {
+            outputs_tag.insert(name.clone(), tag);
+            outputs_hash.insert(name, hash);
+        }
+        let task_outputs = TaskOutputs::new(summary.as_bytes(), outputs_tag)
+            .hashes_map(outputs_hash)
+            .log(log);
         Ok(task_outputs)
     }
 
@@ -153,6 +238,36 @@ impl TeaclaveExecutionService {
     }
 }
 
+/// Limits the workers to the threads the enclave can run, keeping one thread
+/// free for the metrics ECall. The ECall starting the service is already
+/// excluded by `get_tcs_num`.
+fn max_worker_num(worker_num: usize, tcs_num: usize) -> usize {
+    worker_num.min(tcs_num.saturating_sub(1)).max(1)
+}
+
+/// Whether pulling failed because there is no task to run yet, which is not
+/// worth logging as an error.
+fn is_idle(error: &TeaclaveServiceResponseError) -> bool {
+    match error {
+        TeaclaveServiceResponseError::NotFound(_) => true,
+        TeaclaveServiceResponseError::ConnectionError(_) => true,
+        _ => false,
+    }
+}
+
+fn next_backoff(backoff: Duration) -> Duration {
+    std::cmp::min(backoff * 2, PULL_BACKOFF_MAX)
+}
+
+/// Count the failure of a task at the stage, i.e., reporting its status,
+/// preparing files, running the function, or uploading outputs.
+fn failed(stage: &'static str) -> impl FnOnce(anyhow::Error) -> anyhow::Error {
+    move |e| {
+        TASK_FAILURES.inc(&[("stage", stage)]);
+        e
+    }
+}
+
 fn prepare_task(task: &StagedTask, file_mgr: &TaskFileManager) -> Result<StagedFunction> {
     let input_files = file_mgr.prepare_staged_inputs()?;
     let output_files = file_mgr.prepare_staged_outputs()?;
@@ -170,7 +285,7 @@ fn prepare_task(task: &StagedTask, file_mgr: &TaskFileManager) -> Result<StagedF
     Ok(staged_function)
 }
 
-fn finalize_task(file_mgr: &TaskFileManager) -> Result<HashMap<String, FileAuthTag>> {
+fn finalize_task(file_mgr: &TaskFileManager) -> Result<HashMap<String, (FileAuthTag, FileHash)>> {
     file_mgr.upload_outputs()
 }
 
@@ -183,6 +298,33 @@ pub mod tests {
     use url::Url;
     use uuid::Uuid;
 
+    pub fn test_max_worker_num() {
+        assert_eq!(max_worker_num(4, 21), 4);
+        assert_eq!(max_worker_num(32, 21), 20);
+        assert_eq!(max_worker_num(0, 21), 1);
+        assert_eq!(max_worker_num(4, 1), 1);
+    }
+
+    pub fn test_pull_backoff() {
+        assert!(is_idle(&TeaclaveServiceResponseError::NotFound(
+            "none error".into()
+        )));
+        assert!(is_idle(&TeaclaveServiceResponseError::ConnectionError(
+            "timeout".into()
+        )));
+        assert!(!is_idle(&TeaclaveServiceResponseError::RequestError(
+            "data error".into()
+        )));
+
+        let mut backoff = PULL_BACKOFF_MIN;
+        let mut backoffs = Vec::new();
+        for _ in 0..7 {
+            backoff = next_backoff(backoff);
+            backoffs.push(backoff.as_millis());
+        }
+        assert_eq!(backoffs, vec![200, 400, 800, 1600, 3000, 3000, 3000]);
+    }
+
     pub fn test_invoke_echo() {
         let task_id = Uuid::new_v4();
         let function_arguments =
@@ -199,6 +341,8 @@ pub mod tests {
             &staged_task.task_id,
             &staged_task.input_data,
             &staged_task.output_data,
+            true,
+            TransferRetryPolicy::default(),
         )
         .unwrap();
         let invocation = prepare_task(&staged_task, &file_mgr).unwrap();
@@ -258,6 +402,8 @@ pub mod tests {
             &staged_task.task_id,
             &staged_task.input_data,
             &staged_task.output_data,
+            true,
+            TransferRetryPolicy::default(),
         )
         .unwrap();
         let invocation = prepare_task(&staged_task, &file_mgr).unwrap();
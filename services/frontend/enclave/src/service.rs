@@ -26,17 +26,21 @@ use teaclave_proto::teaclave_authentication_service::{
 use teaclave_proto::teaclave_common::UserCredential;
 use teaclave_proto::teaclave_frontend_service::{
     ApproveTaskRequest, ApproveTaskResponse, AssignDataRequest, AssignDataResponse,
-    CreateTaskRequest, CreateTaskResponse, GetFunctionRequest, GetFunctionResponse,
-    GetInputFileRequest, GetInputFileResponse, GetOutputFileRequest, GetOutputFileResponse,
-    GetTaskRequest, GetTaskResponse, InvokeTaskRequest, InvokeTaskResponse,
-    RegisterFunctionRequest, RegisterFunctionResponse, RegisterFusionOutputRequest,
-    RegisterFusionOutputResponse, RegisterInputFileRequest, RegisterInputFileResponse,
-    RegisterInputFromOutputRequest, RegisterInputFromOutputResponse, RegisterOutputFileRequest,
-    RegisterOutputFileResponse, TeaclaveFrontend, UpdateInputFileRequest, UpdateInputFileResponse,
-    UpdateOutputFileRequest, UpdateOutputFileResponse,
+    CreateTaskRequest, CreateTaskResponse, DownloadTaskLogRequest, DownloadTaskLogResponse,
+    DownloadTaskResultRequest, DownloadTaskResultResponse, GetFunctionRequest, GetFunctionResponse,
+    GetInputFileRequest, GetInputFileResponse, GetKeyWrappingKeyRequest, GetKeyWrappingKeyResponse,
+    GetOutputFileRequest, GetOutputFileResponse, GetTaskRequest, GetTaskResponse,
+    InvokeTaskRequest, InvokeTaskResponse, RegisterFunctionRequest, RegisterFunctionResponse,
+    RegisterFusionOutputRequest, RegisterFusionOutputResponse, RegisterInputFileRequest,
+    RegisterInputFileResponse, RegisterInputFromOutputRequest, RegisterInputFromOutputResponse,
+    RegisterOutputFileRequest, RegisterOutputFileResponse, RotateFileKeyRequest,
+    RotateFileKeyResponse, TeaclaveFrontend, UpdateFunctionRequest, UpdateFunctionResponse,
+    UpdateInputFileRequest, UpdateInputFileResponse, UpdateOutputFileRequest,
+    UpdateOutputFileResponse, UploadFunctionRequest,
 };
 use teaclave_proto::teaclave_management_service::TeaclaveManagementClient;
 use teaclave_rpc::endpoint::Endpoint;
+use teaclave_rpc::stream::{StreamReader, StreamWriter};
 use teaclave_rpc::Request;
 use teaclave_service_enclave_utils::{bail, teaclave_service};
 use teaclave_types::{TeaclaveServiceResponseError, TeaclaveServiceResponseResult};
@@ -62,13 +66,9 @@ pub(crate) struct TeaclaveFrontendService {
     management_client: Arc<Mutex<TeaclaveManagementClient>>,
 }
 
-macro_rules! authentication_and_forward_to_management {
-    ($service: ident, $request: ident, $func: ident) => {{
-        match $service.authenticate(&$request) {
-            Ok(true) => (),
-            _ => bail!(TeaclaveFrontendError::AuthenticationError),
-        }
-
+// Streams of streaming methods are passed through to the management service.
+macro_rules! forward_to_management {
+    ($service: ident, $request: ident, $func: ident $(, $stream: ident)?) => {{
         let client = $service.management_client.clone();
         let mut client = client
             .lock()
@@ -76,7 +76,7 @@ macro_rules! authentication_and_forward_to_management {
         client.metadata_mut().clear();
         client.metadata_mut().extend($request.metadata);
 
-        let response = client.$func($request.message);
+        let response = client.$func($request.message $(, $stream)?);
 
         client.metadata_mut().clear();
         let response = response?;
@@ -84,39 +84,28 @@ macro_rules! authentication_and_forward_to_management {
     }};
 }
 
+macro_rules! authentication_and_forward_to_management {
+    ($service: ident, $request: ident, $func: ident $(, $stream: ident)?) => {{
+        match $service.authenticate(&$request) {
+            Ok(true) => (),
+            _ => bail!(TeaclaveFrontendError::AuthenticationError),
+        }
+
+        forward_to_management!($service, $request, $func $(, $stream)?)
+    }};
+}
+
 impl TeaclaveFrontendService {
     pub(crate) fn new(
         authentication_service_endpoint: Endpoint,
         management_service_endpoint: Endpoint,
     ) -> Result<Self> {
-        let mut i = 0;
-        let authentication_channel = loop {
-            match authentication_service_endpoint.connect() {
-                Ok(channel) => break channel,
-                Err(_) => {
-                    anyhow::ensure!(i < 10, "failed to connect to authentication service");
-                    log::debug!("Failed to connect to authentication service, retry {}", i);
-                    i += 1;
-                }
-            }
-            std::thread::sleep(std::time::Duration::from_secs(3));
-        };
+        let authentication_channel = authentication_service_endpoint.connect()?;
         let authentication_client = Arc::new(Mutex::new(
             TeaclaveAuthenticationInternalClient::new(authentication_channel)?,
         ));
 
-        let mut i = 0;
-        let management_channel = loop {
-            match management_service_endpoint.connect() {
-                Ok(channel) => break channel,
-                Err(_) => {
-                    anyhow::ensure!(i < 10, "failed to connect to management service");
-                    log::debug!("Failed to connect to management service, retry {}", i);
-                    i += 1;
-                }
-            }
-            std::thread::sleep(std::time::Duration::from_secs(3));
-        };
+        let management_channel = management_service_endpoint.connect()?;
         let management_client = Arc::new(Mutex::new(TeaclaveManagementClient::new(
             management_channel,
         )?));
@@ -191,6 +180,13 @@ impl TeaclaveFrontend for TeaclaveFrontendService {
         authentication_and_forward_to_management!(self, request, register_function)
     }
 
+    fn update_function(
+        &self,
+        request: Request<UpdateFunctionRequest>,
+    ) -> TeaclaveServiceResponseResult<UpdateFunctionResponse> {
+        authentication_and_forward_to_management!(self, request, update_function)
+    }
+
     fn get_function(
         &self,
         request: Request<GetFunctionRequest>,
@@ -232,6 +228,44 @@ impl TeaclaveFrontend for TeaclaveFrontendService {
     ) -> TeaclaveServiceResponseResult<InvokeTaskResponse> {
         authentication_and_forward_to_management!(self, request, invoke_task)
     }
+
+    fn get_key_wrapping_key(
+        &self,
+        request: Request<GetKeyWrappingKeyRequest>,
+    ) -> TeaclaveServiceResponseResult<GetKeyWrappingKeyResponse> {
+        authentication_and_forward_to_management!(self, request, get_key_wrapping_key)
+    }
+
+    fn rotate_file_key(
+        &self,
+        request: Request<RotateFileKeyRequest>,
+    ) -> TeaclaveServiceResponseResult<RotateFileKeyResponse> {
+        authentication_and_forward_to_management!(self, request, rotate_file_key)
+    }
+
+    fn upload_function(
+        &self,
+        request: Request<UploadFunctionRequest>,
+        stream: &mut StreamReader,
+    ) -> TeaclaveServiceResponseResult<RegisterFunctionResponse> {
+        authentication_and_forward_to_management!(self, request, upload_function, stream)
+    }
+
+    fn download_task_result(
+        &self,
+        request: Request<DownloadTaskResultRequest>,
+        stream: &mut StreamWriter,
+    ) -> TeaclaveServiceResponseResult<DownloadTaskResultResponse> {
+        authentication_and_forward_to_management!(self, request, download_task_result, stream)
+    }
+
+    fn download_task_log(
+        &self,
+        request: Request<DownloadTaskLogRequest>,
+        stream: &mut StreamWriter,
+    ) -> TeaclaveServiceResponseResult<DownloadTaskLogResponse> {
+        authentication_and_forward_to_management!(self, request, download_task_log, stream)
+    }
 }
 
 impl TeaclaveFrontendService {
@@ -26,10 +26,11 @@ use anyhow::{anyhow, Result};
 
 use std::prelude::v1::*;
 
-use teaclave_attestation::{verifier, AttestationConfig, RemoteAttestation};
+use teaclave_attestation::policy::AttestationPolicy;
+use teaclave_attestation::{AttestationConfig, RemoteAttestation};
 use teaclave_binder::proto::{
-    ECallCommand, FinalizeEnclaveInput, FinalizeEnclaveOutput, InitEnclaveInput, InitEnclaveOutput,
-    StartServiceInput, StartServiceOutput,
+    ECallCommand, FinalizeEnclaveInput, FinalizeEnclaveOutput, GetMetricsInput, GetMetricsOutput,
+    InitEnclaveInput, InitEnclaveOutput, StartServiceInput, StartServiceOutput,
 };
 use teaclave_binder::{handle_ecall, register_ecall_handler};
 use teaclave_config::build::{AS_ROOT_CA_CERT, AUDITOR_PUBLIC_KEYS, MANAGEMENT_INBOUND_SERVICES};
@@ -39,7 +40,11 @@ use teaclave_proto::teaclave_management_service::{
 };
 use teaclave_rpc::config::SgxTrustedTlsServerConfig;
 use teaclave_rpc::server::SgxTrustedTlsServer;
-use teaclave_service_enclave_utils::{create_trusted_storage_endpoint, ServiceEnclave};
+use teaclave_service_enclave_utils::metrics;
+use teaclave_service_enclave_utils::{
+    create_trusted_scheduler_endpoint, create_trusted_storage_endpoint, watch_revocation_list,
+    ServiceEnclave,
+};
 use teaclave_types::{EnclaveInfo, TeeServiceError, TeeServiceResult};
 
 mod service;
@@ -47,6 +52,8 @@ mod service;
 fn start_service(config: &RuntimeConfig) -> Result<()> {
     let listen_address = config.internal_endpoints.management.listen_address;
     let attestation_config = AttestationConfig::from_teaclave_config(&config)?;
+    let attestation_policy = AttestationPolicy::from_teaclave_config(&config)?;
+    watch_revocation_list(&config)?;
     let attested_tls_config = RemoteAttestation::new(attestation_config)
         .generate_and_endorse()?
         .attested_tls_config()
@@ -68,7 +75,7 @@ fn start_service(config: &RuntimeConfig) -> Result<()> {
             .attestation_report_verifier(
                 accepted_enclave_attrs,
                 AS_ROOT_CA_CERT,
-                verifier::universal_quote_verifier,
+                attestation_policy.clone(),
             )?;
     let mut server =
         SgxTrustedTlsServer::<TeaclaveManagementResponse, TeaclaveManagementRequest>::new(
@@ -80,11 +87,22 @@ fn start_service(config: &RuntimeConfig) -> Result<()> {
         &config.internal_endpoints.storage.advertised_address,
         &enclave_info,
         AS_ROOT_CA_CERT,
-        verifier::universal_quote_verifier,
+        attestation_policy.clone(),
+        attested_tls_config.clone(),
+    )?;
+
+    let scheduler_service_endpoint = create_trusted_scheduler_endpoint(
+        &config.internal_endpoints.scheduler.advertised_address,
+        &enclave_info,
+        AS_ROOT_CA_CERT,
+        attestation_policy,
         attested_tls_config,
     )?;
 
-    let service = service::TeaclaveManagementService::new(storage_service_endpoint)?;
+    let service = service::TeaclaveManagementService::new(
+        storage_service_endpoint,
+        scheduler_service_endpoint,
+    )?;
     match server.start(service) {
         Ok(_) => (),
         Err(e) => {
@@ -117,11 +135,17 @@ fn handle_finalize_enclave(_: &FinalizeEnclaveInput) -> TeeServiceResult<Finaliz
     Ok(FinalizeEnclaveOutput)
 }
 
+#[handle_ecall]
+fn handle_get_metrics(_: &GetMetricsInput) -> TeeServiceResult<GetMetricsOutput> {
+    Ok(GetMetricsOutput::new(metrics::gather()))
+}
+
 register_ecall_handler!(
     type ECallCommand,
     (ECallCommand::StartService, StartServiceInput, StartServiceOutput),
     (ECallCommand::InitEnclave, InitEnclaveInput, InitEnclaveOutput),
     (ECallCommand::FinalizeEnclave, FinalizeEnclaveInput, FinalizeEnclaveOutput),
+    (ECallCommand::GetMetrics, GetMetricsInput, GetMetricsOutput),
 );
 
 #[cfg(feature = "enclave_unit_test")]
@@ -18,31 +18,48 @@
 use anyhow::{anyhow, Result};
 use std::collections::HashMap;
 use std::convert::TryInto;
+use std::io::{Read, Write};
 use std::prelude::v1::*;
 use std::sync::{Arc, SgxMutex as Mutex};
+use teaclave_crypto::KeyWrappingKey;
 use teaclave_proto::teaclave_frontend_service::{
     ApproveTaskRequest, ApproveTaskResponse, AssignDataRequest, AssignDataResponse,
-    CreateTaskRequest, CreateTaskResponse, GetFunctionRequest, GetFunctionResponse,
-    GetInputFileRequest, GetInputFileResponse, GetOutputFileRequest, GetOutputFileResponse,
-    GetTaskRequest, GetTaskResponse, InvokeTaskRequest, InvokeTaskResponse,
-    RegisterFunctionRequest, RegisterFunctionResponse, RegisterFusionOutputRequest,
-    RegisterFusionOutputResponse, RegisterInputFileRequest, RegisterInputFileResponse,
-    RegisterInputFromOutputRequest, RegisterInputFromOutputResponse, RegisterOutputFileRequest,
-    RegisterOutputFileResponse, UpdateInputFileRequest, UpdateInputFileResponse,
-    UpdateOutputFileRequest, UpdateOutputFileResponse,
+    CreateTaskRequest, CreateTaskResponse, DownloadTaskLogRequest, DownloadTaskLogResponse,
+    DownloadTaskResultRequest, DownloadTaskResultResponse, GetFunctionRequest, GetFunctionResponse,
+    GetInputFileRequest, GetInputFileResponse, GetKeyWrappingKeyRequest, GetKeyWrappingKeyResponse,
+    GetOutputFileRequest, GetOutputFileResponse, GetTaskRequest, GetTaskResponse,
+    InvokeTaskRequest, InvokeTaskResponse, RegisterFunctionRequest, RegisterFunctionResponse,
+    RegisterFusionOutputRequest, RegisterFusionOutputResponse, RegisterInputFileRequest,
+    RegisterInputFileResponse, RegisterInputFromOutputRequest, RegisterInputFromOutputResponse,
+    RegisterOutputFileRequest, RegisterOutputFileResponse, RotateFileKeyRequest,
+    RotateFileKeyResponse, UpdateFunctionRequest, UpdateFunctionResponse, UpdateInputFileRequest,
+    UpdateInputFileResponse, UpdateOutputFileRequest, UpdateOutputFileResponse,
+    UploadFunctionRequest,
 };
 use teaclave_proto::teaclave_management_service::TeaclaveManagement;
+use teaclave_proto::teaclave_scheduler_service::{
+    GetKeyWrappingPublicKeyRequest, TeaclaveSchedulerClient,
+};
 use teaclave_proto::teaclave_storage_service::{
-    EnqueueRequest, GetRequest, PutRequest, TeaclaveStorageClient,
+    CompareAndSwapEntry, CompareAndSwapRequest, EnqueueRequest, GetRequest, GetStreamRequest,
+    PutRequest, PutStreamRequest, TeaclaveStorageClient,
 };
 use teaclave_rpc::endpoint::Endpoint;
+use teaclave_rpc::stream::{StreamReader, StreamWriter};
 use teaclave_rpc::Request;
-use teaclave_service_enclave_utils::{ensure, teaclave_service};
+use teaclave_service_enclave_utils::key_wrapping::{self, MANAGEMENT_KEY_WRAPPING_KEY};
+use teaclave_service_enclave_utils::{bail, ensure, teaclave_service};
 use teaclave_types::*;
 use thiserror::Error;
 use url::Url;
 use uuid::Uuid;
 
+// Attempts to update items in storage while other requests keep changing them.
+const MAX_UPDATE_ATTEMPTS: usize = 8;
+// Max length of function payloads uploaded in streams, which are kept in the
+// enclave heap to compute the revision hash.
+const MAX_FUNCTION_PAYLOAD_LEN: u64 = 4 * 1_024 * 1_024;
+
 #[derive(Error, Debug)]
 enum ServiceError {
     #[error("invalid request")]
@@ -55,6 +72,8 @@ enum ServiceError {
     PermissionDenied,
     #[error("bad task")]
     BadTask,
+    #[error("privacy budget exceeded")]
+    PrivacyBudgetExceeded,
 }
 
 impl From<ServiceError> for TeaclaveServiceResponseError {
@@ -67,6 +86,8 @@ impl From<ServiceError> for TeaclaveServiceResponseError {
 #[derive(Clone)]
 pub(crate) struct TeaclaveManagementService {
     storage_client: Arc<Mutex<TeaclaveStorageClient>>,
+    scheduler_endpoint: Arc<Endpoint>,
+    key_wrapping_key: Arc<KeyWrappingKey>,
 }
 
 impl TeaclaveManagement for TeaclaveManagementService {
@@ -77,12 +98,11 @@ impl TeaclaveManagement for TeaclaveManagementService {
     ) -> TeaclaveServiceResponseResult<RegisterInputFileResponse> {
         let user_id = self.get_request_user_id(request.metadata())?;
         let request = request.message;
-        let input_file = TeaclaveInputFile::new(
-            request.url,
-            request.cmac,
-            request.crypto_info,
-            vec![user_id],
-        );
+        let crypto_info = self.wrap_file_crypto(&request.crypto_info)?;
+        let input_file =
+            TeaclaveInputFile::new(request.url, request.cmac, crypto_info, vec![user_id])
+                .privacy_budget(request.privacy_budget)
+                .hash(request.hash);
 
         self.write_to_db(&input_file)
             .map_err(|_| ServiceError::StorageError)?;
@@ -115,7 +135,9 @@ impl TeaclaveManagement for TeaclaveManagementService {
             old_input_file.cmac,
             old_input_file.crypto_info,
             old_input_file.owner,
-        );
+        )
+        .privacy_budget(old_input_file.privacy_budget)
+        .hash(old_input_file.hash);
 
         self.write_to_db(&input_file)
             .map_err(|_| ServiceError::StorageError)?;
@@ -131,7 +153,22 @@ impl TeaclaveManagement for TeaclaveManagementService {
     ) -> TeaclaveServiceResponseResult<RegisterOutputFileResponse> {
         let user_id = self.get_request_user_id(request.metadata())?;
         let request = request.message;
-        let output_file = TeaclaveOutputFile::new(request.url, request.crypto_info, vec![user_id]);
+        let output_file = match (request.crypto_info, request.recipient_public_key) {
+            (Some(crypto_info), None) => {
+                let crypto_info = self.wrap_file_crypto(&crypto_info)?;
+                TeaclaveOutputFile::new(request.url, crypto_info, vec![user_id])
+            }
+            (None, Some(recipient_public_key)) => {
+                let crypto_info = FileCrypto::default();
+                let recipient_crypto_info = crypto_info
+                    .wrap(&recipient_public_key)
+                    .map_err(|_| ServiceError::InvalidRequest)?;
+                let crypto_info = self.wrap_file_crypto(&crypto_info)?;
+                TeaclaveOutputFile::new(request.url, crypto_info, vec![user_id])
+                    .recipient_crypto_info(Some(recipient_crypto_info))
+            }
+            _ => bail!(ServiceError::InvalidRequest),
+        };
 
         self.write_to_db(&output_file)
             .map_err(|_| ServiceError::StorageError)?;
@@ -163,7 +200,8 @@ impl TeaclaveManagement for TeaclaveManagementService {
             request.url,
             old_output_file.crypto_info,
             old_output_file.owner,
-        );
+        )
+        .recipient_crypto_info(old_output_file.recipient_crypto_info);
 
         self.write_to_db(&output_file)
             .map_err(|_| ServiceError::StorageError)?;
@@ -240,7 +278,8 @@ impl TeaclaveManagement for TeaclaveManagementService {
             ServiceError::PermissionDenied
         );
 
-        let response = GetOutputFileResponse::new(output_file.owner, output_file.cmac);
+        let response = GetOutputFileResponse::new(output_file.owner, output_file.cmac)
+            .crypto_info(output_file.recipient_crypto_info);
         Ok(response)
     }
 
@@ -260,7 +299,8 @@ impl TeaclaveManagement for TeaclaveManagementService {
             ServiceError::PermissionDenied
         );
 
-        let response = GetInputFileResponse::new(input_file.owner, input_file.cmac);
+        let response =
+            GetInputFileResponse::new(input_file.owner, input_file.cmac, input_file.privacy_budget);
         Ok(response)
     }
 
@@ -275,13 +315,53 @@ impl TeaclaveManagement for TeaclaveManagementService {
             .id(Uuid::new_v4())
             .owner(user_id);
 
-        self.write_to_db(&function)
+        self.write_function(&function)
             .map_err(|_| ServiceError::StorageError)?;
 
         let response = RegisterFunctionResponse::new(function.external_id());
         Ok(response)
     }
 
+    // access control: old_function.owner == user_id
+    // a new revision is created, the old revision is kept unchanged so that
+    // tasks pinned to it are not affected
+    fn update_function(
+        &self,
+        request: Request<UpdateFunctionRequest>,
+    ) -> TeaclaveServiceResponseResult<UpdateFunctionResponse> {
+        let user_id = self.get_request_user_id(request.metadata())?;
+        let request = request.message;
+
+        let old_function: Function = self
+            .read_from_db(&request.function_id)
+            .map_err(|_| ServiceError::PermissionDenied)?;
+
+        ensure!(
+            old_function.owner == user_id,
+            ServiceError::PermissionDenied
+        );
+
+        let function = Function::new()
+            .id(Uuid::new_v4())
+            .name(old_function.name)
+            .description(request.description)
+            .executor_type(request.executor_type)
+            .payload(request.payload)
+            .public(request.public)
+            .arguments(request.arguments)
+            .inputs(request.inputs)
+            .outputs(request.outputs)
+            .owner(old_function.owner)
+            .version(request.version)
+            .previous_revision(old_function.id);
+
+        self.write_function(&function)
+            .map_err(|_| ServiceError::StorageError)?;
+
+        let response = UpdateFunctionResponse::new(function.external_id(), function.revision_hash);
+        Ok(response)
+    }
+
     // access control: function.public || function.owner == user_id
     fn get_function(
         &self,
@@ -289,8 +369,8 @@ impl TeaclaveManagement for TeaclaveManagementService {
     ) -> TeaclaveServiceResponseResult<GetFunctionResponse> {
         let user_id = self.get_request_user_id(request.metadata())?;
 
-        let function: Function = self
-            .read_from_db(&request.message.function_id)
+        let function = self
+            .read_function(&request.message.function_id)
             .map_err(|_| ServiceError::PermissionDenied)?;
 
         ensure!(
@@ -298,6 +378,8 @@ impl TeaclaveManagement for TeaclaveManagementService {
             ServiceError::PermissionDenied
         );
 
+        // Functions stored before revisions were introduced have no hash
+        let revision_hash = function.compute_revision_hash();
         let response = GetFunctionResponse {
             name: function.name,
             description: function.description,
@@ -306,6 +388,11 @@ impl TeaclaveManagement for TeaclaveManagementService {
             payload: function.payload,
             public: function.public,
             arguments: function.arguments,
+            version: function.version,
+            revision_hash,
+            previous_revision: function
+                .previous_revision
+                .map(|id| ExternalID::new(Function::key_prefix(), id)),
             inputs: function.inputs,
             outputs: function.outputs,
         };
@@ -325,8 +412,8 @@ impl TeaclaveManagement for TeaclaveManagementService {
 
         let request = request.message;
 
-        let function: Function = self
-            .read_from_db(&request.function_id)
+        let function = self
+            .read_function(&request.function_id)
             .map_err(|_| ServiceError::PermissionDenied)?;
 
         let task = Task::<Create>::new(
@@ -364,10 +451,16 @@ impl TeaclaveManagement for TeaclaveManagementService {
 
         log::debug!("GetTask: {:?}", ts);
 
+        let mut result = ts.result.clone();
+        if let TaskResult::Ok(outputs) = &mut result {
+            self.read_task_outputs(&ts, outputs)
+                .map_err(|_| ServiceError::StorageError)?;
+        }
         let response = GetTaskResponse {
             task_id: ts.external_id(),
             creator: ts.creator,
             function_id: ts.function_id,
+            function_revision_hash: ts.function_revision_hash,
             function_owner: ts.function_owner,
             function_arguments: ts.function_arguments,
             inputs_ownership: ts.inputs_ownership,
@@ -376,7 +469,7 @@ impl TeaclaveManagement for TeaclaveManagementService {
             approved_users: ts.approved_users,
             assigned_inputs: ts.assigned_inputs.external_ids(),
             assigned_outputs: ts.assigned_outputs.external_ids(),
-            result: ts.result,
+            result,
             status: ts.status,
         };
         Ok(response)
@@ -477,19 +570,28 @@ impl TeaclaveManagement for TeaclaveManagementService {
         let user_id = self.get_request_user_id(request.metadata())?;
         let request = request.message;
 
-        let ts: TaskState = self
-            .read_from_db(&request.task_id)
+        ensure!(
+            TaskState::match_prefix(&request.task_id.prefix),
+            ServiceError::PermissionDenied
+        );
+        let expected = self
+            .get_from_db(&request.task_id)
             .map_err(|_| ServiceError::PermissionDenied)?;
+        let ts = TaskState::from_slice(&expected).map_err(|_| ServiceError::PermissionDenied)?;
 
         // Early validation
         ensure!(ts.has_creator(&user_id), ServiceError::PermissionDenied);
 
-        let function: Function = self
-            .read_from_db(&ts.function_id)
+        let function = self
+            .read_function(&ts.function_id)
             .map_err(|_| ServiceError::PermissionDenied)?;
 
         log::debug!("InvokeTask: get function: {:?}", function);
 
+        let input_ids = ts.assigned_inputs.external_ids();
+        let function_arguments = ts.function_arguments.clone();
+        let differentially_private = function.is_differentially_private();
+
         let mut task: Task<Stage> = ts.try_into().map_err(|e| {
             log::warn!("Stage state error: {:?}", e);
             ServiceError::PermissionDenied
@@ -498,35 +600,184 @@ impl TeaclaveManagement for TeaclaveManagementService {
         log::debug!("InvokeTask: get task: {:?}", task);
 
         let staged_task = task.stage_for_running(&user_id, function)?;
+        let staged_task = self.wrap_staged_task(staged_task)?;
 
-        log::debug!("InvokeTask: staged task: {:?}", staged_task);
+        // The task is staged only if it is still the approved one, so that a
+        // concurrent invocation can neither spend the budget nor enqueue the
+        // task again.
+        let ts: TaskState = task.into();
+        let key = ts.key();
+        let staged = ts.to_vec().map_err(|_| ServiceError::DataError)?;
+        ensure!(
+            self.swap_in_db(&key, &expected, &staged)?,
+            ServiceError::PermissionDenied
+        );
 
-        self.enqueue_to_db(StagedTask::get_queue_key().as_bytes(), &staged_task)?;
+        // The budget is spent once the task is known to be runnable, and given
+        // back if it cannot be queued. The task is approved again on failures.
+        let spent = match self.spend_privacy_budget(
+            &input_ids,
+            differentially_private,
+            &function_arguments,
+        ) {
+            Ok(spent) => spent,
+            Err(e) => {
+                self.restore_approved_task(&key, &staged, &expected);
+                return Err(e);
+            }
+        };
+        if let Err(e) = self.enqueue_to_db(StagedTask::get_queue_key().as_bytes(), &staged_task) {
+            if let Some(cost) = spent {
+                self.refund_privacy_budget(&input_ids, &cost);
+            }
+            self.restore_approved_task(&key, &staged, &expected);
+            return Err(e);
+        }
+
+        Ok(InvokeTaskResponse)
+    }
+
+    // access control:
+    // 1) exisiting_file.owner_list.len() == 1
+    // 2) user_id in existing_file.owner_list
+    fn rotate_file_key(
+        &self,
+        request: Request<RotateFileKeyRequest>,
+    ) -> TeaclaveServiceResponseResult<RotateFileKeyResponse> {
+        let user_id = self.get_request_user_id(request.metadata())?;
+        let request = request.message;
+
+        let input_file: TeaclaveInputFile = self
+            .read_from_db(&request.data_id)
+            .map_err(|_| ServiceError::PermissionDenied)?;
+
+        ensure!(
+            input_file.owner == OwnerList::from(vec![user_id.clone()]),
+            ServiceError::PermissionDenied
+        );
+
+        // The re-encrypted copy is written to a new location, so the file
+        // stays readable with the old key until the task has finished.
+        let crypto_info = self.wrap_file_crypto(&request.crypto_info)?;
+        let output_file = TeaclaveOutputFile::new(request.url, crypto_info, vec![user_id.clone()]);
+        self.write_to_db(&output_file)
+            .map_err(|_| ServiceError::StorageError)?;
+
+        let mut task = Task::<Create>::rotate_file_key(user_id.clone(), input_file, output_file)
+            .map_err(|e| {
+                log::warn!("RotateFileKey: {:?}", e);
+                ServiceError::BadTask
+            })?;
+
+        let staged_task = task.stage_for_running(&user_id, Function::reencrypt_file())?;
+        let staged_task = self.wrap_staged_task(staged_task)?;
 
         let ts: TaskState = task.into();
         self.write_to_db(&ts)
             .map_err(|_| ServiceError::StorageError)?;
 
-        Ok(InvokeTaskResponse)
+        self.enqueue_to_db(StagedTask::get_queue_key().as_bytes(), &staged_task)?;
+
+        Ok(RotateFileKeyResponse::new(ts.external_id()))
+    }
+
+    // access control: none
+    fn get_key_wrapping_key(
+        &self,
+        _request: Request<GetKeyWrappingKeyRequest>,
+    ) -> TeaclaveServiceResponseResult<GetKeyWrappingKeyResponse> {
+        let public_key = self.key_wrapping_key.public_key();
+        Ok(GetKeyWrappingKeyResponse::new(public_key.to_vec()))
+    }
+
+    // access_control: none
+    fn upload_function(
+        &self,
+        request: Request<UploadFunctionRequest>,
+        stream: &mut StreamReader,
+    ) -> TeaclaveServiceResponseResult<RegisterFunctionResponse> {
+        let user_id = self.get_request_user_id(request.metadata())?;
+
+        let mut payload = Vec::new();
+        stream
+            .take(MAX_FUNCTION_PAYLOAD_LEN + 1)
+            .read_to_end(&mut payload)
+            .map_err(|_| ServiceError::InvalidRequest)?;
+        ensure!(
+            payload.len() as u64 <= MAX_FUNCTION_PAYLOAD_LEN,
+            ServiceError::InvalidRequest
+        );
+        let function = Function::from(request.message.function)
+            .payload(payload)
+            .id(Uuid::new_v4())
+            .owner(user_id);
+
+        self.write_function(&function)
+            .map_err(|_| ServiceError::StorageError)?;
+
+        let response = RegisterFunctionResponse::new(function.external_id());
+        Ok(response)
+    }
+
+    // access control: task.participants.contains(&user_id)
+    fn download_task_result(
+        &self,
+        request: Request<DownloadTaskResultRequest>,
+        stream: &mut StreamWriter,
+    ) -> TeaclaveServiceResponseResult<DownloadTaskResultResponse> {
+        let user_id = self.get_request_user_id(request.metadata())?;
+
+        let ts: TaskState = self
+            .read_from_db(&request.message.task_id)
+            .map_err(|_| ServiceError::PermissionDenied)?;
+
+        ensure!(ts.has_participant(&user_id), ServiceError::PermissionDenied);
+
+        if ts.result.is_ok() {
+            self.get_stream_from_db(ts.return_value_key(), stream)
+                .map_err(|_| ServiceError::StorageError)?;
+        }
+
+        Ok(DownloadTaskResultResponse::new(ts.result))
+    }
+
+    // access control: task.participants.contains(&user_id)
+    fn download_task_log(
+        &self,
+        request: Request<DownloadTaskLogRequest>,
+        stream: &mut StreamWriter,
+    ) -> TeaclaveServiceResponseResult<DownloadTaskLogResponse> {
+        let user_id = self.get_request_user_id(request.metadata())?;
+
+        let ts: TaskState = self
+            .read_from_db(&request.message.task_id)
+            .map_err(|_| ServiceError::PermissionDenied)?;
+
+        ensure!(ts.has_participant(&user_id), ServiceError::PermissionDenied);
+
+        if ts.result.is_ok() {
+            self.get_stream_from_db(ts.log_key(), stream)
+                .map_err(|_| ServiceError::StorageError)?;
+        }
+
+        Ok(DownloadTaskLogResponse)
     }
 }
 
 impl TeaclaveManagementService {
-    pub(crate) fn new(storage_service_endpoint: Endpoint) -> Result<Self> {
-        let mut i = 0;
-        let channel = loop {
-            match storage_service_endpoint.connect() {
-                Ok(channel) => break channel,
-                Err(_) => {
-                    anyhow::ensure!(i < 10, "failed to connect to storage service");
-                    log::debug!("Failed to connect to storage service, retry {}", i);
-                    i += 1;
-                }
-            }
-            std::thread::sleep(std::time::Duration::from_secs(3));
+    pub(crate) fn new(
+        storage_service_endpoint: Endpoint,
+        scheduler_service_endpoint: Endpoint,
+    ) -> Result<Self> {
+        let channel = storage_service_endpoint.connect()?;
+        let mut storage_client = TeaclaveStorageClient::new(channel)?;
+        let key_wrapping_key =
+            key_wrapping::load_or_create(&mut storage_client, MANAGEMENT_KEY_WRAPPING_KEY)?;
+        let service = Self {
+            storage_client: Arc::new(Mutex::new(storage_client)),
+            scheduler_endpoint: Arc::new(scheduler_service_endpoint),
+            key_wrapping_key: Arc::new(key_wrapping_key),
         };
-        let storage_client = Arc::new(Mutex::new(TeaclaveStorageClient::new(channel)?));
-        let service = Self { storage_client };
 
         #[cfg(test_mode)]
         service.add_mock_data()?;
@@ -538,11 +789,58 @@ impl TeaclaveManagementService {
         let uuid = Uuid::new_v4();
         let url = format!("fusion:///TEACLAVE_FUSION_BASE/{}.fusion", uuid.to_string());
         let url = Url::parse(&url).map_err(|_| anyhow!("invalid url"))?;
-        let crypto_info = FileCrypto::default();
+        let crypto_info = FileCrypto::default().wrap(&self.key_wrapping_key.public_key())?;
 
         Ok(TeaclaveOutputFile::new(url, crypto_info, owners))
     }
 
+    // File keys are only stored wrapped to the key wrapping key.
+    fn wrap_file_crypto(
+        &self,
+        crypto_info: &FileCrypto,
+    ) -> TeaclaveServiceResponseResult<FileCrypto> {
+        if crypto_info.is_wrapped() {
+            // Fail early instead of when the task is staged.
+            crypto_info
+                .unwrap_with(&self.key_wrapping_key)
+                .map_err(|_| ServiceError::InvalidRequest)?;
+            return Ok(crypto_info.clone());
+        }
+        let crypto_info = crypto_info
+            .wrap(&self.key_wrapping_key.public_key())
+            .map_err(|_| ServiceError::DataError)?;
+        Ok(crypto_info)
+    }
+
+    // Staged tasks are queued with their file keys wrapped to the scheduler,
+    // which wraps them to the execution enclave pulling the task. The public
+    // key is fetched from the scheduler over the attested channel, so that it
+    // cannot be replaced by anyone else.
+    fn wrap_staged_task(
+        &self,
+        staged_task: StagedTask,
+    ) -> TeaclaveServiceResponseResult<StagedTask> {
+        let scheduler_public_key = self.get_scheduler_public_key().map_err(|e| {
+            log::warn!("Cannot get the public key of the scheduler: {:?}", e);
+            ServiceError::DataError
+        })?;
+        let staged_task = staged_task
+            .rewrap_file_keys(&self.key_wrapping_key, &scheduler_public_key)
+            .map_err(|e| {
+                log::warn!("Cannot wrap file keys: {:?}", e);
+                ServiceError::DataError
+            })?;
+        Ok(staged_task)
+    }
+
+    fn get_scheduler_public_key(&self) -> Result<Vec<u8>> {
+        let channel = self.scheduler_endpoint.connect()?;
+        let mut scheduler_client = TeaclaveSchedulerClient::new(channel)?;
+        let response =
+            scheduler_client.get_key_wrapping_public_key(GetKeyWrappingPublicKeyRequest::new())?;
+        Ok(response.public_key)
+    }
+
     fn get_request_user_id(
         &self,
         meta: &HashMap<String, String>,
@@ -551,6 +849,113 @@ impl TeaclaveManagementService {
         Ok(user_id.to_string().into())
     }
 
+    // Files with a privacy budget can only be consumed by a differentially
+    // private function, and each invocation spends the epsilon and delta in
+    // its arguments from every such file. Returns the cost if any budget was
+    // spent.
+    fn spend_privacy_budget(
+        &self,
+        input_ids: &HashMap<String, ExternalID>,
+        differentially_private: bool,
+        arguments: &FunctionArguments,
+    ) -> TeaclaveServiceResponseResult<Option<PrivacyBudget>> {
+        let mut spent = None;
+        self.update_in_db(input_ids.values(), |file: &mut TeaclaveInputFile| {
+            let budget = match file.privacy_budget.as_mut() {
+                Some(budget) => budget,
+                None => return Ok(()),
+            };
+            ensure!(differentially_private, ServiceError::BadTask);
+            let cost = PrivacyBudget::from_arguments(arguments).map_err(|e| {
+                log::warn!("InvokeTask: invalid privacy cost: {:?}", e);
+                ServiceError::BadTask
+            })?;
+            budget.spend(&cost).map_err(|e| {
+                log::warn!("InvokeTask: {:?}", e);
+                ServiceError::PrivacyBudgetExceeded
+            })?;
+            spent = Some(cost);
+            Ok(())
+        })?;
+
+        Ok(spent)
+    }
+
+    fn refund_privacy_budget(&self, input_ids: &HashMap<String, ExternalID>, cost: &PrivacyBudget) {
+        let result = self.update_in_db(input_ids.values(), |file: &mut TeaclaveInputFile| {
+            if let Some(budget) = file.privacy_budget.as_mut() {
+                budget.refund(cost);
+            }
+            Ok(())
+        });
+        if let Err(e) = result {
+            log::error!("InvokeTask: cannot refund privacy budget: {:?}", e);
+        }
+    }
+
+    // Applies the update to every item and writes them back together, unless
+    // one of them was changed in the meantime; in that case the items are read
+    // and updated again.
+    fn update_in_db<'a, T: Storable>(
+        &self,
+        keys: impl IntoIterator<Item = &'a ExternalID> + Clone,
+        mut update: impl FnMut(&mut T) -> TeaclaveServiceResponseResult<()>,
+    ) -> TeaclaveServiceResponseResult<()> {
+        for _ in 0..MAX_UPDATE_ATTEMPTS {
+            let mut client = self
+                .storage_client
+                .lock()
+                .map_err(|_| ServiceError::StorageError)?;
+            let mut entries: Vec<CompareAndSwapEntry> = Vec::new();
+            for key in keys.clone() {
+                ensure!(T::match_prefix(&key.prefix), ServiceError::DataError);
+                let key = key.to_bytes();
+                if entries.iter().any(|entry| entry.key == key) {
+                    continue;
+                }
+                let expected = client
+                    .get(GetRequest::new(key.as_slice()))
+                    .map_err(|_| ServiceError::StorageError)?
+                    .value;
+                let mut item = T::from_slice(&expected).map_err(|_| ServiceError::DataError)?;
+                update(&mut item)?;
+                let value = item.to_vec().map_err(|_| ServiceError::DataError)?;
+                entries.push(CompareAndSwapEntry::new(key, expected, value));
+            }
+            let request = CompareAndSwapRequest::new(entries);
+            if client.compare_and_swap(request)?.swapped {
+                return Ok(());
+            }
+        }
+
+        Err(ServiceError::StorageError.into())
+    }
+
+    fn restore_approved_task(&self, key: &[u8], staged: &[u8], approved: &[u8]) {
+        match self.swap_in_db(key, staged, approved) {
+            Ok(true) => (),
+            Ok(false) => log::error!("InvokeTask: staged task has changed"),
+            Err(e) => log::error!("InvokeTask: cannot restore approved task: {:?}", e),
+        }
+    }
+
+    // Writes the value only if the key still holds `expected`, and returns
+    // whether it was written.
+    fn swap_in_db(
+        &self,
+        key: &[u8],
+        expected: &[u8],
+        value: &[u8],
+    ) -> TeaclaveServiceResponseResult<bool> {
+        let entry = CompareAndSwapEntry::new(key, expected, value);
+        let response = self
+            .storage_client
+            .lock()
+            .map_err(|_| ServiceError::StorageError)?
+            .compare_and_swap(CompareAndSwapRequest::new(vec![entry]))?;
+        Ok(response.swapped)
+    }
+
     fn write_to_db(&self, item: &impl Storable) -> Result<()> {
         let k = item.key();
         let v = item.to_vec()?;
@@ -567,6 +972,11 @@ impl TeaclaveManagementService {
     fn read_from_db<T: Storable>(&self, key: &ExternalID) -> Result<T> {
         anyhow::ensure!(T::match_prefix(&key.prefix), "Key prefix doesn't match.");
 
+        let value = self.get_from_db(key)?;
+        T::from_slice(value.as_slice())
+    }
+
+    fn get_from_db(&self, key: &ExternalID) -> Result<Vec<u8>> {
         let request = GetRequest::new(key.to_bytes());
         let response = self
             .storage_client
@@ -574,7 +984,62 @@ impl TeaclaveManagementService {
             .lock()
             .map_err(|_| anyhow!("Cannot lock storage client"))?
             .get(request)?;
-        T::from_slice(response.value.as_slice())
+        Ok(response.value)
+    }
+
+    // Values which may be large are stored in chunks and sent in streams,
+    // i.e., function payloads, and return values and logs of tasks.
+    fn put_stream_into_db(&self, key: Vec<u8>, mut value: &[u8]) -> Result<()> {
+        let request = PutStreamRequest::new(key);
+        let _response = self
+            .storage_client
+            .clone()
+            .lock()
+            .map_err(|_| anyhow!("Cannot lock storage client"))?
+            .put_stream(request, &mut value)?;
+        Ok(())
+    }
+
+    fn get_stream_from_db(&self, key: Vec<u8>, writer: &mut dyn Write) -> Result<()> {
+        let request = GetStreamRequest::new(key);
+        let _response = self
+            .storage_client
+            .clone()
+            .lock()
+            .map_err(|_| anyhow!("Cannot lock storage client"))?
+            .get_stream(request, writer)?;
+        Ok(())
+    }
+
+    fn write_function(&self, function: &Function) -> Result<()> {
+        self.put_stream_into_db(function.payload_key(), &function.payload)?;
+        let function = Function {
+            payload: Vec::new(),
+            ..function.clone()
+        };
+        self.write_to_db(&function)
+    }
+
+    fn read_function(&self, key: &ExternalID) -> Result<Function> {
+        let mut function: Function = self.read_from_db(key)?;
+        // Mock functions are written with their payloads.
+        if function.payload.is_empty() {
+            self.get_stream_from_db(function.payload_key(), &mut function.payload)?;
+        }
+        Ok(function)
+    }
+
+    // The return value and the log are stored by the scheduler service, and
+    // left empty in the task state.
+    fn read_task_outputs(&self, ts: &TaskState, outputs: &mut TaskOutputs) -> Result<()> {
+        self.get_stream_from_db(ts.return_value_key(), &mut outputs.return_value)?;
+        let mut log = Vec::new();
+        self.get_stream_from_db(ts.log_key(), &mut log)?;
+        outputs.log = String::from_utf8(log)?
+            .lines()
+            .map(ToString::to_string)
+            .collect();
+        Ok(())
     }
 
     fn enqueue_to_db(&self, key: &[u8], item: &impl Storable) -> TeaclaveServiceResponseResult<()> {
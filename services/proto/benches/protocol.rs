@@ -0,0 +1,118 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License.
+
+//! Throughput of encoding and decoding requests of services in frames of the
+//! protocol. Run with `cargo bench` on a nightly toolchain.
+
+#![feature(test)]
+extern crate test;
+
+use std::io::Cursor;
+use teaclave_proto::teaclave_frontend_service::RegisterFunctionRequest;
+use teaclave_proto::teaclave_frontend_service_proto::TeaclaveFrontendRequest;
+use teaclave_proto::teaclave_storage_service::PutRequest;
+use teaclave_proto::teaclave_storage_service_proto::TeaclaveStorageRequest;
+use teaclave_rpc::protocol::{Encoding, ProtobufMessage, Protocol};
+use teaclave_rpc::{IntoRequest, Request};
+use teaclave_types::{ExecutorType, FunctionInput, FunctionOutput};
+use test::Bencher;
+
+fn payload(len: usize) -> Vec<u8> {
+    (0..len).map(|i| i as u8).collect()
+}
+
+/// A user registering a function with the frontend service.
+fn register_function(payload_len: usize) -> Request<TeaclaveFrontendRequest> {
+    let mut request = RegisterFunctionRequest::new()
+        .name("bench_function")
+        .description("Function of the benchmark")
+        .executor_type(ExecutorType::Python)
+        .payload(payload(payload_len))
+        .arguments(vec!["arg1", "arg2"])
+        .inputs(vec![FunctionInput::new("input", "Input of the function")])
+        .outputs(vec![FunctionOutput::new(
+            "output",
+            "Output of the function",
+        )])
+        .into_request();
+    request
+        .metadata
+        .insert("id".to_string(), "bench_user".to_string());
+    request
+        .metadata
+        .insert("token".to_string(), "0123456789abcdef".to_string());
+    request
+}
+
+/// A service putting a value into the storage service.
+fn put(payload_len: usize) -> Request<TeaclaveStorageRequest> {
+    PutRequest::new(
+        "function-00000000-0000-0000-0000-000000000000",
+        payload(payload_len),
+    )
+    .into_request()
+}
+
+/// Write a request in a frame and read it back.
+fn round_trip<T>(b: &mut Bencher, encoding: Encoding, request: Request<T>, payload_len: usize)
+where
+    T: serde::Serialize + for<'de> serde::Deserialize<'de> + ProtobufMessage + Clone,
+{
+    let mut buf = Cursor::new(Vec::new());
+    b.bytes = payload_len as u64;
+    b.iter(|| {
+        buf.set_position(0);
+        let mut protocol = Protocol::new(&mut buf, encoding);
+        protocol.write_message(request.clone()).unwrap();
+        protocol.transport.set_position(0);
+        protocol.read_message::<Request<T>>().unwrap()
+    });
+}
+
+#[bench]
+fn register_function_json_small(b: &mut Bencher) {
+    round_trip(b, Encoding::Json, register_function(64), 64);
+}
+
+#[bench]
+fn register_function_protobuf_small(b: &mut Bencher) {
+    round_trip(b, Encoding::Protobuf, register_function(64), 64);
+}
+
+#[bench]
+fn register_function_json_large(b: &mut Bencher) {
+    let len = 1024 * 1024;
+    round_trip(b, Encoding::Json, register_function(len), len);
+}
+
+#[bench]
+fn register_function_protobuf_large(b: &mut Bencher) {
+    let len = 1024 * 1024;
+    round_trip(b, Encoding::Protobuf, register_function(len), len);
+}
+
+#[bench]
+fn put_json_large(b: &mut Bencher) {
+    let len = 1024 * 1024;
+    round_trip(b, Encoding::Json, put(len), len);
+}
+
+#[bench]
+fn put_protobuf_large(b: &mut Bencher) {
+    let len = 1024 * 1024;
+    round_trip(b, Encoding::Protobuf, put(len), len);
+}
@@ -31,12 +31,16 @@ struct ProtoTemplate {
 }
 
 struct Method {
+    id: u32,
     name: String,
     proto_name: String,
     input_type: String,
     impl_input_type: String,
     output_type: String,
     impl_output_type: String,
+    client_streaming: bool,
+    server_streaming: bool,
+    idempotent: bool,
 }
 
 struct Service {
@@ -44,6 +48,36 @@ struct Service {
     methods: Vec<Method>,
 }
 
+/// Attributes of a method declared by a trailing comment, e.g.,
+/// `rpc Get(GetRequest) returns (GetResponse); // id = 1, idempotent`.
+fn method_attributes(method: &prost_build::Method) -> Vec<String> {
+    method
+        .comments
+        .trailing
+        .iter()
+        .flat_map(|c| c.split(','))
+        .map(|c| c.trim().to_string())
+        .collect()
+}
+
+/// The stable ID of a method on the wire, which is declared explicitly by the
+/// `id = 1` attribute so that methods can be reordered or added without
+/// breaking peers.
+fn method_id(method: &prost_build::Method) -> u32 {
+    method_attributes(method)
+        .iter()
+        .find(|c| c.starts_with("id ="))
+        .map(|c| c["id =".len()..].trim().parse().expect("Invalid method id"))
+        .unwrap_or_else(|| panic!("Method {} has no id", method.proto_name))
+}
+
+/// Whether the method is declared `idempotent`, so that its client resends
+/// requests failed with connection errors. Requests of other methods may have
+/// been handled before failing, and are never resent.
+fn method_idempotent(method: &prost_build::Method) -> bool {
+    method_attributes(method).iter().any(|c| c == "idempotent")
+}
+
 impl Service {
     fn from_prost(prost_service: &prost_build::Service) -> Self {
         fn convert_to_impl_type(current_package_name: &str, proto_type: &str) -> String {
@@ -56,16 +90,41 @@ impl Service {
         let mut methods = vec![];
         let package_name = prost_service.package.trim_end_matches("_proto");
         for m in prost_service.methods.iter() {
+            assert!(
+                !(m.client_streaming && m.server_streaming),
+                "Bidirectional streaming method {} is not supported",
+                m.proto_name
+            );
             let impl_input_type = convert_to_impl_type(&package_name, &m.input_type);
             let impl_output_type = convert_to_impl_type(&package_name, &m.output_type);
 
+            let id = method_id(m);
+            assert!(id > 0, "Method {} has an invalid id", m.proto_name);
+            assert!(
+                methods.iter().all(|method: &Method| method.id != id),
+                "Method {} has a duplicate id {}",
+                m.proto_name,
+                id
+            );
+
+            let idempotent = method_idempotent(m);
+            assert!(
+                !(idempotent && (m.client_streaming || m.server_streaming)),
+                "Streaming method {} cannot be idempotent",
+                m.proto_name
+            );
+
             let method = Method {
+                id,
                 name: m.name.clone(),
                 proto_name: m.proto_name.clone(),
                 input_type: m.input_type.clone(),
                 impl_input_type,
                 output_type: m.output_type.clone(),
                 impl_output_type,
+                client_streaming: m.client_streaming,
+                server_streaming: m.server_streaming,
+                idempotent,
             };
             methods.push(method);
         }
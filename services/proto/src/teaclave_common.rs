@@ -19,10 +19,14 @@
 use std::prelude::v1::*;
 
 use crate::teaclave_common_proto as proto;
-use anyhow::{bail, Error, Result};
-use std::convert::TryInto;
-use teaclave_crypto::TeaclaveFile128Key;
-use teaclave_types::{FileCrypto, TaskFailure, TaskOutputs, TaskResult, TaskStatus};
+use anyhow::{anyhow, bail, Error, Result};
+use std::collections::HashMap;
+use std::convert::{TryFrom, TryInto};
+use teaclave_crypto::{TeaclaveFile128Key, WrappedKey};
+use teaclave_types::{
+    FileCrypto, FileHash, TaskFailure, TaskFileFailure, TaskOutputs, TaskResult, TaskStatus,
+    WrappedFileCrypto,
+};
 
 #[derive(Debug)]
 pub struct UserCredential {
@@ -64,7 +68,19 @@ impl From<UserCredential> for proto::UserCredential {
 impl std::convert::TryFrom<proto::FileCryptoInfo> for FileCrypto {
     type Error = Error;
     fn try_from(proto: proto::FileCryptoInfo) -> Result<Self> {
-        FileCrypto::new(&proto.schema, &proto.key, &proto.iv)
+        match proto.wrapped_key {
+            Some(wrapped_key) => {
+                anyhow::ensure!(
+                    proto.key.is_empty() && proto.iv.is_empty(),
+                    "Key and IV are not empty for a wrapped key"
+                );
+                let wrapped_key =
+                    WrappedKey::new(&wrapped_key.ephemeral_public_key, wrapped_key.ciphertext)?;
+                let wrapped = WrappedFileCrypto::new(&proto.schema, wrapped_key);
+                Ok(FileCrypto::Wrapped(wrapped))
+            }
+            None => FileCrypto::new(&proto.schema, &proto.key, &proto.iv),
+        }
     }
 }
 
@@ -83,10 +99,18 @@ impl std::convert::TryFrom<proto::FileCryptoInfo> for TeaclaveFile128Key {
 impl std::convert::From<FileCrypto> for proto::FileCryptoInfo {
     fn from(crypto: FileCrypto) -> Self {
         let (key, iv) = crypto.key_iv();
+        let wrapped_key = match &crypto {
+            FileCrypto::Wrapped(wrapped) => Some(proto::WrappedKey {
+                ephemeral_public_key: wrapped.wrapped_key.ephemeral_public_key.to_vec(),
+                ciphertext: wrapped.wrapped_key.ciphertext.clone(),
+            }),
+            _ => None,
+        };
         proto::FileCryptoInfo {
             schema: crypto.schema().to_owned(),
             key,
             iv,
+            wrapped_key,
         }
     }
 }
@@ -99,6 +123,7 @@ impl std::convert::From<TeaclaveFile128Key> for proto::FileCryptoInfo {
             schema: crypto.schema().to_owned(),
             key,
             iv,
+            wrapped_key: None,
         }
     }
 }
@@ -130,18 +155,68 @@ pub fn i32_from_task_status(status: TaskStatus) -> i32 {
 impl std::convert::TryFrom<proto::TaskOutputs> for TaskOutputs {
     type Error = Error;
     fn try_from(proto: proto::TaskOutputs) -> Result<Self> {
+        let mut hashes_map = HashMap::new();
+        for (name, hash) in proto.hashes_map {
+            let pos = hash
+                .find(':')
+                .ok_or_else(|| anyhow!("Invalid hash: {}", hash))?;
+            let hash = FileHash::new(&hash[..pos], &hash[pos + 1..])?;
+            hashes_map.insert(name, hash);
+        }
         let ret = TaskOutputs {
             return_value: proto.return_value,
             tags_map: proto.tags_map.try_into()?,
+            hashes_map,
+            log: proto.log,
         };
         Ok(ret)
     }
 }
 impl std::convert::From<TaskOutputs> for proto::TaskOutputs {
     fn from(outputs: TaskOutputs) -> Self {
+        let hashes_map = outputs
+            .hashes_map
+            .into_iter()
+            .map(|(name, hash)| (name, format!("{}:{}", hash.algorithm(), hash.hex())))
+            .collect();
         proto::TaskOutputs {
             return_value: outputs.return_value,
             tags_map: outputs.tags_map.into(),
+            hashes_map,
+            log: outputs.log,
+        }
+    }
+}
+
+impl std::convert::TryFrom<proto::TaskFileFailure> for TaskFileFailure {
+    type Error = Error;
+    fn try_from(proto: proto::TaskFileFailure) -> Result<Self> {
+        let http_status = match proto.http_status {
+            0 => None,
+            status => Some(status.try_into()?),
+        };
+        let io_error_kind = if proto.io_error_kind.is_empty() {
+            None
+        } else {
+            Some(proto.io_error_kind)
+        };
+        let ret = TaskFileFailure {
+            file: proto.file,
+            error_kind: proto.error_kind,
+            http_status,
+            io_error_kind,
+        };
+        Ok(ret)
+    }
+}
+
+impl std::convert::From<TaskFileFailure> for proto::TaskFileFailure {
+    fn from(failure: TaskFileFailure) -> Self {
+        proto::TaskFileFailure {
+            file: failure.file,
+            error_kind: failure.error_kind,
+            http_status: failure.http_status.map(u32::from).unwrap_or_default(),
+            io_error_kind: failure.io_error_kind.unwrap_or_default(),
         }
     }
 }
@@ -149,8 +224,14 @@ impl std::convert::From<TaskOutputs> for proto::TaskOutputs {
 impl std::convert::TryFrom<proto::TaskFailure> for TaskFailure {
     type Error = Error;
     fn try_from(proto: proto::TaskFailure) -> Result<Self> {
+        let file_failures = proto
+            .file_failures
+            .into_iter()
+            .map(TaskFileFailure::try_from)
+            .collect::<Result<_>>()?;
         let ret = TaskFailure {
             reason: proto.reason,
+            file_failures,
         };
         Ok(ret)
     }
@@ -159,6 +240,11 @@ impl std::convert::From<TaskFailure> for proto::TaskFailure {
     fn from(outputs: TaskFailure) -> Self {
         proto::TaskFailure {
             reason: outputs.reason,
+            file_failures: outputs
+                .file_failures
+                .into_iter()
+                .map(proto::TaskFileFailure::from)
+                .collect(),
         }
     }
 }
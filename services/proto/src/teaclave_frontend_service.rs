@@ -21,14 +21,14 @@ use crate::teaclave_management_service::TeaclaveManagementRequest;
 use crate::teaclave_management_service::TeaclaveManagementResponse;
 use anyhow::anyhow;
 use anyhow::{Error, Result};
-use core::convert::TryInto;
+use core::convert::{TryFrom, TryInto};
 use std::collections::HashMap;
 use std::prelude::v1::*;
 use teaclave_rpc::into_request;
 use teaclave_types::{
-    Executor, ExecutorType, ExternalID, FileAuthTag, FileCrypto, Function, FunctionArguments,
-    FunctionInput, FunctionOutput, OwnerList, TaskFileOwners, TaskResult, TaskStatus, UserID,
-    UserList,
+    Executor, ExecutorType, ExternalID, FileAuthTag, FileCrypto, FileHash, Function,
+    FunctionArguments, FunctionInput, FunctionOutput, OwnerList, PrivacyBudget, TaskFileOwners,
+    TaskResult, TaskStatus, UserID, UserList,
 };
 use url::Url;
 use uuid::Uuid;
@@ -45,6 +45,8 @@ pub struct RegisterInputFileRequest {
     pub url: Url,
     pub cmac: FileAuthTag,
     pub crypto_info: FileCrypto,
+    pub privacy_budget: Option<PrivacyBudget>,
+    pub hash: Option<FileHash>,
 }
 
 impl RegisterInputFileRequest {
@@ -53,6 +55,22 @@ impl RegisterInputFileRequest {
             url,
             cmac,
             crypto_info: crypto.into(),
+            privacy_budget: None,
+            hash: None,
+        }
+    }
+
+    pub fn privacy_budget(self, privacy_budget: PrivacyBudget) -> Self {
+        Self {
+            privacy_budget: Some(privacy_budget),
+            ..self
+        }
+    }
+
+    pub fn hash(self, hash: FileHash) -> Self {
+        Self {
+            hash: Some(hash),
+            ..self
         }
     }
 }
@@ -102,14 +120,26 @@ impl UpdateInputFileResponse {
 #[derive(Debug)]
 pub struct RegisterOutputFileRequest {
     pub url: Url,
-    pub crypto_info: FileCrypto,
+    pub crypto_info: Option<FileCrypto>,
+    pub recipient_public_key: Option<Vec<u8>>,
 }
 
 impl RegisterOutputFileRequest {
     pub fn new(url: Url, crypto: impl Into<FileCrypto>) -> Self {
         Self {
             url,
-            crypto_info: crypto.into(),
+            crypto_info: Some(crypto.into()),
+            recipient_public_key: None,
+        }
+    }
+
+    /// Has the platform generate the key of the output and wrap it to
+    /// `public_key`, the recipient's X25519 public key.
+    pub fn with_recipient(url: Url, public_key: impl Into<Vec<u8>>) -> Self {
+        Self {
+            url,
+            crypto_info: None,
+            recipient_public_key: Some(public_key.into()),
         }
     }
 }
@@ -227,11 +257,16 @@ impl GetInputFileRequest {
 pub struct GetInputFileResponse {
     pub owner: OwnerList,
     pub cmac: FileAuthTag,
+    pub privacy_budget: Option<PrivacyBudget>,
 }
 
 impl GetInputFileResponse {
-    pub fn new(owner: OwnerList, cmac: FileAuthTag) -> Self {
-        Self { owner, cmac }
+    pub fn new(owner: OwnerList, cmac: FileAuthTag, privacy_budget: Option<PrivacyBudget>) -> Self {
+        Self {
+            owner,
+            cmac,
+            privacy_budget,
+        }
     }
 }
 
@@ -254,11 +289,23 @@ impl GetOutputFileRequest {
 pub struct GetOutputFileResponse {
     pub owner: OwnerList,
     pub cmac: Option<FileAuthTag>,
+    pub crypto_info: Option<FileCrypto>,
 }
 
 impl GetOutputFileResponse {
     pub fn new(owner: OwnerList, cmac: Option<FileAuthTag>) -> Self {
-        Self { owner, cmac }
+        Self {
+            owner,
+            cmac,
+            crypto_info: None,
+        }
+    }
+
+    pub fn crypto_info(self, crypto_info: Option<FileCrypto>) -> Self {
+        Self {
+            crypto_info,
+            ..self
+        }
     }
 }
 
@@ -272,6 +319,7 @@ pub struct RegisterFunctionRequest {
     pub payload: Vec<u8>,
     pub public: bool,
     pub arguments: Vec<String>,
+    pub version: String,
     pub inputs: Vec<FunctionInput>,
     pub outputs: Vec<FunctionOutput>,
 }
@@ -331,12 +379,19 @@ impl RegisterFunctionRequest {
     pub fn outputs(self, outputs: Vec<FunctionOutput>) -> Self {
         Self { outputs, ..self }
     }
+
+    pub fn version(self, version: impl ToString) -> Self {
+        Self {
+            version: version.to_string(),
+            ..self
+        }
+    }
 }
 
 // We explicitly construct Function here in case of missing any field
 impl From<RegisterFunctionRequest> for Function {
     fn from(request: RegisterFunctionRequest) -> Self {
-        Function {
+        let function = Function {
             id: Uuid::default(),
             owner: UserID::default(),
             name: request.name,
@@ -347,6 +402,14 @@ impl From<RegisterFunctionRequest> for Function {
             arguments: request.arguments,
             inputs: request.inputs,
             outputs: request.outputs,
+            version: request.version,
+            revision_hash: String::new(),
+            previous_revision: None,
+        };
+        let revision_hash = function.compute_revision_hash();
+        Function {
+            revision_hash,
+            ..function
         }
     }
 }
@@ -363,6 +426,95 @@ impl RegisterFunctionResponse {
     }
 }
 
+#[into_request(TeaclaveManagementRequest::UpdateFunction)]
+#[into_request(TeaclaveFrontendRequest::UpdateFunction)]
+#[derive(Debug, Default)]
+pub struct UpdateFunctionRequest {
+    pub function_id: ExternalID,
+    pub version: String,
+    pub description: String,
+    pub executor_type: ExecutorType,
+    pub payload: Vec<u8>,
+    pub public: bool,
+    pub arguments: Vec<String>,
+    pub inputs: Vec<FunctionInput>,
+    pub outputs: Vec<FunctionOutput>,
+}
+
+impl UpdateFunctionRequest {
+    pub fn new(function_id: ExternalID) -> Self {
+        Self {
+            function_id,
+            executor_type: ExecutorType::Builtin,
+            public: true,
+            ..Default::default()
+        }
+    }
+
+    pub fn version(self, version: impl ToString) -> Self {
+        Self {
+            version: version.to_string(),
+            ..self
+        }
+    }
+
+    pub fn description(self, description: impl ToString) -> Self {
+        Self {
+            description: description.to_string(),
+            ..self
+        }
+    }
+
+    pub fn executor_type(self, executor_type: ExecutorType) -> Self {
+        Self {
+            executor_type,
+            ..self
+        }
+    }
+
+    pub fn payload(self, payload: Vec<u8>) -> Self {
+        Self { payload, ..self }
+    }
+
+    pub fn public(self, public: bool) -> Self {
+        Self { public, ..self }
+    }
+
+    pub fn arguments<T: IntoIterator>(self, args: T) -> Self
+    where
+        <T as IntoIterator>::Item: ToString,
+    {
+        Self {
+            arguments: args.into_iter().map(|x| x.to_string()).collect(),
+            ..self
+        }
+    }
+
+    pub fn inputs(self, inputs: Vec<FunctionInput>) -> Self {
+        Self { inputs, ..self }
+    }
+
+    pub fn outputs(self, outputs: Vec<FunctionOutput>) -> Self {
+        Self { outputs, ..self }
+    }
+}
+
+#[into_request(TeaclaveManagementResponse::UpdateFunction)]
+#[derive(Debug)]
+pub struct UpdateFunctionResponse {
+    pub function_id: ExternalID,
+    pub revision_hash: String,
+}
+
+impl UpdateFunctionResponse {
+    pub fn new(function_id: ExternalID, revision_hash: impl ToString) -> Self {
+        Self {
+            function_id,
+            revision_hash: revision_hash.to_string(),
+        }
+    }
+}
+
 #[into_request(TeaclaveManagementRequest::GetFunction)]
 #[into_request(TeaclaveFrontendRequest::GetFunction)]
 #[derive(Debug)]
@@ -386,6 +538,9 @@ pub struct GetFunctionResponse {
     pub public: bool,
     pub executor_type: ExecutorType,
     pub arguments: Vec<String>,
+    pub version: String,
+    pub revision_hash: String,
+    pub previous_revision: Option<ExternalID>,
     pub inputs: Vec<FunctionInput>,
     pub outputs: Vec<FunctionOutput>,
 }
@@ -473,6 +628,7 @@ pub struct GetTaskResponse {
     pub task_id: ExternalID,
     pub creator: UserID,
     pub function_id: ExternalID,
+    pub function_revision_hash: String,
     pub function_owner: UserID,
     pub function_arguments: FunctionArguments,
     pub inputs_ownership: TaskFileOwners,
@@ -543,6 +699,115 @@ impl InvokeTaskRequest {
 #[derive(Debug)]
 pub struct InvokeTaskResponse;
 
+#[into_request(TeaclaveManagementRequest::GetKeyWrappingKey)]
+#[into_request(TeaclaveFrontendRequest::GetKeyWrappingKey)]
+#[derive(Debug)]
+pub struct GetKeyWrappingKeyRequest;
+
+#[into_request(TeaclaveManagementResponse::GetKeyWrappingKey)]
+#[into_request(TeaclaveFrontendResponse::GetKeyWrappingKey)]
+#[derive(Debug)]
+pub struct GetKeyWrappingKeyResponse {
+    pub public_key: Vec<u8>,
+}
+
+impl GetKeyWrappingKeyResponse {
+    pub fn new(public_key: impl Into<Vec<u8>>) -> Self {
+        Self {
+            public_key: public_key.into(),
+        }
+    }
+}
+
+#[into_request(TeaclaveManagementRequest::RotateFileKey)]
+#[into_request(TeaclaveFrontendRequest::RotateFileKey)]
+#[derive(Debug)]
+pub struct RotateFileKeyRequest {
+    pub data_id: ExternalID,
+    pub url: Url,
+    pub crypto_info: FileCrypto,
+}
+
+impl RotateFileKeyRequest {
+    pub fn new(data_id: ExternalID, url: Url, crypto_info: impl Into<FileCrypto>) -> Self {
+        Self {
+            data_id,
+            url,
+            crypto_info: crypto_info.into(),
+        }
+    }
+}
+
+#[into_request(TeaclaveManagementResponse::RotateFileKey)]
+#[into_request(TeaclaveFrontendResponse::RotateFileKey)]
+#[derive(Debug)]
+pub struct RotateFileKeyResponse {
+    pub task_id: ExternalID,
+}
+
+impl RotateFileKeyResponse {
+    pub fn new(task_id: ExternalID) -> Self {
+        Self { task_id }
+    }
+}
+
+/// Register a function with the payload streamed after the request.
+#[into_request(TeaclaveManagementRequest::UploadFunction)]
+#[into_request(TeaclaveFrontendRequest::UploadFunction)]
+#[derive(Debug)]
+pub struct UploadFunctionRequest {
+    pub function: RegisterFunctionRequest,
+}
+
+impl UploadFunctionRequest {
+    pub fn new(function: RegisterFunctionRequest) -> Self {
+        Self { function }
+    }
+}
+
+/// Get the result of a task with the return value streamed before the
+/// response.
+#[into_request(TeaclaveManagementRequest::DownloadTaskResult)]
+#[into_request(TeaclaveFrontendRequest::DownloadTaskResult)]
+#[derive(Debug)]
+pub struct DownloadTaskResultRequest {
+    pub task_id: ExternalID,
+}
+
+impl DownloadTaskResultRequest {
+    pub fn new(task_id: ExternalID) -> Self {
+        Self { task_id }
+    }
+}
+
+#[derive(Debug)]
+pub struct DownloadTaskResultResponse {
+    pub result: TaskResult,
+}
+
+impl DownloadTaskResultResponse {
+    pub fn new(result: TaskResult) -> Self {
+        Self { result }
+    }
+}
+
+/// Get the log of a task streamed before the response.
+#[into_request(TeaclaveManagementRequest::DownloadTaskLog)]
+#[into_request(TeaclaveFrontendRequest::DownloadTaskLog)]
+#[derive(Debug)]
+pub struct DownloadTaskLogRequest {
+    pub task_id: ExternalID,
+}
+
+impl DownloadTaskLogRequest {
+    pub fn new(task_id: ExternalID) -> Self {
+        Self { task_id }
+    }
+}
+
+#[derive(Debug)]
+pub struct DownloadTaskLogResponse;
+
 impl std::convert::TryFrom<proto::RegisterInputFileRequest> for RegisterInputFileRequest {
     type Error = Error;
 
@@ -553,10 +818,14 @@ impl std::convert::TryFrom<proto::RegisterInputFileRequest> for RegisterInputFil
             .crypto_info
             .ok_or_else(|| anyhow!("missing crypto_info"))?
             .try_into()?;
+        let privacy_budget = proto.privacy_budget.map(PrivacyBudget::from);
+        let hash = proto.hash.map(FileHash::try_from).transpose()?;
         Ok(RegisterInputFileRequest {
             url,
             cmac,
             crypto_info,
+            privacy_budget,
+            hash,
         })
     }
 }
@@ -567,6 +836,8 @@ impl From<RegisterInputFileRequest> for proto::RegisterInputFileRequest {
             url: request.url.into_string(),
             cmac: request.cmac.to_hex(),
             crypto_info: Some(request.crypto_info.into()),
+            privacy_budget: request.privacy_budget.map(proto::PrivacyBudget::from),
+            hash: request.hash.map(proto::FileHash::from),
         }
     }
 }
@@ -629,12 +900,20 @@ impl std::convert::TryFrom<proto::RegisterOutputFileRequest> for RegisterOutputF
     type Error = Error;
 
     fn try_from(proto: proto::RegisterOutputFileRequest) -> Result<Self> {
+        let crypto_info = proto.crypto_info.map(FileCrypto::try_from).transpose()?;
+        let recipient_public_key = if proto.recipient_public_key.is_empty() {
+            None
+        } else {
+            Some(proto.recipient_public_key)
+        };
+        anyhow::ensure!(
+            crypto_info.is_some() != recipient_public_key.is_some(),
+            "either crypto_info or recipient_public_key is required"
+        );
         let ret = Self {
             url: Url::parse(&proto.url)?,
-            crypto_info: proto
-                .crypto_info
-                .ok_or_else(|| anyhow!("missing crypto_info"))?
-                .try_into()?,
+            crypto_info,
+            recipient_public_key,
         };
 
         Ok(ret)
@@ -645,7 +924,8 @@ impl From<RegisterOutputFileRequest> for proto::RegisterOutputFileRequest {
     fn from(request: RegisterOutputFileRequest) -> Self {
         Self {
             url: request.url.into_string(),
-            crypto_info: Some(request.crypto_info.into()),
+            crypto_info: request.crypto_info.map(|crypto| crypto.into()),
+            recipient_public_key: request.recipient_public_key.unwrap_or_default(),
         }
     }
 }
@@ -809,6 +1089,7 @@ impl std::convert::TryFrom<proto::GetInputFileResponse> for GetInputFileResponse
         Ok(Self {
             owner: OwnerList::new(proto.owner),
             cmac: FileAuthTag::from_hex(proto.cmac)?,
+            privacy_budget: proto.privacy_budget.map(PrivacyBudget::from),
         })
     }
 }
@@ -818,6 +1099,7 @@ impl From<GetInputFileResponse> for proto::GetInputFileResponse {
         Self {
             owner: request.owner.into(),
             cmac: request.cmac.to_hex(),
+            privacy_budget: request.privacy_budget.map(proto::PrivacyBudget::from),
         }
     }
 }
@@ -856,6 +1138,7 @@ impl std::convert::TryFrom<proto::GetOutputFileResponse> for GetOutputFileRespon
         Ok(Self {
             owner: OwnerList::new(proto.owner),
             cmac,
+            crypto_info: proto.crypto_info.map(FileCrypto::try_from).transpose()?,
         })
     }
 }
@@ -865,6 +1148,39 @@ impl From<GetOutputFileResponse> for proto::GetOutputFileResponse {
         Self {
             owner: request.owner.into(),
             cmac: request.cmac.map_or_else(String::new, |cmac| cmac.to_hex()),
+            crypto_info: request.crypto_info.map(|crypto| crypto.into()),
+        }
+    }
+}
+
+impl std::convert::TryFrom<proto::FileHash> for FileHash {
+    type Error = Error;
+
+    fn try_from(proto: proto::FileHash) -> Result<Self> {
+        FileHash::new(&proto.algorithm, &proto.value)
+    }
+}
+
+impl From<FileHash> for proto::FileHash {
+    fn from(hash: FileHash) -> Self {
+        Self {
+            algorithm: hash.algorithm().to_string(),
+            value: hash.hex().to_string(),
+        }
+    }
+}
+
+impl From<proto::PrivacyBudget> for PrivacyBudget {
+    fn from(proto: proto::PrivacyBudget) -> Self {
+        PrivacyBudget::new(proto.epsilon, proto.delta)
+    }
+}
+
+impl From<PrivacyBudget> for proto::PrivacyBudget {
+    fn from(budget: PrivacyBudget) -> Self {
+        Self {
+            epsilon: budget.epsilon,
+            delta: budget.delta,
         }
     }
 }
@@ -876,6 +1192,8 @@ impl std::convert::TryFrom<proto::FunctionInput> for FunctionInput {
         let ret = Self {
             name: proto.name,
             description: proto.description,
+            optional: proto.optional,
+            repeated: proto.repeated,
         };
 
         Ok(ret)
@@ -887,6 +1205,8 @@ impl From<FunctionInput> for proto::FunctionInput {
         Self {
             name: input.name,
             description: input.description,
+            optional: input.optional,
+            repeated: input.repeated,
         }
     }
 }
@@ -898,6 +1218,8 @@ impl std::convert::TryFrom<proto::FunctionOutput> for FunctionOutput {
         let ret = Self {
             name: proto.name,
             description: proto.description,
+            optional: proto.optional,
+            repeated: proto.repeated,
         };
 
         Ok(ret)
@@ -909,6 +1231,8 @@ impl From<FunctionOutput> for proto::FunctionOutput {
         Self {
             name: output.name,
             description: output.description,
+            optional: output.optional,
+            repeated: output.repeated,
         }
     }
 }
@@ -936,6 +1260,7 @@ impl std::convert::TryFrom<proto::RegisterFunctionRequest> for RegisterFunctionR
             payload: proto.payload,
             public: proto.public,
             arguments: proto.arguments,
+            version: proto.version,
             inputs: inputs?,
             outputs: outputs?,
         };
@@ -963,6 +1288,7 @@ impl From<RegisterFunctionRequest> for proto::RegisterFunctionRequest {
             payload: request.payload,
             public: request.public,
             arguments: request.arguments,
+            version: request.version,
             inputs,
             outputs,
         }
@@ -988,6 +1314,88 @@ impl From<RegisterFunctionResponse> for proto::RegisterFunctionResponse {
     }
 }
 
+impl std::convert::TryFrom<proto::UpdateFunctionRequest> for UpdateFunctionRequest {
+    type Error = Error;
+
+    fn try_from(proto: proto::UpdateFunctionRequest) -> Result<Self> {
+        let inputs: Result<Vec<FunctionInput>> = proto
+            .inputs
+            .into_iter()
+            .map(FunctionInput::try_from)
+            .collect();
+        let outputs: Result<Vec<FunctionOutput>> = proto
+            .outputs
+            .into_iter()
+            .map(FunctionOutput::try_from)
+            .collect();
+        let function_id = proto.function_id.try_into()?;
+        let executor_type = proto.executor_type.try_into()?;
+
+        let ret = Self {
+            function_id,
+            version: proto.version,
+            description: proto.description,
+            executor_type,
+            payload: proto.payload,
+            public: proto.public,
+            arguments: proto.arguments,
+            inputs: inputs?,
+            outputs: outputs?,
+        };
+        Ok(ret)
+    }
+}
+
+impl From<UpdateFunctionRequest> for proto::UpdateFunctionRequest {
+    fn from(request: UpdateFunctionRequest) -> Self {
+        let inputs: Vec<proto::FunctionInput> = request
+            .inputs
+            .into_iter()
+            .map(proto::FunctionInput::from)
+            .collect();
+        let outputs: Vec<proto::FunctionOutput> = request
+            .outputs
+            .into_iter()
+            .map(proto::FunctionOutput::from)
+            .collect();
+
+        Self {
+            function_id: request.function_id.to_string(),
+            version: request.version,
+            description: request.description,
+            executor_type: request.executor_type.into(),
+            payload: request.payload,
+            public: request.public,
+            arguments: request.arguments,
+            inputs,
+            outputs,
+        }
+    }
+}
+
+impl std::convert::TryFrom<proto::UpdateFunctionResponse> for UpdateFunctionResponse {
+    type Error = Error;
+
+    fn try_from(proto: proto::UpdateFunctionResponse) -> Result<Self> {
+        let function_id = proto.function_id.try_into()?;
+        let ret = Self {
+            function_id,
+            revision_hash: proto.revision_hash,
+        };
+
+        Ok(ret)
+    }
+}
+
+impl From<UpdateFunctionResponse> for proto::UpdateFunctionResponse {
+    fn from(response: UpdateFunctionResponse) -> Self {
+        Self {
+            function_id: response.function_id.to_string(),
+            revision_hash: response.revision_hash,
+        }
+    }
+}
+
 impl std::convert::TryFrom<proto::GetFunctionRequest> for GetFunctionRequest {
     type Error = Error;
 
@@ -1022,6 +1430,11 @@ impl std::convert::TryFrom<proto::GetFunctionResponse> for GetFunctionResponse {
             .map(FunctionOutput::try_from)
             .collect();
         let executor_type = proto.executor_type.try_into()?;
+        let previous_revision = if proto.previous_revision.is_empty() {
+            None
+        } else {
+            Some(proto.previous_revision.try_into()?)
+        };
 
         let ret = Self {
             name: proto.name,
@@ -1031,6 +1444,9 @@ impl std::convert::TryFrom<proto::GetFunctionResponse> for GetFunctionResponse {
             payload: proto.payload,
             public: proto.public,
             arguments: proto.arguments,
+            version: proto.version,
+            revision_hash: proto.revision_hash,
+            previous_revision,
             inputs: inputs?,
             outputs: outputs?,
         };
@@ -1060,8 +1476,13 @@ impl From<GetFunctionResponse> for proto::GetFunctionResponse {
             payload: response.payload,
             public: response.public,
             arguments: response.arguments,
+            version: response.version,
+            revision_hash: response.revision_hash,
             inputs,
             outputs,
+            previous_revision: response
+                .previous_revision
+                .map_or_else(String::new, |id| id.to_string()),
         }
     }
 }
@@ -1197,6 +1618,7 @@ impl std::convert::TryFrom<proto::GetTaskResponse> for GetTaskResponse {
             task_id,
             creator: proto.creator.into(),
             function_id,
+            function_revision_hash: proto.function_revision_hash,
             function_owner: proto.function_owner.into(),
             function_arguments,
             inputs_ownership,
@@ -1233,6 +1655,7 @@ impl From<GetTaskResponse> for proto::GetTaskResponse {
             approved_users: response.approved_users.into(),
             assigned_inputs,
             assigned_outputs,
+            function_revision_hash: response.function_revision_hash,
             status,
             result: Some(response.result.into()),
         }
@@ -1347,3 +1770,163 @@ impl From<InvokeTaskResponse> for proto::InvokeTaskResponse {
         Self {}
     }
 }
+
+impl std::convert::TryFrom<proto::GetKeyWrappingKeyRequest> for GetKeyWrappingKeyRequest {
+    type Error = Error;
+
+    fn try_from(_proto: proto::GetKeyWrappingKeyRequest) -> Result<Self> {
+        Ok(GetKeyWrappingKeyRequest)
+    }
+}
+
+impl From<GetKeyWrappingKeyRequest> for proto::GetKeyWrappingKeyRequest {
+    fn from(_request: GetKeyWrappingKeyRequest) -> Self {
+        Self {}
+    }
+}
+
+impl std::convert::TryFrom<proto::GetKeyWrappingKeyResponse> for GetKeyWrappingKeyResponse {
+    type Error = Error;
+
+    fn try_from(proto: proto::GetKeyWrappingKeyResponse) -> Result<Self> {
+        Ok(Self::new(proto.public_key))
+    }
+}
+
+impl From<GetKeyWrappingKeyResponse> for proto::GetKeyWrappingKeyResponse {
+    fn from(response: GetKeyWrappingKeyResponse) -> Self {
+        Self {
+            public_key: response.public_key,
+        }
+    }
+}
+
+impl std::convert::TryFrom<proto::RotateFileKeyRequest> for RotateFileKeyRequest {
+    type Error = Error;
+
+    fn try_from(proto: proto::RotateFileKeyRequest) -> Result<Self> {
+        let data_id = proto.data_id.try_into()?;
+        let url = Url::parse(&proto.url)?;
+        let crypto_info = proto
+            .crypto_info
+            .ok_or_else(|| anyhow!("missing crypto_info"))?
+            .try_into()?;
+        Ok(RotateFileKeyRequest {
+            data_id,
+            url,
+            crypto_info,
+        })
+    }
+}
+
+impl From<RotateFileKeyRequest> for proto::RotateFileKeyRequest {
+    fn from(request: RotateFileKeyRequest) -> Self {
+        Self {
+            data_id: request.data_id.to_string(),
+            url: request.url.into_string(),
+            crypto_info: Some(request.crypto_info.into()),
+        }
+    }
+}
+
+impl std::convert::TryFrom<proto::RotateFileKeyResponse> for RotateFileKeyResponse {
+    type Error = Error;
+
+    fn try_from(proto: proto::RotateFileKeyResponse) -> Result<Self> {
+        let task_id = proto.task_id.try_into()?;
+        Ok(Self { task_id })
+    }
+}
+
+impl From<RotateFileKeyResponse> for proto::RotateFileKeyResponse {
+    fn from(response: RotateFileKeyResponse) -> Self {
+        Self {
+            task_id: response.task_id.to_string(),
+        }
+    }
+}
+
+impl std::convert::TryFrom<proto::UploadFunctionRequest> for UploadFunctionRequest {
+    type Error = Error;
+
+    fn try_from(proto: proto::UploadFunctionRequest) -> Result<Self> {
+        let function = proto
+            .function
+            .ok_or_else(|| anyhow!("missing function"))?
+            .try_into()?;
+        Ok(Self { function })
+    }
+}
+
+impl From<UploadFunctionRequest> for proto::UploadFunctionRequest {
+    fn from(request: UploadFunctionRequest) -> Self {
+        Self {
+            function: Some(request.function.into()),
+        }
+    }
+}
+
+impl std::convert::TryFrom<proto::DownloadTaskResultRequest> for DownloadTaskResultRequest {
+    type Error = Error;
+
+    fn try_from(proto: proto::DownloadTaskResultRequest) -> Result<Self> {
+        let task_id = proto.task_id.try_into()?;
+        Ok(Self { task_id })
+    }
+}
+
+impl From<DownloadTaskResultRequest> for proto::DownloadTaskResultRequest {
+    fn from(request: DownloadTaskResultRequest) -> Self {
+        Self {
+            task_id: request.task_id.to_string(),
+        }
+    }
+}
+
+impl std::convert::TryFrom<proto::DownloadTaskResultResponse> for DownloadTaskResultResponse {
+    type Error = Error;
+
+    fn try_from(proto: proto::DownloadTaskResultResponse) -> Result<Self> {
+        let result = proto.result.try_into()?;
+        Ok(Self { result })
+    }
+}
+
+impl From<DownloadTaskResultResponse> for proto::DownloadTaskResultResponse {
+    fn from(response: DownloadTaskResultResponse) -> Self {
+        Self {
+            result: Some(response.result.into()),
+        }
+    }
+}
+
+impl std::convert::TryFrom<proto::DownloadTaskLogRequest> for DownloadTaskLogRequest {
+    type Error = Error;
+
+    fn try_from(proto: proto::DownloadTaskLogRequest) -> Result<Self> {
+        let task_id = proto.task_id.try_into()?;
+        Ok(Self { task_id })
+    }
+}
+
+impl From<DownloadTaskLogRequest> for proto::DownloadTaskLogRequest {
+    fn from(request: DownloadTaskLogRequest) -> Self {
+        Self {
+            task_id: request.task_id.to_string(),
+        }
+    }
+}
+
+impl std::convert::TryFrom<proto::DownloadTaskLogResponse> for DownloadTaskLogResponse {
+    type Error = Error;
+
+    fn try_from(_proto: proto::DownloadTaskLogResponse) -> Result<Self> {
+        Ok(DownloadTaskLogResponse)
+    }
+}
+
+impl From<DownloadTaskLogResponse> for proto::DownloadTaskLogResponse {
+    fn from(_response: DownloadTaskLogResponse) -> Self {
+        Self {}
+    }
+}
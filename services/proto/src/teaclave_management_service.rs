@@ -44,6 +44,8 @@ pub type GetOutputFileRequest = crate::teaclave_frontend_service::GetOutputFileR
 pub type GetOutputFileResponse = crate::teaclave_frontend_service::GetOutputFileResponse;
 pub type RegisterFunctionRequest = crate::teaclave_frontend_service::RegisterFunctionRequest;
 pub type RegisterFunctionResponse = crate::teaclave_frontend_service::RegisterFunctionResponse;
+pub type UpdateFunctionRequest = crate::teaclave_frontend_service::UpdateFunctionRequest;
+pub type UpdateFunctionResponse = crate::teaclave_frontend_service::UpdateFunctionResponse;
 pub type GetFunctionRequest = crate::teaclave_frontend_service::GetFunctionRequest;
 pub type GetFunctionResponse = crate::teaclave_frontend_service::GetFunctionResponse;
 pub type CreateTaskRequest = crate::teaclave_frontend_service::CreateTaskRequest;
@@ -56,3 +58,12 @@ pub type ApproveTaskRequest = crate::teaclave_frontend_service::ApproveTaskReque
 pub type ApproveTaskResponse = crate::teaclave_frontend_service::ApproveTaskResponse;
 pub type InvokeTaskRequest = crate::teaclave_frontend_service::InvokeTaskRequest;
 pub type InvokeTaskResponse = crate::teaclave_frontend_service::InvokeTaskResponse;
+pub type GetKeyWrappingKeyRequest = crate::teaclave_frontend_service::GetKeyWrappingKeyRequest;
+pub type GetKeyWrappingKeyResponse = crate::teaclave_frontend_service::GetKeyWrappingKeyResponse;
+pub type RotateFileKeyRequest = crate::teaclave_frontend_service::RotateFileKeyRequest;
+pub type RotateFileKeyResponse = crate::teaclave_frontend_service::RotateFileKeyResponse;
+pub type UploadFunctionRequest = crate::teaclave_frontend_service::UploadFunctionRequest;
+pub type DownloadTaskResultRequest = crate::teaclave_frontend_service::DownloadTaskResultRequest;
+pub type DownloadTaskResultResponse = crate::teaclave_frontend_service::DownloadTaskResultResponse;
+pub type DownloadTaskLogRequest = crate::teaclave_frontend_service::DownloadTaskLogRequest;
+pub type DownloadTaskLogResponse = crate::teaclave_frontend_service::DownloadTaskLogResponse;
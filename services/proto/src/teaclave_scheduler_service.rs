@@ -42,7 +42,19 @@ pub struct SubscribeResponse {
 }
 
 #[into_request(TeaclaveSchedulerRequest::PullTask)]
-pub struct PullTaskRequest {}
+pub struct PullTaskRequest {
+    /// Public key of the key wrapping key of the execution enclave, to which
+    /// the file keys of the pulled task are wrapped.
+    pub public_key: Vec<u8>,
+}
+
+impl PullTaskRequest {
+    pub fn new(public_key: impl Into<Vec<u8>>) -> Self {
+        Self {
+            public_key: public_key.into(),
+        }
+    }
+}
 
 #[into_request(TeaclaveSchedulerResponse::PullTask)]
 #[derive(Debug)]
@@ -66,9 +78,7 @@ impl UpdateTaskResultRequest {
     pub fn new(task_id: Uuid, task_result: Result<TaskOutputs>) -> Self {
         let result = match task_result {
             Ok(task_output) => TaskResult::Ok(task_output),
-            Err(e) => TaskResult::Err(TaskFailure {
-                reason: e.to_string(),
-            }),
+            Err(e) => TaskResult::Err(TaskFailure::from_error(&e)),
         };
         Self {
             task_id,
@@ -97,6 +107,30 @@ impl UpdateTaskStatusRequest {
 #[into_request(TeaclaveSchedulerResponse::UpdateTaskStatus)]
 pub struct UpdateTaskStatusResponse {}
 
+#[into_request(TeaclaveSchedulerRequest::GetKeyWrappingPublicKey)]
+pub struct GetKeyWrappingPublicKeyRequest {}
+
+impl GetKeyWrappingPublicKeyRequest {
+    pub fn new() -> Self {
+        Self {}
+    }
+}
+
+#[into_request(TeaclaveSchedulerResponse::GetKeyWrappingPublicKey)]
+pub struct GetKeyWrappingPublicKeyResponse {
+    /// Public key of the key wrapping key of the scheduler, to which the file
+    /// keys of staged tasks are wrapped.
+    pub public_key: Vec<u8>,
+}
+
+impl GetKeyWrappingPublicKeyResponse {
+    pub fn new(public_key: impl Into<Vec<u8>>) -> Self {
+        Self {
+            public_key: public_key.into(),
+        }
+    }
+}
+
 #[into_request(TeaclaveSchedulerRequest::PublishTask)]
 pub struct PublishTaskRequest {
     pub staged_task: StagedTask,
@@ -140,14 +174,18 @@ impl std::convert::From<SubscribeResponse> for proto::SubscribeResponse {
 impl std::convert::TryFrom<proto::PullTaskRequest> for PullTaskRequest {
     type Error = Error;
     fn try_from(proto: proto::PullTaskRequest) -> Result<Self> {
-        let ret = Self {};
+        let ret = Self {
+            public_key: proto.public_key,
+        };
         Ok(ret)
     }
 }
 
 impl std::convert::From<PullTaskRequest> for proto::PullTaskRequest {
     fn from(req: PullTaskRequest) -> Self {
-        proto::PullTaskRequest {}
+        proto::PullTaskRequest {
+            public_key: req.public_key,
+        }
     }
 }
 
@@ -168,6 +206,43 @@ impl std::convert::From<PullTaskResponse> for proto::PullTaskResponse {
     }
 }
 
+impl std::convert::TryFrom<proto::GetKeyWrappingPublicKeyRequest>
+    for GetKeyWrappingPublicKeyRequest
+{
+    type Error = Error;
+    fn try_from(proto: proto::GetKeyWrappingPublicKeyRequest) -> Result<Self> {
+        Ok(Self {})
+    }
+}
+
+impl std::convert::From<GetKeyWrappingPublicKeyRequest> for proto::GetKeyWrappingPublicKeyRequest {
+    fn from(req: GetKeyWrappingPublicKeyRequest) -> Self {
+        proto::GetKeyWrappingPublicKeyRequest {}
+    }
+}
+
+impl std::convert::TryFrom<proto::GetKeyWrappingPublicKeyResponse>
+    for GetKeyWrappingPublicKeyResponse
+{
+    type Error = Error;
+    fn try_from(proto: proto::GetKeyWrappingPublicKeyResponse) -> Result<Self> {
+        let ret = Self {
+            public_key: proto.public_key,
+        };
+        Ok(ret)
+    }
+}
+
+impl std::convert::From<GetKeyWrappingPublicKeyResponse>
+    for proto::GetKeyWrappingPublicKeyResponse
+{
+    fn from(req: GetKeyWrappingPublicKeyResponse) -> Self {
+        proto::GetKeyWrappingPublicKeyResponse {
+            public_key: req.public_key,
+        }
+    }
+}
+
 impl std::convert::TryFrom<proto::UpdateTaskResultRequest> for UpdateTaskResultRequest {
     type Error = Error;
     fn try_from(proto: proto::UpdateTaskResultRequest) -> Result<Self> {
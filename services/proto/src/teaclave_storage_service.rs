@@ -16,6 +16,7 @@
 // under the License.
 
 use anyhow::{Error, Result};
+use std::convert::TryFrom;
 use std::prelude::v1::*;
 
 use crate::teaclave_storage_service_proto as proto;
@@ -123,16 +124,94 @@ impl DequeueRequest {
 #[derive(Debug)]
 pub struct DequeueResponse {
     pub value: Vec<u8>,
+    /// Elements left in the queue after the dequeue.
+    pub remaining: u32,
 }
 
 impl DequeueResponse {
-    pub fn new(value: impl Into<Vec<u8>>) -> Self {
+    pub fn new(value: impl Into<Vec<u8>>, remaining: u32) -> Self {
         Self {
             value: value.into(),
+            remaining,
         }
     }
 }
 
+// Each entry is swapped only if the key currently holds the expected value.
+// The entries of a request are swapped all together or not at all.
+#[derive(Debug, Clone)]
+pub struct CompareAndSwapEntry {
+    pub key: Vec<u8>,
+    pub expected: Vec<u8>,
+    pub value: Vec<u8>,
+}
+
+impl CompareAndSwapEntry {
+    pub fn new(
+        key: impl Into<Vec<u8>>,
+        expected: impl Into<Vec<u8>>,
+        value: impl Into<Vec<u8>>,
+    ) -> Self {
+        Self {
+            key: key.into(),
+            expected: expected.into(),
+            value: value.into(),
+        }
+    }
+}
+
+#[into_request(TeaclaveStorageRequest::CompareAndSwap)]
+#[derive(Debug)]
+pub struct CompareAndSwapRequest {
+    pub entries: Vec<CompareAndSwapEntry>,
+}
+
+impl CompareAndSwapRequest {
+    pub fn new(entries: Vec<CompareAndSwapEntry>) -> Self {
+        Self { entries }
+    }
+}
+
+#[into_request(TeaclaveStorageResponse::CompareAndSwap)]
+#[derive(Debug)]
+pub struct CompareAndSwapResponse {
+    pub swapped: bool,
+}
+
+impl CompareAndSwapResponse {
+    pub fn new(swapped: bool) -> Self {
+        Self { swapped }
+    }
+}
+
+#[into_request(TeaclaveStorageRequest::PutStream)]
+#[derive(Debug)]
+pub struct PutStreamRequest {
+    pub key: Vec<u8>,
+}
+
+impl PutStreamRequest {
+    pub fn new(key: impl Into<Vec<u8>>) -> Self {
+        Self { key: key.into() }
+    }
+}
+
+#[into_request(TeaclaveStorageRequest::GetStream)]
+#[derive(Debug)]
+pub struct GetStreamRequest {
+    pub key: Vec<u8>,
+}
+
+impl GetStreamRequest {
+    pub fn new(key: impl Into<Vec<u8>>) -> Self {
+        Self { key: key.into() }
+    }
+}
+
+#[into_request(TeaclaveStorageResponse::GetStream)]
+#[derive(Debug, Default)]
+pub struct GetStreamResponse;
+
 impl std::convert::TryFrom<proto::GetRequest> for GetRequest {
     type Error = Error;
 
@@ -289,7 +368,10 @@ impl std::convert::TryFrom<proto::DequeueResponse> for DequeueResponse {
     type Error = Error;
 
     fn try_from(proto: proto::DequeueResponse) -> Result<Self> {
-        Ok(Self { value: proto.value })
+        Ok(Self {
+            value: proto.value,
+            remaining: proto.remaining,
+        })
     }
 }
 
@@ -297,6 +379,117 @@ impl From<DequeueResponse> for proto::DequeueResponse {
     fn from(response: DequeueResponse) -> Self {
         Self {
             value: response.value,
+            remaining: response.remaining,
+        }
+    }
+}
+
+impl std::convert::TryFrom<proto::CompareAndSwapEntry> for CompareAndSwapEntry {
+    type Error = Error;
+
+    fn try_from(proto: proto::CompareAndSwapEntry) -> Result<Self> {
+        let ret = Self {
+            key: proto.key,
+            expected: proto.expected,
+            value: proto.value,
+        };
+
+        Ok(ret)
+    }
+}
+
+impl From<CompareAndSwapEntry> for proto::CompareAndSwapEntry {
+    fn from(entry: CompareAndSwapEntry) -> Self {
+        Self {
+            key: entry.key,
+            expected: entry.expected,
+            value: entry.value,
         }
     }
 }
+
+impl std::convert::TryFrom<proto::CompareAndSwapRequest> for CompareAndSwapRequest {
+    type Error = Error;
+
+    fn try_from(proto: proto::CompareAndSwapRequest) -> Result<Self> {
+        let entries = proto
+            .entries
+            .into_iter()
+            .map(CompareAndSwapEntry::try_from)
+            .collect::<Result<_>>()?;
+
+        Ok(Self { entries })
+    }
+}
+
+impl From<CompareAndSwapRequest> for proto::CompareAndSwapRequest {
+    fn from(request: CompareAndSwapRequest) -> Self {
+        Self {
+            entries: request.entries.into_iter().map(|e| e.into()).collect(),
+        }
+    }
+}
+
+impl std::convert::TryFrom<proto::CompareAndSwapResponse> for CompareAndSwapResponse {
+    type Error = Error;
+
+    fn try_from(proto: proto::CompareAndSwapResponse) -> Result<Self> {
+        Ok(Self {
+            swapped: proto.swapped,
+        })
+    }
+}
+
+impl From<CompareAndSwapResponse> for proto::CompareAndSwapResponse {
+    fn from(response: CompareAndSwapResponse) -> Self {
+        Self {
+            swapped: response.swapped,
+        }
+    }
+}
+
+impl std::convert::TryFrom<proto::PutStreamRequest> for PutStreamRequest {
+    type Error = Error;
+
+    fn try_from(proto: proto::PutStreamRequest) -> Result<Self> {
+        let ret = Self { key: proto.key };
+
+        Ok(ret)
+    }
+}
+
+impl From<PutStreamRequest> for proto::PutStreamRequest {
+    fn from(request: PutStreamRequest) -> Self {
+        Self { key: request.key }
+    }
+}
+
+impl std::convert::TryFrom<proto::GetStreamRequest> for GetStreamRequest {
+    type Error = Error;
+
+    fn try_from(proto: proto::GetStreamRequest) -> Result<Self> {
+        let ret = Self { key: proto.key };
+
+        Ok(ret)
+    }
+}
+
+impl From<GetStreamRequest> for proto::GetStreamRequest {
+    fn from(request: GetStreamRequest) -> Self {
+        Self { key: request.key }
+    }
+}
+
+impl std::convert::TryFrom<proto::GetStreamResponse> for GetStreamResponse {
+    type Error = Error;
+
+    fn try_from(_proto: proto::GetStreamResponse) -> Result<Self> {
+        Ok(Self {})
+    }
+}
+
+impl From<GetStreamResponse> for proto::GetStreamResponse {
+    fn from(_response: GetStreamResponse) -> Self {
+        Self {}
+    }
+}
@@ -30,10 +30,11 @@ use anyhow::{anyhow, Result};
 mod publisher;
 mod service;
 
-use teaclave_attestation::{verifier, AttestationConfig, RemoteAttestation};
+use teaclave_attestation::policy::AttestationPolicy;
+use teaclave_attestation::{AttestationConfig, RemoteAttestation};
 use teaclave_binder::proto::{
-    ECallCommand, FinalizeEnclaveInput, FinalizeEnclaveOutput, InitEnclaveInput, InitEnclaveOutput,
-    StartServiceInput, StartServiceOutput,
+    ECallCommand, FinalizeEnclaveInput, FinalizeEnclaveOutput, GetMetricsInput, GetMetricsOutput,
+    InitEnclaveInput, InitEnclaveOutput, StartServiceInput, StartServiceOutput,
 };
 use teaclave_binder::{handle_ecall, register_ecall_handler};
 use teaclave_config::build::{AS_ROOT_CA_CERT, AUDITOR_PUBLIC_KEYS, SCHEDULER_INBOUND_SERVICES};
@@ -44,12 +45,15 @@ use teaclave_proto::teaclave_scheduler_service::{
 use teaclave_rpc::config::SgxTrustedTlsServerConfig;
 use teaclave_rpc::server::SgxTrustedTlsServer;
 use teaclave_service_enclave_utils::create_trusted_storage_endpoint;
-use teaclave_service_enclave_utils::ServiceEnclave;
+use teaclave_service_enclave_utils::metrics;
+use teaclave_service_enclave_utils::{watch_revocation_list, ServiceEnclave};
 use teaclave_types::{EnclaveInfo, TeeServiceError, TeeServiceResult};
 
 fn start_service(config: &RuntimeConfig) -> Result<()> {
     let listen_address = config.internal_endpoints.scheduler.listen_address;
     let attestation_config = AttestationConfig::from_teaclave_config(&config)?;
+    let attestation_policy = AttestationPolicy::from_teaclave_config(&config)?;
+    watch_revocation_list(&config)?;
     let attested_tls_config = RemoteAttestation::new(attestation_config)
         .generate_and_endorse()?
         .attested_tls_config()
@@ -71,7 +75,7 @@ fn start_service(config: &RuntimeConfig) -> Result<()> {
             .attestation_report_verifier(
                 accepted_enclave_attrs,
                 AS_ROOT_CA_CERT,
-                verifier::universal_quote_verifier,
+                attestation_policy.clone(),
             )?;
 
     let mut server =
@@ -85,11 +89,15 @@ fn start_service(config: &RuntimeConfig) -> Result<()> {
         &storage_service_address,
         &enclave_info,
         AS_ROOT_CA_CERT,
-        verifier::universal_quote_verifier,
+        attestation_policy,
         attested_tls_config,
     )?;
 
-    let service = service::TeaclaveSchedulerService::new(storage_service_endpoint)?;
+    let execution_enclave_attr = enclave_info
+        .get_enclave_attr("teaclave_execution_service")
+        .ok_or_else(|| anyhow!("cannot get enclave attribute of execution service"))?;
+    let service =
+        service::TeaclaveSchedulerService::new(storage_service_endpoint, execution_enclave_attr)?;
     match server.start(service) {
         Ok(_) => (),
         Err(e) => {
@@ -122,17 +130,28 @@ fn handle_finalize_enclave(_: &FinalizeEnclaveInput) -> TeeServiceResult<Finaliz
     Ok(FinalizeEnclaveOutput)
 }
 
+#[handle_ecall]
+fn handle_get_metrics(_: &GetMetricsInput) -> TeeServiceResult<GetMetricsOutput> {
+    Ok(GetMetricsOutput::new(metrics::gather()))
+}
+
 register_ecall_handler!(
     type ECallCommand,
     (ECallCommand::StartService, StartServiceInput, StartServiceOutput),
     (ECallCommand::InitEnclave, InitEnclaveInput, InitEnclaveOutput),
     (ECallCommand::FinalizeEnclave, FinalizeEnclaveInput, FinalizeEnclaveOutput),
+    (ECallCommand::GetMetrics, GetMetricsInput, GetMetricsOutput),
 );
 
 #[cfg(feature = "enclave_unit_test")]
 pub mod tests {
+    use super::*;
+    use teaclave_test_utils::*;
 
     pub fn run_tests() -> bool {
-        true
+        run_tests!(
+            service::tests::test_execution_enclave_peer,
+            service::tests::test_permission_denied,
+        )
     }
 }
@@ -50,32 +50,10 @@ impl PublisherService {
         storage_service_endpoint: Endpoint,
         scheduler_service_endpoint: Endpoint,
     ) -> Result<Self> {
-        let mut i = 0;
-        let channel = loop {
-            match storage_service_endpoint.connect() {
-                Ok(channel) => break channel,
-                Err(_) => {
-                    anyhow::ensure!(i < 10, "failed to connect to storage service");
-                    log::debug!("Failed to connect to storage service, retry {}", i);
-                    i += 1;
-                }
-            }
-            std::thread::sleep(std::time::Duration::from_secs(3));
-        };
+        let channel = storage_service_endpoint.connect()?;
         let storage_client = Arc::new(Mutex::new(TeaclaveStorageClient::new(channel)?));
 
-        let mut i = 0;
-        let channel = loop {
-            match scheduler_service_endpoint.connect() {
-                Ok(channel) => break channel,
-                Err(_) => {
-                    anyhow::ensure!(i < 10, "failed to connect to storage service");
-                    log::debug!("Failed to connect to storage service, retry {}", i);
-                    i += 1;
-                }
-            }
-            std::thread::sleep(std::time::Duration::from_secs(1));
-        };
+        let channel = scheduler_service_endpoint.connect()?;
         let scheduler_client = Arc::new(Mutex::new(TeaclaveSchedulerClient::new(channel)?));
 
         let service = Self {
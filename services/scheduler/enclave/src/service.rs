@@ -25,10 +25,13 @@ use std::prelude::v1::*;
 use std::sync::{Arc, SgxMutex as Mutex};
 
 use std::collections::HashMap;
+use teaclave_crypto::{KeyWrappingKey, KEY_WRAPPING_PUBLIC_KEY_LENGTH};
 use teaclave_proto::teaclave_scheduler_service::*;
 use teaclave_proto::teaclave_storage_service::*;
 use teaclave_rpc::endpoint::Endpoint;
-use teaclave_rpc::Request;
+use teaclave_rpc::{PeerInfo, Request};
+use teaclave_service_enclave_utils::key_wrapping::{self, SCHEDULER_KEY_WRAPPING_KEY};
+use teaclave_service_enclave_utils::metrics::{Counter, Gauge};
 use teaclave_service_enclave_utils::teaclave_service;
 use teaclave_types::*;
 use uuid::Uuid;
@@ -37,6 +40,20 @@ use anyhow::anyhow;
 use anyhow::Result;
 use thiserror::Error;
 
+// Attempts to update items in storage while other requests keep changing them.
+const MAX_UPDATE_ATTEMPTS: usize = 8;
+
+// Staged tasks wait in the queue of the storage service, whose length is
+// updated on every pull.
+static TASK_QUEUE_LENGTH: Gauge = Gauge::new(
+    "teaclave_scheduler_task_queue_length",
+    "Staged tasks waiting to be pulled",
+);
+static PULLED_TASKS: Counter = Counter::new(
+    "teaclave_scheduler_pulled_tasks_total",
+    "Staged tasks pulled by execution services",
+);
+
 #[derive(Error, Debug)]
 pub enum TeaclaveSchedulerError {
     #[error("scheduler service error")]
@@ -45,6 +62,8 @@ pub enum TeaclaveSchedulerError {
     DataError,
     #[error("storage error")]
     StorageError,
+    #[error("permission denied")]
+    PermissionDenied,
 }
 
 impl From<TeaclaveSchedulerError> for TeaclaveServiceResponseError {
@@ -58,32 +77,52 @@ impl From<TeaclaveSchedulerError> for TeaclaveServiceResponseError {
 pub(crate) struct TeaclaveSchedulerService {
     storage_client: Arc<Mutex<TeaclaveStorageClient>>,
     task_queue: Arc<Mutex<VecDeque<StagedTask>>>,
+    execution_enclave_attr: EnclaveAttr,
+    key_wrapping_key: Arc<KeyWrappingKey>,
 }
 
 impl TeaclaveSchedulerService {
-    pub(crate) fn new(storage_service_endpoint: Endpoint) -> Result<Self> {
-        let mut i = 0;
-        let channel = loop {
-            match storage_service_endpoint.connect() {
-                Ok(channel) => break channel,
-                Err(_) => {
-                    anyhow::ensure!(i < 10, "failed to connect to storage service");
-                    log::debug!("Failed to connect to storage service, retry {}", i);
-                    i += 1;
-                }
-            }
-            std::thread::sleep(std::time::Duration::from_secs(3));
-        };
-        let storage_client = Arc::new(Mutex::new(TeaclaveStorageClient::new(channel)?));
+    pub(crate) fn new(
+        storage_service_endpoint: Endpoint,
+        execution_enclave_attr: EnclaveAttr,
+    ) -> Result<Self> {
+        let channel = storage_service_endpoint.connect()?;
+        let mut storage_client = TeaclaveStorageClient::new(channel)?;
+        let key_wrapping_key =
+            key_wrapping::load_or_create(&mut storage_client, SCHEDULER_KEY_WRAPPING_KEY)?;
         let task_queue = Arc::new(Mutex::new(VecDeque::new()));
         let service = Self {
-            storage_client,
+            storage_client: Arc::new(Mutex::new(storage_client)),
             task_queue,
+            execution_enclave_attr,
+            key_wrapping_key: Arc::new(key_wrapping_key),
         };
 
         Ok(service)
     }
 
+    /// Ensure the request is sent by a genuine execution enclave, judging from
+    /// the attestation report of the peer, rather than by anything able to
+    /// reach the internal endpoint.
+    fn ensure_execution_enclave<T>(
+        &self,
+        request: &Request<T>,
+    ) -> TeaclaveServiceResponseResult<()> {
+        let peer = request.peer();
+        match peer.and_then(|p| p.attestation_report.as_ref()) {
+            // Enclaves are not attested in simulation mode
+            None if cfg!(sgx_sim) => return Ok(()),
+            // Like in the handshake, enclave measurements are not checked in
+            // test mode since the test enclave is not known to production
+            // enclaves
+            Some(_) if cfg!(test_mode) => return Ok(()),
+            _ => (),
+        }
+
+        check_peer_measurement(peer, &self.execution_enclave_attr)?;
+        Ok(())
+    }
+
     fn pull_staged_task<T: Storable>(&self, key: &[u8]) -> TeaclaveServiceResponseResult<T> {
         let dequeue_request = DequeueRequest::new(key);
         let dequeue_response = self
@@ -91,7 +130,14 @@ impl TeaclaveSchedulerService {
             .clone()
             .lock()
             .map_err(|_| TeaclaveSchedulerError::StorageError)?
-            .dequeue(dequeue_request)?;
+            .dequeue(dequeue_request)
+            .map_err(|e| {
+                if let TeaclaveServiceResponseError::NotFound(_) = e {
+                    TASK_QUEUE_LENGTH.set(&[], 0.0);
+                }
+                e
+            })?;
+        TASK_QUEUE_LENGTH.set(&[], dequeue_response.remaining as f64);
         T::from_slice(dequeue_response.value.as_slice())
             .map_err(|_| TeaclaveSchedulerError::DataError.into())
     }
@@ -101,6 +147,18 @@ impl TeaclaveSchedulerService {
         self.get_from_db(&key)
     }
 
+    // Finishes a staged task with the failure, through the same states as
+    // if it had been run.
+    fn fail_staged_task(&self, task_id: &Uuid, failure: TaskFailure) -> Result<()> {
+        let key = ExternalID::new(TaskState::key_prefix(), task_id.to_owned());
+        self.update_in_db(&key, |ts: TaskState| {
+            let task: Task<Run> = ts.try_into()?;
+            let mut task: Task<Finish> = TaskState::from(task).try_into()?;
+            task.update_result(TaskResult::Err(failure.clone()))?;
+            Ok(TaskState::from(task))
+        })
+    }
+
     fn get_from_db<T: Storable>(&self, key: &ExternalID) -> Result<T> {
         anyhow::ensure!(T::match_prefix(&key.prefix), "Key prefix doesn't match.");
         let get_request = GetRequest::new(key.to_bytes());
@@ -113,6 +171,35 @@ impl TeaclaveSchedulerService {
         T::from_slice(response.value.as_slice())
     }
 
+    /// Updates an item in storage with compare-and-swap, so that changes
+    /// written by other services in the meantime are not overwritten.
+    fn update_in_db<T: Storable>(
+        &self,
+        key: &ExternalID,
+        mut update: impl FnMut(T) -> Result<T>,
+    ) -> Result<()> {
+        anyhow::ensure!(T::match_prefix(&key.prefix), "Key prefix doesn't match.");
+        let id = key;
+        let key = id.to_bytes();
+        for _ in 0..MAX_UPDATE_ATTEMPTS {
+            let mut client = self
+                .storage_client
+                .lock()
+                .map_err(|_| anyhow!("Cannot lock storage client"))?;
+            let expected = client.get(GetRequest::new(key.as_slice()))?.value;
+            let value = update(T::from_slice(&expected)?)?.to_vec()?;
+            let entry = CompareAndSwapEntry::new(key.as_slice(), expected, value);
+            if client
+                .compare_and_swap(CompareAndSwapRequest::new(vec![entry]))?
+                .swapped
+            {
+                return Ok(());
+            }
+        }
+
+        Err(anyhow!("Cannot update {} in storage", id.to_string()))
+    }
+
     fn put_into_db(&self, item: &impl Storable) -> Result<()> {
         let k = item.key();
         let v = item.to_vec()?;
@@ -125,6 +212,17 @@ impl TeaclaveSchedulerService {
             .put(put_request)?;
         Ok(())
     }
+
+    fn put_stream_into_db(&self, key: Vec<u8>, mut value: &[u8]) -> Result<()> {
+        let put_request = PutStreamRequest::new(key);
+        let _put_response = self
+            .storage_client
+            .clone()
+            .lock()
+            .map_err(|_| anyhow!("Cannot lock storage client"))?
+            .put_stream(put_request, &mut value)?;
+        Ok(())
+    }
 }
 
 impl TeaclaveScheduler for TeaclaveSchedulerService {
@@ -156,8 +254,29 @@ impl TeaclaveScheduler for TeaclaveSchedulerService {
         &self,
         request: Request<PullTaskRequest>,
     ) -> TeaclaveServiceResponseResult<PullTaskResponse> {
+        self.ensure_execution_enclave(&request)?;
+        // Refuse a malformed key before the task is taken off the queue.
+        let public_key = request.message.public_key;
+        if public_key.len() != KEY_WRAPPING_PUBLIC_KEY_LENGTH {
+            return Err(TeaclaveSchedulerError::DataError.into());
+        }
         let key = StagedTask::get_queue_key().as_bytes();
-        let staged_task = self.pull_staged_task(key)?;
+        let staged_task: StagedTask = self.pull_staged_task(key)?;
+        let task_id = staged_task.task_id;
+        let staged_task = match staged_task.rewrap_file_keys(&self.key_wrapping_key, &public_key) {
+            Ok(staged_task) => staged_task,
+            // The file keys in the queue cannot be unwrapped, so the task
+            // would fail again if it were queued again.
+            Err(e) => {
+                log::warn!("Cannot wrap file keys of task {}: {:?}", task_id, e);
+                let failure = TaskFailure::new("Cannot wrap the file keys of the task");
+                if let Err(e) = self.fail_staged_task(&task_id, failure) {
+                    log::error!("Cannot mark task {} as failed: {:?}", task_id, e);
+                }
+                return Err(TeaclaveSchedulerError::DataError.into());
+            }
+        };
+        PULLED_TASKS.inc(&[]);
         let response = PullTaskResponse::new(staged_task);
         Ok(response)
     }
@@ -166,6 +285,7 @@ impl TeaclaveScheduler for TeaclaveSchedulerService {
         &self,
         request: Request<UpdateTaskStatusRequest>,
     ) -> TeaclaveServiceResponseResult<UpdateTaskStatusResponse> {
+        self.ensure_execution_enclave(&request)?;
         let request = request.message;
         let ts = self.get_task_state(&request.task_id)?;
         let task: Task<Run> = ts.try_into()?;
@@ -182,15 +302,35 @@ impl TeaclaveScheduler for TeaclaveSchedulerService {
         &self,
         request: Request<UpdateTaskResultRequest>,
     ) -> TeaclaveServiceResponseResult<UpdateTaskResultResponse> {
-        let request = request.message;
+        self.ensure_execution_enclave(&request)?;
+        let mut request = request.message;
         let ts = self.get_task_state(&request.task_id)?;
+        let return_value_key = ts.return_value_key();
+        let log_key = ts.log_key();
         let mut task: Task<Finish> = ts.try_into()?;
 
-        if let TaskResult::Ok(outputs) = &request.task_result {
+        if let TaskResult::Ok(outputs) = &mut request.task_result {
             for (key, auth_tag) in outputs.tags_map.iter() {
                 let outfile = task.update_output_cmac(key, auth_tag)?;
                 self.put_into_db(outfile)?;
             }
+
+            // Point the rotated input file to its re-encrypted copy. The
+            // current record is updated in place to keep its spent privacy
+            // budget, even if it is being spent concurrently.
+            if let Some((data_id, output_name, output_file)) = task.rotated_file() {
+                let hash = outputs.hashes_map.get(output_name);
+                self.update_in_db(data_id, |input_file: TeaclaveInputFile| {
+                    input_file.rotate_key(output_file, hash)
+                })?;
+            }
+
+            // The return value and the log are stored in chunks, and left
+            // empty in the task state.
+            let return_value = std::mem::take(&mut outputs.return_value);
+            self.put_stream_into_db(return_value_key, &return_value)?;
+            let log: String = outputs.log.drain(..).map(|line| line + "\n").collect();
+            self.put_stream_into_db(log_key, log.as_bytes())?;
         };
 
         // Updating task result means we have finished execution
@@ -201,9 +341,114 @@ impl TeaclaveScheduler for TeaclaveSchedulerService {
         self.put_into_db(&ts)?;
         Ok(UpdateTaskResultResponse {})
     }
+
+    // The management service wraps the file keys of staged tasks to the
+    // public key, which it gets over the attested channel to the scheduler.
+    fn get_key_wrapping_public_key(
+        &self,
+        request: Request<GetKeyWrappingPublicKeyRequest>,
+    ) -> TeaclaveServiceResponseResult<GetKeyWrappingPublicKeyResponse> {
+        let public_key = self.key_wrapping_key.public_key();
+        Ok(GetKeyWrappingPublicKeyResponse::new(&public_key[..]))
+    }
+}
+
+/// Check the peer is attested with the measurement of the execution enclave.
+fn check_peer_measurement(
+    peer: Option<&PeerInfo>,
+    execution_enclave_attr: &EnclaveAttr,
+) -> std::result::Result<(), TeaclaveSchedulerError> {
+    let report = peer
+        .and_then(|p| p.attestation_report.as_ref())
+        .ok_or(TeaclaveSchedulerError::PermissionDenied)?;
+    let enclave_report = &report.sgx_quote_body.isv_enclave_report;
+    let measurement = &execution_enclave_attr.measurement;
+    if enclave_report.mr_enclave != measurement.mr_enclave
+        || enclave_report.mr_signer != measurement.mr_signer
+    {
+        return Err(TeaclaveSchedulerError::PermissionDenied);
+    }
+
+    Ok(())
 }
 
 #[cfg(test_mode)]
 mod test_mode {
     use super::*;
 }
+
+#[cfg(feature = "enclave_unit_test")]
+pub mod tests {
+    use super::*;
+    use teaclave_attestation::report::*;
+
+    const MR_ENCLAVE: SgxMeasurement = [1; 32];
+    const MR_SIGNER: SgxMeasurement = [2; 32];
+
+    fn peer(mr_enclave: SgxMeasurement, mr_signer: SgxMeasurement) -> PeerInfo {
+        let isv_enclave_report = SgxEnclaveReport {
+            cpu_svn: [0; 16],
+            misc_select: 0,
+            attributes: [0; 16],
+            mr_enclave,
+            mr_signer,
+            isv_prod_id: 0,
+            isv_svn: 0,
+            report_data: [0; 64],
+        };
+        let attestation_report = AttestationReport {
+            freshness: std::time::Duration::from_secs(0),
+            timestamp: None,
+            sgx_quote_status: SgxQuoteStatus::OK,
+            advisory_ids: Vec::new(),
+            tcb_evaluation_data_number: None,
+            tcb_date: None,
+            sgx_quote_body: SgxQuote {
+                version: SgxQuoteVersion::V2(SgxEpidQuoteSigType::Linkable),
+                gid: 0,
+                isv_svn_qe: 0,
+                isv_svn_pce: 0,
+                qe_vendor_id: Uuid::nil(),
+                user_data: [0; 20],
+                isv_enclave_report,
+            },
+        };
+        PeerInfo {
+            attestation_report: Some(attestation_report),
+            tls_exporter_secret: Vec::new(),
+        }
+    }
+
+    fn execution_enclave_attr() -> EnclaveAttr {
+        EnclaveAttr {
+            measurement: EnclaveMeasurement::new(MR_ENCLAVE, MR_SIGNER),
+        }
+    }
+
+    pub fn test_execution_enclave_peer() {
+        let attr = execution_enclave_attr();
+        assert!(check_peer_measurement(Some(&peer(MR_ENCLAVE, MR_SIGNER)), &attr).is_ok());
+    }
+
+    pub fn test_permission_denied() {
+        let attr = execution_enclave_attr();
+        let denied = |peer: Option<&PeerInfo>| {
+            matches!(
+                check_peer_measurement(peer, &attr),
+                Err(TeaclaveSchedulerError::PermissionDenied)
+            )
+        };
+
+        // Not attested, e.g., clients of the API endpoints
+        assert!(denied(None));
+        let unattested = PeerInfo {
+            attestation_report: None,
+            tls_exporter_secret: Vec::new(),
+        };
+        assert!(denied(Some(&unattested)));
+
+        // Attested, but not the execution enclave
+        assert!(denied(Some(&peer([3; 32], MR_SIGNER))));
+        assert!(denied(Some(&peer(MR_ENCLAVE, [3; 32]))));
+    }
+}
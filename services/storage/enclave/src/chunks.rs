@@ -0,0 +1,150 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License.
+
+//! Values put with streams, which are stored in chunks so that they are never
+//! sent or stored in one piece. Chunks are read and written with the requests
+//! of single keys, so the same functions serve the proxy, which receives the
+//! streams, and the service.
+
+use crate::service::TeaclaveStorageError;
+use std::convert::TryInto;
+use std::io::{Read, Write};
+use std::prelude::v1::*;
+use std::sync::atomic::{AtomicU64, Ordering};
+use teaclave_proto::teaclave_storage_service::{
+    DeleteRequest, GetRequest, PutRequest, TeaclaveStorageRequest, TeaclaveStorageResponse,
+};
+use teaclave_rpc::stream::CHUNK_SIZE;
+use teaclave_rpc::{IntoRequest, Request};
+use teaclave_types::TeaclaveServiceResponseResult;
+
+// chunks-key: generation (u64) and number (u32) of chunks of the value
+// chunks-key-generation-index: Vec<u8>; chunks of the value
+//
+// Every put writes chunks of a new generation before switching the value to
+// them, so a value is never read half written. The database lives in memory
+// and starts empty, so generations only have to be unique in this process.
+static GENERATION: AtomicU64 = AtomicU64::new(0);
+
+type DispatchResult = TeaclaveServiceResponseResult<TeaclaveStorageResponse>;
+/// Sends a request of a single key to the database.
+pub(crate) type Dispatch<'a> = &'a dyn Fn(Request<TeaclaveStorageRequest>) -> DispatchResult;
+
+fn header_key(key: &[u8]) -> Vec<u8> {
+    let mut header_key = b"chunks-".to_vec();
+    header_key.extend_from_slice(key);
+    header_key
+}
+
+pub(crate) fn chunk_key(key: &[u8], generation: u64, index: u32) -> Vec<u8> {
+    let mut chunk_key = header_key(key);
+    chunk_key.extend_from_slice(b"-");
+    chunk_key.extend_from_slice(&generation.to_le_bytes());
+    chunk_key.extend_from_slice(b"-");
+    chunk_key.extend_from_slice(&index.to_le_bytes());
+    chunk_key
+}
+
+fn get(dispatch: Dispatch, key: Vec<u8>) -> TeaclaveServiceResponseResult<Vec<u8>> {
+    match dispatch(GetRequest::new(key).into_request())? {
+        TeaclaveStorageResponse::Get(response) => Ok(response.value),
+        _ => Err(TeaclaveStorageError::Connection.into()),
+    }
+}
+
+fn put(dispatch: Dispatch, key: Vec<u8>, value: Vec<u8>) -> TeaclaveServiceResponseResult<()> {
+    dispatch(PutRequest::new(key, value).into_request()).map(|_| ())
+}
+
+fn delete_chunks(dispatch: Dispatch, key: &[u8], generation: u64, count: u32) {
+    for index in 0..count {
+        let request = DeleteRequest::new(chunk_key(key, generation, index)).into_request();
+        if let Err(e) = dispatch(request) {
+            log::warn!("Cannot delete chunk {} of a value: {:?}", index, e);
+        }
+    }
+}
+
+fn read_header(dispatch: Dispatch, key: &[u8]) -> TeaclaveServiceResponseResult<(u64, u32)> {
+    let header = get(dispatch, header_key(key))?;
+    if header.len() != 12 {
+        return Err(TeaclaveStorageError::None.into());
+    }
+    let generation = u64::from_le_bytes(header[..8].try_into().unwrap());
+    let count = u32::from_le_bytes(header[8..].try_into().unwrap());
+    Ok((generation, count))
+}
+
+/// Store the value read from `reader` in chunks.
+pub(crate) fn put_stream(
+    dispatch: Dispatch,
+    key: &[u8],
+    reader: &mut dyn Read,
+) -> TeaclaveServiceResponseResult<()> {
+    let generation = GENERATION.fetch_add(1, Ordering::SeqCst);
+    let mut count = 0;
+    let written = loop {
+        let mut chunk = Vec::with_capacity(CHUNK_SIZE);
+        if let Err(e) = (&mut *reader)
+            .take(CHUNK_SIZE as u64)
+            .read_to_end(&mut chunk)
+        {
+            log::debug!("Cannot read the value: {:?}", e);
+            break Err(TeaclaveStorageError::Stream.into());
+        }
+        if chunk.is_empty() {
+            break Ok(());
+        }
+        if let Err(e) = put(dispatch, chunk_key(key, generation, count), chunk) {
+            break Err(e);
+        }
+        count += 1;
+    };
+    if let Err(e) = written {
+        delete_chunks(dispatch, key, generation, count);
+        return Err(e);
+    }
+
+    let previous = read_header(dispatch, key).ok();
+    let mut header = generation.to_le_bytes().to_vec();
+    header.extend_from_slice(&count.to_le_bytes());
+    put(dispatch, header_key(key), header)?;
+    // Values are written once in practice, e.g., payloads of new functions.
+    // Chunks of concurrent puts of the same key may be left behind.
+    if let Some((generation, count)) = previous {
+        delete_chunks(dispatch, key, generation, count);
+    }
+
+    Ok(())
+}
+
+/// Write the value stored in chunks to `writer`.
+pub(crate) fn get_stream(
+    dispatch: Dispatch,
+    key: &[u8],
+    writer: &mut dyn Write,
+) -> TeaclaveServiceResponseResult<()> {
+    let (generation, count) = read_header(dispatch, key)?;
+    for index in 0..count {
+        let chunk = get(dispatch, chunk_key(key, generation, index))?;
+        writer
+            .write_all(&chunk)
+            .map_err(|_| TeaclaveStorageError::Stream)?;
+    }
+
+    Ok(())
+}
@@ -15,11 +15,14 @@
 // specific language governing permissions and limitations
 // under the License.
 
+use crate::chunks;
 use crate::service::TeaclaveStorageError;
 use std::prelude::v1::*;
 use std::sync::mpsc::{channel, Sender};
 use teaclave_proto::teaclave_storage_service::{TeaclaveStorageRequest, TeaclaveStorageResponse};
+use teaclave_rpc::stream::Stream;
 use teaclave_rpc::Request;
+use teaclave_service_enclave_utils::metrics;
 use teaclave_types::TeaclaveServiceResponseResult;
 
 #[derive(Clone)]
@@ -33,10 +36,8 @@ impl ProxyService {
     }
 }
 
-impl teaclave_rpc::TeaclaveService<TeaclaveStorageRequest, TeaclaveStorageResponse>
-    for ProxyService
-{
-    fn handle_request(
+impl ProxyService {
+    fn forward(
         &self,
         request: Request<TeaclaveStorageRequest>,
     ) -> TeaclaveServiceResponseResult<TeaclaveStorageResponse> {
@@ -50,6 +51,64 @@ impl teaclave_rpc::TeaclaveService<TeaclaveStorageRequest, TeaclaveStorageRespon
     }
 }
 
+impl teaclave_rpc::TeaclaveService<TeaclaveStorageRequest, TeaclaveStorageResponse>
+    for ProxyService
+{
+    fn handle_request(
+        &self,
+        request: Request<TeaclaveStorageRequest>,
+    ) -> TeaclaveServiceResponseResult<TeaclaveStorageResponse> {
+        // The storage service is dispatched in its own thread, so requests are
+        // observed here including the time waiting for the thread.
+        let method = request.message.method_name();
+        metrics::observe_request("teaclave_storage_service", method, || self.forward(request))
+    }
+
+    // Streams cannot be passed to the thread of the storage service, so they
+    // are read and written here, and their chunks are forwarded one by one.
+    fn handle_stream_request(
+        &self,
+        request: Request<TeaclaveStorageRequest>,
+        stream: &mut Stream,
+    ) -> TeaclaveServiceResponseResult<TeaclaveStorageResponse> {
+        let method = request.message.method_name();
+        let dispatch = |request| self.forward(request);
+        match request.message {
+            TeaclaveStorageRequest::PutStream(r) => {
+                metrics::observe_request("teaclave_storage_service", method, || {
+                    let mut reader = stream.reader();
+                    match chunks::put_stream(&dispatch, &r.key, &mut reader) {
+                        Ok(()) => reader.drain()?,
+                        Err(e) => {
+                            reader.close();
+                            return Err(e);
+                        }
+                    }
+                    Ok(TeaclaveStorageResponse::Put(Default::default()))
+                })
+            }
+            TeaclaveStorageRequest::GetStream(r) => {
+                metrics::observe_request("teaclave_storage_service", method, || {
+                    let mut writer = stream.writer();
+                    match chunks::get_stream(&dispatch, &r.key, &mut writer) {
+                        Ok(()) => writer.finish()?,
+                        Err(e) => {
+                            writer.close()?;
+                            return Err(e);
+                        }
+                    }
+                    Ok(TeaclaveStorageResponse::GetStream(Default::default()))
+                })
+            }
+            message => self.handle_request(Request {
+                metadata: request.metadata,
+                message,
+                peer: request.peer,
+            }),
+        }
+    }
+}
+
 #[derive(Clone)]
 pub(crate) struct ProxyRequest {
     pub sender: Sender<TeaclaveServiceResponseResult<TeaclaveStorageResponse>>,
@@ -15,20 +15,31 @@
 // specific language governing permissions and limitations
 // under the License.
 
+use crate::chunks;
 use crate::proxy::ProxyRequest;
 use rusty_leveldb::DB;
 use std::cell::RefCell;
 use std::prelude::v1::*;
 use std::sync::mpsc::Receiver;
 use teaclave_proto::teaclave_storage_service::{
-    DeleteRequest, DeleteResponse, DequeueRequest, DequeueResponse, EnqueueRequest,
-    EnqueueResponse, GetRequest, GetResponse, PutRequest, PutResponse, TeaclaveStorage,
+    CompareAndSwapRequest, CompareAndSwapResponse, DeleteRequest, DeleteResponse, DequeueRequest,
+    DequeueResponse, EnqueueRequest, EnqueueResponse, GetRequest, GetResponse, GetStreamRequest,
+    GetStreamResponse, PutRequest, PutResponse, PutStreamRequest, TeaclaveStorage,
 };
+use teaclave_rpc::stream::{StreamReader, StreamWriter};
 use teaclave_rpc::Request;
+use teaclave_service_enclave_utils::metrics::Gauge;
 use teaclave_service_enclave_utils::{bail, teaclave_service};
-use teaclave_types::{TeaclaveServiceResponseError, TeaclaveServiceResponseResult};
+use teaclave_types::{StagedTask, TeaclaveServiceResponseError, TeaclaveServiceResponseResult};
 use thiserror::Error;
 
+static DB_KEYS: Gauge = Gauge::new("teaclave_storage_db_keys", "Keys in the database");
+static DB_BYTES: Gauge = Gauge::new(
+    "teaclave_storage_db_bytes",
+    "Total size of keys and values in the database",
+);
+static QUEUE_LENGTH: Gauge = Gauge::new("teaclave_storage_queue_length", "Elements in the queue");
+
 #[derive(Error, Debug)]
 pub(crate) enum TeaclaveStorageError {
     #[error("connection error")]
@@ -37,11 +48,16 @@ pub(crate) enum TeaclaveStorageError {
     LevelDb(#[from] rusty_leveldb::Status),
     #[error("none error")]
     None,
+    #[error("stream error")]
+    Stream,
 }
 
 impl From<TeaclaveStorageError> for TeaclaveServiceResponseError {
     fn from(error: TeaclaveStorageError) -> Self {
-        TeaclaveServiceResponseError::RequestError(error.to_string())
+        match error {
+            TeaclaveStorageError::None => TeaclaveServiceResponseError::NotFound(error.to_string()),
+            _ => TeaclaveServiceResponseError::RequestError(error.to_string()),
+        }
     }
 }
 
@@ -60,6 +76,32 @@ impl TeaclaveStorageService {
     }
 }
 
+// The database has no API for its size, so the size is tracked on every write
+// by reading the old value first. The database lives in memory and starts
+// empty, so the tracked size is exact.
+fn db_put(database: &mut DB, key: &[u8], value: &[u8]) -> Result<(), rusty_leveldb::Status> {
+    let old_value = database.get(key);
+    database.put(key, value)?;
+    match old_value {
+        Some(old_value) => DB_BYTES.add(&[], value.len() as f64 - old_value.len() as f64),
+        None => {
+            DB_KEYS.add(&[], 1.0);
+            DB_BYTES.add(&[], (key.len() + value.len()) as f64);
+        }
+    }
+    Ok(())
+}
+
+fn db_delete(database: &mut DB, key: &[u8]) -> Result<(), rusty_leveldb::Status> {
+    let old_value = database.get(key);
+    database.delete(key)?;
+    if let Some(old_value) = old_value {
+        DB_KEYS.add(&[], -1.0);
+        DB_BYTES.add(&[], -((key.len() + old_value.len()) as f64));
+    }
+    Ok(())
+}
+
 // queue-key-head: u32; include element
 // queue-key-tail: u32; not include element; if head == tail, queue is empty
 // queue-key-index: Vec<u8>; elements
@@ -120,14 +162,14 @@ impl<'a> DBQueue<'a> {
     pub fn enqueue(&mut self, value: &[u8]) -> TeaclaveServiceResponseResult<()> {
         let mut tail_index = self.get_tail();
         // put element
-        self.database
-            .put(&self.get_element_key(tail_index), value)
-            .map_err(TeaclaveStorageError::LevelDb)?;
+        let element_key = self.get_element_key(tail_index);
+        db_put(self.database, &element_key, value).map_err(TeaclaveStorageError::LevelDb)?;
         // tail + 1
         tail_index += 1;
-        self.database
-            .put(&self.get_tail_key(), &tail_index.to_le_bytes())
+        let tail_key = self.get_tail_key();
+        db_put(self.database, &tail_key, &tail_index.to_le_bytes())
             .map_err(TeaclaveStorageError::LevelDb)?;
+        self.update_length();
         Ok(())
     }
 
@@ -145,19 +187,31 @@ impl<'a> DBQueue<'a> {
             };
             // update head
             head_index += 1;
-            self.database
-                .put(&self.get_head_key(), &head_index.to_le_bytes())
+            let head_key = self.get_head_key();
+            db_put(self.database, &head_key, &head_index.to_le_bytes())
                 .map_err(TeaclaveStorageError::LevelDb)?;
             // delete element; it's ok to ignore the error
-            let _ = self.database.delete(&element_key);
+            let _ = db_delete(self.database, &element_key);
+            self.update_length();
             Ok(result)
         }
     }
 
-    #[allow(unused)]
     pub fn len(&mut self) -> u32 {
         self.get_tail() - self.get_head()
     }
+
+    // Keys of queues are chosen by clients, so they are mapped to fixed names
+    // to keep labels of metrics free of user data.
+    fn update_length(&mut self) {
+        let queue = if self.key == StagedTask::get_queue_key().as_bytes() {
+            "staged_task"
+        } else {
+            "other"
+        };
+        let length = self.len();
+        QUEUE_LENGTH.set(&[("queue", queue)], length as f64);
+    }
 }
 
 impl TeaclaveStorageService {
@@ -195,10 +249,8 @@ impl TeaclaveStorage for TeaclaveStorageService {
 
     fn put(&self, request: Request<PutRequest>) -> TeaclaveServiceResponseResult<PutResponse> {
         let request = request.message;
-        self.database
-            .borrow_mut()
-            .put(&request.key, &request.value)
-            .map_err(TeaclaveStorageError::LevelDb)?;
+        let mut db = self.database.borrow_mut();
+        db_put(&mut db, &request.key, &request.value).map_err(TeaclaveStorageError::LevelDb)?;
         Ok(PutResponse)
     }
 
@@ -207,10 +259,8 @@ impl TeaclaveStorage for TeaclaveStorageService {
         request: Request<DeleteRequest>,
     ) -> TeaclaveServiceResponseResult<DeleteResponse> {
         let request = request.message;
-        self.database
-            .borrow_mut()
-            .delete(&request.key)
-            .map_err(TeaclaveStorageError::LevelDb)?;
+        let mut db = self.database.borrow_mut();
+        db_delete(&mut db, &request.key).map_err(TeaclaveStorageError::LevelDb)?;
         Ok(DeleteResponse)
     }
 
@@ -231,7 +281,49 @@ impl TeaclaveStorage for TeaclaveStorageService {
         let request = request.message;
         let mut db = self.database.borrow_mut();
         let mut queue = DBQueue::open(&mut db, &request.key);
-        queue.dequeue().map(|value| DequeueResponse { value })
+        let value = queue.dequeue()?;
+        Ok(DequeueResponse::new(value, queue.len()))
+    }
+
+    // Requests are handled one at a time, so nothing is written between the
+    // comparison and the swap.
+    fn compare_and_swap(
+        &self,
+        request: Request<CompareAndSwapRequest>,
+    ) -> TeaclaveServiceResponseResult<CompareAndSwapResponse> {
+        let request = request.message;
+        let mut db = self.database.borrow_mut();
+        for entry in request.entries.iter() {
+            if db.get(&entry.key).as_ref() != Some(&entry.expected) {
+                return Ok(CompareAndSwapResponse::new(false));
+            }
+        }
+        for entry in request.entries.iter() {
+            db_put(&mut db, &entry.key, &entry.value).map_err(TeaclaveStorageError::LevelDb)?;
+        }
+        Ok(CompareAndSwapResponse::new(true))
+    }
+
+    // Streams are received by the proxy, which stores the chunks with the
+    // same functions, so these are only called without the proxy.
+    fn put_stream(
+        &self,
+        request: Request<PutStreamRequest>,
+        stream: &mut StreamReader,
+    ) -> TeaclaveServiceResponseResult<PutResponse> {
+        let dispatch = |request| self.dispatch(request);
+        chunks::put_stream(&dispatch, &request.message.key, stream)?;
+        Ok(PutResponse)
+    }
+
+    fn get_stream(
+        &self,
+        request: Request<GetStreamRequest>,
+        stream: &mut StreamWriter,
+    ) -> TeaclaveServiceResponseResult<GetStreamResponse> {
+        let dispatch = |request| self.dispatch(request);
+        chunks::get_stream(&dispatch, &request.message.key, stream)?;
+        Ok(GetStreamResponse)
     }
 }
 
@@ -252,7 +344,10 @@ mod test_mode {
 #[cfg(feature = "enclave_unit_test")]
 pub mod tests {
     use super::*;
+    use std::convert::TryInto;
     use std::sync::mpsc::channel;
+    use teaclave_proto::teaclave_storage_service::CompareAndSwapEntry;
+    use teaclave_rpc::stream::CHUNK_SIZE;
     use teaclave_rpc::IntoRequest;
 
     fn get_mock_service() -> TeaclaveStorageService {
@@ -297,6 +392,11 @@ pub mod tests {
         assert!(service.enqueue(request).is_ok());
         let request = EnqueueRequest::new("test_enqueue_key", "2").into_request();
         assert!(service.enqueue(request).is_ok());
+
+        // Keys of queues are not exported in metrics.
+        let metrics = teaclave_service_enclave_utils::metrics::gather();
+        assert!(metrics.contains("teaclave_storage_queue_length{queue=\"other\"}"));
+        assert!(!metrics.contains("test_enqueue_key"));
     }
 
     pub fn test_dequeue() {
@@ -308,8 +408,74 @@ pub mod tests {
         let request = EnqueueRequest::new("test_dequeue_key", "2").into_request();
         assert!(service.enqueue(request).is_ok());
         let request = DequeueRequest::new("test_dequeue_key").into_request();
-        assert_eq!(service.dequeue(request).unwrap().value, b"1");
+        let response = service.dequeue(request).unwrap();
+        assert_eq!(response.value, b"1");
+        assert_eq!(response.remaining, 1);
         let request = DequeueRequest::new("test_dequeue_key").into_request();
-        assert_eq!(service.dequeue(request).unwrap().value, b"2");
+        let response = service.dequeue(request).unwrap();
+        assert_eq!(response.value, b"2");
+        assert_eq!(response.remaining, 0);
+    }
+
+    pub fn test_compare_and_swap() {
+        let service = get_mock_service();
+        let entries = vec![
+            CompareAndSwapEntry::new("test_get_key", "test_get_value", "1"),
+            CompareAndSwapEntry::new("test_delete_key", "stale_value", "2"),
+        ];
+        let request = CompareAndSwapRequest::new(entries).into_request();
+        assert!(!service.compare_and_swap(request).unwrap().swapped);
+        let request = GetRequest::new("test_get_key").into_request();
+        assert_eq!(service.get(request).unwrap().value, b"test_get_value");
+
+        let entries = vec![
+            CompareAndSwapEntry::new("test_get_key", "test_get_value", "1"),
+            CompareAndSwapEntry::new("test_delete_key", "test_delete_value", "2"),
+        ];
+        let request = CompareAndSwapRequest::new(entries).into_request();
+        assert!(service.compare_and_swap(request).unwrap().swapped);
+        let request = GetRequest::new("test_get_key").into_request();
+        assert_eq!(service.get(request).unwrap().value, b"1");
+        let request = GetRequest::new("test_delete_key").into_request();
+        assert_eq!(service.get(request).unwrap().value, b"2");
+
+        let entries = vec![CompareAndSwapEntry::new("test_key_not_exist", "", "1")];
+        let request = CompareAndSwapRequest::new(entries).into_request();
+        assert!(!service.compare_and_swap(request).unwrap().swapped);
+    }
+
+    pub fn test_put_and_get_stream() {
+        let service = get_mock_service();
+        let dispatch = |request| service.dispatch(request);
+        let key = b"test_stream_key";
+        // Three chunks, the last one partial
+        let value: Vec<u8> = (0..CHUNK_SIZE * 2 + 1).map(|i| i as u8).collect();
+
+        chunks::put_stream(&dispatch, key, &mut value.as_slice()).unwrap();
+        let mut read = Vec::new();
+        chunks::get_stream(&dispatch, key, &mut read).unwrap();
+        assert_eq!(read, value);
+        let chunk = |index| {
+            let header = service.database.borrow_mut().get(b"chunks-test_stream_key");
+            let generation = u64::from_le_bytes(header.unwrap()[..8].try_into().unwrap());
+            chunks::chunk_key(key, generation, index)
+        };
+        let old_chunk = chunk(2);
+        assert!(service.database.borrow_mut().get(&old_chunk).is_some());
+
+        // Chunks of the previous value are replaced.
+        chunks::put_stream(&dispatch, key, &mut &b"short value"[..]).unwrap();
+        let mut read = Vec::new();
+        chunks::get_stream(&dispatch, key, &mut read).unwrap();
+        assert_eq!(read, b"short value");
+        assert!(service.database.borrow_mut().get(&old_chunk).is_none());
+        assert!(service.database.borrow_mut().get(&chunk(0)).is_some());
+
+        let mut read = Vec::new();
+        let result = chunks::get_stream(&dispatch, b"test_key_not_exist", &mut read);
+        assert!(matches!(
+            result,
+            Err(TeaclaveServiceResponseError::NotFound(_))
+        ));
     }
 }
@@ -16,14 +16,19 @@
 // under the License.
 
 use anyhow::{bail, Context, Result};
+use std::net::SocketAddr;
 use std::path::Path;
 use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::Arc;
-use teaclave_binder::proto::{ECallCommand, StartServiceInput, StartServiceOutput};
+use teaclave_binder::proto::{
+    ECallCommand, GetMetricsInput, GetMetricsOutput, StartServiceInput, StartServiceOutput,
+};
 use teaclave_binder::TeeBinder;
 use teaclave_config::RuntimeConfig;
 use teaclave_types::TeeServiceResult;
 
+mod metrics;
+
 pub struct TeaclaveServiceLauncher {
     tee: TeeBinder,
     config: RuntimeConfig,
@@ -50,6 +55,32 @@ impl TeaclaveServiceLauncher {
         }
     }
 
+    pub fn config(&self) -> &RuntimeConfig {
+        &self.config
+    }
+
+    /// Serve metrics of the service over HTTP in a new thread. Each request
+    /// gathers the metrics from the enclave, which needs a free TCS besides
+    /// the ones used by the service.
+    pub fn start_metrics_endpoint(self: Arc<Self>, listen_address: SocketAddr) -> Result<()> {
+        let listener = metrics::bind(listen_address)?;
+        log::info!("Serving metrics at http://{}/metrics", listen_address);
+        std::thread::spawn(move || metrics::serve(listener, || self.get_metrics()));
+        Ok(())
+    }
+
+    fn get_metrics(&self) -> Result<String> {
+        let command = ECallCommand::GetMetrics;
+        match self
+            .tee
+            .invoke::<GetMetricsInput, TeeServiceResult<GetMetricsOutput>>(command, GetMetricsInput)
+        {
+            Err(e) => bail!("TEE invocation error: {:?}", e),
+            Ok(Err(e)) => bail!("Failed to get metrics: {:?}", e),
+            Ok(Ok(output)) => Ok(output.metrics),
+        }
+    }
+
     pub fn finalize(&self) {
         self.tee.finalize();
     }
@@ -0,0 +1,181 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License.
+
+//! A minimal HTTP endpoint serving metrics gathered from the enclave at
+//! `/metrics` in the Prometheus text format.
+
+use anyhow::{Context, Result};
+use std::io::{BufRead, BufReader, Read, Write};
+use std::net::{SocketAddr, TcpListener, TcpStream};
+use std::time::Duration;
+
+const CONTENT_TYPE: &str = "text/plain; version=0.0.4";
+const TIMEOUT: Duration = Duration::from_secs(10);
+const MAX_LINE_LENGTH: u64 = 8 * 1024;
+const MAX_HEADERS: usize = 100;
+
+pub(crate) fn bind(listen_address: SocketAddr) -> Result<TcpListener> {
+    TcpListener::bind(listen_address)
+        .with_context(|| format!("Failed to bind the metrics endpoint to {}", listen_address))
+}
+
+/// Serve requests one by one, since they are only sent by the monitoring
+/// system every few seconds.
+pub(crate) fn serve(listener: TcpListener, gather: impl Fn() -> Result<String>) {
+    for stream in listener.incoming() {
+        let result = stream
+            .map_err(anyhow::Error::from)
+            .and_then(|stream| handle(stream, &gather));
+        if let Err(e) = result {
+            log::debug!("Failed to handle the metrics request: {:?}", e);
+        }
+    }
+}
+
+fn handle(mut stream: TcpStream, gather: &impl Fn() -> Result<String>) -> Result<()> {
+    stream.set_read_timeout(Some(TIMEOUT))?;
+    stream.set_write_timeout(Some(TIMEOUT))?;
+
+    let mut reader = BufReader::new(stream.try_clone()?);
+    let request_line = read_line(&mut reader)?;
+    // Headers and body of the request are ignored
+    for _ in 0..MAX_HEADERS {
+        let line = read_line(&mut reader)?;
+        if line.trim_end().is_empty() {
+            break;
+        }
+    }
+
+    let mut parts = request_line.split_whitespace();
+    let method = parts.next();
+    let path = parts.next().map(|p| p.split('?').next().unwrap_or(p));
+    let response = match (method, path) {
+        (Some("GET"), Some("/metrics")) => match gather() {
+            Ok(metrics) => response("200 OK", CONTENT_TYPE, &metrics),
+            Err(e) => {
+                log::warn!("Failed to gather metrics: {:?}", e);
+                response(
+                    "503 Service Unavailable",
+                    "text/plain",
+                    "Metrics are unavailable\n",
+                )
+            }
+        },
+        (Some("GET"), _) => response("404 Not Found", "text/plain", "Not found\n"),
+        _ => response(
+            "405 Method Not Allowed",
+            "text/plain",
+            "Method not allowed\n",
+        ),
+    };
+    stream.write_all(response.as_bytes())?;
+    stream.flush()?;
+
+    Ok(())
+}
+
+fn read_line(reader: &mut BufReader<TcpStream>) -> Result<String> {
+    let mut line = String::new();
+    reader.take(MAX_LINE_LENGTH).read_line(&mut line)?;
+    Ok(line)
+}
+
+fn response(status: &str, content_type: &str, body: &str) -> String {
+    format!(
+        "HTTP/1.1 {}\r\nContent-Type: {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+        status,
+        content_type,
+        body.len(),
+        body
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use anyhow::anyhow;
+    use std::net::Shutdown;
+
+    const METRICS: &str = "teaclave_test_total 1\n";
+
+    fn start(gather: fn() -> Result<String>) -> SocketAddr {
+        let listener = bind("127.0.0.1:0".parse().unwrap()).unwrap();
+        let address = listener.local_addr().unwrap();
+        std::thread::spawn(move || serve(listener, gather));
+        address
+    }
+
+    fn send(address: SocketAddr, request: &[u8]) -> String {
+        let mut stream = TcpStream::connect(address).unwrap();
+        stream.write_all(request).unwrap();
+        stream.shutdown(Shutdown::Write).unwrap();
+        let mut response = String::new();
+        stream.read_to_string(&mut response).unwrap();
+        response
+    }
+
+    fn gather() -> Result<String> {
+        Ok(METRICS.to_string())
+    }
+
+    #[test]
+    fn test_metrics() {
+        let address = start(gather);
+        let response = send(address, b"GET /metrics HTTP/1.1\r\nHost: localhost\r\n\r\n");
+        assert_eq!(
+            response,
+            "HTTP/1.1 200 OK\r\nContent-Type: text/plain; version=0.0.4\r\n\
+             Content-Length: 22\r\nConnection: close\r\n\r\nteaclave_test_total 1\n"
+        );
+
+        // Query strings are ignored, and requests are served one by one.
+        let response = send(address, b"GET /metrics?name=test HTTP/1.1\r\n\r\n");
+        assert!(response.starts_with("HTTP/1.1 200 OK\r\n"));
+        assert!(response.ends_with(METRICS));
+    }
+
+    #[test]
+    fn test_metrics_unavailable() {
+        let address = start(|| Err(anyhow!("enclave error")));
+        let response = send(address, b"GET /metrics HTTP/1.1\r\n\r\n");
+        assert!(response.starts_with("HTTP/1.1 503 Service Unavailable\r\n"));
+        assert!(!response.contains("enclave error"));
+    }
+
+    #[test]
+    fn test_bad_requests() {
+        let address = start(gather);
+        let response = send(address, b"GET /metrics/ HTTP/1.1\r\n\r\n");
+        assert!(response.starts_with("HTTP/1.1 404 Not Found\r\n"));
+
+        let response = send(address, b"POST /metrics HTTP/1.1\r\n\r\n");
+        assert!(response.starts_with("HTTP/1.1 405 Method Not Allowed\r\n"));
+
+        let response = send(address, b"");
+        assert!(response.starts_with("HTTP/1.1 405 Method Not Allowed\r\n"));
+
+        // Lines are read up to the limit, so a long line is read as several.
+        let mut request = b"GET /metrics".to_vec();
+        request.resize(3 * MAX_LINE_LENGTH as usize, b'a');
+        let response = send(address, &request);
+        assert!(response.starts_with("HTTP/1.1 404 Not Found\r\n"));
+
+        // The server still serves after bad requests.
+        let response = send(address, b"GET /metrics HTTP/1.1\r\n\r\n");
+        assert!(response.ends_with(METRICS));
+    }
+}
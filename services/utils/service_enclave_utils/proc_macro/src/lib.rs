@@ -29,6 +29,7 @@ pub fn teaclave_service(attr: TokenStream, input: TokenStream) -> TokenStream {
     let splits: Vec<&str> = attr_str.split(",").map(|s| s.trim()).collect();
     let crate_name = Ident::new(splits[0], Span::call_site());
     let crate_name_proto = Ident::new(&format!("{}_proto", crate_name), Span::call_site());
+    let service_name = splits[0];
     let trait_name = splits[1];
     let trait_name_ident = Ident::new(trait_name, Span::call_site());
     let request = Ident::new(&format!("{}Request", trait_name), Span::call_site());
@@ -49,7 +50,24 @@ pub fn teaclave_service(attr: TokenStream, input: TokenStream) -> TokenStream {
                 use teaclave_proto::#crate_name_proto::#trait_name_ident;
                 use log::trace;
                 trace!("Dispatching request.");
-                self.dispatch(request)
+                let method = request.message.method_name();
+                teaclave_service_enclave_utils::metrics::observe_request(#service_name, method, || {
+                    self.dispatch(request)
+                })
+            }
+
+            fn handle_stream_request(
+                &self,
+                request: teaclave_rpc::Request<teaclave_proto::#crate_name_proto::#request>,
+                stream: &mut teaclave_rpc::stream::Stream,
+            ) -> std::result::Result<teaclave_proto::#crate_name_proto::#response, teaclave_types::TeaclaveServiceResponseError> {
+                use teaclave_proto::#crate_name_proto::#trait_name_ident;
+                use log::trace;
+                trace!("Dispatching request with stream.");
+                let method = request.message.method_name();
+                teaclave_service_enclave_utils::metrics::observe_request(#service_name, method, || {
+                    self.dispatch_stream(request, stream)
+                })
             }
         }
     );
@@ -0,0 +1,72 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License.
+
+//! Key wrapping keys of services, to which file keys are wrapped. The private
+//! key is sealed to the enclave signer and kept in the storage service to
+//! survive restarts, so that updated enclaves of the same signer can still
+//! unseal it. Since enclaves with a lower ISV SVN cannot unseal a key sealed
+//! by a higher one, an updated enclave seals the key again to its own SVN,
+//! which keeps it from rolled back enclaves.
+
+use crate::sealing;
+use anyhow::{anyhow, Result};
+use sgx_types::{sgx_isv_svn_t, SGX_KEYPOLICY_MRSIGNER};
+use std::prelude::v1::*;
+use teaclave_crypto::KeyWrappingKey;
+use teaclave_proto::teaclave_storage_service::{GetRequest, PutRequest, TeaclaveStorageClient};
+use teaclave_types::TeaclaveServiceResponseError;
+
+/// Storage key of the sealed key wrapping key of the management service.
+pub const MANAGEMENT_KEY_WRAPPING_KEY: &[u8] = b"management-key-wrapping-key";
+/// Storage key of the sealed key wrapping key of the scheduler service.
+pub const SCHEDULER_KEY_WRAPPING_KEY: &[u8] = b"scheduler-key-wrapping-key";
+
+/// Loads the key wrapping key sealed at `record`, or creates it if the record
+/// does not exist.
+pub fn load_or_create(
+    storage_client: &mut TeaclaveStorageClient,
+    record: &[u8],
+) -> Result<KeyWrappingKey> {
+    match storage_client.get(GetRequest::new(record)) {
+        Ok(response) => {
+            let (key, isv_svn) = unseal(response.value)?;
+            if isv_svn < sealing::isv_svn() {
+                log::info!("Sealing the key wrapping key to the updated enclave");
+                storage_client.put(PutRequest::new(record, seal(&key)?))?;
+            }
+            Ok(key)
+        }
+        // Only a missing record is replaced, any other error would otherwise
+        // make the wrapped keys in storage unrecoverable.
+        Err(TeaclaveServiceResponseError::NotFound(_)) => {
+            log::info!("Creating a new key wrapping key");
+            let key = KeyWrappingKey::random();
+            storage_client.put(PutRequest::new(record, seal(&key)?))?;
+            Ok(key)
+        }
+        Err(e) => Err(anyhow!("Cannot load the key wrapping key: {}", e)),
+    }
+}
+
+fn seal(key: &KeyWrappingKey) -> Result<Vec<u8>> {
+    sealing::seal(SGX_KEYPOLICY_MRSIGNER, &key.to_bytes())
+}
+
+fn unseal(buf: Vec<u8>) -> Result<(KeyWrappingKey, sgx_isv_svn_t)> {
+    let (bytes, isv_svn) = sealing::unseal_ex(buf)?;
+    Ok((KeyWrappingKey::from_bytes(&bytes)?, isv_svn))
+}
@@ -24,13 +24,26 @@ use log::debug;
 use log::error;
 use std::backtrace;
 use std::sync::{Arc, SgxRwLock as RwLock};
-use teaclave_attestation::verifier::AttestationReportVerificationFn;
+use std::time::Duration;
+use teaclave_attestation::verifier::AttestationReportPolicy;
 use teaclave_attestation::AttestedTlsConfig;
 use teaclave_rpc::config::SgxTrustedTlsClientConfig;
-use teaclave_rpc::endpoint::Endpoint;
+use teaclave_rpc::endpoint::{Endpoint, RetryPolicy};
+use teaclave_rpc::protocol::Encoding;
 use teaclave_types::EnclaveInfo;
 
+pub mod key_wrapping;
 mod macros;
+pub mod metrics;
+mod revocation;
+mod sealing;
+
+// Services may start in any order, so connecting to internal endpoints is
+// retried for up to half a minute.
+const INTERNAL_CONNECT_TIMEOUT: Duration = Duration::from_secs(3);
+const INTERNAL_CONNECT_RETRIES: u32 = 10;
+const INTERNAL_CONNECT_RETRY_INTERVAL: Duration = Duration::from_secs(3);
+const INTERNAL_REQUEST_TIMEOUT: Duration = Duration::from_secs(60);
 
 #[cfg(feature = "cov")]
 use sgx_trts::global_dtors_object;
@@ -70,6 +83,7 @@ impl ServiceEnclave {
     }
 }
 
+pub use revocation::watch_revocation_list;
 pub use teaclave_service_enclave_utils_proc_macro::teaclave_service;
 
 macro_rules! impl_create_trusted_endpoint_fn {
@@ -78,7 +92,7 @@ macro_rules! impl_create_trusted_endpoint_fn {
             advertised_address: &str,
             enclave_info: &EnclaveInfo,
             as_root_ca_cert: &[u8],
-            verifier: AttestationReportVerificationFn,
+            policy: impl AttestationReportPolicy + 'static,
             attested_tls_config: Arc<RwLock<AttestedTlsConfig>>,
         ) -> anyhow::Result<Endpoint> {
             let service_enclave_attrs = enclave_info
@@ -89,11 +103,23 @@ macro_rules! impl_create_trusted_endpoint_fn {
                     .attestation_report_verifier(
                         vec![service_enclave_attrs],
                         as_root_ca_cert,
-                        verifier,
+                        policy,
                     );
             let service_address = &advertised_address;
 
-            Ok(Endpoint::new(service_address).config(service_client_config))
+            // Both ends of internal endpoints are enclaves of the same
+            // release, so the binary encoding is always understood. Requests
+            // are not resent, since a request failed with a connection error,
+            // e.g., a dequeue, may still have been handled.
+            Ok(Endpoint::new(service_address)
+                .config(service_client_config)
+                .encoding(Encoding::Protobuf)
+                .connect_timeout(INTERNAL_CONNECT_TIMEOUT)
+                .request_timeout(INTERNAL_REQUEST_TIMEOUT)
+                .connect_retry(RetryPolicy::fixed(
+                    INTERNAL_CONNECT_RETRIES,
+                    INTERNAL_CONNECT_RETRY_INTERVAL,
+                )))
         }
     };
 }
@@ -111,3 +137,16 @@ impl_create_trusted_endpoint_fn!(
     create_trusted_scheduler_endpoint,
     "teaclave_scheduler_service"
 );
+
+#[cfg(feature = "enclave_unit_test")]
+pub mod tests {
+    use super::*;
+    use teaclave_test_utils::*;
+
+    pub fn run_tests() -> bool {
+        run_tests!(
+            metrics::tests::test_counter_and_gauge,
+            metrics::tests::test_histogram,
+        )
+    }
+}
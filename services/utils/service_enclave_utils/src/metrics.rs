@@ -0,0 +1,380 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License.
+
+//! Metrics of the service, e.g., counts and latencies of requests, gathered in
+//! the Prometheus text format by the untrusted part of the service through an
+//! ECall and served over HTTP. Metrics leave the enclave in plaintext, so
+//! labels must only contain names of services, methods, results or reasons,
+//! never any user data.
+//!
+//! Metrics are declared as statics and registered on first update:
+//!
+//! ```ignore
+//! static TASKS: Counter = Counter::new("teaclave_tasks_total", "Tasks handled");
+//!
+//! TASKS.inc(&[("result", "ok")]);
+//! ```
+
+use lazy_static::lazy_static;
+use std::collections::BTreeMap;
+use std::prelude::v1::*;
+use std::sync::SgxMutex as Mutex;
+use std::time::Instant;
+use std::untrusted::time::InstantEx;
+
+/// Buckets of durations in seconds
+pub const DURATION_BUCKETS: &[f64] = &[
+    0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0, 30.0, 60.0, 300.0,
+];
+
+static RPC_REQUESTS: Counter = Counter::new(
+    "teaclave_rpc_requests_total",
+    "RPC requests handled by the service",
+);
+static RPC_REQUEST_DURATION: Histogram = Histogram::new(
+    "teaclave_rpc_request_duration_seconds",
+    "Time to handle RPC requests",
+    DURATION_BUCKETS,
+);
+
+lazy_static! {
+    static ref REGISTRY: Mutex<BTreeMap<&'static str, Family>> = Mutex::new(BTreeMap::new());
+}
+
+type Labels = Vec<(String, String)>;
+
+#[derive(Clone, Copy)]
+enum Kind {
+    Counter,
+    Gauge,
+    Histogram,
+}
+
+impl Kind {
+    fn name(self) -> &'static str {
+        match self {
+            Kind::Counter => "counter",
+            Kind::Gauge => "gauge",
+            Kind::Histogram => "histogram",
+        }
+    }
+}
+
+struct Family {
+    help: &'static str,
+    kind: Kind,
+    series: BTreeMap<Labels, Series>,
+}
+
+enum Series {
+    Value(f64),
+    Histogram {
+        buckets: &'static [f64],
+        // Number of observations in each bucket, not cumulative
+        counts: Vec<u64>,
+        sum: f64,
+        count: u64,
+    },
+}
+
+/// Update the series of the labels, which is created with `init` first if
+/// there is none. Metrics are dropped if the registry cannot be locked.
+fn update(
+    name: &'static str,
+    help: &'static str,
+    kind: Kind,
+    labels: &[(&str, &str)],
+    init: impl FnOnce() -> Series,
+    f: impl FnOnce(&mut Series),
+) {
+    let mut registry = match REGISTRY.lock() {
+        Ok(registry) => registry,
+        Err(_) => return,
+    };
+    let family = registry.entry(name).or_insert_with(|| Family {
+        help,
+        kind,
+        series: BTreeMap::new(),
+    });
+    let labels = labels
+        .iter()
+        .map(|(k, v)| (k.to_string(), v.to_string()))
+        .collect();
+    f(family.series.entry(labels).or_insert_with(init));
+}
+
+/// A monotonically increasing count, e.g., of requests
+pub struct Counter {
+    name: &'static str,
+    help: &'static str,
+}
+
+impl Counter {
+    pub const fn new(name: &'static str, help: &'static str) -> Self {
+        Self { name, help }
+    }
+
+    pub fn inc(&self, labels: &[(&str, &str)]) {
+        self.inc_by(labels, 1.0);
+    }
+
+    pub fn inc_by(&self, labels: &[(&str, &str)], value: f64) {
+        update(
+            self.name,
+            self.help,
+            Kind::Counter,
+            labels,
+            || Series::Value(0.0),
+            |series| {
+                if let Series::Value(v) = series {
+                    *v += value;
+                }
+            },
+        );
+    }
+}
+
+/// A value going up and down, e.g., length of a queue
+pub struct Gauge {
+    name: &'static str,
+    help: &'static str,
+}
+
+impl Gauge {
+    pub const fn new(name: &'static str, help: &'static str) -> Self {
+        Self { name, help }
+    }
+
+    pub fn set(&self, labels: &[(&str, &str)], value: f64) {
+        self.update(labels, |v| *v = value);
+    }
+
+    pub fn add(&self, labels: &[(&str, &str)], value: f64) {
+        self.update(labels, |v| *v += value);
+    }
+
+    fn update(&self, labels: &[(&str, &str)], f: impl FnOnce(&mut f64)) {
+        update(
+            self.name,
+            self.help,
+            Kind::Gauge,
+            labels,
+            || Series::Value(0.0),
+            |series| {
+                if let Series::Value(v) = series {
+                    f(v);
+                }
+            },
+        );
+    }
+}
+
+/// Distribution of observed values in buckets, e.g., latencies of requests
+pub struct Histogram {
+    name: &'static str,
+    help: &'static str,
+    buckets: &'static [f64],
+}
+
+impl Histogram {
+    /// Create a histogram with the upper bounds of its buckets in increasing
+    /// order.
+    pub const fn new(name: &'static str, help: &'static str, buckets: &'static [f64]) -> Self {
+        Self {
+            name,
+            help,
+            buckets,
+        }
+    }
+
+    pub fn observe(&self, labels: &[(&str, &str)], value: f64) {
+        let buckets = self.buckets;
+        update(
+            self.name,
+            self.help,
+            Kind::Histogram,
+            labels,
+            || Series::Histogram {
+                buckets,
+                counts: vec![0; buckets.len()],
+                sum: 0.0,
+                count: 0,
+            },
+            |series| {
+                if let Series::Histogram {
+                    buckets,
+                    counts,
+                    sum,
+                    count,
+                } = series
+                {
+                    if let Some(i) = buckets.iter().position(|bound| value <= *bound) {
+                        counts[i] += 1;
+                    }
+                    *sum += value;
+                    *count += 1;
+                }
+            },
+        );
+    }
+
+    /// Start a timer to observe the elapsed time in seconds.
+    pub fn start_timer(&'static self) -> HistogramTimer {
+        HistogramTimer {
+            histogram: self,
+            start: Instant::now(),
+        }
+    }
+}
+
+pub struct HistogramTimer {
+    histogram: &'static Histogram,
+    start: Instant,
+}
+
+impl HistogramTimer {
+    pub fn observe_duration(self, labels: &[(&str, &str)]) {
+        let elapsed = self.start.elapsed().as_secs_f64();
+        self.histogram.observe(labels, elapsed);
+    }
+}
+
+/// Handle an RPC request, counting it by its result and observing the time
+/// to handle it. Used by services implemented with `teaclave_service`.
+pub fn observe_request<T, E>(
+    service: &str,
+    method: &str,
+    handle: impl FnOnce() -> Result<T, E>,
+) -> Result<T, E> {
+    let timer = RPC_REQUEST_DURATION.start_timer();
+    let response = handle();
+    timer.observe_duration(&[("service", service), ("method", method)]);
+    let result = if response.is_ok() { "ok" } else { "error" };
+    RPC_REQUESTS.inc(&[("service", service), ("method", method), ("result", result)]);
+
+    response
+}
+
+/// Gather all metrics in the Prometheus text format.
+pub fn gather() -> String {
+    let registry = match REGISTRY.lock() {
+        Ok(registry) => registry,
+        Err(_) => return String::new(),
+    };
+    let mut text = String::new();
+    for (name, family) in registry.iter() {
+        text.push_str(&format!("# HELP {} {}\n", name, family.help));
+        text.push_str(&format!("# TYPE {} {}\n", name, family.kind.name()));
+        for (labels, series) in family.series.iter() {
+            match series {
+                Series::Value(v) => {
+                    text.push_str(&format!("{}{} {}\n", name, format_labels(labels, None), v));
+                }
+                Series::Histogram {
+                    buckets,
+                    counts,
+                    sum,
+                    count,
+                } => {
+                    let mut cumulative = 0;
+                    for (bound, n) in buckets.iter().zip(counts.iter()) {
+                        cumulative += n;
+                        let le = bound.to_string();
+                        text.push_str(&format!(
+                            "{}_bucket{} {}\n",
+                            name,
+                            format_labels(labels, Some(&le)),
+                            cumulative
+                        ));
+                    }
+                    text.push_str(&format!(
+                        "{}_bucket{} {}\n",
+                        name,
+                        format_labels(labels, Some("+Inf")),
+                        count
+                    ));
+                    let labels = format_labels(labels, None);
+                    text.push_str(&format!("{}_sum{} {}\n", name, labels, sum));
+                    text.push_str(&format!("{}_count{} {}\n", name, labels, count));
+                }
+            }
+        }
+    }
+
+    text
+}
+
+fn format_labels(labels: &[(String, String)], le: Option<&str>) -> String {
+    let mut pairs: Vec<String> = labels
+        .iter()
+        .map(|(k, v)| format!("{}=\"{}\"", k, escape(v)))
+        .collect();
+    if let Some(le) = le {
+        pairs.push(format!("le=\"{}\"", le));
+    }
+    if pairs.is_empty() {
+        String::new()
+    } else {
+        format!("{{{}}}", pairs.join(","))
+    }
+}
+
+fn escape(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
+
+#[cfg(feature = "enclave_unit_test")]
+pub mod tests {
+    use super::*;
+
+    static TEST_COUNTER: Counter = Counter::new("test_counter_total", "Test counter");
+    static TEST_GAUGE: Gauge = Gauge::new("test_gauge", "Test gauge");
+    static TEST_HISTOGRAM: Histogram =
+        Histogram::new("test_histogram_seconds", "Test histogram", &[0.1, 1.0]);
+
+    pub fn test_counter_and_gauge() {
+        TEST_COUNTER.inc(&[("result", "ok")]);
+        TEST_COUNTER.inc_by(&[("result", "ok")], 2.0);
+        TEST_COUNTER.inc(&[("result", "a \"b\"")]);
+        TEST_GAUGE.set(&[], 3.0);
+        TEST_GAUGE.add(&[], -1.5);
+
+        let text = gather();
+        assert!(text.contains("# TYPE test_counter_total counter\n"));
+        assert!(text.contains("test_counter_total{result=\"ok\"} 3\n"));
+        assert!(text.contains("test_counter_total{result=\"a \\\"b\\\"\"} 1\n"));
+        assert!(text.contains("# HELP test_gauge Test gauge\n"));
+        assert!(text.contains("test_gauge 1.5\n"));
+    }
+
+    pub fn test_histogram() {
+        for value in &[0.05, 0.5, 0.5, 5.0] {
+            TEST_HISTOGRAM.observe(&[("method", "m")], *value);
+        }
+
+        let text = gather();
+        assert!(text.contains("# TYPE test_histogram_seconds histogram\n"));
+        assert!(text.contains("test_histogram_seconds_bucket{method=\"m\",le=\"0.1\"} 1\n"));
+        assert!(text.contains("test_histogram_seconds_bucket{method=\"m\",le=\"1\"} 3\n"));
+        assert!(text.contains("test_histogram_seconds_bucket{method=\"m\",le=\"+Inf\"} 4\n"));
+        assert!(text.contains("test_histogram_seconds_sum{method=\"m\"} 6.05\n"));
+        assert!(text.contains("test_histogram_seconds_count{method=\"m\"} 4\n"));
+    }
+}
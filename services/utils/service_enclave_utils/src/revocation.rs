@@ -0,0 +1,97 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License.
+
+//! Installs the revocation list of the runtime config and its updates. The
+//! version of the installed list is kept sealed on disk, so that a restarted
+//! service refuses lists of lower versions which could lift revocations.
+
+use crate::sealing;
+use anyhow::{ensure, Context, Result};
+use log::error;
+use sgx_types::SGX_KEYPOLICY_MRSIGNER;
+use std::convert::TryFrom;
+use std::io;
+use std::path::Path;
+use std::prelude::v1::*;
+use std::thread;
+use std::time::Duration;
+use std::untrusted::fs;
+use teaclave_attestation::revocation::{self, RevocationList};
+use teaclave_config::build::AUDITOR_PUBLIC_KEYS;
+use teaclave_config::{RevocationListConfig, RuntimeConfig};
+
+/// Install the revocation list in the runtime config, and read it again every
+/// poll interval in a background thread to install updates. Only lists signed
+/// by all the auditors are installed.
+pub fn watch_revocation_list(config: &RuntimeConfig) -> Result<()> {
+    let list_config = match &config.audit.revocation_list {
+        Some(list_config) => list_config.clone(),
+        None => return Ok(()),
+    };
+
+    install_revocation_list(&list_config)?;
+    thread::spawn(move || loop {
+        thread::sleep(Duration::from_secs(list_config.poll_interval));
+        if let Err(e) = install_revocation_list(&list_config) {
+            error!("Failed to update the revocation list: {:?}", e);
+        }
+    });
+
+    Ok(())
+}
+
+fn install_revocation_list(list_config: &RevocationListConfig) -> Result<()> {
+    let (list, signatures) = list_config.read()?;
+    let list = RevocationList::verify_and_new(&list, AUDITOR_PUBLIC_KEYS, &signatures)?;
+
+    let min_version = load_version(&list_config.version_path)?;
+    ensure!(
+        list.version >= min_version,
+        "Revocation list version {} is lower than the installed version {}",
+        list.version,
+        min_version
+    );
+    let version = list.version;
+    if revocation::install(list) && version > min_version {
+        save_version(&list_config.version_path, version)?;
+    }
+
+    Ok(())
+}
+
+/// Version of the last installed list, 0 before any list is installed. It is
+/// sealed to the signer of the enclaves, so every service can read it.
+fn load_version(path: &Path) -> Result<u64> {
+    let sealed = match fs::read(path) {
+        Ok(sealed) => sealed,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(0),
+        Err(e) => {
+            return Err(e)
+                .with_context(|| format!("Cannot read revocation list version from {:?}", path))
+        }
+    };
+    let version = sealing::unseal(sealed).context("Invalid revocation list version")?;
+    let version =
+        <[u8; 8]>::try_from(version.as_slice()).context("Invalid revocation list version")?;
+    Ok(u64::from_le_bytes(version))
+}
+
+fn save_version(path: &Path, version: u64) -> Result<()> {
+    let sealed = sealing::seal(SGX_KEYPOLICY_MRSIGNER, &version.to_le_bytes())?;
+    fs::write(path, sealed)
+        .with_context(|| format!("Cannot write revocation list version to {:?}", path))
+}
@@ -0,0 +1,78 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License.
+
+//! Sealing of enclave state kept outside of the enclave, e.g., in the storage
+//! service or on disk.
+
+use anyhow::{anyhow, Result};
+use sgx_tse::rsgx_self_report;
+use sgx_tseal::SgxSealedData;
+use sgx_types::{
+    sgx_attributes_t, sgx_isv_svn_t, sgx_sealed_data_t, TSEAL_DEFAULT_FLAGSMASK,
+    TSEAL_DEFAULT_MISCMASK,
+};
+use std::prelude::v1::*;
+
+/// Seals `data` with the sealing key derived by `key_policy`, e.g.,
+/// `SGX_KEYPOLICY_MRENCLAVE` so that only the same enclave can unseal it. The
+/// key is derived from the current ISV SVN of the enclave, so enclaves with a
+/// lower SVN cannot unseal the data.
+pub fn seal(key_policy: u16, data: &[u8]) -> Result<Vec<u8>> {
+    let attribute_mask = sgx_attributes_t {
+        flags: TSEAL_DEFAULT_FLAGSMASK,
+        xfrm: 0,
+    };
+    let sealed = SgxSealedData::<[u8]>::seal_data_ex(
+        key_policy,
+        attribute_mask,
+        TSEAL_DEFAULT_MISCMASK,
+        &[],
+        data,
+    )
+    .map_err(|e| anyhow!("Cannot seal data: {:?}", e))?;
+    let size = SgxSealedData::<[u8]>::calc_raw_sealed_data_size(0, data.len() as u32);
+    let mut buf = vec![0u8; size as usize];
+    unsafe { sealed.to_raw_sealed_data_t(buf.as_mut_ptr() as *mut sgx_sealed_data_t, size) }
+        .ok_or_else(|| anyhow!("Cannot serialize sealed data"))?;
+    Ok(buf)
+}
+
+pub fn unseal(buf: Vec<u8>) -> Result<Vec<u8>> {
+    unseal_ex(buf).map(|(data, _)| data)
+}
+
+/// Unseals `buf` like `unseal`, and also returns the ISV SVN of the enclave
+/// which sealed it.
+pub fn unseal_ex(mut buf: Vec<u8>) -> Result<(Vec<u8>, sgx_isv_svn_t)> {
+    let sealed = unsafe {
+        SgxSealedData::<[u8]>::from_raw_sealed_data_t(
+            buf.as_mut_ptr() as *mut sgx_sealed_data_t,
+            buf.len() as u32,
+        )
+    }
+    .ok_or_else(|| anyhow!("Invalid sealed data"))?;
+    let isv_svn = sealed.get_key_request().isv_svn;
+    let unsealed = sealed
+        .unseal_data()
+        .map_err(|e| anyhow!("Cannot unseal data: {:?}", e))?;
+    Ok((unsealed.get_decrypt_txt().to_vec(), isv_svn))
+}
+
+/// ISV SVN of the current enclave, as set in its signing configuration.
+pub fn isv_svn() -> sgx_isv_svn_t {
+    rsgx_self_report().body.isv_svn
+}
@@ -16,7 +16,7 @@
 // under the License.
 
 use std::prelude::v1::*;
-use teaclave_attestation::verifier;
+use teaclave_attestation::policy::AttestationPolicy;
 use teaclave_config::build::AS_ROOT_CA_CERT;
 use teaclave_config::RuntimeConfig;
 use teaclave_proto::teaclave_authentication_service::*;
@@ -35,7 +35,7 @@ fn get_api_client() -> TeaclaveAuthenticationApiClient {
     let config = SgxTrustedTlsClientConfig::new().attestation_report_verifier(
         vec![enclave_attr],
         AS_ROOT_CA_CERT,
-        verifier::universal_quote_verifier,
+        AttestationPolicy::from_teaclave_config(&runtime_config).unwrap(),
     );
 
     let channel = Endpoint::new("localhost:7776")
@@ -54,7 +54,7 @@ fn get_internal_client() -> TeaclaveAuthenticationInternalClient {
     let config = SgxTrustedTlsClientConfig::new().attestation_report_verifier(
         vec![enclave_attr],
         AS_ROOT_CA_CERT,
-        verifier::universal_quote_verifier,
+        AttestationPolicy::from_teaclave_config(&runtime_config).unwrap(),
     );
 
     let channel = Endpoint::new(
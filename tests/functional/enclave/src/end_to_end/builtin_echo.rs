@@ -63,4 +63,19 @@ pub fn test_echo_task_success() {
     // Get Task
     let ret_val = get_task_until(&mut client, &task_id, TaskStatus::Finished);
     assert_eq!(&ret_val, "Hello From Teaclave!");
+
+    // Download the return value and the log in streams
+    let mut return_value = Vec::new();
+    let request = DownloadTaskResultRequest::new(task_id.clone());
+    let response = client
+        .download_task_result(request, &mut return_value)
+        .unwrap();
+    assert!(response.result.is_ok());
+    assert_eq!(return_value, b"Hello From Teaclave!");
+
+    let mut log = Vec::new();
+    let request = DownloadTaskLogRequest::new(task_id);
+    client.download_task_log(request, &mut log).unwrap();
+    let log = String::from_utf8(log).unwrap();
+    assert!(log.contains("Invoking function builtin-echo"));
 }
@@ -0,0 +1,56 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License.
+
+use super::*;
+use teaclave_crypto::TeaclaveFile128Key;
+use teaclave_test_utils::test_case;
+
+#[test_case]
+pub fn test_rotate_file_key() {
+    let mut api_client =
+        create_authentication_api_client(shared_enclave_info(), AUTH_SERVICE_ADDR).unwrap();
+    let cred = login(&mut api_client, USERNAME, TEST_PASSWORD).unwrap();
+    let mut client =
+        create_frontend_client(shared_enclave_info(), FRONTEND_SERVICE_ADDR, cred).unwrap();
+
+    // Register the file under the old key
+    let url =
+        Url::parse("http://localhost:6789/fixtures/functions/gbdt_training/train.enc").unwrap();
+    let crypto = TeaclaveFile128Key::new(&[0; 16]).unwrap();
+    let cmac = FileAuthTag::from_hex("881adca6b0524472da0a9d0bb02b9af9").unwrap();
+    let request = RegisterInputFileRequest::new(url, cmac, crypto);
+    let data_id = client.register_input_file(request).unwrap().data_id;
+
+    // Re-encrypt it under a new key to a new location
+    let url = Url::parse("http://localhost:6789/fixtures/functions/gbdt_training/e2e_rotated.enc")
+        .unwrap();
+    let request = RotateFileKeyRequest::new(data_id.clone(), url, TeaclaveFile128Key::random());
+    let task_id = client.rotate_file_key(request).unwrap().task_id;
+
+    let ret_val = get_task_until(&mut client, &task_id, TaskStatus::Finished);
+    assert_eq!(&ret_val, "Re-encrypted 2400 bytes.");
+
+    // The file keeps its id and now points to the re-encrypted copy
+    let new_cmac = match get_task(&mut client, &task_id).result {
+        TaskResult::Ok(outputs) => *outputs.tags_map.get(REENCRYPT_FILE_OUTPUT).unwrap(),
+        _ => unreachable!(),
+    };
+    let request = GetInputFileRequest::new(data_id);
+    let response = client.get_input_file(request).unwrap();
+    assert_eq!(response.cmac, new_cmac);
+    assert_ne!(response.cmac, cmac);
+}
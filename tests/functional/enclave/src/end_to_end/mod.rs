@@ -23,6 +23,7 @@ use url::Url;
 
 mod builtin_echo;
 mod builtin_gbdt_train;
+mod builtin_rotate_file_key;
 mod mesapy_data_fusion;
 mod mesapy_echo;
 
@@ -57,8 +57,20 @@ fn test_execute_function() {
     let get_request = GetRequest::new(ts.key().as_slice());
     let get_response = storage_client.get(get_request).unwrap();
     let updated_task = TaskState::from_slice(get_response.value.as_slice()).unwrap();
-    assert_eq!(
-        updated_task.result.unwrap().return_value,
-        b"Hello, Teaclave Tests!"
-    );
+    assert!(updated_task.result.is_ok());
+
+    // The return value and the log are stored apart from the task state.
+    let mut return_value = Vec::new();
+    let get_request = GetStreamRequest::new(updated_task.return_value_key());
+    storage_client
+        .get_stream(get_request, &mut return_value)
+        .unwrap();
+    assert_eq!(return_value, b"Hello, Teaclave Tests!");
+
+    let mut log = Vec::new();
+    let get_request = GetStreamRequest::new(updated_task.log_key());
+    storage_client.get_stream(get_request, &mut log).unwrap();
+    assert!(String::from_utf8(log)
+        .unwrap()
+        .contains("Function returned 22 bytes"));
 }
@@ -18,6 +18,7 @@
 use crate::utils::*;
 use std::convert::TryFrom;
 use std::prelude::v1::*;
+use teaclave_crypto::{KeyWrappingKey, TeaclaveFile128Key};
 use teaclave_proto::teaclave_common::*;
 use teaclave_proto::teaclave_frontend_service::*;
 use teaclave_proto::teaclave_scheduler_service::*;
@@ -43,7 +44,7 @@ fn test_register_input_file() {
     let cmac = FileAuthTag::mock();
     let crypto_info = FileCrypto::default();
 
-    let request = RegisterInputFileRequest::new(url.clone(), cmac, crypto_info);
+    let request = RegisterInputFileRequest::new(url.clone(), cmac, crypto_info.clone());
     let response = authorized_client().register_input_file(request);
     assert!(response.is_ok());
 
@@ -75,7 +76,7 @@ fn test_register_output_file() {
     let url = Url::parse("https://external-storage.com/filepath?presigned_token").unwrap();
     let crypto_info = FileCrypto::default();
 
-    let request = RegisterOutputFileRequest::new(url.clone(), crypto_info);
+    let request = RegisterOutputFileRequest::new(url.clone(), crypto_info.clone());
     let response = authorized_client().register_output_file(request);
     assert!(response.is_ok());
 
@@ -164,6 +165,39 @@ fn test_get_input_file() {
     assert!(response.is_err());
 }
 
+#[test_case]
+fn test_key_wrapping() {
+    let mut client = authorized_client();
+
+    let request = GetKeyWrappingKeyRequest;
+    let public_key = client.get_key_wrapping_key(request).unwrap().public_key;
+
+    let url = Url::parse("https://external-storage.com/filepath?presigned_token").unwrap();
+    let cmac = FileAuthTag::mock();
+    let crypto_info = FileCrypto::default().wrap(&public_key).unwrap();
+    let request = RegisterInputFileRequest::new(url.clone(), cmac, crypto_info);
+    assert!(client.register_input_file(request).is_ok());
+
+    // A key wrapped to another public key is rejected.
+    let other_key = KeyWrappingKey::random();
+    let crypto_info = FileCrypto::default().wrap(&other_key.public_key()).unwrap();
+    let request = RegisterInputFileRequest::new(url.clone(), cmac, crypto_info);
+    assert!(client.register_input_file(request).is_err());
+
+    let recipient = KeyWrappingKey::random();
+    let request = RegisterOutputFileRequest::with_recipient(url, recipient.public_key().to_vec());
+    let data_id = client.register_output_file(request).unwrap().data_id;
+    let request = GetOutputFileRequest::new(data_id);
+    let crypto_info = client
+        .get_output_file(request)
+        .unwrap()
+        .crypto_info
+        .unwrap();
+    assert!(crypto_info.is_wrapped());
+    let crypto_info = crypto_info.unwrap_with(&recipient).unwrap();
+    assert_eq!(crypto_info.schema(), TeaclaveFile128Key::SCHEMA);
+}
+
 #[test_case]
 fn test_register_function() {
     let request = RegisterFunctionRequest::default();
@@ -175,6 +209,21 @@ fn test_register_function() {
     assert!(response.is_err());
 }
 
+#[test_case]
+fn test_update_function() {
+    let request = RegisterFunctionRequest::default();
+    let response = authorized_client().register_function(request).unwrap();
+    let function_id = response.function_id;
+
+    let request = UpdateFunctionRequest::new(function_id.clone()).version("v2");
+    let response = authorized_client().update_function(request);
+    assert!(response.is_ok());
+
+    let request = UpdateFunctionRequest::new(function_id);
+    let response = unauthorized_client().update_function(request);
+    assert!(response.is_err());
+}
+
 #[test_case]
 fn test_get_function() {
     let function_id =
@@ -189,6 +238,30 @@ fn test_get_function() {
     assert!(response.is_err());
 }
 
+#[test_case]
+fn test_upload_function() {
+    let mut client = authorized_client();
+    // The payload is stored in several chunks.
+    let payload = vec![1u8; 3 * 1_024 * 1_024];
+    let request = UploadFunctionRequest::new(RegisterFunctionRequest::default());
+    let response = client
+        .upload_function(request, &mut payload.as_slice())
+        .unwrap();
+
+    let request = GetFunctionRequest::new(response.function_id);
+    let response = client.get_function(request).unwrap();
+    assert_eq!(response.payload, payload);
+
+    let payload = vec![1u8; 5 * 1_024 * 1_024];
+    let request = UploadFunctionRequest::new(RegisterFunctionRequest::default());
+    let response = client.upload_function(request, &mut payload.as_slice());
+    assert!(response.is_err());
+
+    let request = UploadFunctionRequest::new(RegisterFunctionRequest::default());
+    let response = unauthorized_client().upload_function(request, &mut &b"payload"[..]);
+    assert!(response.is_err());
+}
+
 #[test_case]
 fn test_create_task() {
     let function_id =
@@ -234,6 +307,44 @@ fn test_get_task() {
     assert!(response.is_err());
 }
 
+#[test_case]
+fn test_download_task_result() {
+    let mut client = authorized_client();
+    let function_id =
+        ExternalID::try_from("function-00000000-0000-0000-0000-000000000002").unwrap();
+
+    let request = CreateTaskRequest::new()
+        .function_id(function_id)
+        .function_arguments(hashmap!("arg1" => "arg1_value"))
+        .executor(Executor::MesaPy)
+        .outputs_ownership(hashmap!("output" => vec!["frontend_user", "mock_user"]));
+    let response = client.create_task(request).unwrap();
+    let task_id = response.task_id;
+
+    // Nothing is streamed before the task finishes.
+    let mut return_value = Vec::new();
+    let request = DownloadTaskResultRequest::new(task_id.clone());
+    let response = client
+        .download_task_result(request, &mut return_value)
+        .unwrap();
+    assert!(matches!(response.result, TaskResult::NotReady));
+    assert!(return_value.is_empty());
+
+    let mut log = Vec::new();
+    let request = DownloadTaskLogRequest::new(task_id.clone());
+    let response = client.download_task_log(request, &mut log);
+    assert!(response.is_ok());
+    assert!(log.is_empty());
+
+    let request = DownloadTaskResultRequest::new(task_id.clone());
+    let response = unauthorized_client().download_task_result(request, &mut Vec::new());
+    assert!(response.is_err());
+
+    let request = DownloadTaskLogRequest::new(task_id);
+    let response = unauthorized_client().download_task_log(request, &mut Vec::new());
+    assert!(response.is_err());
+}
+
 #[test_case]
 fn test_assign_data() {
     let mut client = authorized_client();
@@ -343,7 +454,7 @@ fn test_invoke_task() {
     let response = client.get_task(request).unwrap();
     assert_eq!(response.status, TaskStatus::Staged);
 
-    let request = PullTaskRequest {};
+    let request = PullTaskRequest::new(&KeyWrappingKey::random().public_key()[..]);
     let mut scheduler_client = get_scheduler_client();
     let response = scheduler_client.pull_task(request);
     assert!(response.is_ok());
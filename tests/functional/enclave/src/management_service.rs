@@ -18,6 +18,7 @@
 use crate::utils::*;
 use std::convert::TryFrom;
 use std::prelude::v1::*;
+use teaclave_crypto::KeyWrappingKey;
 use teaclave_proto::teaclave_management_service::*;
 use teaclave_proto::teaclave_scheduler_service::*;
 use teaclave_test_utils::test_case;
@@ -116,6 +117,7 @@ fn test_get_input_file() {
     let request = GetInputFileRequest::new(data_id.clone());
     let response = client.get_input_file(request);
     assert!(response.is_ok());
+    assert!(response.unwrap().privacy_budget.is_none());
 
     let mut client = authorized_client("mock_another_user");
     let request = GetInputFileRequest::new(data_id);
@@ -123,6 +125,42 @@ fn test_get_input_file() {
     assert!(response.is_err());
 }
 
+#[test_case]
+fn test_rotate_file_key() {
+    let url = Url::parse("https://external-storage.com/filepath?presigned_token").unwrap();
+    let cmac = FileAuthTag::mock();
+
+    let mut client = authorized_client("mock_user");
+    let request = RegisterInputFileRequest::new(url, cmac, FileCrypto::default());
+    let data_id = client.register_input_file(request).unwrap().data_id;
+
+    let url = Url::parse("https://external-storage.com/rotated?presigned_token").unwrap();
+    let request = RotateFileKeyRequest::new(data_id.clone(), url.clone(), FileCrypto::default());
+    let response = authorized_client("mock_another_user").rotate_file_key(request);
+    assert!(response.is_err());
+
+    let request = RotateFileKeyRequest::new(data_id, url, FileCrypto::default());
+    let task_id = client.rotate_file_key(request).unwrap().task_id;
+    let request = GetTaskRequest::new(task_id);
+    let response = client.get_task(request).unwrap();
+    assert_eq!(response.status, TaskStatus::Staged);
+}
+
+#[test_case]
+fn test_register_input_file_with_privacy_budget() {
+    let url = Url::parse("https://external-storage.com/filepath?presigned_token").unwrap();
+    let cmac = FileAuthTag::mock();
+    let budget = PrivacyBudget::new(1.0, 1e-5);
+
+    let mut client = authorized_client("mock_user");
+    let request =
+        RegisterInputFileRequest::new(url, cmac, FileCrypto::default()).privacy_budget(budget);
+    let response = client.register_input_file(request).unwrap();
+    let request = GetInputFileRequest::new(response.data_id);
+    let response = client.get_input_file(request).unwrap();
+    assert_eq!(response.privacy_budget, Some(budget));
+}
+
 #[test_case]
 fn test_register_function() {
     let function_input = FunctionInput::new("input", "input_desc");
@@ -175,6 +213,50 @@ fn test_get_function() {
     assert!(response.is_ok());
 }
 
+#[test_case]
+fn test_update_function() {
+    let request = RegisterFunctionRequest::new()
+        .name("mock_function")
+        .executor_type(ExecutorType::Python)
+        .payload(b"def entrypoint:\n\treturn".to_vec())
+        .public(true)
+        .arguments(vec!["arg"])
+        .version("v1");
+
+    let mut client = authorized_client("mock_user");
+    let response = client.register_function(request).unwrap();
+    let function_id = response.function_id;
+
+    let request = GetFunctionRequest::new(function_id.clone());
+    let old_function = client.get_function(request).unwrap();
+
+    let request = UpdateFunctionRequest::new(function_id.clone())
+        .executor_type(ExecutorType::Python)
+        .payload(b"def entrypoint:\n\treturn 1".to_vec())
+        .public(true)
+        .arguments(vec!["arg"])
+        .version("v2");
+    let response = client.update_function(request).unwrap();
+    assert_ne!(response.function_id, function_id);
+    assert_ne!(response.revision_hash, old_function.revision_hash);
+
+    let request = GetFunctionRequest::new(response.function_id);
+    let new_function = client.get_function(request).unwrap();
+    assert_eq!(new_function.name, "mock_function");
+    assert_eq!(new_function.version, "v2");
+    assert_eq!(new_function.previous_revision, Some(function_id.clone()));
+
+    // the old revision is immutable
+    let request = GetFunctionRequest::new(function_id.clone());
+    let response = client.get_function(request).unwrap();
+    assert_eq!(response.revision_hash, old_function.revision_hash);
+    assert_eq!(response.version, "v1");
+
+    let request = UpdateFunctionRequest::new(function_id);
+    let response = authorized_client("mock_another_user").update_function(request);
+    assert!(response.is_err());
+}
+
 fn create_valid_task_request() -> CreateTaskRequest {
     let function_id =
         ExternalID::try_from("function-00000000-0000-0000-0000-000000000001").unwrap();
@@ -238,6 +320,9 @@ fn test_get_task() {
     let request = GetTaskRequest::new(task_id);
     let response = client.get_task(request).unwrap();
     assert!(response.participants.len() == 4);
+    let request = GetFunctionRequest::new(response.function_id.clone());
+    let function = client.get_function(request).unwrap();
+    assert_eq!(response.function_revision_hash, function.revision_hash);
 
     let participants = vec!["mock_user1", "mock_user3", "mock_user2", "mock_user"];
     for name in participants {
@@ -587,8 +672,109 @@ fn test_invoke_task() {
     let response = client2.get_task(request).unwrap();
     assert_eq!(response.status, TaskStatus::Staged);
 
-    let request = PullTaskRequest {};
+    // File keys are only sent wrapped to the execution enclave pulling the task
+    let key_wrapping_key = KeyWrappingKey::random();
+    let request = PullTaskRequest::new(&key_wrapping_key.public_key()[..]);
     let mut scheduler_client = get_scheduler_client();
-    let response = scheduler_client.pull_task(request);
-    assert!(response.is_ok());
+    let staged_task = scheduler_client.pull_task(request).unwrap().staged_task;
+    let crypto_infos = staged_task
+        .input_data
+        .inner
+        .values()
+        .map(|file| &file.crypto_info)
+        .chain(
+            staged_task
+                .output_data
+                .inner
+                .values()
+                .map(|file| &file.crypto_info),
+        );
+    for crypto_info in crypto_infos {
+        assert!(crypto_info.is_wrapped());
+    }
+    assert!(staged_task.unwrap_file_keys(&key_wrapping_key).is_ok());
+}
+
+fn create_dp_aggregate_task(data_id: &ExternalID, epsilon: f64) -> ExternalID {
+    let mut client = authorized_client("mock_user1");
+    let request = RegisterFunctionRequest::new()
+        .name(DP_AGGREGATE_FUNCTION_NAME)
+        .executor_type(ExecutorType::Builtin)
+        .public(true)
+        .arguments(vec![
+            "aggregation",
+            "mechanism",
+            "epsilon",
+            "delta",
+            "lower_bound",
+            "upper_bound",
+        ])
+        .inputs(vec![FunctionInput::new("input_data", "input_desc")])
+        .outputs(vec![FunctionOutput::new("output_data", "output_desc")]);
+    let function_id = client.register_function(request).unwrap().function_id;
+
+    let arguments = FunctionArguments::from_json(serde_json::json!({
+        "aggregation": "sum",
+        "mechanism": "laplace",
+        "epsilon": epsilon,
+        "delta": 0.0,
+        "lower_bound": 0.0,
+        "upper_bound": 1.0,
+    }))
+    .unwrap();
+    let request = CreateTaskRequest::new()
+        .function_id(function_id)
+        .function_arguments(arguments)
+        .executor(Executor::Builtin)
+        .inputs_ownership(hashmap!("input_data" => vec!["mock_user1"]))
+        .outputs_ownership(hashmap!("output_data" => vec!["mock_user1"]));
+    let task_id = client.create_task(request).unwrap().task_id;
+
+    let url = Url::parse("https://output_file_path").unwrap();
+    let request = RegisterOutputFileRequest::new(url, FileCrypto::default());
+    let output_id = client.register_output_file(request).unwrap().data_id;
+    let request = AssignDataRequest::new(
+        task_id.clone(),
+        hashmap!("input_data" => data_id.clone()),
+        hashmap!("output_data" => output_id),
+    );
+    client.assign_data(request).unwrap();
+    let request = ApproveTaskRequest::new(task_id.clone());
+    client.approve_task(request).unwrap();
+    task_id
+}
+
+#[test_case]
+fn test_invoke_task_with_privacy_budget() {
+    let url = Url::parse("https://external-storage.com/filepath?presigned_token").unwrap();
+    let cmac = FileAuthTag::mock();
+    let mut client = authorized_client("mock_user1");
+    let request = RegisterInputFileRequest::new(url, cmac, FileCrypto::default())
+        .privacy_budget(PrivacyBudget::new(1.0, 0.0));
+    let data_id = client.register_input_file(request).unwrap().data_id;
+
+    let task_id = create_dp_aggregate_task(&data_id, 0.75);
+    let request = InvokeTaskRequest::new(task_id.clone());
+    assert!(client.invoke_task(request).is_ok());
+    let request = GetInputFileRequest::new(data_id.clone());
+    let response = client.get_input_file(request).unwrap();
+    assert_eq!(response.privacy_budget, Some(PrivacyBudget::new(0.25, 0.0)));
+
+    // a staged task is neither charged nor staged again
+    let request = InvokeTaskRequest::new(task_id);
+    assert!(client.invoke_task(request).is_err());
+    let request = GetInputFileRequest::new(data_id.clone());
+    let response = client.get_input_file(request).unwrap();
+    assert_eq!(response.privacy_budget, Some(PrivacyBudget::new(0.25, 0.0)));
+
+    // the budget is not spent if the task is rejected, which stays approved
+    let task_id = create_dp_aggregate_task(&data_id, 0.5);
+    let request = InvokeTaskRequest::new(task_id.clone());
+    assert!(client.invoke_task(request).is_err());
+    let request = GetInputFileRequest::new(data_id);
+    let response = client.get_input_file(request).unwrap();
+    assert_eq!(response.privacy_budget, Some(PrivacyBudget::new(0.25, 0.0)));
+    let request = GetTaskRequest::new(task_id);
+    let response = client.get_task(request).unwrap();
+    assert_eq!(response.status, TaskStatus::Approved);
 }
@@ -17,6 +17,7 @@
 
 use crate::utils::*;
 use std::prelude::v1::*;
+use teaclave_crypto::KeyWrappingKey;
 use teaclave_proto::teaclave_scheduler_service::*;
 use teaclave_proto::teaclave_storage_service::*;
 use teaclave_test_utils::test_case;
@@ -41,13 +42,55 @@ fn test_pull_task() {
     let _enqueue_response = storage_client.enqueue(enqueue_request).unwrap();
 
     let mut client = get_scheduler_client();
-    let request = PullTaskRequest {};
+    let request = PullTaskRequest::new(&KeyWrappingKey::random().public_key()[..]);
     let response = client.pull_task(request);
     log::debug!("response: {:?}", response);
     assert!(response.is_ok());
     assert_eq!(response.unwrap().staged_task.function_id, function_id);
 }
 
+#[test_case]
+fn test_pull_task_invalid_public_key() {
+    let function_id = Uuid::new_v4();
+    let staged_task = StagedTask::new()
+        .task_id(Uuid::new_v4())
+        .function_name("builtin-echo")
+        .function_id(function_id.clone())
+        .executor(Executor::Builtin);
+
+    let mut storage_client = get_storage_client();
+    let enqueue_request = EnqueueRequest::new(
+        StagedTask::get_queue_key().as_bytes(),
+        staged_task.to_vec().unwrap(),
+    );
+    let _enqueue_response = storage_client.enqueue(enqueue_request).unwrap();
+
+    let mut client = get_scheduler_client();
+    let request = PullTaskRequest::new(&b"invalid public key"[..]);
+    assert!(client.pull_task(request).is_err());
+
+    // The task is left in the queue for the next pull.
+    let request = PullTaskRequest::new(&KeyWrappingKey::random().public_key()[..]);
+    let response = client.pull_task(request).unwrap();
+    assert_eq!(response.staged_task.function_id, function_id);
+}
+
+#[test_case]
+fn test_get_key_wrapping_public_key() {
+    let mut client = get_scheduler_client();
+    let request = GetKeyWrappingPublicKeyRequest::new();
+    let public_key = client
+        .get_key_wrapping_public_key(request)
+        .unwrap()
+        .public_key;
+    assert!(!public_key.is_empty());
+
+    // The key is sealed in storage, rather than created for each request.
+    let request = GetKeyWrappingPublicKeyRequest::new();
+    let response = client.get_key_wrapping_public_key(request).unwrap();
+    assert_eq!(response.public_key, public_key);
+}
+
 #[test_case]
 fn test_update_task_status_result() {
     let task_id = Uuid::new_v4();
@@ -75,7 +118,7 @@ fn test_update_task_status_result() {
     let _put_response = storage_client.put(put_request).unwrap();
 
     let mut client = get_scheduler_client();
-    let request = PullTaskRequest {};
+    let request = PullTaskRequest::new(&KeyWrappingKey::random().public_key()[..]);
     let response = client.pull_task(request).unwrap();
     log::debug!("response: {:?}", response);
     let task_id = response.staged_task.task_id;
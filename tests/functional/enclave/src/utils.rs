@@ -19,7 +19,7 @@ use anyhow::Result;
 use lazy_static::lazy_static;
 use std::collections::HashMap;
 use std::prelude::v1::*;
-use teaclave_attestation::verifier;
+use teaclave_attestation::policy::AttestationPolicy;
 use teaclave_config::build::AS_ROOT_CA_CERT;
 use teaclave_config::RuntimeConfig;
 use teaclave_proto::teaclave_access_control_service::*;
@@ -98,10 +98,12 @@ pub fn create_client_config(
     let enclave_attr = enclave_info
         .get_enclave_attr(service_name)
         .expect("enclave attr");
+    let runtime_config = RuntimeConfig::from_toml(CONFIG_FILE)?;
+    let policy = AttestationPolicy::from_teaclave_config(&runtime_config)?;
     let config = SgxTrustedTlsClientConfig::new().attestation_report_verifier(
         vec![enclave_attr],
         AS_ROOT_CA_CERT,
-        verifier::universal_quote_verifier,
+        policy,
     );
     Ok(config)
 }
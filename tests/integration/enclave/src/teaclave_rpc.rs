@@ -16,14 +16,21 @@
 // under the License.
 
 use anyhow::Result;
+use prost::Message;
 use rustls::internal::pemfile;
 use serde::{Deserialize, Serialize};
 use std::io;
+use std::net::{Shutdown, TcpListener, TcpStream};
 use std::prelude::v1::*;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, SgxMutex as Mutex};
+use std::thread;
+use std::time::Duration;
 use std::untrusted::fs;
 use teaclave_rpc::channel::*;
 use teaclave_rpc::config::*;
 use teaclave_rpc::endpoint::*;
+use teaclave_rpc::protocol::{Encoding, ProtobufMessage};
 use teaclave_rpc::server::*;
 use teaclave_rpc::*;
 use teaclave_types::TeaclaveServiceResponseError;
@@ -31,15 +38,20 @@ use teaclave_types::TeaclaveServiceResponseResult;
 
 const END_FULLCHAIN: &str = "./fixtures/end_fullchain.pem";
 const END_KEY: &str = "./fixtures/end_key.pem";
+const ECHO_SERVICE_ADDR: &str = "127.0.0.1:12345";
 
-#[derive(Serialize, Deserialize, Debug)]
+// Items taken by "dequeue" requests to the echo service
+static DEQUEUED: AtomicUsize = AtomicUsize::new(0);
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
 #[serde(tag = "request", rename_all = "snake_case")]
 enum EchoRequest {
     Say(SayRequest),
 }
 
-#[derive(Serialize, Deserialize, Debug)]
+#[derive(Serialize, Deserialize, Clone, PartialEq, prost::Message)]
 struct SayRequest {
+    #[prost(string, tag = "1")]
     message: String,
 }
 
@@ -49,11 +61,38 @@ enum EchoResponse {
     Say(SayResponse),
 }
 
-#[derive(Serialize, Deserialize, Debug)]
+#[derive(Serialize, Deserialize, Clone, PartialEq, prost::Message)]
 struct SayResponse {
+    #[prost(string, tag = "1")]
     message: String,
 }
 
+impl ProtobufMessage for EchoRequest {
+    fn encode_protobuf(&self, buf: &mut Vec<u8>) -> Result<()> {
+        match self {
+            EchoRequest::Say(r) => r.encode(buf)?,
+        }
+        Ok(())
+    }
+
+    fn decode_protobuf(buf: &[u8]) -> Result<Self> {
+        Ok(EchoRequest::Say(SayRequest::decode(buf)?))
+    }
+}
+
+impl ProtobufMessage for EchoResponse {
+    fn encode_protobuf(&self, buf: &mut Vec<u8>) -> Result<()> {
+        match self {
+            EchoResponse::Say(r) => r.encode(buf)?,
+        }
+        Ok(())
+    }
+
+    fn decode_protobuf(buf: &[u8]) -> Result<Self> {
+        Ok(EchoResponse::Say(SayResponse::decode(buf)?))
+    }
+}
+
 #[derive(Clone)]
 struct EchoService;
 
@@ -66,6 +105,49 @@ impl TeaclaveService<EchoRequest, EchoResponse> for EchoService {
         let message = match request.message {
             EchoRequest::Say(s) => s.message,
         };
+        // Like a dequeue of the storage service, taking an item before a slow
+        // response.
+        if message == "dequeue" {
+            DEQUEUED.fetch_add(1, Ordering::SeqCst);
+            thread::sleep(Duration::from_secs(2));
+        }
+        Ok(EchoResponse::Say(SayResponse { message }))
+    }
+
+    // Echoes the length of the stream uploaded with "upload", or downloads
+    // the number of bytes requested in the message. An upload with "reject"
+    // is refused after reading its first bytes.
+    fn handle_stream_request(
+        &self,
+        request: teaclave_rpc::Request<EchoRequest>,
+        stream: &mut teaclave_rpc::stream::Stream,
+    ) -> TeaclaveServiceResponseResult<EchoResponse> {
+        use std::io::Read;
+
+        let internal = |e: io::Error| TeaclaveServiceResponseError::InternalError(e.to_string());
+        let message = match &request.message {
+            EchoRequest::Say(s) => s.message.clone(),
+        };
+        let message = if message == "upload" {
+            let mut reader = stream.reader();
+            let len = io::copy(&mut reader, &mut io::sink()).map_err(internal)?;
+            reader.drain()?;
+            len.to_string()
+        } else if message == "reject" {
+            let mut reader = stream.reader();
+            reader.read_exact(&mut [0u8; 16]).map_err(internal)?;
+            reader.close();
+            return Err(TeaclaveServiceResponseError::RequestError(
+                "rejected".to_string(),
+            ));
+        } else if let Ok(len) = message.parse::<u64>() {
+            let mut writer = stream.writer();
+            io::copy(&mut io::repeat(0).take(len), &mut writer).map_err(internal)?;
+            writer.finish()?;
+            message
+        } else {
+            return self.handle_request(request);
+        };
         Ok(EchoResponse::Say(SayResponse { message }))
     }
 }
@@ -81,10 +163,7 @@ impl EchoClient {
 
     fn say(&mut self, request: SayRequest) -> TeaclaveServiceResponseResult<SayResponse> {
         let request = EchoRequest::Say(request);
-        let request = Request {
-            metadata: std::collections::HashMap::<String, String>::new(),
-            message: request,
-        };
+        let request = Request::new(request);
         let response = match self.channel.invoke(request) {
             Ok(response_result) => response_result,
             Err(_) => {
@@ -97,6 +176,28 @@ impl EchoClient {
             EchoResponse::Say(r) => Ok(r),
         }
     }
+
+    fn upload(
+        &mut self,
+        request: SayRequest,
+        upload: &mut dyn io::Read,
+    ) -> TeaclaveServiceResponseResult<SayResponse> {
+        let request = Request::new(EchoRequest::Say(request));
+        match self.channel.invoke_upload(request, upload)? {
+            EchoResponse::Say(r) => Ok(r),
+        }
+    }
+
+    fn download(
+        &mut self,
+        request: SayRequest,
+        download: &mut dyn io::Write,
+    ) -> TeaclaveServiceResponseResult<SayResponse> {
+        let request = Request::new(EchoRequest::Say(request));
+        match self.channel.invoke_download(request, download)? {
+            EchoResponse::Say(r) => Ok(r),
+        }
+    }
 }
 
 pub fn run_tests() -> bool {
@@ -104,13 +205,21 @@ pub fn run_tests() -> bool {
 
     start_echo_service();
 
-    run_tests!(echo_success)
+    run_tests!(
+        echo_success,
+        echo_protobuf_success,
+        echo_upload_success,
+        echo_upload_rejected,
+        echo_download_success,
+        echo_pool_reuse,
+        echo_reconnect_after_restart,
+        echo_connect_retry,
+        echo_request_timeout,
+        echo_timeout_not_replayed
+    )
 }
 
 fn start_echo_service() {
-    use super::*;
-    use std::thread;
-    use std::time::Duration;
     thread::spawn(move || {
         let cert = pemfile::certs(&mut io::BufReader::new(
             fs::File::open(END_FULLCHAIN).unwrap(),
@@ -119,7 +228,7 @@ fn start_echo_service() {
         let private_key =
             &pemfile::pkcs8_private_keys(&mut io::BufReader::new(fs::File::open(END_KEY).unwrap()))
                 .unwrap()[0];
-        let addr = "127.0.0.1:12345".parse().unwrap();
+        let addr = ECHO_SERVICE_ADDR.parse().unwrap();
         let config = SgxTrustedTlsServerConfig::new()
             .server_cert(&cert[0].as_ref(), &private_key.0)
             .unwrap();
@@ -129,6 +238,56 @@ fn start_echo_service() {
     thread::sleep(Duration::from_secs(3));
 }
 
+/// Forwards connections to the echo service, and closes all of them when the
+/// service is restarted.
+struct Proxy {
+    streams: Arc<Mutex<Vec<TcpStream>>>,
+}
+
+impl Proxy {
+    fn start(addr: &str) -> Self {
+        let listener = TcpListener::bind(addr).unwrap();
+        let streams = Arc::new(Mutex::new(Vec::new()));
+        let accepted = streams.clone();
+        thread::spawn(move || {
+            for client in listener.incoming() {
+                let client = client.unwrap();
+                let service = TcpStream::connect(ECHO_SERVICE_ADDR).unwrap();
+                let mut accepted = accepted.lock().unwrap();
+                accepted.push(client.try_clone().unwrap());
+                accepted.push(service.try_clone().unwrap());
+                pipe(client.try_clone().unwrap(), service.try_clone().unwrap());
+                pipe(service, client);
+            }
+        });
+
+        Self { streams }
+    }
+
+    /// Close the connections like a restarted service.
+    fn restart(&self) {
+        for stream in self.streams.lock().unwrap().drain(..) {
+            let _ = stream.shutdown(Shutdown::Both);
+        }
+        // Wait for the connections to be closed on the client side.
+        thread::sleep(Duration::from_millis(500));
+    }
+}
+
+fn pipe(mut from: TcpStream, mut to: TcpStream) {
+    thread::spawn(move || {
+        let _ = io::copy(&mut from, &mut to);
+        let _ = to.shutdown(Shutdown::Both);
+    });
+}
+
+fn say_hello(client: &mut EchoClient) -> TeaclaveServiceResponseResult<SayResponse> {
+    let request = SayRequest {
+        message: "Hello, World!".to_string(),
+    };
+    client.say(request)
+}
+
 fn echo_success() {
     use super::*;
 
@@ -143,3 +302,191 @@ fn echo_success() {
     assert!(response_result.is_ok());
     assert!(response_result.unwrap().message == "Hello, World!");
 }
+
+fn echo_protobuf_success() {
+    use super::*;
+
+    let channel = Endpoint::new("localhost:12345")
+        .encoding(Encoding::Protobuf)
+        .connect()
+        .unwrap();
+    let mut client = EchoClient::new(channel).unwrap();
+    let request = SayRequest {
+        message: "Hello, World!".to_string(),
+    };
+    let response_result = client.say(request);
+    debug!("{:?}", response_result);
+
+    assert!(response_result.is_ok());
+    assert!(response_result.unwrap().message == "Hello, World!");
+}
+
+fn echo_upload_success() {
+    use super::*;
+
+    let channel = Endpoint::new("localhost:12345").connect().unwrap();
+    let mut client = EchoClient::new(channel).unwrap();
+    let request = SayRequest {
+        message: "upload".to_string(),
+    };
+    // Larger than the maximum length of a frame.
+    let payload = vec![0u8; 9 * 1024 * 1024];
+    let response_result = client.upload(request, &mut payload.as_slice());
+    debug!("{:?}", response_result);
+
+    assert!(response_result.is_ok());
+    assert!(response_result.unwrap().message == payload.len().to_string());
+}
+
+fn echo_upload_rejected() {
+    use super::*;
+
+    let channel = Endpoint::new("localhost:12345").connect().unwrap();
+    let mut client = EchoClient::new(channel).unwrap();
+    let request = SayRequest {
+        message: "reject".to_string(),
+    };
+    // The rest of the stream is not read by the service.
+    let payload = vec![0u8; 9 * 1024 * 1024];
+    let response_result = client.upload(request, &mut payload.as_slice());
+    debug!("{:?}", response_result);
+    assert!(response_result.is_err());
+
+    // The connection can still be used after the stream is closed.
+    let request = SayRequest {
+        message: "Hello, World!".to_string(),
+    };
+    let response_result = client.say(request);
+    assert!(response_result.unwrap().message == "Hello, World!");
+}
+
+fn echo_download_success() {
+    use super::*;
+
+    let channel = Endpoint::new("localhost:12345")
+        .encoding(Encoding::Protobuf)
+        .connect()
+        .unwrap();
+    let mut client = EchoClient::new(channel).unwrap();
+    // Larger than the maximum length of a frame.
+    let len = 9 * 1024 * 1024;
+    let request = SayRequest {
+        message: len.to_string(),
+    };
+    let mut payload = Vec::with_capacity(len);
+    let response_result = client.download(request, &mut payload);
+    debug!("{:?}", response_result);
+
+    assert!(response_result.is_ok());
+    assert!(response_result.unwrap().message == len.to_string());
+    assert!(payload.len() == len);
+}
+
+fn echo_pool_reuse() {
+    let endpoint = Endpoint::new("localhost:12345");
+    let mut client = EchoClient::new(endpoint.connect().unwrap()).unwrap();
+    assert!(say_hello(&mut client).is_ok());
+    let secret = client.channel.tls_exporter_secret().unwrap();
+    drop(client);
+
+    // The connection is returned to the pool and reused, even though the
+    // service sent session tickets after the handshake.
+    let mut client = EchoClient::new(endpoint.connect().unwrap()).unwrap();
+    assert_eq!(client.channel.tls_exporter_secret().unwrap(), secret);
+    assert!(say_hello(&mut client).is_ok());
+
+    // A connection in use is not shared.
+    let mut other = EchoClient::new(endpoint.connect().unwrap()).unwrap();
+    assert_ne!(other.channel.tls_exporter_secret().unwrap(), secret);
+    assert!(say_hello(&mut other).is_ok());
+
+    // No connection is kept without a pool.
+    let endpoint = Endpoint::new("localhost:12345").pool_size(0);
+    let mut client = EchoClient::new(endpoint.connect().unwrap()).unwrap();
+    let secret = client.channel.tls_exporter_secret().unwrap();
+    drop(client);
+    let mut client = EchoClient::new(endpoint.connect().unwrap()).unwrap();
+    assert_ne!(client.channel.tls_exporter_secret().unwrap(), secret);
+}
+
+fn echo_reconnect_after_restart() {
+    let proxy = Proxy::start("127.0.0.1:12346");
+    let endpoint = Endpoint::new("localhost:12346");
+    let mut client = EchoClient::new(endpoint.connect().unwrap()).unwrap();
+    assert!(say_hello(&mut client).is_ok());
+    let secret = client.channel.tls_exporter_secret().unwrap();
+
+    // The channel reconnects instead of failing, without retrying requests.
+    proxy.restart();
+    assert!(say_hello(&mut client).is_ok());
+    assert_ne!(client.channel.tls_exporter_secret().unwrap(), secret);
+
+    // Closed connections in the pool are not reused.
+    let secret = client.channel.tls_exporter_secret().unwrap();
+    drop(client);
+    proxy.restart();
+    let mut client = EchoClient::new(endpoint.connect().unwrap()).unwrap();
+    assert!(say_hello(&mut client).is_ok());
+    assert_ne!(client.channel.tls_exporter_secret().unwrap(), secret);
+}
+
+fn echo_connect_retry() {
+    let addr = "127.0.0.1:12347";
+    let policy = RetryPolicy::fixed(2, Duration::from_millis(100));
+    let endpoint = Endpoint::new("localhost:12347").connect_retry(policy);
+    assert!(endpoint.connect::<EchoRequest, EchoResponse>().is_err());
+
+    // The service starts while connecting is retried.
+    thread::spawn(move || {
+        thread::sleep(Duration::from_millis(500));
+        Proxy::start(addr);
+    });
+    let policy = RetryPolicy::exponential(10, Duration::from_millis(100), Duration::from_secs(1));
+    let endpoint = Endpoint::new("localhost:12347").connect_retry(policy);
+    let mut client = EchoClient::new(endpoint.connect().unwrap()).unwrap();
+    assert!(say_hello(&mut client).is_ok());
+}
+
+fn echo_request_timeout() {
+    // A service accepting connections without ever responding
+    let listener = TcpListener::bind("127.0.0.1:12348").unwrap();
+    let channel = Endpoint::new("localhost:12348")
+        .request_timeout(Duration::from_secs(1))
+        .request_retry(RetryPolicy::fixed(1, Duration::from_millis(100)))
+        .connect()
+        .unwrap();
+    let mut client = EchoClient::new(channel).unwrap();
+    let request = Request::new(EchoRequest::Say(SayRequest {
+        message: "Hello, World!".to_string(),
+    }));
+    // The idempotent request is resent once, and both times out.
+    let response = client.channel.invoke_idempotent(request);
+    assert!(matches!(
+        response,
+        Err(TeaclaveServiceResponseError::ConnectionError(_))
+    ));
+    drop(listener);
+}
+
+fn echo_timeout_not_replayed() {
+    let channel = Endpoint::new("localhost:12345")
+        .request_timeout(Duration::from_secs(1))
+        .request_retry(RetryPolicy::fixed(2, Duration::from_millis(100)))
+        .connect()
+        .unwrap();
+    let mut client = EchoClient::new(channel).unwrap();
+    let request = Request::new(EchoRequest::Say(SayRequest {
+        message: "dequeue".to_string(),
+    }));
+
+    // The dequeue times out after the service has taken an item, which would
+    // be lost if the request were resent.
+    let dequeued = DEQUEUED.load(Ordering::SeqCst);
+    let response = client.channel.invoke(request);
+    assert!(matches!(
+        response,
+        Err(TeaclaveServiceResponseError::ConnectionError(_))
+    ));
+    thread::sleep(Duration::from_secs(1));
+    assert_eq!(DEQUEUED.load(Ordering::SeqCst), dequeued + 1);
+}
@@ -7,11 +7,19 @@ import sys
 
 class HTTPRequestHandler(SimpleHTTPRequestHandler):
     def do_PUT(self):
-        length = int(self.headers["Content-Length"])
-
         path = self.translate_path(self.path)
         with open(path, "wb") as dst:
-            dst.write(self.rfile.read(length))
+            if self.headers.get("Transfer-Encoding") == "chunked":
+                # Streamed uploads have no length up front.
+                while True:
+                    length = int(self.rfile.readline().strip(), 16)
+                    dst.write(self.rfile.read(length))
+                    self.rfile.readline()
+                    if length == 0:
+                        break
+            else:
+                length = int(self.headers["Content-Length"])
+                dst.write(self.rfile.read(length))
 
         self.send_response(200)
         self.end_headers()
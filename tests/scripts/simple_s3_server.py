@@ -0,0 +1,127 @@
+#!/usr/bin/env python3
+
+# A minimal in-memory stand-in of S3-compatible object storage for testing
+# the s3:// scheme of the file agent. It supports path-style GET (with Range),
+# PUT and the multipart upload API. Requests must be signed, but signatures
+# are not verified.
+
+from http.server import BaseHTTPRequestHandler
+from urllib.parse import urlparse, parse_qs
+import hashlib
+import re
+import socketserver
+import sys
+import uuid
+
+objects = {}
+uploads = {}
+
+
+class S3RequestHandler(BaseHTTPRequestHandler):
+    def _parse(self):
+        url = urlparse(self.path)
+        return url.path, parse_qs(url.query, keep_blank_values=True)
+
+    def _authorized(self):
+        auth = self.headers.get("Authorization", "")
+        if not auth.startswith("AWS4-HMAC-SHA256 Credential="):
+            self._reply(403, b"<Error><Code>AccessDenied</Code></Error>")
+            return False
+        return True
+
+    def _reply(self, status, body=b"", headers=None):
+        self.send_response(status)
+        for name, value in (headers or {}).items():
+            self.send_header(name, value)
+        self.send_header("Content-Length", str(len(body)))
+        self.end_headers()
+        self.wfile.write(body)
+
+    def _read_body(self):
+        length = int(self.headers.get("Content-Length", 0))
+        return self.rfile.read(length)
+
+    def do_GET(self):
+        if not self._authorized():
+            return
+        path, _ = self._parse()
+        if path not in objects:
+            self._reply(404, b"<Error><Code>NoSuchKey</Code></Error>")
+            return
+        data = objects[path]
+        match = re.match(r"bytes=(\d+)-$", self.headers.get("Range", ""))
+        if match:
+            start = int(match.group(1))
+            self._reply(
+                206, data[start:], {
+                    "Content-Range":
+                    "bytes {}-{}/{}".format(start,
+                                            len(data) - 1, len(data))
+                })
+        else:
+            self._reply(200, data)
+
+    def do_PUT(self):
+        if not self._authorized():
+            return
+        path, query = self._parse()
+        body = self._read_body()
+        etag = '"{}"'.format(hashlib.md5(body).hexdigest())
+        if "uploadId" in query:
+            upload_id = query["uploadId"][0]
+            if upload_id not in uploads:
+                self._reply(404, b"<Error><Code>NoSuchUpload</Code></Error>")
+                return
+            part_number = int(query["partNumber"][0])
+            uploads[upload_id][part_number] = body
+        else:
+            objects[path] = body
+        self._reply(200, headers={"ETag": etag})
+
+    def do_POST(self):
+        if not self._authorized():
+            return
+        path, query = self._parse()
+        body = self._read_body()
+        if "uploads" in query:
+            upload_id = uuid.uuid4().hex
+            uploads[upload_id] = {}
+            self._reply(
+                200,
+                "<InitiateMultipartUploadResult><UploadId>{}</UploadId>"
+                "</InitiateMultipartUploadResult>".format(upload_id).encode())
+        elif "uploadId" in query:
+            parts = uploads.pop(query["uploadId"][0], None)
+            if parts is None:
+                self._reply(404, b"<Error><Code>NoSuchUpload</Code></Error>")
+                return
+            numbers = [
+                int(n) for n in re.findall(rb"<PartNumber>(\d+)</PartNumber>",
+                                           body)
+            ]
+            objects[path] = b"".join(parts[n] for n in numbers)
+            self._reply(
+                200, b"<CompleteMultipartUploadResult>"
+                b"</CompleteMultipartUploadResult>")
+        else:
+            self._reply(400, b"<Error><Code>InvalidRequest</Code></Error>")
+
+    def do_DELETE(self):
+        if not self._authorized():
+            return
+        _, query = self._parse()
+        if "uploadId" in query:
+            uploads.pop(query["uploadId"][0], None)
+        self._reply(204)
+
+
+if __name__ == '__main__':
+    if len(sys.argv) > 1:
+        port = int(sys.argv[1])
+    else:
+        port = 6790
+    socketserver.TCPServer.allow_reuse_address = True
+    with socketserver.TCPServer(("localhost", port),
+                                S3RequestHandler) as httpd:
+        print("serving at port", port)
+        httpd.serve_forever()
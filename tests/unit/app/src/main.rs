@@ -20,7 +20,10 @@ use teaclave_binder::proto::{ECallCommand, RunTestInput, RunTestOutput};
 use teaclave_binder::TeeBinder;
 use teaclave_types::TeeServiceResult;
 
-pub use teaclave_file_agent::ocall_handle_file_request;
+pub use teaclave_file_agent::{
+    ocall_close_upload, ocall_handle_file_request, ocall_open_upload, ocall_read_file_range,
+    ocall_write_upload,
+};
 
 fn main() -> anyhow::Result<()> {
     env_logger::init();
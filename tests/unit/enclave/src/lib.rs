@@ -37,6 +37,7 @@ fn handle_run_test(_: &RunTestInput) -> TeeServiceResult<RunTestOutput> {
         teaclave_storage_service_enclave::tests::run_tests(),
         teaclave_access_control_service_enclave::tests::run_tests(),
         teaclave_execution_service_enclave::tests::run_tests(),
+        teaclave_scheduler_service_enclave::tests::run_tests(),
         teaclave_authentication_service_enclave::tests::run_tests(),
         teaclave_worker::tests::run_tests(),
         teaclave_runtime::tests::run_tests(),
@@ -44,6 +45,8 @@ fn handle_run_test(_: &RunTestInput) -> TeeServiceResult<RunTestOutput> {
         teaclave_function::tests::run_tests(),
         teaclave_types::tests::run_tests(),
         teaclave_crypto::tests::run_tests(),
+        teaclave_rpc::tests::run_tests(),
+        teaclave_service_enclave_utils::tests::run_tests(),
         rusty_leveldb::tests::run_tests(),
     );
 
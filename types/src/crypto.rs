@@ -60,6 +60,12 @@ impl std::convert::From<[u8; FILE_AUTH_TAG_LENGTH]> for FileAuthTag {
     }
 }
 
+impl std::convert::From<FileAuthTag> for [u8; FILE_AUTH_TAG_LENGTH] {
+    fn from(tag: FileAuthTag) -> Self {
+        tag.tag
+    }
+}
+
 impl std::cmp::PartialEq<[u8]> for FileAuthTag {
     fn eq(&self, other: &[u8]) -> bool {
         self.tag == other
@@ -72,11 +78,16 @@ impl std::cmp::PartialEq<[u8; FILE_AUTH_TAG_LENGTH]> for FileAuthTag {
     }
 }
 
-#[derive(Copy, Clone, Debug, Serialize, Deserialize, PartialEq)]
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
 pub enum FileCrypto {
     AesGcm128(AesGcm128Key),
     AesGcm256(AesGcm256Key),
     TeaclaveFile128(TeaclaveFile128Key),
+    AesGcm128Stream(AesGcm128StreamKey),
+    AesGcm256Stream(AesGcm256StreamKey),
+    ChaCha20Poly1305(ChaCha20Poly1305Key),
+    AesGcmSiv(AesGcmSivKey),
+    Wrapped(WrappedFileCrypto),
     Raw,
 }
 
@@ -96,6 +107,24 @@ impl FileCrypto {
                 let crypto = TeaclaveFile128Key::new(key)?;
                 FileCrypto::TeaclaveFile128(crypto)
             }
+            AesGcm128StreamKey::SCHEMA => {
+                ensure!(iv.is_empty(), "IV is not empty for aes_gcm_128_stream");
+                let crypto = AesGcm128StreamKey::new(key)?;
+                FileCrypto::AesGcm128Stream(crypto)
+            }
+            AesGcm256StreamKey::SCHEMA => {
+                ensure!(iv.is_empty(), "IV is not empty for aes_gcm_256_stream");
+                let crypto = AesGcm256StreamKey::new(key)?;
+                FileCrypto::AesGcm256Stream(crypto)
+            }
+            ChaCha20Poly1305Key::SCHEMA => {
+                let crypto = ChaCha20Poly1305Key::new(key, iv)?;
+                FileCrypto::ChaCha20Poly1305(crypto)
+            }
+            AesGcmSivKey::SCHEMA => {
+                let crypto = AesGcmSivKey::new(key, iv)?;
+                FileCrypto::AesGcmSiv(crypto)
+            }
             "raw" => FileCrypto::Raw,
             _ => bail!("Invalid crypto schema: {}", schema),
         };
@@ -108,6 +137,11 @@ impl FileCrypto {
             FileCrypto::AesGcm128(_) => AesGcm128Key::SCHEMA,
             FileCrypto::AesGcm256(_) => AesGcm256Key::SCHEMA,
             FileCrypto::TeaclaveFile128(_) => TeaclaveFile128Key::SCHEMA,
+            FileCrypto::AesGcm128Stream(_) => AesGcm128StreamKey::SCHEMA,
+            FileCrypto::AesGcm256Stream(_) => AesGcm256StreamKey::SCHEMA,
+            FileCrypto::ChaCha20Poly1305(_) => ChaCha20Poly1305Key::SCHEMA,
+            FileCrypto::AesGcmSiv(_) => AesGcmSivKey::SCHEMA,
+            FileCrypto::Wrapped(wrapped) => &wrapped.schema,
             FileCrypto::Raw => "raw",
         }
     }
@@ -117,9 +151,132 @@ impl FileCrypto {
             FileCrypto::AesGcm128(crypto) => (crypto.key.to_vec(), crypto.iv.to_vec()),
             FileCrypto::AesGcm256(crypto) => (crypto.key.to_vec(), crypto.iv.to_vec()),
             FileCrypto::TeaclaveFile128(crypto) => (crypto.key.to_vec(), Vec::new()),
+            FileCrypto::AesGcm128Stream(crypto) => (crypto.key.to_vec(), Vec::new()),
+            FileCrypto::AesGcm256Stream(crypto) => (crypto.key.to_vec(), Vec::new()),
+            FileCrypto::ChaCha20Poly1305(crypto) => (crypto.key.to_vec(), crypto.iv.to_vec()),
+            FileCrypto::AesGcmSiv(crypto) => (crypto.key.to_vec(), crypto.iv.to_vec()),
+            FileCrypto::Wrapped(_) => (vec![], vec![]),
             FileCrypto::Raw => (vec![], vec![]),
         }
     }
+
+    pub fn is_wrapped(&self) -> bool {
+        match self {
+            FileCrypto::Wrapped(_) => true,
+            _ => false,
+        }
+    }
+
+    /// Wraps the key and IV to `recipient`, the public key of a
+    /// `KeyWrappingKey`.
+    pub fn wrap(&self, recipient: &[u8]) -> Result<FileCrypto> {
+        ensure!(!self.is_wrapped(), "Crypto info is already wrapped");
+        let (key, iv) = self.key_iv();
+        let mut plaintext = vec![key.len() as u8];
+        plaintext.extend_from_slice(&key);
+        plaintext.extend_from_slice(&iv);
+        let wrapped_key = WrappedKey::wrap(recipient, &plaintext)?;
+        Ok(FileCrypto::Wrapped(WrappedFileCrypto::new(
+            self.schema(),
+            wrapped_key,
+        )))
+    }
+
+    /// Unwraps the key and IV of a wrapped crypto info, other crypto infos are
+    /// returned as they are.
+    pub fn unwrap_with(&self, wrapping_key: &KeyWrappingKey) -> Result<FileCrypto> {
+        let wrapped = match self {
+            FileCrypto::Wrapped(wrapped) => wrapped,
+            _ => return Ok(self.clone()),
+        };
+        let plaintext = wrapping_key.unwrap(&wrapped.wrapped_key)?;
+        ensure!(!plaintext.is_empty(), "Invalid wrapped key");
+        let key_len = plaintext[0] as usize;
+        ensure!(plaintext.len() > key_len, "Invalid wrapped key");
+        let (key, iv) = plaintext[1..].split_at(key_len);
+        FileCrypto::new(&wrapped.schema, key, iv)
+    }
+
+    /// Wraps the key and IV, which may be wrapped to `wrapping_key`, to
+    /// `recipient` instead.
+    pub fn rewrap(&self, wrapping_key: &KeyWrappingKey, recipient: &[u8]) -> Result<FileCrypto> {
+        self.unwrap_with(wrapping_key)?.wrap(recipient)
+    }
+
+    /// Decrypts a whole file in memory and returns its tag. Protected files
+    /// are decrypted from their path instead, and raw files are not
+    /// encrypted.
+    pub fn decrypt(&self, in_out: &mut Vec<u8>) -> Result<FileAuthTag> {
+        let cmac = match self {
+            FileCrypto::AesGcm128(crypto) => crypto.decrypt(in_out)?,
+            FileCrypto::AesGcm256(crypto) => crypto.decrypt(in_out)?,
+            FileCrypto::ChaCha20Poly1305(crypto) => crypto.decrypt_with_prefixed_iv(in_out)?,
+            FileCrypto::AesGcmSiv(crypto) => crypto.decrypt(in_out)?,
+            FileCrypto::AesGcm128Stream(crypto) => crypto.decrypt(in_out)?,
+            FileCrypto::AesGcm256Stream(crypto) => crypto.decrypt(in_out)?,
+            _ => bail!("Cannot decrypt {} in memory", self.schema()),
+        };
+        Ok(cmac.into())
+    }
+
+    /// Encrypts a whole file in memory and returns its tag, the counterpart
+    /// of `decrypt`.
+    pub fn encrypt(&self, in_out: &mut Vec<u8>) -> Result<FileAuthTag> {
+        let cmac = match self {
+            FileCrypto::AesGcm128(crypto) => crypto.encrypt(in_out)?,
+            FileCrypto::AesGcm256(crypto) => crypto.encrypt(in_out)?,
+            FileCrypto::ChaCha20Poly1305(crypto) => crypto.encrypt_with_prefixed_iv(in_out)?,
+            FileCrypto::AesGcmSiv(crypto) => crypto.encrypt(in_out)?,
+            FileCrypto::AesGcm128Stream(crypto) => crypto.encrypt(in_out)?,
+            FileCrypto::AesGcm256Stream(crypto) => crypto.encrypt(in_out)?,
+            _ => bail!("Cannot encrypt {} in memory", self.schema()),
+        };
+        Ok(cmac.into())
+    }
+
+    /// Encrypts the output of a task in memory. The iv of the key is fixed at
+    /// registration, while an output may be written by more than one task
+    /// run, so only schemas that stay safe when an iv is reused, or whose iv
+    /// is picked for each file, are allowed.
+    pub fn encrypt_output(&self, in_out: &mut Vec<u8>) -> Result<FileAuthTag> {
+        let cmac = match self {
+            FileCrypto::ChaCha20Poly1305(crypto) => crypto.encrypt_with_random_iv(in_out)?,
+            FileCrypto::AesGcmSiv(crypto) => crypto.encrypt(in_out)?,
+            FileCrypto::AesGcm128Stream(crypto) => crypto.encrypt(in_out)?,
+            FileCrypto::AesGcm256Stream(crypto) => crypto.encrypt(in_out)?,
+            _ => bail!("Unsupported output crypto schema: {}", self.schema()),
+        };
+        Ok(cmac.into())
+    }
+
+    /// Whether the file is in the chunked stream format, which is decrypted
+    /// and encrypted incrementally.
+    pub fn is_stream(&self) -> bool {
+        match self {
+            FileCrypto::AesGcm128Stream(_) | FileCrypto::AesGcm256Stream(_) => true,
+            _ => false,
+        }
+    }
+
+    pub fn stream_decryptor<R: std::io::Read>(&self, inner: R) -> Result<StreamDecryptor<R>> {
+        match self {
+            FileCrypto::AesGcm128Stream(crypto) => crypto.decryptor(inner),
+            FileCrypto::AesGcm256Stream(crypto) => crypto.decryptor(inner),
+            _ => bail!("Not a stream crypto schema: {}", self.schema()),
+        }
+    }
+
+    pub fn stream_encryptor<W: std::io::Write>(&self, inner: W) -> Result<StreamEncryptor<W>> {
+        match self {
+            FileCrypto::AesGcm128Stream(crypto) => {
+                crypto.encryptor(inner, STREAM_DEFAULT_CHUNK_SIZE)
+            }
+            FileCrypto::AesGcm256Stream(crypto) => {
+                crypto.encryptor(inner, STREAM_DEFAULT_CHUNK_SIZE)
+            }
+            _ => bail!("Not a stream crypto schema: {}", self.schema()),
+        }
+    }
 }
 
 impl std::convert::From<AesGcm128Key> for FileCrypto {
@@ -140,6 +297,47 @@ impl std::convert::From<TeaclaveFile128Key> for FileCrypto {
     }
 }
 
+impl std::convert::From<ChaCha20Poly1305Key> for FileCrypto {
+    fn from(crypto: ChaCha20Poly1305Key) -> Self {
+        FileCrypto::ChaCha20Poly1305(crypto)
+    }
+}
+
+impl std::convert::From<AesGcmSivKey> for FileCrypto {
+    fn from(crypto: AesGcmSivKey) -> Self {
+        FileCrypto::AesGcmSiv(crypto)
+    }
+}
+
+impl std::convert::From<AesGcm256StreamKey> for FileCrypto {
+    fn from(crypto: AesGcm256StreamKey) -> Self {
+        FileCrypto::AesGcm256Stream(crypto)
+    }
+}
+
+impl std::convert::From<AesGcm128StreamKey> for FileCrypto {
+    fn from(crypto: AesGcm128StreamKey) -> Self {
+        FileCrypto::AesGcm128Stream(crypto)
+    }
+}
+
+/// The key and IV of a file crypto info sealed to the public key of a
+/// `KeyWrappingKey`, so that they are never stored or sent in plaintext.
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
+pub struct WrappedFileCrypto {
+    pub schema: String,
+    pub wrapped_key: WrappedKey,
+}
+
+impl WrappedFileCrypto {
+    pub fn new(schema: impl ToString, wrapped_key: WrappedKey) -> Self {
+        Self {
+            schema: schema.to_string(),
+            wrapped_key,
+        }
+    }
+}
+
 impl Default for FileCrypto {
     fn default() -> Self {
         FileCrypto::TeaclaveFile128(TeaclaveFile128Key::random())
@@ -16,8 +16,8 @@
 // under the License.
 
 use crate::storage::Storable;
-use crate::{FileAuthTag, FileCrypto, OwnerList};
-use anyhow::{anyhow, Result};
+use crate::{FileAuthTag, FileCrypto, FunctionArguments, OwnerList};
+use anyhow::{anyhow, ensure, Result};
 use serde::{Deserialize, Serialize};
 use std::prelude::v1::*;
 use url::Url;
@@ -30,6 +30,128 @@ fn create_uuid() -> Uuid {
     Uuid::new_v4()
 }
 
+/// Remaining (epsilon, delta) differential privacy budget of a dataset. Every
+/// task running on the dataset spends the epsilon and delta in its function
+/// arguments, and is rejected once the budget would be exceeded.
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize, Serialize)]
+pub struct PrivacyBudget {
+    pub epsilon: f64,
+    pub delta: f64,
+}
+
+impl PrivacyBudget {
+    pub fn new(epsilon: f64, delta: f64) -> Self {
+        Self { epsilon, delta }
+    }
+
+    pub fn from_arguments(arguments: &FunctionArguments) -> Result<Self> {
+        let get = |key: &str| -> Result<f64> {
+            arguments
+                .get(key)?
+                .as_f64()
+                .ok_or_else(|| anyhow!("argument {} is not a number", key))
+        };
+        let cost = Self::new(get("epsilon")?, get("delta")?);
+        ensure!(
+            cost.epsilon > 0.0 && cost.delta >= 0.0,
+            "invalid privacy cost: {:?}",
+            cost
+        );
+        Ok(cost)
+    }
+
+    pub fn spend(&mut self, cost: &PrivacyBudget) -> Result<()> {
+        ensure!(
+            cost.epsilon <= self.epsilon && cost.delta <= self.delta,
+            "privacy budget exceeded: remaining {:?}, requested {:?}",
+            self,
+            cost
+        );
+        self.epsilon -= cost.epsilon;
+        self.delta -= cost.delta;
+        Ok(())
+    }
+
+    /// Gives back a cost spent by a task that could not be invoked.
+    pub fn refund(&mut self, cost: &PrivacyBudget) {
+        self.epsilon += cost.epsilon;
+        self.delta += cost.delta;
+    }
+}
+
+/// Expected digest of an input file as stored remotely, i.e., of the
+/// encrypted content. The file agent checks it after downloading.
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub enum FileHash {
+    Md5(String),
+    Sha256(String),
+}
+
+impl FileHash {
+    pub fn new(algorithm: &str, hex: &str) -> Result<Self> {
+        let hex = hex.to_lowercase();
+        let (hash, len) = match algorithm {
+            "md5" => (FileHash::Md5(hex.clone()), 32),
+            "sha256" => (FileHash::Sha256(hex.clone()), 64),
+            _ => anyhow::bail!("Invalid hash algorithm: {}", algorithm),
+        };
+        ensure!(
+            hex.len() == len && hex.chars().all(|c| c.is_ascii_hexdigit()),
+            "Invalid {} hash: {}",
+            algorithm,
+            hex
+        );
+        Ok(hash)
+    }
+
+    pub fn algorithm(&self) -> &str {
+        match self {
+            FileHash::Md5(_) => "md5",
+            FileHash::Sha256(_) => "sha256",
+        }
+    }
+
+    pub fn hex(&self) -> &str {
+        match self {
+            FileHash::Md5(hex) | FileHash::Sha256(hex) => hex,
+        }
+    }
+
+    pub fn sha256(content: &[u8]) -> Self {
+        let mut hasher = FileHasher::new();
+        hasher.update(content);
+        hasher.finish()
+    }
+}
+
+/// Computes the SHA-256 `FileHash` of a file written piece by piece.
+#[derive(Clone)]
+pub struct FileHasher {
+    context: ring::digest::Context,
+}
+
+impl FileHasher {
+    pub fn new() -> Self {
+        Self {
+            context: ring::digest::Context::new(&ring::digest::SHA256),
+        }
+    }
+
+    pub fn update(&mut self, data: &[u8]) {
+        self.context.update(data);
+    }
+
+    pub fn finish(self) -> FileHash {
+        FileHash::Sha256(hex::encode(self.context.finish().as_ref()))
+    }
+}
+
+impl Default for FileHasher {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 #[derive(Debug, Clone, Deserialize, Serialize)]
 pub struct TeaclaveInputFile {
     pub url: Url,
@@ -37,6 +159,8 @@ pub struct TeaclaveInputFile {
     pub crypto_info: FileCrypto,
     pub owner: OwnerList,
     pub uuid: Uuid,
+    pub privacy_budget: Option<PrivacyBudget>,
+    pub hash: Option<FileHash>,
 }
 
 #[derive(Debug, Clone, Deserialize, Serialize)]
@@ -46,6 +170,10 @@ pub struct TeaclaveOutputFile {
     pub crypto_info: FileCrypto,
     pub owner: OwnerList,
     pub uuid: Uuid,
+    /// The key wrapped to the recipient's public key, if the platform
+    /// generated it.
+    #[serde(default)]
+    pub recipient_crypto_info: Option<FileCrypto>,
 }
 
 impl TeaclaveInputFile {
@@ -61,9 +189,45 @@ impl TeaclaveInputFile {
             crypto_info,
             owner: owner.into(),
             uuid: create_uuid(),
+            privacy_budget: None,
+            hash: None,
         }
     }
 
+    pub fn hash(self, hash: Option<FileHash>) -> Self {
+        Self { hash, ..self }
+    }
+
+    pub fn privacy_budget(self, privacy_budget: Option<PrivacyBudget>) -> Self {
+        Self {
+            privacy_budget,
+            ..self
+        }
+    }
+
+    /// Points the file to a copy encrypted under a new key, e.g., written by
+    /// a key rotation task. The id, owners and privacy budget are kept. A file
+    /// pinned by a hash stays pinned, by `hash` of the uploaded copy.
+    pub fn rotate_key(self, output: &TeaclaveOutputFile, hash: Option<&FileHash>) -> Result<Self> {
+        let cmac = output
+            .cmac
+            .ok_or_else(|| anyhow!("output is not finished"))?;
+        let hash = match self.hash {
+            Some(_) => Some(
+                hash.cloned()
+                    .ok_or_else(|| anyhow!("hash of the re-encrypted file is unknown"))?,
+            ),
+            None => None,
+        };
+        Ok(Self {
+            url: output.url.clone(),
+            cmac,
+            crypto_info: output.crypto_info.clone(),
+            hash,
+            ..self
+        })
+    }
+
     pub fn from_output(output: TeaclaveOutputFile) -> Result<TeaclaveInputFile> {
         let input = TeaclaveInputFile {
             url: output.url,
@@ -73,6 +237,8 @@ impl TeaclaveInputFile {
             crypto_info: output.crypto_info,
             owner: output.owner,
             uuid: output.uuid,
+            privacy_budget: None,
+            hash: None,
         };
         Ok(input)
     }
@@ -100,6 +266,14 @@ impl TeaclaveOutputFile {
             crypto_info,
             owner: owner.into(),
             uuid: create_uuid(),
+            recipient_crypto_info: None,
+        }
+    }
+
+    pub fn recipient_crypto_info(self, recipient_crypto_info: Option<FileCrypto>) -> Self {
+        Self {
+            recipient_crypto_info,
+            ..self
         }
     }
 
@@ -119,3 +293,42 @@ impl Storable for TeaclaveOutputFile {
         self.uuid
     }
 }
+
+#[cfg(feature = "enclave_unit_test")]
+pub mod tests {
+    use super::*;
+    use serde_json::json;
+    use teaclave_test_utils::*;
+
+    pub fn run_tests() -> bool {
+        run_tests!(test_privacy_budget_spend, test_privacy_budget_exceeded)
+    }
+
+    fn test_privacy_budget_spend() {
+        let arguments =
+            FunctionArguments::from_json(json!({"epsilon": 0.5, "delta": 0.25})).unwrap();
+        let cost = PrivacyBudget::from_arguments(&arguments).unwrap();
+        assert_eq!(cost, PrivacyBudget::new(0.5, 0.25));
+
+        let mut budget = PrivacyBudget::new(1.0, 0.5);
+        budget.spend(&cost).unwrap();
+        assert_eq!(budget, PrivacyBudget::new(0.5, 0.25));
+        budget.spend(&cost).unwrap();
+        assert_eq!(budget, PrivacyBudget::new(0.0, 0.0));
+
+        budget.refund(&cost);
+        assert_eq!(budget, PrivacyBudget::new(0.5, 0.25));
+
+        let arguments =
+            FunctionArguments::from_json(json!({"epsilon": 0.0, "delta": 0.0})).unwrap();
+        assert!(PrivacyBudget::from_arguments(&arguments).is_err());
+    }
+
+    fn test_privacy_budget_exceeded() {
+        let mut budget = PrivacyBudget::new(1.0, 0.5);
+        assert!(budget.spend(&PrivacyBudget::new(1.5, 0.0)).is_err());
+        assert!(budget.spend(&PrivacyBudget::new(0.5, 1.0)).is_err());
+        // A rejected spend leaves the budget untouched.
+        assert_eq!(budget, PrivacyBudget::new(1.0, 0.5));
+    }
+}
@@ -15,21 +15,63 @@
 // specific language governing permissions and limitations
 // under the License.
 
+use crate::FileHash;
 use serde::{Deserialize, Serialize};
+use std::format;
 use std::path::{Path, PathBuf};
 use std::prelude::v1::*;
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
 pub enum HandleFileCommand {
     Download,
     Upload,
 }
 
+/// How the file agent retries a failed transfer. The delay before the n-th
+/// retry is `initial_backoff_ms * 2^(n-1)`, capped by `max_backoff_ms`.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub struct TransferRetryPolicy {
+    pub max_retries: u32,
+    pub initial_backoff_ms: u64,
+    pub max_backoff_ms: u64,
+}
+
+impl Default for TransferRetryPolicy {
+    fn default() -> Self {
+        TransferRetryPolicy {
+            max_retries: 3,
+            initial_backoff_ms: 500,
+            max_backoff_ms: 10_000,
+        }
+    }
+}
+
+impl TransferRetryPolicy {
+    pub fn no_retry() -> Self {
+        TransferRetryPolicy {
+            max_retries: 0,
+            ..Default::default()
+        }
+    }
+
+    pub fn backoff(&self, retry: u32) -> std::time::Duration {
+        let factor = 1u64
+            .checked_shl(retry.saturating_sub(1))
+            .unwrap_or(std::u64::MAX);
+        let backoff_ms = self
+            .initial_backoff_ms
+            .saturating_mul(factor)
+            .min(self.max_backoff_ms);
+        std::time::Duration::from_millis(backoff_ms)
+    }
+}
+
 #[derive(Debug, Serialize, Deserialize)]
 pub struct FileAgentRequest {
     pub cmd: HandleFileCommand,
     pub info: Vec<HandleFileInfo>,
     pub fusion_base: PathBuf,
+    pub retry: TransferRetryPolicy,
 }
 
 impl FileAgentRequest {
@@ -45,14 +87,20 @@ impl FileAgentRequest {
             cmd,
             info: info.into_iter().map(|x| x.into()).collect(),
             fusion_base: fusion_base.as_ref().to_owned(),
+            retry: TransferRetryPolicy::default(),
         }
     }
+
+    pub fn retry(self, retry: TransferRetryPolicy) -> Self {
+        Self { retry, ..self }
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct HandleFileInfo {
     pub local: PathBuf,
     pub remote: url::Url,
+    pub hash: Option<FileHash>,
 }
 
 impl HandleFileInfo {
@@ -60,8 +108,13 @@ impl HandleFileInfo {
         HandleFileInfo {
             local: local.as_ref().to_owned(),
             remote: remote.to_owned(),
+            hash: None,
         }
     }
+
+    pub fn hash(self, hash: Option<FileHash>) -> Self {
+        Self { hash, ..self }
+    }
 }
 
 impl std::convert::From<&HandleFileInfo> for HandleFileInfo {
@@ -69,3 +122,171 @@ impl std::convert::From<&HandleFileInfo> for HandleFileInfo {
         info.clone()
     }
 }
+
+/// Reads up to `len` bytes of a remote file starting at `offset`, used to
+/// stream inputs into the enclave without downloading them first. Fewer
+/// bytes are returned at the end of the file.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ReadFileRangeRequest {
+    pub remote: url::Url,
+    pub offset: u64,
+    pub len: u32,
+    pub fusion_base: PathBuf,
+    pub retry: TransferRetryPolicy,
+}
+
+impl ReadFileRangeRequest {
+    pub fn new(remote: &url::Url, offset: u64, len: u32, fusion_base: impl AsRef<Path>) -> Self {
+        ReadFileRangeRequest {
+            remote: remote.to_owned(),
+            offset,
+            len,
+            fusion_base: fusion_base.as_ref().to_owned(),
+            retry: TransferRetryPolicy::default(),
+        }
+    }
+
+    pub fn retry(self, retry: TransferRetryPolicy) -> Self {
+        Self { retry, ..self }
+    }
+}
+
+/// Opens an upload to a remote file whose content is then sent piece by
+/// piece, used to stream outputs out of the enclave without writing them to
+/// the local disk first.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct OpenUploadRequest {
+    pub remote: url::Url,
+    pub fusion_base: PathBuf,
+    pub retry: TransferRetryPolicy,
+}
+
+impl OpenUploadRequest {
+    pub fn new(remote: &url::Url, fusion_base: impl AsRef<Path>) -> Self {
+        OpenUploadRequest {
+            remote: remote.to_owned(),
+            fusion_base: fusion_base.as_ref().to_owned(),
+            retry: TransferRetryPolicy::default(),
+        }
+    }
+
+    pub fn retry(self, retry: TransferRetryPolicy) -> Self {
+        Self { retry, ..self }
+    }
+}
+
+/// Why the file agent failed to download or upload a file.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, thiserror::Error)]
+pub enum HandleFileError {
+    #[error("HTTP status {status}")]
+    Http { status: u16 },
+    #[error("network error: {message}")]
+    Network { message: String },
+    #[error("I/O error ({kind}): {message}")]
+    Io { kind: String, message: String },
+    #[error("{algorithm} mismatch: expected {expected}, got {actual}")]
+    HashMismatch {
+        algorithm: String,
+        expected: String,
+        actual: String,
+    },
+    #[error("{message}")]
+    Other { message: String },
+}
+
+impl HandleFileError {
+    /// The kind of the error, which unlike the message reveals nothing about
+    /// the file.
+    pub fn kind(&self) -> &'static str {
+        match self {
+            HandleFileError::Http { .. } => "http",
+            HandleFileError::Network { .. } => "network",
+            HandleFileError::Io { .. } => "io",
+            HandleFileError::HashMismatch { .. } => "hash_mismatch",
+            HandleFileError::Other { .. } => "other",
+        }
+    }
+}
+
+/// Result of handling one file of a `FileAgentRequest`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct HandleFileResult {
+    pub local: PathBuf,
+    pub remote: url::Url,
+    pub error: Option<HandleFileError>,
+}
+
+impl HandleFileResult {
+    pub fn new(info: HandleFileInfo, error: Option<HandleFileError>) -> Self {
+        HandleFileResult {
+            local: info.local,
+            remote: info.remote,
+            error,
+        }
+    }
+
+    pub fn redacted_remote(&self) -> String {
+        redact_url(&self.remote)
+    }
+}
+
+/// Only the scheme, host and path of a URL, which can be logged or reported.
+/// Credentials, query and fragment may carry secrets such as presigned tokens.
+pub fn redact_url(url: &url::Url) -> String {
+    format!(
+        "{}://{}{}",
+        url.scheme(),
+        url.host_str().unwrap_or_default(),
+        url.path()
+    )
+}
+
+/// Serialized back to the enclave through the file agent ocall.
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct FileAgentResponse {
+    pub results: Vec<HandleFileResult>,
+}
+
+impl FileAgentResponse {
+    pub fn new(results: Vec<HandleFileResult>) -> Self {
+        FileAgentResponse { results }
+    }
+
+    pub fn into_result(self) -> std::result::Result<(), FileAgentError> {
+        let failures: Vec<HandleFileResult> = self
+            .results
+            .into_iter()
+            .filter(|r| r.error.is_some())
+            .collect();
+        if failures.is_empty() {
+            Ok(())
+        } else {
+            Err(FileAgentError { failures })
+        }
+    }
+}
+
+/// Files that the file agent failed to handle.
+#[derive(Debug, Clone, thiserror::Error)]
+pub struct FileAgentError {
+    pub failures: Vec<HandleFileResult>,
+}
+
+impl std::fmt::Display for FileAgentError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let details: Vec<String> = self
+            .failures
+            .iter()
+            .map(|r| match &r.error {
+                Some(e) => format!("{}: {}", r.redacted_remote(), e),
+                None => r.redacted_remote(),
+            })
+            .collect();
+        write!(
+            f,
+            "Failed to handle {} file(s): {}",
+            self.failures.len(),
+            details.join("; ")
+        )
+    }
+}
@@ -16,14 +16,33 @@
 // under the License.
 
 use crate::{ExecutorType, Storable, UserID};
+use anyhow::{anyhow, ensure, Result};
 use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
 use std::prelude::v1::*;
 use uuid::Uuid;
 
-#[derive(Debug, Deserialize, Serialize)]
+/// Name of the builtin function allowed to run on input files with a privacy
+/// budget (see `PrivacyBudget`).
+pub const DP_AGGREGATE_FUNCTION_NAME: &str = "builtin-dp-aggregate";
+
+/// Name of the builtin function run by key rotation tasks to copy an input
+/// file to a file encrypted under a new key.
+pub const REENCRYPT_FILE_FUNCTION_NAME: &str = "builtin-reencrypt-file";
+pub const REENCRYPT_FILE_INPUT: &str = "input_file";
+pub const REENCRYPT_FILE_OUTPUT: &str = "output_file";
+
+/// Input slot of a function. An optional slot may be left out when creating a
+/// task, and a repeated slot accepts a list of files named `name[0]`,
+/// `name[1]`, ... (see `indexed_file_name`).
+#[derive(Debug, Clone, Deserialize, Serialize)]
 pub struct FunctionInput {
     pub name: String,
     pub description: String,
+    #[serde(default)]
+    pub optional: bool,
+    #[serde(default)]
+    pub repeated: bool,
 }
 
 impl FunctionInput {
@@ -31,14 +50,29 @@ impl FunctionInput {
         Self {
             name: name.into(),
             description: description.into(),
+            optional: false,
+            repeated: false,
         }
     }
+
+    pub fn optional(self, optional: bool) -> Self {
+        Self { optional, ..self }
+    }
+
+    pub fn repeated(self, repeated: bool) -> Self {
+        Self { repeated, ..self }
+    }
 }
 
-#[derive(Debug, Deserialize, Serialize)]
+/// Output slot of a function, see `FunctionInput` for the slot cardinalities.
+#[derive(Debug, Clone, Deserialize, Serialize)]
 pub struct FunctionOutput {
     pub name: String,
     pub description: String,
+    #[serde(default)]
+    pub optional: bool,
+    #[serde(default)]
+    pub repeated: bool,
 }
 
 impl FunctionOutput {
@@ -46,13 +80,119 @@ impl FunctionOutput {
         Self {
             name: name.into(),
             description: description.into(),
+            optional: false,
+            repeated: false,
+        }
+    }
+
+    pub fn optional(self, optional: bool) -> Self {
+        Self { optional, ..self }
+    }
+
+    pub fn repeated(self, repeated: bool) -> Self {
+        Self { repeated, ..self }
+    }
+}
+
+/// Returns the file name of the `index`-th file of a repeated slot.
+pub fn indexed_file_name(name: &str, index: usize) -> String {
+    format!("{}[{}]", name, index)
+}
+
+/// Splits a file name of a repeated slot into the slot name and the index.
+pub fn parse_indexed_file_name(fname: &str) -> Option<(&str, usize)> {
+    if !fname.ends_with(']') {
+        return None;
+    }
+    let pos = fname.rfind('[')?;
+    let index = fname[pos + 1..fname.len() - 1].parse::<usize>().ok()?;
+    Some((&fname[..pos], index))
+}
+
+/// Cardinality of an input or output slot of a function.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct FunctionSlot {
+    pub name: String,
+    #[serde(default)]
+    pub optional: bool,
+    #[serde(default)]
+    pub repeated: bool,
+}
+
+impl std::convert::From<&FunctionInput> for FunctionSlot {
+    fn from(input: &FunctionInput) -> Self {
+        Self {
+            name: input.name.clone(),
+            optional: input.optional,
+            repeated: input.repeated,
         }
     }
 }
 
+impl std::convert::From<&FunctionOutput> for FunctionSlot {
+    fn from(output: &FunctionOutput) -> Self {
+        Self {
+            name: output.name.clone(),
+            optional: output.optional,
+            repeated: output.repeated,
+        }
+    }
+}
+
+/// Finds the slot a file name belongs to.
+pub fn find_function_slot<'a>(slots: &'a [FunctionSlot], fname: &str) -> Option<&'a FunctionSlot> {
+    slots.iter().find(|slot| {
+        if slot.repeated {
+            match parse_indexed_file_name(fname) {
+                Some((name, _)) => name == slot.name,
+                None => false,
+            }
+        } else {
+            slot.name == fname
+        }
+    })
+}
+
+/// Checks whether a set of file names fills the slots. Every file name must
+/// belong to a slot, non-optional slots must be filled, and the indices of a
+/// repeated slot must be contiguous from zero.
+pub fn check_function_slots<'a>(
+    slots: &[FunctionSlot],
+    fnames: impl IntoIterator<Item = &'a String>,
+) -> Result<()> {
+    let mut indices: HashMap<&str, Vec<usize>> = HashMap::new();
+    for fname in fnames {
+        let slot = find_function_slot(slots, fname)
+            .ok_or_else(|| anyhow!("Unknown file name: {}", fname))?;
+        let index = if slot.repeated {
+            parse_indexed_file_name(fname).map_or(0, |(_, i)| i)
+        } else {
+            0
+        };
+        indices.entry(&slot.name).or_default().push(index);
+    }
+
+    for slot in slots {
+        match indices.get_mut(slot.name.as_str()) {
+            Some(slot_indices) => {
+                slot_indices.sort();
+                let expected: Vec<usize> = (0..slot_indices.len()).collect();
+                ensure!(
+                    *slot_indices == expected,
+                    "Indices of repeated slot are not contiguous: {}",
+                    slot.name
+                );
+            }
+            None => ensure!(slot.optional, "Missing file for slot: {}", slot.name),
+        }
+    }
+
+    Ok(())
+}
+
 const FUNCION_PREFIX: &str = "function";
 
-#[derive(Default, Debug, Deserialize, Serialize)]
+#[derive(Default, Debug, Clone, Deserialize, Serialize)]
 pub struct Function {
     pub id: Uuid,
     pub name: String,
@@ -64,11 +204,19 @@ pub struct Function {
     pub inputs: Vec<FunctionInput>,
     pub outputs: Vec<FunctionOutput>,
     pub owner: UserID,
+    // Functions stored before revisions were introduced have none of the
+    // following fields.
+    #[serde(default)]
+    pub version: String,
+    #[serde(default)]
+    pub revision_hash: String,
+    #[serde(default)]
+    pub previous_revision: Option<Uuid>,
 }
 
 impl Function {
     pub fn new() -> Self {
-        Self::default()
+        Self::default().rehash()
     }
 
     pub fn id(self, id: Uuid) -> Self {
@@ -80,6 +228,7 @@ impl Function {
             executor_type,
             ..self
         }
+        .rehash()
     }
 
     pub fn name(self, name: impl ToString) -> Self {
@@ -87,6 +236,7 @@ impl Function {
             name: name.to_string(),
             ..self
         }
+        .rehash()
     }
 
     pub fn description(self, description: impl ToString) -> Self {
@@ -97,7 +247,7 @@ impl Function {
     }
 
     pub fn payload(self, payload: Vec<u8>) -> Self {
-        Self { payload, ..self }
+        Self { payload, ..self }.rehash()
     }
 
     pub fn public(self, public: bool) -> Self {
@@ -105,15 +255,15 @@ impl Function {
     }
 
     pub fn arguments(self, arguments: Vec<String>) -> Self {
-        Self { arguments, ..self }
+        Self { arguments, ..self }.rehash()
     }
 
     pub fn inputs(self, inputs: Vec<FunctionInput>) -> Self {
-        Self { inputs, ..self }
+        Self { inputs, ..self }.rehash()
     }
 
     pub fn outputs(self, outputs: Vec<FunctionOutput>) -> Self {
-        Self { outputs, ..self }
+        Self { outputs, ..self }.rehash()
     }
 
     pub fn owner(self, owner: impl Into<UserID>) -> Self {
@@ -122,6 +272,110 @@ impl Function {
             ..self
         }
     }
+
+    pub fn version(self, version: impl ToString) -> Self {
+        Self {
+            version: version.to_string(),
+            ..self
+        }
+    }
+
+    pub fn previous_revision(self, previous_revision: Uuid) -> Self {
+        Self {
+            previous_revision: Some(previous_revision),
+            ..self
+        }
+    }
+
+    /// Returns the hex-encoded SHA-256 digest of everything affecting how
+    /// the function is executed: its name (which selects a builtin), executor
+    /// type, payload, arguments, and input and output slots. A revision is
+    /// immutable once stored, so this hash identifies exactly what a task will
+    /// run. Descriptions, visibility, owner and version are not hashed.
+    pub fn compute_revision_hash(&self) -> String {
+        let mut encoding = RevisionEncoding::default();
+        encoding.bytes(self.name.as_bytes());
+        encoding.bytes(format!("{}", self.executor_type).as_bytes());
+        encoding.bytes(&self.payload);
+        encoding.len(self.arguments.len());
+        for argument in &self.arguments {
+            encoding.bytes(argument.as_bytes());
+        }
+        let input_slots: Vec<FunctionSlot> = self.inputs.iter().map(Into::into).collect();
+        let output_slots: Vec<FunctionSlot> = self.outputs.iter().map(Into::into).collect();
+        for slots in &[input_slots, output_slots] {
+            encoding.len(slots.len());
+            for slot in slots {
+                encoding.bytes(slot.name.as_bytes());
+                encoding.flag(slot.optional);
+                encoding.flag(slot.repeated);
+            }
+        }
+
+        let digest = ring::digest::digest(&ring::digest::SHA256, &encoding.0);
+        hex::encode(digest.as_ref())
+    }
+
+    /// Key of the payload, which is stored in chunks apart from the function.
+    pub fn payload_key(&self) -> Vec<u8> {
+        format!("{}-payload", self.key_string()).into_bytes()
+    }
+
+    pub fn verify_revision_hash(&self, revision_hash: &str) -> bool {
+        self.compute_revision_hash() == revision_hash
+    }
+
+    fn rehash(self) -> Self {
+        let revision_hash = self.compute_revision_hash();
+        Self {
+            revision_hash,
+            ..self
+        }
+    }
+
+    /// The builtin function run by key rotation tasks. It is not stored, so
+    /// it has no owner and the nil id.
+    pub fn reencrypt_file() -> Self {
+        Self::new()
+            .name(REENCRYPT_FILE_FUNCTION_NAME)
+            .executor_type(ExecutorType::Builtin)
+            .public(true)
+            .inputs(vec![FunctionInput::new(
+                REENCRYPT_FILE_INPUT,
+                "File to re-encrypt",
+            )])
+            .outputs(vec![FunctionOutput::new(
+                REENCRYPT_FILE_OUTPUT,
+                "File encrypted under the new key",
+            )])
+    }
+
+    /// Whether the function adds differentially private noise to its results
+    /// and therefore may spend the privacy budget of its inputs.
+    pub fn is_differentially_private(&self) -> bool {
+        self.executor_type == ExecutorType::Builtin && self.name == DP_AGGREGATE_FUNCTION_NAME
+    }
+}
+
+// Canonical encoding of a function for its revision hash. Every variable
+// length field is prefixed with its length, so different functions never
+// share an encoding.
+#[derive(Default)]
+struct RevisionEncoding(Vec<u8>);
+
+impl RevisionEncoding {
+    fn len(&mut self, len: usize) {
+        self.0.extend_from_slice(&(len as u64).to_le_bytes());
+    }
+
+    fn bytes(&mut self, bytes: &[u8]) {
+        self.len(bytes.len());
+        self.0.extend_from_slice(bytes);
+    }
+
+    fn flag(&mut self, flag: bool) {
+        self.0.push(flag as u8);
+    }
 }
 
 impl Storable for Function {
@@ -133,3 +387,100 @@ impl Storable for Function {
         self.id
     }
 }
+
+#[cfg(feature = "enclave_unit_test")]
+pub mod tests {
+    use super::*;
+    use teaclave_test_utils::*;
+
+    pub fn run_tests() -> bool {
+        run_tests!(
+            test_indexed_file_name,
+            test_check_function_slots,
+            test_revision_hash
+        )
+    }
+
+    fn test_indexed_file_name() {
+        let fname = indexed_file_name("input_data", 2);
+        assert_eq!(fname, "input_data[2]");
+        assert_eq!(parse_indexed_file_name(&fname), Some(("input_data", 2)));
+        assert_eq!(parse_indexed_file_name("input_data"), None);
+        assert_eq!(parse_indexed_file_name("input_data[a]"), None);
+    }
+
+    fn test_check_function_slots() {
+        let slots: Vec<FunctionSlot> = vec![
+            (&FunctionInput::new("model", "")).into(),
+            (&FunctionInput::new("config", "").optional(true)).into(),
+            (&FunctionInput::new("data", "").repeated(true)).into(),
+        ];
+        let check = |fnames: &[&str]| {
+            let fnames: Vec<String> = fnames.iter().map(|f| f.to_string()).collect();
+            check_function_slots(&slots, fnames.iter())
+        };
+
+        assert!(check(&["model", "data[0]"]).is_ok());
+        assert!(check(&["model", "config", "data[0]", "data[1]"]).is_ok());
+        // required slots must be filled
+        assert!(check(&["data[0]"]).is_err());
+        assert!(check(&["model"]).is_err());
+        // indices must be contiguous from zero
+        assert!(check(&["model", "data[1]"]).is_err());
+        assert!(check(&["model", "data[0]", "data[2]"]).is_err());
+        // unknown names are rejected
+        assert!(check(&["model", "data[0]", "data"]).is_err());
+        assert!(check(&["model", "data[0]", "other"]).is_err());
+    }
+
+    fn test_revision_hash() {
+        let function = Function::new()
+            .name("builtin-echo")
+            .executor_type(ExecutorType::Builtin)
+            .arguments(vec!["message".to_string()])
+            .inputs(vec![FunctionInput::new("input", "")])
+            .outputs(vec![FunctionOutput::new("output", "")]);
+        let revision_hash = function.revision_hash.clone();
+        assert!(function.verify_revision_hash(&revision_hash));
+
+        // builtins without payload are told apart by name
+        let other = Function::new()
+            .name("builtin-gbdt-train")
+            .executor_type(ExecutorType::Builtin);
+        assert_ne!(other.revision_hash, Function::new().revision_hash);
+
+        // every field affecting the execution is pinned
+        let changed = vec![
+            function.clone().name("builtin-other"),
+            function.clone().executor_type(ExecutorType::Python),
+            function.clone().payload(b"payload".to_vec()),
+            function.clone().arguments(vec![]),
+            function
+                .clone()
+                .inputs(vec![FunctionInput::new("input", "").optional(true)]),
+            function
+                .clone()
+                .outputs(vec![FunctionOutput::new("output", "").repeated(true)]),
+        ];
+        for changed in changed {
+            assert_ne!(changed.revision_hash, revision_hash);
+            assert!(!changed.verify_revision_hash(&revision_hash));
+        }
+
+        // a stored revision whose fields were altered no longer verifies
+        let mut altered = function.clone();
+        altered.arguments.push("extra".to_string());
+        assert!(!altered.verify_revision_hash(&revision_hash));
+
+        // descriptions and versions are not pinned
+        let relabeled = function.clone().description("echo").version("v2");
+        assert_eq!(relabeled.revision_hash, revision_hash);
+
+        // arguments are length-prefixed, so shifting bytes changes the hash
+        let split = function
+            .clone()
+            .arguments(vec!["ab".to_string(), "c".to_string()]);
+        let joined = function.arguments(vec!["a".to_string(), "bc".to_string()]);
+        assert_ne!(split.revision_hash, joined.revision_hash);
+    }
+}
@@ -219,6 +219,8 @@ pub enum TeaclaveServiceResponseError {
     ConnectionError(String),
     #[error("Internal error: {0}")]
     InternalError(String),
+    #[error("Not found: {0}")]
+    NotFound(String),
 }
 
 impl From<anyhow::Error> for TeaclaveServiceResponseError {
@@ -232,8 +234,15 @@ pub type TeaclaveServiceResponseResult<T> = std::result::Result<T, TeaclaveServi
 #[cfg(feature = "enclave_unit_test")]
 pub mod tests {
     use super::*;
+    use teaclave_test_utils::check_all_passed;
 
     pub fn run_tests() -> bool {
-        worker::tests::run_tests()
+        check_all_passed!(
+            worker::tests::run_tests(),
+            function::tests::run_tests(),
+            file::tests::run_tests(),
+            task::tests::run_tests(),
+            task_state::tests::run_tests()
+        )
     }
 }
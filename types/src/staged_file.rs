@@ -23,18 +23,27 @@ use std::fs::File;
 use std::io::{self, BufRead, BufReader, Read, Write};
 use std::path::{Path, PathBuf};
 use std::prelude::v1::*;
+use std::sync::Arc;
 #[cfg(feature = "mesalock_sgx")]
 use std::untrusted::fs::File;
 
-use crate::FileAuthTag;
+use crate::{indexed_file_name, FileAuthTag, FileCrypto};
 use anyhow::Context;
 use protected_fs::ProtectedFile;
 
+/// The content of a staged file which is streamed from or to its remote
+/// location instead of being kept in a local protected file.
+pub trait StagedFileStream: std::fmt::Debug + Send + Sync {
+    fn open_read(&self) -> anyhow::Result<Box<dyn io::Read>>;
+    fn open_write(&self) -> anyhow::Result<Box<dyn io::Write>>;
+}
+
 #[derive(Clone, Debug, Default)]
 pub struct StagedFileInfo {
     pub path: PathBuf,
     pub crypto_info: TeaclaveFile128Key,
     pub cmac: FileAuthTag,
+    pub stream: Option<Arc<dyn StagedFileStream>>,
 }
 
 impl StagedFileInfo {
@@ -47,10 +56,24 @@ impl StagedFileInfo {
             path: path.as_ref().into(),
             crypto_info,
             cmac: cmac.into(),
+            stream: None,
+        }
+    }
+
+    /// A staged file read or written through `stream`, `path` is only used to
+    /// identify the file.
+    pub fn new_stream(path: impl AsRef<Path>, stream: Arc<dyn StagedFileStream>) -> Self {
+        StagedFileInfo {
+            path: path.as_ref().into(),
+            stream: Some(stream),
+            ..Default::default()
         }
     }
 
     pub fn create_readable_io(&self) -> anyhow::Result<Box<dyn io::Read>> {
+        if let Some(stream) = &self.stream {
+            return stream.open_read();
+        }
         let f = ProtectedFile::open_ex(&self.path, &self.crypto_info.key)?;
         let tag = f
             .current_meta_gmac()
@@ -60,6 +83,9 @@ impl StagedFileInfo {
     }
 
     pub fn create_writable_io(&self) -> anyhow::Result<Box<dyn io::Write>> {
+        if let Some(stream) = &self.stream {
+            return stream.open_write();
+        }
         let f = ProtectedFile::create_ex(&self.path, &self.crypto_info.key)?;
         Ok(Box::new(f))
     }
@@ -99,6 +125,43 @@ impl StagedFileInfo {
         Ok(StagedFileInfo::new(dst, crypto, tag))
     }
 
+    /// Encrypts the staged file into `dst` in the chunked stream format and
+    /// returns the tag of the stream.
+    pub fn convert_to_stream(
+        &self,
+        dst: impl AsRef<Path>,
+        crypto: &FileCrypto,
+    ) -> anyhow::Result<FileAuthTag> {
+        let mut src_file = ProtectedFile::open_ex(&self.path, &self.crypto_info.key)
+            .context("Convert: failed to open src file")?;
+        let dest_file = File::create(dst.as_ref()).context("Convert: failed to create dst file")?;
+
+        let mut encryptor = crypto.stream_encryptor(dest_file)?;
+        io::copy(&mut src_file, &mut encryptor)?;
+        let (_, tag) = encryptor.finish()?;
+        Ok(tag.into())
+    }
+
+    /// Encrypts the staged file into `dst` as a task output and returns the
+    /// tag, see `FileCrypto::encrypt_output`. The whole file is encrypted in
+    /// memory.
+    pub fn convert_to_output(
+        &self,
+        dst: impl AsRef<Path>,
+        crypto: &FileCrypto,
+    ) -> anyhow::Result<FileAuthTag> {
+        let mut src_file = ProtectedFile::open_ex(&self.path, &self.crypto_info.key)
+            .context("Convert: failed to open src file")?;
+        let mut content = Vec::new();
+        src_file.read_to_end(&mut content)?;
+
+        let tag = crypto.encrypt_output(&mut content)?;
+        let mut dest_file =
+            File::create(dst.as_ref()).context("Convert: failed to create dst file")?;
+        dest_file.write_all(&content)?;
+        Ok(tag)
+    }
+
     #[cfg(test_mode)]
     pub fn create_with_plaintext_file(path: impl AsRef<Path>) -> anyhow::Result<StagedFileInfo> {
         let bytes = read_all_bytes(path.as_ref())?;
@@ -114,6 +177,19 @@ impl StagedFileInfo {
         Ok(content)
     }
 
+    /// Stages everything read from `reader` in a new protected file.
+    pub fn create_with_reader(
+        path: impl AsRef<Path>,
+        mut reader: impl io::Read,
+    ) -> anyhow::Result<StagedFileInfo> {
+        let crypto = TeaclaveFile128Key::random();
+        let mut f = ProtectedFile::create_ex(&path, &crypto.key)?;
+        io::copy(&mut reader, &mut f)?;
+        f.flush()?;
+        let tag = f.current_meta_gmac()?;
+        Ok(Self::new(path.as_ref(), crypto, tag))
+    }
+
     pub fn create_with_bytes(
         path: impl AsRef<Path>,
         bytes: &[u8],
@@ -148,6 +224,15 @@ impl StagedFiles {
         self.entries.get(key)
     }
 
+    pub fn slot_len(&self, name: &str) -> usize {
+        if self.entries.contains_key(name) {
+            return 1;
+        }
+        (0..)
+            .take_while(|i| self.entries.contains_key(&indexed_file_name(name, *i)))
+            .count()
+    }
+
     pub fn len(&self) -> usize {
         self.entries.len()
     }
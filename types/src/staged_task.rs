@@ -20,6 +20,7 @@ use std::collections::HashMap;
 use std::prelude::v1::*;
 
 use serde::{Deserialize, Serialize};
+use teaclave_crypto::KeyWrappingKey;
 use url::Url;
 use uuid::Uuid;
 
@@ -129,6 +130,7 @@ pub struct FunctionInputFile {
     pub url: Url,
     pub cmac: FileAuthTag,
     pub crypto_info: FileCrypto,
+    pub hash: Option<FileHash>,
 }
 
 impl FunctionInputFile {
@@ -137,6 +139,7 @@ impl FunctionInputFile {
             url,
             cmac,
             crypto_info: crypto.into(),
+            hash: None,
         }
     }
 }
@@ -147,6 +150,7 @@ impl From<TeaclaveInputFile> for FunctionInputFile {
             url: file.url,
             cmac: file.cmac,
             crypto_info: file.crypto_info,
+            hash: file.hash,
         }
     }
 }
@@ -253,6 +257,33 @@ impl StagedTask {
         }
     }
 
+    /// Wraps the file keys, which may be wrapped to `wrapping_key`, to
+    /// `recipient`, so that they are never queued or sent in plaintext.
+    pub fn rewrap_file_keys(
+        mut self,
+        wrapping_key: &KeyWrappingKey,
+        recipient: &[u8],
+    ) -> anyhow::Result<Self> {
+        for file in self.input_data.inner.values_mut() {
+            file.crypto_info = file.crypto_info.rewrap(wrapping_key, recipient)?;
+        }
+        for file in self.output_data.inner.values_mut() {
+            file.crypto_info = file.crypto_info.rewrap(wrapping_key, recipient)?;
+        }
+        Ok(self)
+    }
+
+    /// Unwraps the file keys which are wrapped to `wrapping_key`.
+    pub fn unwrap_file_keys(mut self, wrapping_key: &KeyWrappingKey) -> anyhow::Result<Self> {
+        for file in self.input_data.inner.values_mut() {
+            file.crypto_info = file.crypto_info.unwrap_with(wrapping_key)?;
+        }
+        for file in self.output_data.inner.values_mut() {
+            file.crypto_info = file.crypto_info.unwrap_with(wrapping_key)?;
+        }
+        Ok(self)
+    }
+
     pub fn executor_type(self, executor_type: ExecutorType) -> Self {
         Self {
             executor_type,
@@ -199,6 +199,12 @@ impl std::iter::FromIterator<(String, FileAuthTag)> for OutputsTags {
 pub struct TaskOutputs {
     pub return_value: Vec<u8>,
     pub tags_map: OutputsTags,
+    /// Hashes of the outputs as uploaded, i.e., of the encrypted content.
+    #[serde(default)]
+    pub hashes_map: HashMap<String, FileHash>,
+    /// Lines logged by the execution service while running the task.
+    #[serde(default)]
+    pub log: Vec<String>,
 }
 
 impl TaskOutputs {
@@ -206,19 +212,96 @@ impl TaskOutputs {
         TaskOutputs {
             return_value: value.into(),
             tags_map: OutputsTags::new(tags_map),
+            hashes_map: HashMap::new(),
+            log: Vec::new(),
         }
     }
+
+    pub fn hashes_map(self, hashes_map: HashMap<String, FileHash>) -> Self {
+        Self { hashes_map, ..self }
+    }
+
+    pub fn log(self, log: Vec<String>) -> Self {
+        Self { log, ..self }
+    }
+}
+
+/// A file that could not be staged for or uploaded from a task. Failures are
+/// reported to every participant of the task, so the file is named by its
+/// slot, and its URL and the error message are only logged by the execution
+/// service.
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub struct TaskFileFailure {
+    pub file: String,
+    /// See `HandleFileError::kind`.
+    pub error_kind: String,
+    pub http_status: Option<u16>,
+    pub io_error_kind: Option<String>,
+}
+
+impl TaskFileFailure {
+    pub fn new(file: impl ToString, error: &HandleFileError) -> Self {
+        let (http_status, io_error_kind) = match error {
+            HandleFileError::Http { status } => (Some(*status), None),
+            HandleFileError::Io { kind, .. } => (None, Some(kind.clone())),
+            _ => (None, None),
+        };
+        TaskFileFailure {
+            file: file.to_string(),
+            error_kind: error.kind().to_string(),
+            http_status,
+            io_error_kind,
+        }
+    }
+}
+
+/// Files of a task that could not be staged or uploaded.
+#[derive(Debug, Clone, thiserror::Error)]
+pub struct TaskFileError {
+    pub failures: Vec<TaskFileFailure>,
+}
+
+impl std::fmt::Display for TaskFileError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let details: Vec<String> = self
+            .failures
+            .iter()
+            .map(|failure| format!("{}: {} error", failure.file, failure.error_kind))
+            .collect();
+        write!(
+            f,
+            "Failed to handle {} file(s): {}",
+            self.failures.len(),
+            details.join("; ")
+        )
+    }
 }
 
 #[derive(Debug, Clone, Deserialize, Serialize)]
 pub struct TaskFailure {
     pub reason: String,
+    #[serde(default)]
+    pub file_failures: Vec<TaskFileFailure>,
 }
 
 impl TaskFailure {
     pub fn new(reason: impl ToString) -> Self {
         TaskFailure {
             reason: reason.to_string(),
+            file_failures: Vec::new(),
+        }
+    }
+
+    /// Builds a failure from an error, keeping the per-file details if the
+    /// error comes from staging or uploading files.
+    pub fn from_error(error: &Error) -> Self {
+        let file_failures = match error.downcast_ref::<TaskFileError>() {
+            Some(e) => e.failures.clone(),
+            None => Vec::new(),
+        };
+        TaskFailure {
+            reason: error.to_string(),
+            file_failures,
         }
     }
 }
@@ -445,6 +528,10 @@ where
         self.inner.keys()
     }
 
+    pub fn get(&self, fname: &str) -> Option<&T> {
+        self.inner.get(fname)
+    }
+
     pub fn external_ids(&self) -> HashMap<String, ExternalID> {
         self.inner
             .iter()
@@ -494,3 +581,19 @@ impl std::convert::From<TaskFiles<TeaclaveOutputFile>> for FunctionOutputFiles {
         files.into_iter().collect()
     }
 }
+
+#[cfg(feature = "enclave_unit_test")]
+pub mod tests {
+    use super::*;
+    use teaclave_test_utils::*;
+
+    pub fn run_tests() -> bool {
+        run_tests!(test_load_baseline_task_failure)
+    }
+
+    fn test_load_baseline_task_failure() {
+        let failure: TaskFailure = serde_json::from_str(r#"{"reason": "failed"}"#).unwrap();
+        assert_eq!(failure.reason, "failed");
+        assert!(failure.file_failures.is_empty());
+    }
+}
@@ -16,7 +16,7 @@
 // under the License.
 
 use crate::*;
-use anyhow::{bail, ensure, Error, Result};
+use anyhow::{anyhow, bail, ensure, Error, Result};
 use serde::{Deserialize, Serialize};
 use std::collections::HashSet;
 use std::convert::TryInto;
@@ -29,10 +29,20 @@ pub struct TaskState {
     pub task_id: Uuid,
     pub creator: UserID,
     pub function_id: ExternalID,
+    /// Empty for tasks created before function revisions were pinned, see
+    /// `Task::<Stage>::stage_for_running`.
+    #[serde(default)]
+    pub function_revision_hash: String,
     pub function_arguments: FunctionArguments,
     pub executor: Executor,
     pub inputs_ownership: TaskFileOwners,
     pub outputs_ownership: TaskFileOwners,
+    /// Empty for tasks created before optional and repeated slots, see
+    /// `legacy_slots`.
+    #[serde(default)]
+    pub input_slots: Vec<FunctionSlot>,
+    #[serde(default)]
+    pub output_slots: Vec<FunctionSlot>,
     pub function_owner: UserID,
     pub participants: UserList,
     pub approved_users: UserList,
@@ -40,6 +50,9 @@ pub struct TaskState {
     pub assigned_outputs: TaskFiles<TeaclaveOutputFile>,
     pub result: TaskResult,
     pub status: TaskStatus,
+    /// The input file re-encrypted by a key rotation task.
+    #[serde(default)]
+    pub rotated_file: Option<ExternalID>,
 }
 
 impl Storable for TaskState {
@@ -59,15 +72,15 @@ impl TaskState {
     }
 
     pub fn all_data_assigned(&self) -> bool {
-        let input_args: HashSet<&String> = self.inputs_ownership.keys().collect();
+        let input_slots = legacy_slots(&self.input_slots, &self.inputs_ownership);
         let assiged_inputs: HashSet<&String> = self.assigned_inputs.keys().collect();
-        if input_args != assiged_inputs {
+        if !all_slots_assigned(&input_slots, &self.inputs_ownership, &assiged_inputs) {
             return false;
         }
 
-        let output_args: HashSet<&String> = self.outputs_ownership.keys().collect();
+        let output_slots = legacy_slots(&self.output_slots, &self.outputs_ownership);
         let assiged_outputs: HashSet<&String> = self.assigned_outputs.keys().collect();
-        if output_args != assiged_outputs {
+        if !all_slots_assigned(&output_slots, &self.outputs_ownership, &assiged_outputs) {
             return false;
         }
 
@@ -81,6 +94,51 @@ impl TaskState {
     pub fn has_creator(&self, user_id: &UserID) -> bool {
         &self.creator == user_id
     }
+
+    /// Key of the return value, which is stored in chunks apart from the
+    /// task state.
+    pub fn return_value_key(&self) -> Vec<u8> {
+        format!("{}-return-value", self.key_string()).into_bytes()
+    }
+
+    /// Key of the log, which is stored in chunks apart from the task state.
+    pub fn log_key(&self) -> Vec<u8> {
+        format!("{}-log", self.key_string()).into_bytes()
+    }
+}
+
+// Tasks created before optional and repeated slots have no slots stored, and
+// every file in their ownership spec fills a required slot of its own.
+fn legacy_slots(slots: &[FunctionSlot], ownership: &TaskFileOwners) -> Vec<FunctionSlot> {
+    if !slots.is_empty() {
+        return slots.to_vec();
+    }
+    ownership
+        .keys()
+        .map(|name| FunctionSlot {
+            name: name.to_string(),
+            optional: false,
+            repeated: false,
+        })
+        .collect()
+}
+
+// Files of non-optional slots in the ownership spec must all be assigned,
+// while files of optional slots may be left out as long as the assigned ones
+// still fill the slots.
+fn all_slots_assigned(
+    slots: &[FunctionSlot],
+    ownership: &TaskFileOwners,
+    assigned: &HashSet<&String>,
+) -> bool {
+    for fname in ownership.keys() {
+        let optional = find_function_slot(slots, fname).map_or(false, |slot| slot.optional);
+        if !optional && !assigned.contains(fname) {
+            return false;
+        }
+    }
+
+    check_function_slots(slots, assigned.iter().cloned()).is_ok()
 }
 
 #[derive(Debug, Clone, Default, Deserialize, Serialize)]
@@ -125,24 +183,27 @@ impl Task<Create> {
         ensure!(fn_args_spec == req_args, "function_arguments mismatch");
 
         // check input fkeys
-        let inputs_spec: HashSet<&String> = function.inputs.iter().map(|f| &f.name).collect();
-        let req_input_fkeys: HashSet<&String> = req_input_owners.keys().collect();
-        ensure!(inputs_spec == req_input_fkeys, "input keys mismatch");
+        let input_slots: Vec<FunctionSlot> = function.inputs.iter().map(Into::into).collect();
+        check_function_slots(&input_slots, req_input_owners.keys())
+            .map_err(|e| anyhow!("input keys mismatch: {}", e))?;
 
         // check output fkeys
-        let outputs_spec: HashSet<&String> = function.outputs.iter().map(|f| &f.name).collect();
-        let req_output_fkeys: HashSet<&String> = req_output_owners.keys().collect();
-        ensure!(outputs_spec == req_output_fkeys, "output keys mismatch");
+        let output_slots: Vec<FunctionSlot> = function.outputs.iter().map(Into::into).collect();
+        check_function_slots(&output_slots, req_output_owners.keys())
+            .map_err(|e| anyhow!("output keys mismatch: {}", e))?;
 
         let ts = TaskState {
             task_id: Uuid::new_v4(),
             creator: requester,
             executor: req_executor,
             function_id: function.external_id(),
+            function_revision_hash: function.compute_revision_hash(),
             function_owner: function.owner.clone(),
             function_arguments: req_func_args,
             inputs_ownership: req_input_owners,
             outputs_ownership: req_output_owners,
+            input_slots,
+            output_slots,
             participants,
             ..Default::default()
         };
@@ -154,6 +215,43 @@ impl Task<Create> {
     }
 }
 
+impl Task<Create> {
+    /// Creates a task copying an input file to `output`, encrypted under the
+    /// new key of `output`. The task needs no approval since the requester
+    /// must be the only owner, and the input file is pointed to the copy once
+    /// the task has finished (see `Task::<Finish>::rotated_file`).
+    pub fn rotate_file_key(
+        requester: UserID,
+        input: TeaclaveInputFile,
+        output: TeaclaveOutputFile,
+    ) -> Result<Task<Stage>> {
+        let owners = OwnerList::from(vec![requester.clone()]);
+        ensure!(
+            input.owner == owners && output.owner == owners,
+            "RotateFileKey: requester is not the only owner"
+        );
+
+        let input_owners: TaskFileOwners =
+            std::iter::once((REENCRYPT_FILE_INPUT.to_string(), owners.clone())).collect();
+        let output_owners: TaskFileOwners =
+            std::iter::once((REENCRYPT_FILE_OUTPUT.to_string(), owners)).collect();
+        let mut task = Task::<Create>::new(
+            requester.clone(),
+            Executor::Builtin,
+            FunctionArguments::default(),
+            input_owners,
+            output_owners,
+            Function::reencrypt_file(),
+        )?;
+        task.state.rotated_file = Some(input.external_id());
+
+        let mut task: Task<Assign> = TaskState::from(task).try_into()?;
+        task.assign_input(&requester, REENCRYPT_FILE_INPUT, input)?;
+        task.assign_output(&requester, REENCRYPT_FILE_OUTPUT, output)?;
+        TaskState::from(task).try_into()
+    }
+}
+
 impl Task<Assign> {
     pub fn new(ts: TaskState) -> Result<Self> {
         let task = Task::<Assign> {
@@ -237,6 +335,15 @@ impl Task<Stage> {
             self.state.has_creator(&requester),
             "Requestor is not the task creater"
         );
+        // Tasks created before function revisions were pinned are pinned to
+        // the revision they are first staged with.
+        if self.state.function_revision_hash.is_empty() {
+            self.state.function_revision_hash = function.compute_revision_hash();
+        }
+        ensure!(
+            function.verify_revision_hash(&self.state.function_revision_hash),
+            "Function revision does not match the approved one"
+        );
 
         let function_arguments = self.state.function_arguments.clone();
         let staged_task = StagedTask {
@@ -281,6 +388,14 @@ impl Task<Finish> {
         self.state.assigned_outputs.update_cmac(fname, auth_tag)
     }
 
+    /// Returns the id of the input file rotated by a key rotation task, and
+    /// the name and file of the re-encrypted copy to point it to.
+    pub fn rotated_file(&self) -> Option<(&ExternalID, &str, &TeaclaveOutputFile)> {
+        let data_id = self.state.rotated_file.as_ref()?;
+        let output = self.state.assigned_outputs.get(REENCRYPT_FILE_OUTPUT)?;
+        Some((data_id, REENCRYPT_FILE_OUTPUT, output))
+    }
+
     pub fn update_result(&mut self, result: TaskResult) -> Result<()> {
         self.state.result = result;
         Ok(())
@@ -504,3 +619,92 @@ impl std::convert::From<Done> for TaskStatus {
         TaskStatus::Finished
     }
 }
+
+#[cfg(feature = "enclave_unit_test")]
+pub mod tests {
+    use super::*;
+    use teaclave_test_utils::*;
+
+    pub fn run_tests() -> bool {
+        run_tests!(test_pin_revision_at_first_stage, test_load_baseline_task)
+    }
+
+    fn test_pin_revision_at_first_stage() {
+        let creator = UserID::from("creator");
+        let function = Function::new().name("echo").payload(b"echo".to_vec());
+        let ts = TaskState {
+            creator: creator.clone(),
+            status: TaskStatus::Approved,
+            ..Default::default()
+        };
+
+        let mut task: Task<Stage> = ts.clone().try_into().unwrap();
+        task.stage_for_running(&creator, function.clone()).unwrap();
+        assert_eq!(
+            task.state.function_revision_hash,
+            function.compute_revision_hash()
+        );
+
+        let updated = function.clone().payload(b"updated".to_vec());
+        assert!(task.stage_for_running(&creator, updated).is_err());
+
+        let mut task: Task<Stage> = TaskState {
+            function_revision_hash: function.compute_revision_hash(),
+            ..ts
+        }
+        .try_into()
+        .unwrap();
+        assert!(task.stage_for_running(&creator, function).is_ok());
+    }
+
+    fn test_load_baseline_task() {
+        let owner = UserID::from("owner");
+        let function = Function::new()
+            .inputs(vec![FunctionInput::new("input", "")])
+            .outputs(vec![FunctionOutput::new("output", "")]);
+        let input_owners: TaskFileOwners =
+            std::iter::once(("input".to_string(), vec![owner.clone()])).collect();
+        let output_owners: TaskFileOwners =
+            std::iter::once(("output".to_string(), vec![owner.clone()])).collect();
+        let task = Task::<Create>::new(
+            owner.clone(),
+            Executor::Builtin,
+            FunctionArguments::default(),
+            input_owners,
+            output_owners,
+            function,
+        )
+        .unwrap();
+
+        // Tasks stored before this series have none of the following fields.
+        let mut record = serde_json::to_value(TaskState::from(task)).unwrap();
+        let fields = record.as_object_mut().unwrap();
+        for field in &[
+            "function_revision_hash",
+            "input_slots",
+            "output_slots",
+            "rotated_file",
+        ] {
+            assert!(fields.remove(*field).is_some());
+        }
+        let bytes = serde_json::to_vec(&record).unwrap();
+        let ts = TaskState::from_slice(&bytes).unwrap();
+        assert!(ts.input_slots.is_empty());
+
+        let mut task: Task<Assign> = ts.try_into().unwrap();
+        let url = url::Url::parse("file:///tmp/input").unwrap();
+        let input = TeaclaveInputFile::new(
+            url,
+            FileAuthTag::mock(),
+            FileCrypto::default(),
+            vec![owner.clone()],
+        );
+        let url = url::Url::parse("file:///tmp/output").unwrap();
+        let output = TeaclaveOutputFile::new(url, FileCrypto::default(), vec![owner.clone()]);
+        task.assign_input(&owner, "input", input).unwrap();
+        task.assign_output(&owner, "output", output).unwrap();
+
+        let ts: TaskState = task.into();
+        assert_eq!(ts.status, TaskStatus::DataAssigned);
+    }
+}
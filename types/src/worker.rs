@@ -15,7 +15,7 @@
 // specific language governing permissions and limitations
 // under the License.
 
-use crate::{FunctionArguments, FunctionRuntime, OutputsTags};
+use crate::{indexed_file_name, FunctionArguments, FunctionRuntime, OutputsTags};
 use serde::{Deserialize, Serialize};
 use std::collections::HashSet;
 use std::convert::TryInto;
@@ -25,6 +25,22 @@ use std::prelude::v1::*;
 pub trait TeaclaveRuntime {
     fn open_input(&self, identifier: &str) -> anyhow::Result<Box<dyn io::Read>>;
     fn create_output(&self, identifier: &str) -> anyhow::Result<Box<dyn io::Write>>;
+    /// Number of files staged for an input slot. A scalar slot has one file
+    /// at most, a repeated slot has files indexed from zero.
+    fn input_count(&self, identifier: &str) -> usize;
+    fn output_count(&self, identifier: &str) -> usize;
+
+    fn open_input_at(&self, identifier: &str, index: usize) -> anyhow::Result<Box<dyn io::Read>> {
+        self.open_input(&indexed_file_name(identifier, index))
+    }
+
+    fn create_output_at(
+        &self,
+        identifier: &str,
+        index: usize,
+    ) -> anyhow::Result<Box<dyn io::Write>> {
+        self.create_output(&indexed_file_name(identifier, index))
+    }
 }
 
 pub trait TeaclaveExecutor {